@@ -0,0 +1,285 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use futures::future;
+use hyper::{HeaderMap, StatusCode};
+use log::debug;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+/// Defines a binding for storing request headers in `State`, as a struct with a field per header.
+///
+/// This trait is typically derived, where each field is read from the header named after it,
+/// with underscores replaced by hyphens (so `x_tenant_id` reads `X-Tenant-Id`). Fields of any
+/// type implementing `FromStr` are supported, such as `String` or `u64`, and fields wrapped in
+/// `Option` are `None` when the header is absent. The derive also implements `StateData`.
+///
+/// The extractor is added to a route via `with_header_extractor`. Requests missing a required
+/// header, or carrying a value which can't be parsed, are answered with a `400 Bad Request`
+/// naming the header, without reaching the handler.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # #[macro_use]
+/// # extern crate gotham_derive;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::state::{FromState, State};
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::router::Router;
+/// # use gotham::router::builder::*;
+/// # use gotham::test::TestServer;
+/// #
+/// #[derive(HeaderExtractor)]
+/// struct AuthHeaders {
+///     authorization: String,
+///     x_tenant_id: Option<u32>,
+/// }
+///
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let headers = AuthHeaders::borrow_from(&state);
+///     let body = format!("{} for {:?}", headers.authorization, headers.x_tenant_id);
+///
+///     let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, body);
+///     (state, response)
+/// }
+///
+/// fn router() -> Router {
+///     build_simple_router(|route| {
+///         route
+///             .get("/test")
+///             .with_header_extractor::<AuthHeaders>()
+///             .to(handler);
+///     })
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let client = test_server.client();
+/// #
+/// #   let response = client
+/// #       .get("http://example.com/test")
+/// #       .with_header("authorization", "Bearer abc".parse().unwrap())
+/// #       .with_header("x-tenant-id", "42".parse().unwrap())
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "Bearer abc for Some(42)");
+/// #
+/// #   let response = client.get("http://example.com/test").perform().unwrap();
+/// #   assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "missing header: authorization");
+/// # }
+/// ```
+pub trait HeaderExtractor: StateData + Sized {
+    /// Extracts the struct from the request headers.
+    fn extract(headers: &HeaderMap) -> Result<Self, HeaderError>;
+}
+
+/// The reasons a `HeaderExtractor` may fail, each naming the header at fault.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HeaderError {
+    /// A required header is absent.
+    Missing(&'static str),
+    /// A header value isn't valid UTF-8, or can't be parsed into the type of its field.
+    Invalid(&'static str),
+}
+
+impl Display for HeaderError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            HeaderError::Missing(name) => write!(f, "missing header: {}", name),
+            HeaderError::Invalid(name) => write!(f, "invalid header: {}", name),
+        }
+    }
+}
+
+impl Error for HeaderError {}
+
+/// Parses a required header, as used by the `HeaderExtractor` derive.
+pub fn required_header<T>(headers: &HeaderMap, name: &'static str) -> Result<T, HeaderError>
+where
+    T: FromStr,
+{
+    optional_header(headers, name)?.ok_or(HeaderError::Missing(name))
+}
+
+/// Parses an optional header, as used by the `HeaderExtractor` derive.
+pub fn optional_header<T>(headers: &HeaderMap, name: &'static str) -> Result<Option<T>, HeaderError>
+where
+    T: FromStr,
+{
+    match headers.get(name) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .map(Some)
+            .ok_or(HeaderError::Invalid(name)),
+        None => Ok(None),
+    }
+}
+
+/// Middleware binding to run a `HeaderExtractor` for a single route, as added by
+/// `with_header_extractor`.
+pub struct HeaderExtractorMiddleware<T> {
+    _extractor: PhantomData<fn() -> T>,
+}
+
+impl<T> HeaderExtractorMiddleware<T>
+where
+    T: HeaderExtractor,
+{
+    /// Creates a new `HeaderExtractorMiddleware` for the extractor type.
+    pub fn new() -> Self {
+        HeaderExtractorMiddleware {
+            _extractor: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for HeaderExtractorMiddleware<T>
+where
+    T: HeaderExtractor,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for HeaderExtractorMiddleware<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for HeaderExtractorMiddleware<T> {}
+
+/// `Middleware` trait implementation.
+impl<T> Middleware for HeaderExtractorMiddleware<T>
+where
+    T: HeaderExtractor,
+{
+    /// Stores the extracted headers in `State`, or answers with a `400 Bad Request`.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        match T::extract(HeaderMap::borrow_from(&state)) {
+            Ok(extracted) => {
+                state.put(extracted);
+                chain(state)
+            }
+            Err(e) => {
+                debug!("[{}] header extractor failed: {}", request_id(&state), e);
+                let response = create_response(
+                    &state,
+                    StatusCode::BAD_REQUEST,
+                    mime::TEXT_PLAIN,
+                    e.to_string(),
+                );
+                Box::new(future::ok((state, response)))
+            }
+        }
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl<T> NewMiddleware for HeaderExtractorMiddleware<T>
+where
+    T: HeaderExtractor,
+{
+    type Instance = Self;
+
+    /// Copies the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{Future, Stream};
+    use hyper::{Body, Method, Response};
+
+    // as expanded by the derive
+    struct AuthHeaders {
+        authorization: String,
+        x_tenant_id: Option<u32>,
+    }
+
+    impl HeaderExtractor for AuthHeaders {
+        fn extract(headers: &HeaderMap) -> Result<Self, HeaderError> {
+            Ok(AuthHeaders {
+                authorization: required_header(headers, "authorization")?,
+                x_tenant_id: optional_header(headers, "x-tenant-id")?,
+            })
+        }
+    }
+
+    impl StateData for AuthHeaders {}
+
+    fn call(headers: &[(&'static str, &'static str)]) -> (StatusCode, String) {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(*name, value.parse().unwrap());
+        }
+
+        let mut state = State::new();
+        state.put(Method::GET);
+        state.put(map);
+        crate::state::set_request_id(&mut state);
+
+        let (_, response) = HeaderExtractorMiddleware::<AuthHeaders>::new()
+            .call(state, |state| {
+                let headers = AuthHeaders::borrow_from(&state);
+                let body = format!("{} {:?}", headers.authorization, headers.x_tenant_id);
+                Box::new(future::ok((state, Response::new(Body::from(body)))))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap();
+
+        let status = response.status();
+        let body = response.into_body().concat2().wait().unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn extracts_headers() {
+        assert_eq!(
+            call(&[("authorization", "Bearer abc"), ("x-tenant-id", "7")]),
+            (StatusCode::OK, "Bearer abc Some(7)".to_owned())
+        );
+        assert_eq!(
+            call(&[("authorization", "Bearer abc")]),
+            (StatusCode::OK, "Bearer abc None".to_owned())
+        );
+        assert_eq!(
+            call(&[("x-tenant-id", "7")]),
+            (
+                StatusCode::BAD_REQUEST,
+                "missing header: authorization".to_owned()
+            )
+        );
+        assert_eq!(
+            call(&[("authorization", "Bearer abc"), ("x-tenant-id", "acme")]),
+            (
+                StatusCode::BAD_REQUEST,
+                "invalid header: x-tenant-id".to_owned()
+            )
+        );
+    }
+}
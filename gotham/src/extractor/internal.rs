@@ -82,6 +82,10 @@ pub(crate) enum ExtractorError {
     /// informative error messages.
     Custom(String),
 
+    /// An error occurred while deserializing the values of the named field, such as a
+    /// `ParseError` for a value of the wrong type.
+    InvalidField(String, Box<ExtractorError>),
+
     // Variants may be added in future, and it will not be considered a breaking change.
     #[doc(hidden)]
     __NonExhaustive,
@@ -114,12 +118,12 @@ macro_rules! single_value_type {
     ($trait_fn:ident, $visitor_fn:ident) => {
         fn $trait_fn<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
-            V: Visitor<'de>
+            V: Visitor<'de>,
         {
             let v = parse_single_value(self.values)?;
             visitor.$visitor_fn(v)
         }
-    }
+    };
 }
 
 /// Implements one `Deserializer` function (`$trait_fn`) to return the error defined by the `$err`
@@ -407,11 +411,12 @@ where
         V: DeserializeSeed<'de>,
     {
         match self.current.take() {
-            Some((_k, values)) => {
+            Some((k, values)) => {
                 let deserializer = DeserializeValues {
                     values: values.into_iter().map(convert_to_string_ref),
                 };
                 seed.deserialize(deserializer)
+                    .map_err(|e| ExtractorError::InvalidField(k.to_owned(), Box::new(e)))
             }
             None => Err(ExtractorError::NoCurrentItem),
         }
@@ -705,7 +710,6 @@ mod tests {
     use super::*;
     use crate::helpers::http::{FormUrlDecoded, PercentDecoded};
     use serde_derive::Deserialize;
-    use std;
 
     #[derive(Deserialize)]
     struct SimpleValues {
@@ -770,8 +774,8 @@ mod tests {
         assert_eq!(p.u16_val, 40511);
         assert_eq!(p.u32_val, 4_000_000_000);
         assert_eq!(p.u64_val, 9_000_000_000);
-        assert!((p.f32_val - 1.4).abs() < std::f32::EPSILON);
-        assert!((p.f64_val - 2.6).abs() < std::f64::EPSILON);
+        assert!((p.f32_val - 1.4).abs() < f32::EPSILON);
+        assert!((p.f64_val - 2.6).abs() < f64::EPSILON);
         assert_eq!(p.string_val, "this is an owned string");
         assert_eq!(p.char_val, 'a');
         assert_eq!(p.optional_val, Some("this is optional".to_owned()));
@@ -849,8 +853,8 @@ mod tests {
         assert_eq!(p.u16_val, 40511);
         assert_eq!(p.u32_val, 4_000_000_000);
         assert_eq!(p.u64_val, 9_000_000_000);
-        assert!((p.f32_val - 1.4).abs() < std::f32::EPSILON);
-        assert!((p.f64_val - 2.6).abs() < std::f64::EPSILON);
+        assert!((p.f32_val - 1.4).abs() < f32::EPSILON);
+        assert!((p.f64_val - 2.6).abs() < f64::EPSILON);
         assert_eq!(p.string_val, "this is an owned string");
         assert_eq!(p.char_val, 'a');
         assert_eq!(p.optional_val, Some("this is optional".to_owned()));
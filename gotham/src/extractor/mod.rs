@@ -1,16 +1,21 @@
 //! Extracts request data into type-safe structs using Serde.
 //!
 //! Extractors are added to route definitions when defining a `Router`. The `PathExtractor` and
-//! `QueryStringExtractor` traits provide usage examples.
+//! `QueryStringExtractor` traits provide usage examples. Request headers are extracted in the
+//! same fashion by a `HeaderExtractor`, which is typically derived.
 //!
 //! The request data is extracted by the `Route` implementation when dispatching the request. The
 //! application-provided data structure which implements the extractor trait is used to deserialize
 //! the data and store it within the request `State` before the request is dispatched to the
 //! `Handler`.
 
+mod header;
 pub(crate) mod internal;
 mod path;
 mod query_string;
+mod validate;
 
+pub use self::header::*;
 pub use self::path::*;
 pub use self::query_string::*;
+pub use self::validate::*;
@@ -16,6 +16,9 @@ use crate::state::{State, StateData};
 /// behaviour from Serde, and result in a `400 Bad Request` HTTP response if the query string is
 /// not able to be deserialized.
 ///
+/// Rules which can't be expressed through the type system can be checked by implementing
+/// `Validate` and marking the struct with `#[validate]`; see the `Validate` trait for details.
+///
 /// # Examples
 ///
 /// ```rust
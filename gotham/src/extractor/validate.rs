@@ -0,0 +1,95 @@
+use crate::state::StateData;
+
+/// Semantic validation of an extracted value, run immediately after it has been deserialized.
+///
+/// Serde can only verify that request data has the right shape; rules such as `page >= 1` or
+/// `limit <= 100` belong here instead. The validation is opted into by adding `#[validate]` to a
+/// type which derives `StaticResponseExtender`, in which case an `Err` prevents the request from
+/// reaching the handler and results in a `400 Bad Request` response carrying the message as a
+/// plain text body.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # #[macro_use]
+/// # extern crate gotham_derive;
+/// # extern crate hyper;
+/// # extern crate serde;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::extractor::Validate;
+/// # use gotham::helpers::http::response::create_empty_response;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// #[derive(Deserialize, StateData, StaticResponseExtender)]
+/// #[validate]
+/// struct Pagination {
+///     page: u32,
+///     limit: u32,
+/// }
+///
+/// impl Validate for Pagination {
+///     fn validate(&self) -> Result<(), String> {
+///         if self.page < 1 {
+///             return Err("page must be at least 1".to_owned());
+///         }
+///         if self.limit > 100 {
+///             return Err("limit must be at most 100".to_owned());
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   let res = create_empty_response(&state, StatusCode::OK);
+/// #   (state, res)
+/// # }
+/// #
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route
+///         .get("/items")
+///         .with_query_string_extractor::<Pagination>()
+///         .to(handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://example.com/items?page=0&limit=10")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+/// # assert_eq!(response.read_utf8_body().unwrap(), "page must be at least 1");
+/// # }
+/// ```
+pub trait Validate {
+    /// Validates the extracted value, returning a message describing the problem on failure.
+    fn validate(&self) -> Result<(), String>;
+}
+
+/// Stored in `State` when an extracted value fails validation, so that the failure can be
+/// described by the `StaticResponseExtender` of the extractor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationError {
+    message: String,
+}
+
+impl ValidationError {
+    /// Creates a new `ValidationError` with the provided message.
+    pub fn new(message: String) -> Self {
+        ValidationError { message }
+    }
+
+    /// Returns the message describing why validation failed.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl StateData for ValidationError {}
@@ -15,13 +15,14 @@ use hyper::header::*;
 use hyper::{Body, Chunk, Response, StatusCode};
 use log::debug;
 use mime::{self, Mime};
-use mime_guess::guess_mime_type_opt;
 use serde_derive::Deserialize;
 use tokio::fs::File;
 use tokio::io::AsyncRead;
 
 use self::accepted_encoding::accepted_encodings;
 use crate::handler::{Handler, HandlerFuture, IntoHandlerError, NewHandler};
+use crate::helpers::http::header::content_disposition::ContentDisposition;
+use crate::helpers::mime::detect;
 use crate::router::response::extender::StaticResponseExtender;
 use crate::state::{FromState, State, StateData};
 
@@ -31,6 +32,7 @@ use std::fs::Metadata;
 use std::io;
 use std::iter::FromIterator;
 use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 use std::time::UNIX_EPOCH;
 
 /// Represents a handler for any files under a directory.
@@ -57,13 +59,14 @@ pub struct FileHandler {
 ///
 /// ```rust
 /// # extern crate gotham;
-/// # use gotham::handler::assets::FileOptions;
+/// # use gotham::handler::assets::{DispositionPolicy, FileOptions};
 ///
 /// let default_options = FileOptions::from("my_static_path");
 /// let from_builder = FileOptions::new("my_static_path")
 ///     .with_cache_control("public")
 ///     .with_gzip(false)
 ///     .with_brotli(false)
+///     .with_disposition_policy(DispositionPolicy::Omit)
 ///     .build();
 ///
 /// assert_eq!(default_options, from_builder);
@@ -74,6 +77,51 @@ pub struct FileOptions {
     cache_control: String,
     gzip: bool,
     brotli: bool,
+    disposition_policy: DispositionPolicy,
+}
+
+/// Controls the `Content-Disposition` header sent alongside static files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DispositionPolicy {
+    /// No `Content-Disposition` header is sent, leaving the choice to the client.
+    Omit,
+    /// Files are marked `inline`, to be displayed by the client.
+    Inline,
+    /// Files are marked as an `attachment` to be downloaded, using the name of the file.
+    Attachment,
+    /// Files which browsers commonly display (text, images, audio, video and PDF documents) are
+    /// marked `inline`, and all others as an `attachment`.
+    Auto,
+}
+
+impl Default for DispositionPolicy {
+    fn default() -> Self {
+        DispositionPolicy::Omit
+    }
+}
+
+impl DispositionPolicy {
+    /// Returns the `Content-Disposition` value for a file, if any should be sent.
+    fn header_value(self, path: &Path, mime_type: &Mime) -> Option<HeaderValue> {
+        let attachment = match self {
+            DispositionPolicy::Omit => return None,
+            DispositionPolicy::Inline => false,
+            DispositionPolicy::Attachment => true,
+            DispositionPolicy::Auto => !matches!(
+                (mime_type.type_(), mime_type.subtype()),
+                (mime::TEXT, _)
+                    | (mime::IMAGE, _)
+                    | (mime::AUDIO, _)
+                    | (mime::VIDEO, _)
+                    | (mime::APPLICATION, mime::PDF)
+            ),
+        };
+
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some(filename) if attachment => Some(ContentDisposition::attachment(filename)),
+            _ => Some(ContentDisposition::inline()),
+        }
+    }
 }
 
 impl FileOptions {
@@ -87,6 +135,7 @@ impl FileOptions {
             cache_control: "public".to_string(),
             gzip: false,
             brotli: false,
+            disposition_policy: DispositionPolicy::default(),
         }
     }
 
@@ -110,6 +159,13 @@ impl FileOptions {
         self
     }
 
+    /// Sets the policy used for the `Content-Disposition` header of static file responses
+    /// (defaults to `DispositionPolicy::Omit`, sending no header).
+    pub fn with_disposition_policy(&mut self, policy: DispositionPolicy) -> &mut Self {
+        self.disposition_policy = policy;
+        self
+    }
+
     /// Clones `self` to return an owned value for passing to a handler.
     pub fn build(&mut self) -> Self {
         self.clone()
@@ -179,7 +235,16 @@ impl Handler for DirHandler {
     fn handle(self, state: State) -> Box<HandlerFuture> {
         let path = {
             let mut base_path = self.options.path;
-            let file_path = PathBuf::from_iter(&FilePathExtractor::borrow_from(&state).parts);
+            let parts = &FilePathExtractor::borrow_from(&state).parts;
+
+            // rewritten paths, such as fingerprinted ones, are served from the original file
+            let original = AssetPathRewrite::try_borrow_from(&state)
+                .and_then(|rewrite| rewrite.rewrite(&parts.join("/")));
+
+            let file_path = match original {
+                Some(original) => PathBuf::from(original),
+                None => PathBuf::from_iter(parts),
+            };
             base_path.extend(&normalize_path(&file_path));
             base_path
         };
@@ -201,7 +266,10 @@ impl Handler for FileHandler {
 
 // Creates the `HandlerFuture` response based on the given `FileOptions`.
 fn create_file_response(options: FileOptions, state: State) -> Box<HandlerFuture> {
-    let mime_type = mime_for_path(&options.path);
+    let mime_type = detect(&options.path);
+    let disposition = options
+        .disposition_policy
+        .header_value(&options.path, &mime_type);
     let headers = HeaderMap::borrow_from(&state).clone();
 
     let (path, encoding) = check_compressed_options(&options, &headers);
@@ -233,6 +301,9 @@ fn create_file_response(options: FileOptions, state: State) -> Box<HandlerFuture
                 if let Some(content_encoding) = encoding {
                     response.header(CONTENT_ENCODING, content_encoding);
                 }
+                if let Some(disposition) = disposition {
+                    response.header(CONTENT_DISPOSITION, disposition);
+                }
 
                 Ok(response.body(body).unwrap())
             });
@@ -294,10 +365,6 @@ fn get_extension(encoding: &str, options: &FileOptions) -> Option<String> {
     None
 }
 
-fn mime_for_path(path: &Path) -> Mime {
-    guess_mime_type_opt(path).unwrap_or_else(|| mime::APPLICATION_OCTET_STREAM)
-}
-
 fn normalize_path(path: &Path) -> PathBuf {
     path.components()
         .fold(PathBuf::new(), |mut result, p| match p {
@@ -356,6 +423,38 @@ pub struct FilePathExtractor {
 
 impl StateData for FilePathExtractor {}
 
+/// Rewrites the paths requested from a `to_dir` route before they're read from the directory.
+///
+/// A middleware places this into `State` to serve files under names other than their own, such
+/// as the fingerprinted names provided by the `CacheBustingMiddleware`. Paths are relative to the
+/// directory and joined by `/`; those the rewrite returns `None` for are served as requested.
+#[derive(Clone)]
+pub struct AssetPathRewrite {
+    rewrite: Arc<RewriteFn>,
+}
+
+/// Maps a requested path to the path to read in its place, as provided to `AssetPathRewrite`.
+type RewriteFn = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+impl AssetPathRewrite {
+    /// Creates a new `AssetPathRewrite` from the provided function.
+    pub fn new<F>(rewrite: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        AssetPathRewrite {
+            rewrite: Arc::new(rewrite),
+        }
+    }
+
+    /// Returns the path to read in place of the provided path, if rewritten.
+    pub fn rewrite(&self, path: &str) -> Option<String> {
+        (self.rewrite)(path)
+    }
+}
+
+impl StateData for AssetPathRewrite {}
+
 impl StaticResponseExtender for FilePathExtractor {
     type ResBody = Body;
     fn extend(_state: &mut State, _res: &mut Response<Self::ResBody>) {}
@@ -420,7 +519,7 @@ fn get_block_size(metadata: &Metadata) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use super::FileOptions;
+    use super::{DispositionPolicy, FileOptions};
     use crate::router::builder::{build_simple_router, DefineSingleRoute, DrawRoutes};
     use crate::router::Router;
     use crate::test::TestServer;
@@ -517,6 +616,42 @@ mod tests {
         assert_eq!(&body[..], b"<html>I am a doc.</html>");
     }
 
+    #[test]
+    fn assets_disposition_policy() {
+        let disposition = |policy| {
+            let mut options = FileOptions::new("resources/test/assets/doc.html");
+            options.with_disposition_policy(policy);
+
+            let test_server = TestServer::new(build_simple_router(|route| {
+                route.get("/").to_file(options.build())
+            }))
+            .unwrap();
+
+            let response = test_server
+                .client()
+                .get("http://localhost/")
+                .perform()
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+            response.headers().get(CONTENT_DISPOSITION).cloned()
+        };
+
+        assert_eq!(disposition(DispositionPolicy::Omit), None);
+        assert_eq!(disposition(DispositionPolicy::Inline).unwrap(), "inline");
+        assert_eq!(
+            disposition(DispositionPolicy::Attachment).unwrap(),
+            "attachment; filename=\"doc.html\""
+        );
+        assert_eq!(disposition(DispositionPolicy::Auto).unwrap(), "inline");
+
+        let value = DispositionPolicy::Auto.header_value(
+            std::path::Path::new("data/report.zip"),
+            &"application/zip".parse().unwrap(),
+        );
+        assert_eq!(value.unwrap(), "attachment; filename=\"report.zip\"");
+    }
+
     #[test]
     fn assets_if_none_match_etag() {
         use hyper::header::{ETAG, IF_NONE_MATCH};
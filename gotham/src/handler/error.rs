@@ -126,6 +126,12 @@ impl HandlerError {
             ..self
         }
     }
+
+    /// Returns the HTTP status code of the response which is generated by the `IntoResponse`
+    /// implementation.
+    pub fn status(&self) -> StatusCode {
+        self.status_code
+    }
 }
 
 impl IntoResponse for HandlerError {
@@ -0,0 +1,62 @@
+//! Defines a probe which checks the health of a service over HTTP.
+//!
+//! This module is only available with the `http-probe` feature enabled.
+use std::panic::AssertUnwindSafe;
+
+use futures::Future;
+use hyper::{Client, Uri};
+
+use crate::handler::health::ProbeResult;
+
+/// Creates a probe which sends a `GET` request to `uri`, for use with
+/// `HealthCheckHandler::add_async_probe`.
+///
+/// The probe succeeds when the service responds with a `2xx` status, and fails with the status
+/// or connection error otherwise. Only plain HTTP is supported. A single client is shared by
+/// every run of the probe, so connections to the service are reused between health checks.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::handler::health::http::http_probe;
+/// # use gotham::handler::health::HealthCheckHandler;
+/// let health = HealthCheckHandler::new()
+///     .add_async_probe("search", http_probe("http://search.internal:9200/".parse().unwrap()));
+/// # let _ = health;
+/// ```
+pub fn http_probe(
+    uri: Uri,
+) -> impl Fn() -> Box<dyn Future<Item = ProbeResult, Error = String> + Send> + Send + Sync {
+    // the connection pool is shared behind a lock, so a panic mid-check can't corrupt it
+    let client = AssertUnwindSafe(Client::new());
+
+    move || {
+        let f = client.get(uri.clone()).then(|result| match result {
+            Ok(ref response) if response.status().is_success() => Ok(ProbeResult::Healthy),
+            Ok(response) => Ok(ProbeResult::Unhealthy(format!(
+                "unexpected status {}",
+                response.status()
+            ))),
+            Err(e) => Ok(ProbeResult::Unhealthy(e.to_string())),
+        });
+
+        Box::new(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn fails_when_unreachable() {
+        let probe = http_probe("http://127.0.0.1:1/".parse().unwrap());
+
+        let mut runtime = Runtime::new().unwrap();
+        match runtime.block_on(probe()).unwrap() {
+            ProbeResult::Unhealthy(_) => (),
+            ProbeResult::Healthy => panic!("unreachable service reported as healthy"),
+        }
+    }
+}
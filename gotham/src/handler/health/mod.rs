@@ -0,0 +1,319 @@
+//! Defines the `HealthCheckHandler`, which reports the health of an application by running a set
+//! of asynchronous probes against the services it depends on.
+use std::fmt::Display;
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::{future, Future, IntoFuture};
+use hyper::StatusCode;
+use serde_json::{Map, Value};
+use tokio::timer::Timeout;
+
+use crate::error::Result;
+use crate::handler::{Handler, HandlerFuture, NewHandler};
+use crate::helpers::http::response::create_response;
+use crate::state::State;
+
+#[cfg(feature = "http-probe")]
+pub mod http;
+#[cfg(feature = "sqlx")]
+pub mod postgres;
+#[cfg(feature = "redis")]
+pub mod redis;
+
+// the time allowed for each probe when no timeout is configured
+const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The outcome of a single health probe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProbeResult {
+    /// The probed service is healthy.
+    Healthy,
+    /// The probed service is unhealthy, for the given reason.
+    Unhealthy(String),
+}
+
+/// The number of failed probes tolerated before the aggregate status becomes `unavailable`.
+///
+/// While no more than this many probes fail, the status is `degraded`. The default of zero
+/// reports any failure as `unavailable`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DegradedThreshold(pub usize);
+
+type ProbeFuture = Box<dyn Future<Item = ProbeResult, Error = String> + Send>;
+
+/// A named probe, creating a new future for each health check.
+struct Probe {
+    name: String,
+    run: Box<dyn Fn() -> ProbeFuture + Send + Sync + RefUnwindSafe>,
+}
+
+/// A `Handler` which runs every configured probe concurrently and reports their results.
+///
+/// The response body is a JSON object such as:
+///
+/// ```json
+/// {
+///   "status": "degraded",
+///   "checks": {
+///     "database": { "status": "ok", "duration_ms": 3 },
+///     "cache": { "status": "failed", "error": "timed out", "duration_ms": 5000 }
+///   }
+/// }
+/// ```
+///
+/// The aggregate `status` is `ok` when every probe succeeds, `degraded` when the number of
+/// failures is within the `DegradedThreshold`, and `unavailable` otherwise. Responses use
+/// `200 OK`, except for `unavailable` which uses `503 Service Unavailable` so that load balancers
+/// can act on the status code alone.
+///
+/// Probes which don't complete within the probe timeout (5 seconds by default) fail with the
+/// error `timed out`, and probes whose future resolves to an error fail with that error.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate futures;
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::time::Duration;
+/// # use futures::future;
+/// # use gotham::handler::health::{DegradedThreshold, HealthCheckHandler, ProbeResult};
+/// # use gotham::router::builder::*;
+/// # use gotham::test::TestServer;
+/// # use hyper::StatusCode;
+/// #
+/// # fn main() {
+/// let health = HealthCheckHandler::new()
+///     .add_async_probe("database", || {
+///         // a real probe would run a trivial query, such as `SELECT 1`
+///         future::ok::<_, String>(ProbeResult::Healthy)
+///     })
+///     .add_async_probe("cache", || {
+///         future::ok::<_, String>(ProbeResult::Unhealthy("connection refused".to_owned()))
+///     })
+///     .probe_timeout(Duration::from_secs(2))
+///     .degraded_threshold(DegradedThreshold(1));
+///
+/// let router = build_simple_router(|route| {
+///     route.get("/health").to_new_handler(health);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/health")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert!(response.read_utf8_body().unwrap().contains(r#""status":"degraded""#));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct HealthCheckHandler {
+    probes: Vec<Arc<Probe>>,
+    timeout: Duration,
+    threshold: DegradedThreshold,
+}
+
+impl HealthCheckHandler {
+    /// Creates a new `HealthCheckHandler` without any probes, which always reports `ok`.
+    pub fn new() -> Self {
+        HealthCheckHandler {
+            probes: Vec::new(),
+            timeout: DEFAULT_PROBE_TIMEOUT,
+            threshold: DegradedThreshold::default(),
+        }
+    }
+
+    /// Adds a probe, reported under `name`, which is called to create a new future each time the
+    /// health of the application is checked.
+    pub fn add_async_probe<F, R>(mut self, name: &str, probe: F) -> Self
+    where
+        F: Fn() -> R + Send + Sync + RefUnwindSafe + 'static,
+        R: IntoFuture<Item = ProbeResult>,
+        R::Future: Send + 'static,
+        R::Error: Display,
+    {
+        let run =
+            move || -> ProbeFuture { Box::new(probe().into_future().map_err(|e| e.to_string())) };
+
+        self.probes.push(Arc::new(Probe {
+            name: name.to_owned(),
+            run: Box::new(run),
+        }));
+        self
+    }
+
+    /// Sets the time allowed for each probe to complete.
+    pub fn probe_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the number of failed probes reported as `degraded` rather than `unavailable`.
+    pub fn degraded_threshold(mut self, threshold: DegradedThreshold) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Runs a probe under the timeout, resolving to its name, result and duration.
+    fn run(
+        &self,
+        probe: &Probe,
+    ) -> impl Future<Item = (String, ProbeResult, Duration), Error = ()> + Send {
+        let name = probe.name.clone();
+        let started = Instant::now();
+
+        Timeout::new((probe.run)(), self.timeout).then(move |result| {
+            let result = match result {
+                Ok(result) => result,
+                Err(ref e) if e.is_elapsed() => ProbeResult::Unhealthy("timed out".to_owned()),
+                Err(e) => match e.into_inner() {
+                    Some(e) => ProbeResult::Unhealthy(e),
+                    None => ProbeResult::Unhealthy("timer unavailable".to_owned()),
+                },
+            };
+
+            Ok((name, result, started.elapsed()))
+        })
+    }
+}
+
+impl Default for HealthCheckHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NewHandler for HealthCheckHandler {
+    type Instance = Self;
+
+    fn new_handler(&self) -> Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl Handler for HealthCheckHandler {
+    fn handle(self, state: State) -> Box<HandlerFuture> {
+        let probes: Vec<_> = self.probes.iter().map(|probe| self.run(probe)).collect();
+        let threshold = self.threshold;
+
+        let f = future::join_all(probes).then(move |results| {
+            // each probe captures its own failure, so joining them can't fail
+            let results = results.unwrap_or_default();
+
+            let mut checks = Map::new();
+            let mut failures = 0;
+
+            for (name, result, duration) in results {
+                let mut check = Map::new();
+
+                match result {
+                    ProbeResult::Healthy => {
+                        check.insert("status".to_owned(), Value::from("ok"));
+                    }
+                    ProbeResult::Unhealthy(error) => {
+                        failures += 1;
+                        check.insert("status".to_owned(), Value::from("failed"));
+                        check.insert("error".to_owned(), Value::from(error));
+                    }
+                }
+
+                let millis = duration.as_millis() as u64;
+                check.insert("duration_ms".to_owned(), Value::from(millis));
+                checks.insert(name, Value::Object(check));
+            }
+
+            let (status, code) = match failures {
+                0 => ("ok", StatusCode::OK),
+                n if n <= threshold.0 => ("degraded", StatusCode::OK),
+                _ => ("unavailable", StatusCode::SERVICE_UNAVAILABLE),
+            };
+
+            let mut body = Map::new();
+            body.insert("status".to_owned(), Value::from(status));
+            body.insert("checks".to_owned(), Value::Object(checks));
+
+            let body = serde_json::to_vec(&body).expect("health serialized to JSON");
+            let response = create_response(&state, code, mime::APPLICATION_JSON, body);
+
+            Ok((state, response))
+        });
+
+        Box::new(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    fn check(health: HealthCheckHandler) -> (StatusCode, Value) {
+        let router = build_simple_router(|route| {
+            route.get("/health").to_new_handler(health);
+        });
+
+        let response = TestServer::new(router)
+            .unwrap()
+            .client()
+            .get("http://localhost/health")
+            .perform()
+            .unwrap();
+
+        let status = response.status();
+        let body = response.read_body().unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn healthy() -> future::FutureResult<ProbeResult, String> {
+        future::ok(ProbeResult::Healthy)
+    }
+
+    fn unhealthy() -> future::FutureResult<ProbeResult, String> {
+        future::ok(ProbeResult::Unhealthy("down".to_owned()))
+    }
+
+    #[test]
+    fn aggregates_probe_results() {
+        let (status, body) = check(HealthCheckHandler::new());
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ok");
+
+        let health = HealthCheckHandler::new()
+            .add_async_probe("a", healthy)
+            .add_async_probe("b", unhealthy)
+            .add_async_probe("c", || future::err::<ProbeResult, _>("refused"))
+            .degraded_threshold(DegradedThreshold(2));
+
+        let (status, body) = check(health.clone());
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "degraded");
+        assert_eq!(body["checks"]["a"]["status"], "ok");
+        assert_eq!(body["checks"]["b"]["status"], "failed");
+        assert_eq!(body["checks"]["b"]["error"], "down");
+        assert_eq!(body["checks"]["c"]["error"], "refused");
+
+        let (status, body) = check(health.degraded_threshold(DegradedThreshold(1)));
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "unavailable");
+    }
+
+    #[test]
+    fn fails_probes_which_time_out() {
+        let health = HealthCheckHandler::new()
+            .add_async_probe("slow", future::empty::<ProbeResult, String>)
+            .add_async_probe("fast", healthy)
+            .probe_timeout(Duration::from_millis(50));
+
+        let (status, body) = check(health);
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["checks"]["slow"]["error"], "timed out");
+        assert_eq!(body["checks"]["fast"]["status"], "ok");
+    }
+}
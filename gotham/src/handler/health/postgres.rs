@@ -0,0 +1,75 @@
+//! Defines a probe which checks the health of a PostgreSQL database via `sqlx`.
+//!
+//! This module is only available with the `sqlx` feature enabled. As `sqlx` is built on
+//! `std::future`, queries run on the `async-std` runtime bundled with `sqlx`, and are bridged
+//! into the futures used by Gotham.
+use std::panic::AssertUnwindSafe;
+
+use futures::Future;
+use futures_util::compat::Compat;
+use futures_util::FutureExt;
+use sqlx_postgres::PgPool;
+
+use crate::handler::health::ProbeResult;
+
+/// Creates a probe which runs `SELECT 1` against a connection from `pool`, for use with
+/// `HealthCheckHandler::add_async_probe`.
+///
+/// The probe succeeds when the query completes, and fails with the database or connection error
+/// otherwise. Connections are returned to the pool once each check completes, so the pool
+/// should usually be the one used by the rest of the application.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate sqlx_postgres as sqlx;
+/// # use gotham::handler::health::postgres::postgres_probe;
+/// # use gotham::handler::health::HealthCheckHandler;
+/// let pool = sqlx::PgPool::connect_lazy("postgres://db.internal/app").unwrap();
+///
+/// let health = HealthCheckHandler::new().add_async_probe("database", postgres_probe(pool));
+/// # let _ = health;
+/// ```
+pub fn postgres_probe(
+    pool: PgPool,
+) -> impl Fn() -> Box<dyn Future<Item = ProbeResult, Error = String> + Send> + Send + Sync {
+    // connections are only handed out under the pool's own locks, so a panic mid-check can't
+    // leave it inconsistent
+    let pool = AssertUnwindSafe(pool);
+
+    move || {
+        let pool = pool.0.clone();
+        let query = async move { sqlx_postgres::query("SELECT 1").execute(&pool).await };
+
+        let f = Compat::new(query.boxed()).then(|result| match result {
+            Ok(_) => Ok(ProbeResult::Healthy),
+            Err(e) => Ok(ProbeResult::Unhealthy(e.to_string())),
+        });
+
+        Box::new(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use sqlx_postgres::postgres::PgPoolOptions;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn fails_when_unreachable() {
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(Duration::from_secs(1))
+            .connect_lazy("postgres://127.0.0.1:1/app")
+            .unwrap();
+        let probe = postgres_probe(pool);
+
+        let mut runtime = Runtime::new().unwrap();
+        match runtime.block_on(probe()).unwrap() {
+            ProbeResult::Unhealthy(_) => (),
+            ProbeResult::Healthy => panic!("unreachable database reported as healthy"),
+        }
+    }
+}
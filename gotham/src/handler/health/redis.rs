@@ -0,0 +1,62 @@
+//! Defines a probe which checks the health of a Redis server.
+//!
+//! This module is only available with the `redis` feature enabled.
+use futures::Future;
+use redis::Client;
+
+use crate::handler::health::ProbeResult;
+
+/// Creates a probe which sends a `PING` to the Redis server described by `client`, for use with
+/// `HealthCheckHandler::add_async_probe`.
+///
+/// The probe succeeds when the server answers with `PONG`, and fails with the reply or
+/// connection error otherwise. A new connection is opened for every run of the probe, so the
+/// check also covers the server accepting connections.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate redis;
+/// # use gotham::handler::health::redis::redis_probe;
+/// # use gotham::handler::health::HealthCheckHandler;
+/// let client = redis::Client::open("redis://cache.internal:6379/").unwrap();
+///
+/// let health = HealthCheckHandler::new().add_async_probe("cache", redis_probe(client));
+/// # let _ = health;
+/// ```
+pub fn redis_probe(
+    client: Client,
+) -> impl Fn() -> Box<dyn Future<Item = ProbeResult, Error = String> + Send> + Send + Sync {
+    move || {
+        let f = client
+            .get_async_connection()
+            .and_then(|connection| redis::cmd("PING").query_async::<_, String>(connection))
+            .then(|result| match result {
+                Ok((_, ref reply)) if reply == "PONG" => Ok(ProbeResult::Healthy),
+                Ok((_, reply)) => Ok(ProbeResult::Unhealthy(format!(
+                    "unexpected reply {}",
+                    reply
+                ))),
+                Err(e) => Ok(ProbeResult::Unhealthy(e.to_string())),
+            });
+
+        Box::new(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn fails_when_unreachable() {
+        let probe = redis_probe(Client::open("redis://127.0.0.1:1/").unwrap());
+
+        let mut runtime = Runtime::new().unwrap();
+        match runtime.block_on(probe()).unwrap() {
+            ProbeResult::Unhealthy(_) => (),
+            ProbeResult::Healthy => panic!("unreachable server reported as healthy"),
+        }
+    }
+}
@@ -15,12 +15,17 @@ use crate::helpers::http::response;
 use crate::state::State;
 
 mod error;
+mod result;
 use crate::error::*;
 
 /// Defines handlers for serving static assets.
 pub mod assets;
 
+/// Defines a handler for reporting the health of an application and its dependencies.
+pub mod health;
+
 pub use self::error::{HandlerError, IntoHandlerError};
+pub use self::result::ResultHandler;
 
 /// A type alias for the trait objects returned by `HandlerService`.
 ///
@@ -290,6 +295,11 @@ impl IntoHandlerFuture for Box<HandlerFuture> {
 /// Represents a type which can be converted to a response. This trait is used in converting the
 /// return type of a function into a response.
 ///
+/// Implementations are provided for common body types such as `String`, `&'static str` and
+/// `Vec<u8>` (sent as `text/plain`), for a bare `StatusCode` (an empty body), for
+/// `(StatusCode, B)` pairs and for a `serde_json::Value` (sent as `application/json`). A
+/// function returning `(State, T)` for any of these types can be used directly as a `Handler`.
+///
 /// # Examples
 ///
 /// ```rust
@@ -382,6 +392,29 @@ where
     }
 }
 
+impl IntoResponse for StatusCode {
+    fn into_response(self, state: &State) -> Response<Body> {
+        response::create_empty_response(state, self)
+    }
+}
+
+impl<B> IntoResponse for (StatusCode, B)
+where
+    B: Into<Body>,
+{
+    fn into_response(self, state: &State) -> Response<Body> {
+        (self.0, mime::TEXT_PLAIN, self.1).into_response(state)
+    }
+}
+
+impl IntoResponse for serde_json::Value {
+    fn into_response(self, state: &State) -> Response<Body> {
+        // serializing a `Value` can't fail, as all keys are strings
+        let body = serde_json::to_vec(&self).expect("Value serialized to JSON");
+        (StatusCode::OK, mime::APPLICATION_JSON, body).into_response(state)
+    }
+}
+
 impl<B> IntoResponse for (StatusCode, Mime, B)
 where
     B: Into<Body>,
@@ -413,3 +446,48 @@ derive_into_response!(&'static str);
 derive_into_response!(&'static [u8]);
 derive_into_response!(Cow<'static, str>);
 derive_into_response!(Cow<'static, [u8]>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::Stream;
+    use hyper::header::{HeaderMap, CONTENT_TYPE};
+    use hyper::Method;
+
+    fn convert<T: IntoResponse>(value: T) -> (Response<Body>, Vec<u8>) {
+        let mut state = State::new();
+        state.put(Method::GET);
+        state.put(HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+
+        let (parts, body) = value.into_response(&state).into_parts();
+        let body = body.concat2().wait().unwrap().to_vec();
+
+        (Response::from_parts(parts, Body::empty()), body)
+    }
+
+    #[test]
+    fn converts_status_codes() {
+        let (response, body) = convert(StatusCode::NO_CONTENT);
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers().get(CONTENT_TYPE), None);
+        assert!(body.is_empty());
+
+        let (response, body) = convert((StatusCode::CREATED, "created".to_owned()));
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/plain");
+        assert_eq!(body, b"created");
+    }
+
+    #[test]
+    fn converts_json_values() {
+        let (response, body) = convert(serde_json::json!({ "id": 1 }));
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(body, br#"{"id":1}"#);
+    }
+}
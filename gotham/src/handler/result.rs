@@ -0,0 +1,100 @@
+//! Defines an adapter for handlers which resolve to application specific error types.
+use futures::{Future, IntoFuture};
+
+use crate::handler::{Handler, HandlerFuture, IntoResponse};
+use crate::state::State;
+
+/// Adapts a function returning a future with an error implementing `IntoResponse`.
+///
+/// The wrapped function is called with the request `State`, and may resolve to any value which
+/// implements `IntoResponse`. Errors are converted into a response via their own `IntoResponse`
+/// implementation, rather than being treated as a `HandlerError`, so a single error type can
+/// describe every failure branch of a handler.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate futures;
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use futures::future;
+/// # use gotham::handler::{IntoResponse, ResultHandler};
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use hyper::{Body, Response, StatusCode};
+/// #
+/// enum ApiError {
+///     NotFound,
+/// }
+///
+/// impl IntoResponse for ApiError {
+///     fn into_response(self, state: &State) -> Response<Body> {
+///         match self {
+///             ApiError::NotFound => {
+///                 create_response(state, StatusCode::NOT_FOUND, mime::TEXT_PLAIN, "not found")
+///             }
+///         }
+///     }
+/// }
+///
+/// fn handler(state: State) -> future::FutureResult<(State, String), (State, ApiError)> {
+///     future::err((state, ApiError::NotFound))
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route.get("/").to(ResultHandler::new(handler));
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::NOT_FOUND);
+/// # assert_eq!(response.read_utf8_body().unwrap(), "not found");
+/// # }
+/// ```
+#[derive(Clone, Copy)]
+pub struct ResultHandler<F> {
+    f: F,
+}
+
+impl<F> ResultHandler<F> {
+    /// Creates a new `ResultHandler` wrapping the provided function.
+    pub fn new(f: F) -> Self {
+        ResultHandler { f }
+    }
+}
+
+impl<F, R, T, E> Handler for ResultHandler<F>
+where
+    F: FnOnce(State) -> R + Send,
+    R: IntoFuture<Item = (State, T), Error = (State, E)>,
+    R::Future: Send + 'static,
+    T: IntoResponse + 'static,
+    E: IntoResponse + 'static,
+{
+    fn handle(self, state: State) -> Box<HandlerFuture> {
+        let f = (self.f)(state).into_future().then(|result| {
+            let (state, response) = match result {
+                Ok((state, t)) => {
+                    let response = t.into_response(&state);
+                    (state, response)
+                }
+                Err((state, e)) => {
+                    let response = e.into_response(&state);
+                    (state, response)
+                }
+            };
+            Ok((state, response))
+        });
+
+        Box::new(f)
+    }
+}
@@ -0,0 +1,362 @@
+//! Helpers for generating and parsing `Content-Disposition` headers.
+//!
+//! Values are generated as described by [RFC 6266](https://tools.ietf.org/html/rfc6266), with
+//! non-ASCII file names encoded using the `filename*` parameter of
+//! [RFC 5987](https://tools.ietf.org/html/rfc5987), alongside an ASCII fallback for older
+//! clients. Parsing accepts the same forms, along with the `form-data` values used within
+//! `multipart/form-data` bodies.
+use std::error::Error;
+use std::fmt::{self, Display, Formatter, Write};
+use std::str::FromStr;
+
+use hyper::header::HeaderValue;
+
+/// The type of a `Content-Disposition` header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DispositionType {
+    /// The content should be displayed by the client, where possible.
+    Inline,
+    /// The content should be downloaded, rather than displayed.
+    Attachment,
+    /// A field within a `multipart/form-data` body.
+    FormData,
+    /// Any other disposition type, in lowercase.
+    Other(String),
+}
+
+/// A parsed `Content-Disposition` header, or a builder of header values.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::helpers::http::header::content_disposition::*;
+/// # fn main() {
+/// let value = ContentDisposition::attachment("résumé.pdf");
+/// assert_eq!(
+///     value,
+///     "attachment; filename=\"r_sum_.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf"
+/// );
+///
+/// let disposition = parse_content_disposition(&value).unwrap();
+/// assert_eq!(disposition.disposition(), &DispositionType::Attachment);
+/// assert_eq!(disposition.filename(), Some("résumé.pdf"));
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContentDisposition {
+    disposition: DispositionType,
+    // parameter names are lowercase, with any `*` suffix kept and values already decoded
+    params: Vec<(String, String)>,
+}
+
+impl ContentDisposition {
+    /// Creates an `attachment` header value, suggesting the provided file name for the download.
+    ///
+    /// File names containing characters other than printable ASCII are sent using the
+    /// `filename*` parameter, with `filename` holding an ASCII approximation.
+    pub fn attachment(filename: &str) -> HeaderValue {
+        let mut value = String::from("attachment; filename=\"");
+
+        for c in filename.chars() {
+            match c {
+                '"' | '\\' => {
+                    value.push('\\');
+                    value.push(c);
+                }
+                ' '..='~' => value.push(c),
+                _ => value.push('_'),
+            }
+        }
+
+        value.push('"');
+
+        if !filename.chars().all(|c| (' '..='~').contains(&c)) {
+            value.push_str("; filename*=UTF-8''");
+            encode_ext_value(&mut value, filename);
+        }
+
+        HeaderValue::from_str(&value).expect("disposition values are always visible ASCII")
+    }
+
+    /// Creates an `inline` header value.
+    pub fn inline() -> HeaderValue {
+        HeaderValue::from_static("inline")
+    }
+
+    /// Returns the disposition type.
+    pub fn disposition(&self) -> &DispositionType {
+        &self.disposition
+    }
+
+    /// Returns the `name` parameter, as used by `form-data` dispositions.
+    pub fn name(&self) -> Option<&str> {
+        self.param("name")
+    }
+
+    /// Returns the file name, preferring the `filename*` parameter when present.
+    pub fn filename(&self) -> Option<&str> {
+        self.param("filename*").or_else(|| self.param("filename"))
+    }
+
+    /// Returns the value of a parameter, matching the name case-insensitively.
+    ///
+    /// Extended parameters are named with their `*` suffix (e.g. `filename*`), and are returned
+    /// already decoded.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Parses a `Content-Disposition` header value.
+pub fn parse_content_disposition(value: &HeaderValue) -> Result<ContentDisposition, ParseError> {
+    value
+        .to_str()
+        .map_err(|_| ParseError::InvalidValue)?
+        .parse()
+}
+
+impl FromStr for ContentDisposition {
+    type Err = ParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = split_params(value).into_iter();
+
+        let disposition = match parts.next() {
+            Some(kind) if !kind.is_empty() => kind.to_ascii_lowercase(),
+            _ => return Err(ParseError::MissingType),
+        };
+
+        let disposition = match disposition.as_str() {
+            "inline" => DispositionType::Inline,
+            "attachment" => DispositionType::Attachment,
+            "form-data" => DispositionType::FormData,
+            _ => DispositionType::Other(disposition),
+        };
+
+        let mut params = Vec::new();
+
+        for param in parts.filter(|param| !param.is_empty()) {
+            let index = param.find('=').ok_or(ParseError::MalformedParameter)?;
+            let key = param[..index].trim().to_ascii_lowercase();
+            let value = param[index + 1..].trim();
+
+            if key.is_empty() {
+                return Err(ParseError::MalformedParameter);
+            }
+
+            let value = if key.ends_with('*') {
+                decode_ext_value(value)?
+            } else {
+                unquote(value)
+            };
+
+            params.push((key, value));
+        }
+
+        Ok(ContentDisposition {
+            disposition,
+            params,
+        })
+    }
+}
+
+/// The reasons a `Content-Disposition` value may fail to parse.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The value contains characters which aren't valid within a header.
+    InvalidValue,
+    /// The value doesn't begin with a disposition type.
+    MissingType,
+    /// A parameter isn't of the form `name=value`.
+    MalformedParameter,
+    /// An extended (`name*`) parameter isn't correctly encoded, or uses an unknown charset.
+    InvalidEncoding,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ParseError::InvalidValue => "invalid content disposition value",
+            ParseError::MissingType => "missing content disposition type",
+            ParseError::MalformedParameter => "malformed content disposition parameter",
+            ParseError::InvalidEncoding => "invalid content disposition parameter encoding",
+        })
+    }
+}
+
+impl Error for ParseError {}
+
+/// Percent-encodes a value using the `attr-char` set of RFC 5987.
+fn encode_ext_value(out: &mut String, value: &str) {
+    for byte in value.bytes() {
+        match byte {
+            b'a'..=b'z'
+            | b'A'..=b'Z'
+            | b'0'..=b'9'
+            | b'!'
+            | b'#'
+            | b'$'
+            | b'&'
+            | b'+'
+            | b'-'
+            | b'.'
+            | b'^'
+            | b'_'
+            | b'`'
+            | b'|'
+            | b'~' => out.push(byte as char),
+            _ => {
+                let _ = write!(out, "%{:02X}", byte);
+            }
+        }
+    }
+}
+
+/// Decodes an RFC 5987 `charset'language'value` parameter.
+fn decode_ext_value(value: &str) -> Result<String, ParseError> {
+    let mut parts = value.splitn(3, '\'');
+
+    let (charset, encoded) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(charset), Some(_language), Some(encoded)) => (charset, encoded),
+        _ => return Err(ParseError::InvalidEncoding),
+    };
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+
+    while let Some(byte) = iter.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+
+        let hex = [
+            iter.next().ok_or(ParseError::InvalidEncoding)?,
+            iter.next().ok_or(ParseError::InvalidEncoding)?,
+        ];
+
+        let hex = std::str::from_utf8(&hex).map_err(|_| ParseError::InvalidEncoding)?;
+        bytes.push(u8::from_str_radix(hex, 16).map_err(|_| ParseError::InvalidEncoding)?);
+    }
+
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8(bytes).map_err(|_| ParseError::InvalidEncoding)
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Ok(bytes.into_iter().map(char::from).collect())
+    } else {
+        Err(ParseError::InvalidEncoding)
+    }
+}
+
+/// Splits a header value on semicolons, ignoring those within quoted strings.
+fn split_params(value: &str) -> Vec<&str> {
+    let mut params = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (index, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                params.push(value[start..index].trim());
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+
+    params.push(value[start..].trim());
+    params
+}
+
+/// Removes the quotes (and any escapes) from a quoted string.
+fn unquote(value: &str) -> String {
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return value.to_owned();
+    }
+
+    let mut out = String::with_capacity(value.len());
+    let mut escaped = false;
+
+    for c in value[1..value.len() - 1].chars() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            c => {
+                escaped = false;
+                out.push(c);
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_header_values() {
+        assert_eq!(ContentDisposition::inline(), "inline");
+        assert_eq!(
+            ContentDisposition::attachment("report \"final\".csv"),
+            "attachment; filename=\"report \\\"final\\\".csv\""
+        );
+        assert_eq!(
+            ContentDisposition::attachment("数据.txt"),
+            "attachment; filename=\"__.txt\"; filename*=UTF-8''%E6%95%B0%E6%8D%AE.txt"
+        );
+    }
+
+    #[test]
+    fn parses_header_values() {
+        let value = HeaderValue::from_static(
+            "Attachment; filename=\"a; \\\"b\\\".txt\"; filename*=iso-8859-1'en'%A3%20rates.txt",
+        );
+        let disposition = parse_content_disposition(&value).unwrap();
+
+        assert_eq!(disposition.disposition(), &DispositionType::Attachment);
+        assert_eq!(disposition.param("FILENAME"), Some("a; \"b\".txt"));
+        assert_eq!(disposition.filename(), Some("£ rates.txt"));
+
+        let disposition: ContentDisposition = "form-data; name=field".parse().unwrap();
+        assert_eq!(disposition.disposition(), &DispositionType::FormData);
+        assert_eq!(disposition.name(), Some("field"));
+        assert_eq!(disposition.filename(), None);
+
+        let disposition: ContentDisposition = "x-custom".parse().unwrap();
+        assert_eq!(
+            disposition.disposition(),
+            &DispositionType::Other("x-custom".to_owned())
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        let parse = |value: &str| value.parse::<ContentDisposition>().err();
+
+        assert_eq!(parse(""), Some(ParseError::MissingType));
+        assert_eq!(parse("; name=a"), Some(ParseError::MissingType));
+        assert_eq!(
+            parse("attachment; filename"),
+            Some(ParseError::MalformedParameter)
+        );
+        assert_eq!(
+            parse("attachment; filename*=%FF"),
+            Some(ParseError::InvalidEncoding)
+        );
+        assert_eq!(
+            parse("attachment; filename*=UTF-8''%FF"),
+            Some(ParseError::InvalidEncoding)
+        );
+        assert_eq!(
+            parse("attachment; filename*=koi8-r''abc"),
+            Some(ParseError::InvalidEncoding)
+        );
+    }
+}
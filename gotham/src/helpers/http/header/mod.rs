@@ -1,8 +1,14 @@
 //! Headers recognised by Gotham which do not exist in the standard headers
-//! provided by the Hyper library.
+//! provided by the Hyper library, along with helpers for working with header values.
+
+pub mod content_disposition;
 
 /// Marks the identifier of a request to a Gotham server.
 pub const X_REQUEST_ID: &str = "x-request-id";
 
 /// Marks the execution time of a Gotham request.
 pub const X_RUNTIME_DURATION: &str = "x-runtime-duration";
+
+/// Reports server side timing metrics, as defined by the
+/// [Server Timing](https://www.w3.org/TR/server-timing/) specification.
+pub const SERVER_TIMING: &str = "server-timing";
@@ -0,0 +1,74 @@
+//! Helpers for accessing the body of a request.
+
+use std::mem;
+
+use hyper::Body;
+
+use crate::state::State;
+
+/// Takes the request `Body` from `State`, without buffering it.
+///
+/// The body is returned as the stream of chunks received from the client, so that it can be
+/// forwarded elsewhere (such as to an upstream server) without ever being held in memory in
+/// full.
+///
+/// The body can only be taken once. An empty `Body` is left in its place, so that `State` still
+/// holds a body for any later middleware; taking the body again returns this empty `Body`.
+///
+/// ```rust
+/// # extern crate futures;
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use futures::{Future, Stream};
+/// # use hyper::{Body, Response};
+/// # use gotham::handler::{HandlerFuture, IntoHandlerError};
+/// # use gotham::helpers::http::request::body::take_body;
+/// # use gotham::state::State;
+/// #
+/// fn my_handler(state: State) -> Box<HandlerFuture> {
+///     let (state, body) = take_body(state);
+///
+///     // each chunk is handled as it arrives, such as by writing it upstream
+///     let f = body
+///         .fold(0, |total, chunk| Ok::<_, hyper::Error>(total + chunk.len()))
+///         .then(|result| match result {
+///             Ok(total) => Ok((state, Response::new(Body::from(total.to_string())))),
+///             Err(e) => Err((state, e.into_handler_error())),
+///         });
+///
+///     Box::new(f)
+/// }
+/// # fn main() { let _ = my_handler; }
+/// ```
+pub fn take_body(mut state: State) -> (State, Body) {
+    let body = match state.try_borrow_mut::<Body>() {
+        Some(body) => mem::replace(body, Body::empty()),
+        None => Body::empty(),
+    };
+
+    (state, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{Future, Stream};
+
+    #[test]
+    fn takes_body_once() {
+        let mut state = State::new();
+        state.put(Body::from("streamed"));
+
+        let (state, body) = take_body(state);
+        assert_eq!(&body.concat2().wait().unwrap()[..], b"streamed");
+
+        let (state, body) = take_body(state);
+        assert!(body.concat2().wait().unwrap().is_empty());
+        assert!(state.has::<Body>());
+
+        let (_, body) = take_body(State::new());
+        assert!(body.concat2().wait().unwrap().is_empty());
+    }
+}
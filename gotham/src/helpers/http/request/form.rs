@@ -0,0 +1,452 @@
+//! Defines helpers for receiving `application/x-www-form-urlencoded` request bodies, as sent by
+//! HTML forms.
+//!
+//! The body is buffered in full and deserialized into an application-provided struct using the
+//! same rules as a `QueryStringExtractor`, so `Option<T>` fields may be omitted and `Vec<T>`
+//! fields collect each value of a repeated key. The result is placed into `State`.
+//!
+//! Forms are usually extracted for a route via `with_form_extractor`, which rejects invalid
+//! bodies before the handler runs. Handlers which need to decide for themselves whether (or
+//! when) to read the body can call `parse_form` instead.
+use std::io;
+use std::marker::PhantomData;
+
+use futures::{future, Future, Stream};
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, HeaderMap, StatusCode};
+use log::{debug, trace};
+use mime::Mime;
+use serde::Deserialize;
+
+use crate::extractor::internal::{self, ExtractorError};
+use crate::handler::{HandlerError, HandlerFuture, IntoHandlerError};
+use crate::helpers::http::request::query_string::QueryStringMapping;
+use crate::helpers::http::response::create_response;
+use crate::helpers::http::{form_url_decode, FormUrlDecoded};
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+/// The reason a form body was rejected, with the status to respond with.
+struct Rejection {
+    status: StatusCode,
+    message: String,
+}
+
+type FormFuture = Box<dyn Future<Item = State, Error = (State, Rejection)> + Send>;
+
+/// Parses the `application/x-www-form-urlencoded` request body in `State`, storing the result as
+/// the provided type `T`.
+///
+/// The type is typically a struct deriving `Deserialize` and `StateData`, and supports the same
+/// field types as a `QueryStringExtractor`. Keys in the body which don't match a field are
+/// ignored.
+///
+/// The returned future fails with a `HandlerError` carrying the appropriate status when:
+///
+/// * the request isn't `application/x-www-form-urlencoded`, with a
+///   `415 Unsupported Media Type`;
+/// * the body isn't correctly encoded, or a field is missing or has a value which can't be
+///   converted to the type of the field, with a `400 Bad Request`. The error names the field.
+///
+/// ```rust
+/// # extern crate futures;
+/// # extern crate gotham;
+/// # #[macro_use]
+/// # extern crate gotham_derive;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// # extern crate serde;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// #
+/// # use futures::Future;
+/// # use gotham::handler::HandlerFuture;
+/// # use gotham::helpers::http::request::form::parse_form;
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// # use hyper::StatusCode;
+/// #
+/// #[derive(Deserialize, StateData)]
+/// struct Signup {
+///     name: String,
+///     age: u8,
+///     referrer: Option<String>,
+///     interests: Vec<String>,
+/// }
+///
+/// fn signup(state: State) -> Box<HandlerFuture> {
+///     let f = parse_form::<Signup>(state).map(|state| {
+///         let body = {
+///             let signup = Signup::borrow_from(&state);
+///             let referrer = signup.referrer.as_ref().map_or("nobody", String::as_str);
+///
+///             format!(
+///                 "{} ({}), referred by {}, likes {}",
+///                 signup.name,
+///                 signup.age,
+///                 referrer,
+///                 signup.interests.join(", ")
+///             )
+///         };
+///
+///         let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, body);
+///         (state, response)
+///     });
+///
+///     Box::new(f)
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(build_simple_router(|route| {
+/// #       route.post("/signup").to(signup);
+/// #   }))
+/// #   .unwrap();
+/// #
+/// #   let response = test_server
+/// #       .client()
+/// #       .post(
+/// #           "http://localhost/signup",
+/// #           "name=Jane+Doe&age=30&interests=rust&interests=http",
+/// #           mime::APPLICATION_WWW_FORM_URLENCODED,
+/// #       )
+/// #       .perform()
+/// #       .unwrap();
+/// #
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(
+/// #       response.read_utf8_body().unwrap(),
+/// #       "Jane Doe (30), referred by nobody, likes rust, http"
+/// #   );
+/// # }
+/// ```
+pub fn parse_form<T>(
+    state: State,
+) -> Box<dyn Future<Item = State, Error = (State, HandlerError)> + Send>
+where
+    T: for<'de> Deserialize<'de> + StateData,
+{
+    let f = read_form::<T>(state).map_err(|(state, rejection)| {
+        let err = invalid(rejection.message)
+            .into_handler_error()
+            .with_status(rejection.status);
+        (state, err)
+    });
+
+    Box::new(f)
+}
+
+/// Middleware binding to parse a form body into `T` for a single route, as added by
+/// `with_form_extractor`.
+///
+/// Requests which aren't `application/x-www-form-urlencoded` are answered with a
+/// `415 Unsupported Media Type`, and bodies which can't be parsed into `T` with a
+/// `400 Bad Request` naming the field at fault, without reaching the handler.
+pub struct FormExtractorMiddleware<T> {
+    _extractor: PhantomData<fn() -> T>,
+}
+
+impl<T> FormExtractorMiddleware<T>
+where
+    T: for<'de> Deserialize<'de> + StateData,
+{
+    /// Creates a new `FormExtractorMiddleware` for the form type.
+    pub fn new() -> Self {
+        FormExtractorMiddleware {
+            _extractor: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for FormExtractorMiddleware<T>
+where
+    T: for<'de> Deserialize<'de> + StateData,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for FormExtractorMiddleware<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for FormExtractorMiddleware<T> {}
+
+/// `Middleware` trait implementation.
+impl<T> Middleware for FormExtractorMiddleware<T>
+where
+    T: for<'de> Deserialize<'de> + StateData,
+{
+    /// Stores the parsed form in `State`, or answers with the reason it was rejected.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        let f = read_form::<T>(state).then(|result| match result {
+            Ok(state) => chain(state),
+            Err((state, rejection)) => {
+                debug!(
+                    "[{}] form extractor failed: {}",
+                    request_id(&state),
+                    rejection.message
+                );
+                let response = create_response(
+                    &state,
+                    rejection.status,
+                    mime::TEXT_PLAIN,
+                    rejection.message,
+                );
+                Box::new(future::ok((state, response)))
+            }
+        });
+
+        Box::new(f)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl<T> NewMiddleware for FormExtractorMiddleware<T>
+where
+    T: for<'de> Deserialize<'de> + StateData,
+{
+    type Instance = Self;
+
+    /// Copies the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+/// Reads the form body in `State` into `T`, describing why it was rejected otherwise.
+fn read_form<T>(mut state: State) -> FormFuture
+where
+    T: for<'de> Deserialize<'de> + StateData,
+{
+    if !is_form(HeaderMap::borrow_from(&state)) {
+        let rejection = Rejection {
+            status: StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            message: "request is not application/x-www-form-urlencoded".to_owned(),
+        };
+        return Box::new(future::err((state, rejection)));
+    }
+
+    let body = state.take::<Body>();
+
+    let f = body.concat2().then(move |result| {
+        let parsed = match result {
+            Ok(chunk) => split(&chunk).and_then(|mapping| {
+                internal::from_query_string_mapping::<T>(&mapping).map_err(describe)
+            }),
+            Err(e) => Err(format!("unable to read form body: {}", e)),
+        };
+
+        match parsed {
+            Ok(form) => {
+                state.put(form);
+                Ok(state)
+            }
+            Err(message) => {
+                trace!(
+                    "[{}] unable to parse form body: {}",
+                    request_id(&state),
+                    message
+                );
+
+                let rejection = Rejection {
+                    status: StatusCode::BAD_REQUEST,
+                    message,
+                };
+                Err((state, rejection))
+            }
+        }
+    });
+
+    Box::new(f)
+}
+
+/// Determines whether a request has an `application/x-www-form-urlencoded` body.
+fn is_form(headers: &HeaderMap) -> bool {
+    let mime = headers
+        .get(CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .and_then(|ct| ct.parse::<Mime>().ok());
+
+    match mime {
+        Some(mime) => mime.essence_str() == mime::APPLICATION_WWW_FORM_URLENCODED.essence_str(),
+        None => false,
+    }
+}
+
+/// Splits a form body into a mapping of keys to their values.
+///
+/// Unlike a query string, a pair which doesn't decode to valid UTF-8 is rejected rather than
+/// skipped, naming the field it belongs to where possible.
+fn split(body: &[u8]) -> Result<QueryStringMapping, String> {
+    let body = std::str::from_utf8(body).map_err(|_| "form body is not valid UTF-8".to_owned())?;
+    let mut mapping = QueryStringMapping::new();
+
+    for pair in body.split('&').filter(|pair| !pair.is_empty()) {
+        let mut parts = pair.splitn(2, '=');
+        let (k, v) = (parts.next().unwrap(), parts.next().unwrap_or(""));
+
+        let key =
+            form_url_decode(k).map_err(|_| "form field name is not valid UTF-8".to_owned())?;
+        let value = match FormUrlDecoded::new(v) {
+            Some(value) => value,
+            None => return Err(format!("form field `{}` is not valid UTF-8", key)),
+        };
+
+        mapping.entry(key).or_default().push(value);
+    }
+
+    Ok(mapping)
+}
+
+/// Describes why a form body could not be deserialized.
+fn describe(error: ExtractorError) -> String {
+    match error {
+        ExtractorError::InvalidField(field, error) => match *error {
+            ExtractorError::ParseError(message) | ExtractorError::Custom(message) => {
+                format!("form field `{}` is invalid: {}", field, message)
+            }
+            ExtractorError::MultipleValues => {
+                format!("form field `{}` must not be repeated", field)
+            }
+            error => format!("form field `{}` is invalid: {:?}", field, error),
+        },
+        ExtractorError::Custom(message) => format!("form body is invalid: {}", message),
+        error => format!("form body is invalid: {:?}", error),
+    }
+}
+
+/// Creates an error describing why a body was rejected.
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::HeaderValue;
+    use hyper::{Method, Response};
+    use serde_derive::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Signup {
+        name: String,
+        age: u8,
+        referrer: Option<String>,
+        interests: Vec<String>,
+    }
+
+    impl StateData for Signup {}
+
+    fn parse(
+        content_type: &'static str,
+        body: &'static str,
+    ) -> Result<State, (StatusCode, String)> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+
+        let mut state = State::new();
+        state.put(headers);
+        state.put(Body::from(body));
+        crate::state::set_request_id(&mut state);
+
+        parse_form::<Signup>(state)
+            .wait()
+            .map_err(|(_, err)| (err.status(), format!("{:?}", err)))
+    }
+
+    #[test]
+    fn parses_form_bodies() {
+        let state = parse(
+            "application/x-www-form-urlencoded; charset=utf-8",
+            "name=Jane+Doe&age=30&interests=rust&interests=caf%C3%A9&other=1",
+        )
+        .unwrap();
+
+        let signup = Signup::borrow_from(&state);
+        assert_eq!(signup.name, "Jane Doe");
+        assert_eq!(signup.age, 30);
+        assert_eq!(signup.referrer, None);
+        assert_eq!(signup.interests, vec!["rust", "café"]);
+    }
+
+    fn extract(content_type: &'static str, body: &'static str) -> (StatusCode, String) {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+
+        let mut state = State::new();
+        state.put(Method::POST);
+        state.put(headers);
+        state.put(Body::from(body));
+        crate::state::set_request_id(&mut state);
+
+        let (_, response) = FormExtractorMiddleware::<Signup>::new()
+            .call(state, |state| {
+                let body = Signup::borrow_from(&state).name.clone();
+                Box::new(future::ok((state, Response::new(Body::from(body)))))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap();
+
+        let status = response.status();
+        let body = response.into_body().concat2().wait().unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn extracts_forms_for_routes() {
+        let form = "application/x-www-form-urlencoded";
+
+        assert_eq!(
+            extract(form, "name=Jane+Doe&age=30&interests=rust"),
+            (StatusCode::OK, "Jane Doe".to_owned())
+        );
+        assert_eq!(
+            extract(form, "name=Jane&age=old&interests=rust"),
+            (
+                StatusCode::BAD_REQUEST,
+                "form field `age` is invalid: invalid digit found in string".to_owned()
+            )
+        );
+        assert_eq!(
+            extract("text/plain", "name=Jane&age=30"),
+            (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "request is not application/x-www-form-urlencoded".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_form_bodies() {
+        let form = "application/x-www-form-urlencoded";
+
+        let (status, _) = parse("application/json", "{}").err().unwrap();
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let (status, message) = parse(form, "name=Jane&age=old&interests=").err().unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("form field `age` is invalid"));
+
+        let (status, message) = parse(form, "name=%FF&age=30&interests=").err().unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("form field `name` is not valid UTF-8"));
+
+        let (status, message) = parse(form, "name=Jane&name=Joe&age=30").err().unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("form field `name` must not be repeated"));
+
+        let (status, message) = parse(form, "name=Jane&interests=rust").err().unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("form body is invalid: missing field `age`"));
+    }
+}
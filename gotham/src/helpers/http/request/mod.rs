@@ -1,4 +1,9 @@
 //! Helpers for HTTP request handling
 
+pub mod body;
+pub mod form;
+pub mod multipart;
 pub mod path;
+pub mod precondition;
 pub mod query_string;
+pub mod version;
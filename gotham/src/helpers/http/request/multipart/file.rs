@@ -0,0 +1,70 @@
+//! Defines the `UploadedFile` type, for files received in a multipart request.
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use mime::Mime;
+use tempfile::NamedTempFile;
+
+/// A file received as part of a multipart request, stored in a temporary file.
+///
+/// The temporary file is deleted when the `UploadedFile` is dropped, which happens alongside the
+/// request `State` for files parsed by `parse_multipart`. Files which need to outlive the request
+/// should be copied elsewhere by the handler.
+#[derive(Debug)]
+pub struct UploadedFile {
+    file: NamedTempFile,
+    original_name: Option<String>,
+    content_type: Mime,
+    size: u64,
+}
+
+impl UploadedFile {
+    /// Creates a new `UploadedFile` from a completed temporary file.
+    pub(super) fn new(
+        file: NamedTempFile,
+        original_name: Option<String>,
+        content_type: Mime,
+        size: u64,
+    ) -> Self {
+        UploadedFile {
+            file,
+            original_name,
+            content_type,
+            size,
+        }
+    }
+
+    /// Returns the file name provided by the client, if any.
+    ///
+    /// This value is entirely under the control of the client, and should never be used as a
+    /// path without being sanitized first.
+    pub fn original_name(&self) -> Option<&str> {
+        self.original_name.as_deref()
+    }
+
+    /// Returns the content type of the file.
+    ///
+    /// This is the type declared by the client, unless it was missing or generic; in which case
+    /// it's detected via `gotham::helpers::mime`, defaulting to `application/octet-stream`.
+    pub fn content_type(&self) -> &Mime {
+        &self.content_type
+    }
+
+    /// Returns the size of the file, in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the path of the temporary file containing the uploaded data.
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    /// Opens a new read-only handle to the uploaded data, starting at the beginning of the file.
+    ///
+    /// This allows the file to be streamed elsewhere without loading it into memory.
+    pub fn open(&self) -> io::Result<File> {
+        self.file.reopen()
+    }
+}
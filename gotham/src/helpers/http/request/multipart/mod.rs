@@ -0,0 +1,499 @@
+//! Defines helpers for receiving `multipart/form-data` request bodies, including file uploads.
+//!
+//! Bodies are parsed as they arrive via `parse_multipart`, with any files written directly to
+//! temporary files rather than being buffered in memory. The parsed `Multipart` is placed into
+//! `State`, and its temporary files are removed once the `State` is dropped.
+use std::io;
+use std::path::PathBuf;
+
+use futures::{future, Future, Stream};
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, HeaderMap, StatusCode};
+use log::trace;
+use mime::Mime;
+use tempfile::NamedTempFile;
+
+use crate::handler::{HandlerError, IntoHandlerError};
+use crate::state::{request_id, FromState, State, StateData};
+
+mod file;
+mod parser;
+
+pub use self::file::UploadedFile;
+
+use self::parser::{Failure, Parser};
+
+// the default limit placed on each uploaded file, 10MB
+const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+// the default limit placed on each text field, 64KB
+const DEFAULT_MAX_FIELD_SIZE: usize = 64 * 1024;
+
+/// Limits applied to the parts of a multipart body as it's received.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate mime;
+/// # use gotham::helpers::http::request::multipart::UploadConfig;
+/// let config = UploadConfig::new()
+///     .max_file_size(10 * 1024 * 1024)
+///     .allowed_types(vec![mime::IMAGE_JPEG, mime::IMAGE_PNG])
+///     .temp_dir("/var/tmp/uploads");
+/// # let _ = config;
+/// ```
+#[derive(Clone, Debug)]
+pub struct UploadConfig {
+    max_file_size: u64,
+    max_field_size: usize,
+    allowed_types: Vec<Mime>,
+    temp_dir: Option<PathBuf>,
+}
+
+impl UploadConfig {
+    /// Creates a new `UploadConfig`, limiting files to 10MB and text fields to 64KB.
+    ///
+    /// Files of any content type are accepted by default.
+    pub fn new() -> Self {
+        UploadConfig {
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            max_field_size: DEFAULT_MAX_FIELD_SIZE,
+            allowed_types: Vec::new(),
+            temp_dir: None,
+        }
+    }
+
+    /// Sets the maximum size, in bytes, of each uploaded file.
+    ///
+    /// A request containing a larger file is rejected with a `413 Payload Too Large`.
+    pub fn max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// Sets the maximum size, in bytes, of each text field.
+    ///
+    /// A request containing a larger field is rejected with a `413 Payload Too Large`.
+    pub fn max_field_size(mut self, max_field_size: usize) -> Self {
+        self.max_field_size = max_field_size;
+        self
+    }
+
+    /// Restricts uploaded files to the provided content types.
+    ///
+    /// Types are compared without their parameters, so `text/plain` also allows files sent as
+    /// `text/plain; charset=utf-8`. A request containing a file of any other type is rejected
+    /// with a `415 Unsupported Media Type`. An empty list allows files of any type.
+    pub fn allowed_types(mut self, allowed_types: Vec<Mime>) -> Self {
+        self.allowed_types = allowed_types;
+        self
+    }
+
+    /// Sets the directory that uploaded files are written to while they're received.
+    ///
+    /// Files are written to the system temporary directory by default. Placing them on the same
+    /// filesystem as their final destination allows handlers to move, rather than copy, large
+    /// uploads once the request has been validated.
+    pub fn temp_dir<P>(mut self, temp_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.temp_dir = Some(temp_dir.into());
+        self
+    }
+
+    /// Creates the temporary file an uploaded file is written to.
+    fn temp_file(&self) -> io::Result<NamedTempFile> {
+        match self.temp_dir {
+            Some(ref temp_dir) => NamedTempFile::new_in(temp_dir),
+            None => NamedTempFile::new(),
+        }
+    }
+
+    /// Determines whether a file of the provided type may be uploaded.
+    fn accepts(&self, content_type: &Mime) -> bool {
+        self.allowed_types.is_empty()
+            || self
+                .allowed_types
+                .iter()
+                .any(|allowed| allowed.essence_str() == content_type.essence_str())
+    }
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The fields and files received in a `multipart/form-data` request body.
+///
+/// Parts are kept in the order they were received. Files are stored in temporary files which
+/// are deleted when the `Multipart` is dropped, alongside the request `State`.
+#[derive(Debug, Default)]
+pub struct Multipart {
+    fields: Vec<(String, String)>,
+    files: Vec<(String, UploadedFile)>,
+}
+
+impl Multipart {
+    /// Returns the value of the first text field with the provided name.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns all text fields, as pairs of names and values.
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+
+    /// Returns the first file uploaded with the provided field name.
+    pub fn file(&self, name: &str) -> Option<&UploadedFile> {
+        self.files
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, file)| file)
+    }
+
+    /// Returns all uploaded files, as pairs of field names and files.
+    pub fn files(&self) -> &[(String, UploadedFile)] {
+        &self.files
+    }
+}
+
+impl StateData for Multipart {}
+
+/// Parses the `multipart/form-data` request body in `State`, storing the result as a `Multipart`.
+///
+/// Each part with a `filename` is written to a temporary file as it arrives, and is available
+/// as an `UploadedFile`; all other parts are treated as text fields. Files are validated against
+/// the `UploadConfig` while they're received, so a request can be rejected without reading the
+/// rest of the body. Files sent without a specific content type have their type detected from
+/// the file name and the leading bytes of their contents, using `gotham::helpers::mime`.
+///
+/// The returned future fails with a `HandlerError` carrying the appropriate status when:
+///
+/// * the request isn't `multipart/form-data`, or a file has a type which isn't allowed, with a
+///   `415 Unsupported Media Type`;
+/// * a file or field exceeds the configured size, with a `413 Payload Too Large`;
+/// * the body is malformed, with a `400 Bad Request`.
+///
+/// ```rust
+/// # extern crate futures;
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use futures::Future;
+/// # use gotham::handler::HandlerFuture;
+/// # use gotham::helpers::http::request::multipart::{parse_multipart, Multipart, UploadConfig};
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::state::{FromState, State};
+/// # use hyper::StatusCode;
+/// #
+/// fn upload(state: State) -> Box<HandlerFuture> {
+///     let config = UploadConfig::new().allowed_types(vec![mime::IMAGE_PNG]);
+///
+///     let f = parse_multipart(state, &config).map(|state| {
+///         let size = Multipart::borrow_from(&state)
+///             .file("avatar")
+///             .map(|file| file.size())
+///             .unwrap_or(0);
+///
+///         let body = format!("received {} bytes", size);
+///         let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, body);
+///         (state, response)
+///     });
+///
+///     Box::new(f)
+/// }
+/// # fn main() { let _ = upload; }
+/// ```
+pub fn parse_multipart(
+    mut state: State,
+    config: &UploadConfig,
+) -> Box<dyn Future<Item = State, Error = (State, HandlerError)> + Send> {
+    let boundary = match boundary(HeaderMap::borrow_from(&state)) {
+        Some(boundary) => boundary,
+        None => {
+            let err = invalid("request is not multipart/form-data")
+                .into_handler_error()
+                .with_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            return Box::new(future::err((state, err)));
+        }
+    };
+
+    let parser = Parser::new(&boundary, config.clone());
+    let body = state.take::<Body>();
+
+    let f = body
+        .map_err(Failure::Read)
+        .fold(parser, Parser::feed_chunk)
+        .and_then(Parser::finish)
+        .then(move |result| match result {
+            Ok(multipart) => {
+                trace!(
+                    "[{}] received {} multipart fields and {} files",
+                    request_id(&state),
+                    multipart.fields.len(),
+                    multipart.files.len()
+                );
+
+                state.put(multipart);
+                Ok(state)
+            }
+            Err(failure) => {
+                trace!(
+                    "[{}] unable to parse multipart body: {:?}",
+                    request_id(&state),
+                    failure
+                );
+
+                let err = match failure {
+                    Failure::Read(e) => e.into_handler_error().with_status(StatusCode::BAD_REQUEST),
+                    Failure::Io(e) => e.into_handler_error(),
+                    Failure::Invalid(message) => invalid(message)
+                        .into_handler_error()
+                        .with_status(StatusCode::BAD_REQUEST),
+                    Failure::TooLarge => invalid("multipart part too large")
+                        .into_handler_error()
+                        .with_status(StatusCode::PAYLOAD_TOO_LARGE),
+                    Failure::UnsupportedType => invalid("file type not allowed")
+                        .into_handler_error()
+                        .with_status(StatusCode::UNSUPPORTED_MEDIA_TYPE),
+                };
+
+                Err((state, err))
+            }
+        });
+
+    Box::new(f)
+}
+
+/// Returns the boundary of a `multipart/form-data` request, if the request is one.
+pub(crate) fn boundary(headers: &HeaderMap) -> Option<String> {
+    let mime = headers
+        .get(CONTENT_TYPE)?
+        .to_str()
+        .ok()?
+        .parse::<Mime>()
+        .ok()?;
+
+    if mime.type_() != mime::MULTIPART || mime.subtype() != mime::FORM_DATA {
+        return None;
+    }
+
+    mime.get_param(mime::BOUNDARY)
+        .map(|boundary| boundary.as_str().to_owned())
+}
+
+/// Creates an error describing why a body was rejected.
+fn invalid(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::io::Read;
+
+    use hyper::header::HeaderValue;
+
+    const BODY: &[u8] = b"preamble\r\n\
+                        --XyZ\r\n\
+                        Content-Disposition: form-data; name=\"title\"\r\n\
+                        \r\n\
+                        Holiday\r\n\
+                        --XyZ\r\n\
+                        Content-Disposition: form-data; name=\"photo\"; filename=\"beach; 1.png\"\r\n\
+                        Content-Type: image/png\r\n\
+                        \r\n\
+                        \x89PNG\r\n--XY\r\n\
+                        --XyZ--\r\n\
+                        epilogue";
+
+    fn state(body: Body) -> State {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("multipart/form-data; boundary=XyZ"),
+        );
+
+        let mut state = State::new();
+        state.put(headers);
+        state.put(body);
+        crate::state::set_request_id(&mut state);
+        state
+    }
+
+    fn parse(body: Body, config: &UploadConfig) -> Result<State, StatusCode> {
+        parse_multipart(state(body), config)
+            .wait()
+            .map_err(|(_, err)| err.status())
+    }
+
+    #[test]
+    fn parses_fields_and_files() {
+        let state = parse(Body::from(BODY), &UploadConfig::new()).unwrap();
+        let multipart = Multipart::borrow_from(&state);
+
+        assert_eq!(multipart.field("title"), Some("Holiday"));
+        assert_eq!(multipart.fields().len(), 1);
+
+        let file = multipart.file("photo").unwrap();
+        assert_eq!(file.original_name(), Some("beach; 1.png"));
+        assert_eq!(file.content_type(), &mime::IMAGE_PNG);
+        assert_eq!(file.size(), 10);
+        assert_eq!(fs::read(file.path()).unwrap(), b"\x89PNG\r\n--XY");
+
+        // temporary files are removed alongside the state
+        let path = file.path().to_owned();
+        drop(state);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn writes_files_on_the_thread_pool() {
+        let f = parse_multipart(state(Body::from(BODY)), &UploadConfig::new());
+        let state = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(f)
+            .map_err(|_| ())
+            .unwrap();
+
+        let file = Multipart::borrow_from(&state).file("photo").unwrap();
+        assert_eq!(fs::read(file.path()).unwrap(), b"\x89PNG\r\n--XY");
+    }
+
+    #[test]
+    fn parses_bodies_split_across_chunks() {
+        let chunks: Vec<Vec<u8>> = BODY.iter().map(|byte| vec![*byte]).collect();
+
+        let body = Body::wrap_stream(futures::stream::iter_ok::<_, hyper::Error>(chunks));
+        let state = parse(body, &UploadConfig::new()).unwrap();
+        let multipart = Multipart::borrow_from(&state);
+
+        assert_eq!(multipart.field("title"), Some("Holiday"));
+        assert_eq!(
+            fs::read(multipart.file("photo").unwrap().path()).unwrap(),
+            b"\x89PNG\r\n--XY"
+        );
+    }
+
+    #[test]
+    fn detects_generic_content_types() {
+        let body = b"--XyZ\r\n\
+                     Content-Disposition: form-data; name=\"notes\"; filename=\"notes.txt\"\r\n\
+                     Content-Type: application/octet-stream\r\n\
+                     \r\n\
+                     Notes\r\n\
+                     --XyZ\r\n\
+                     Content-Disposition: form-data; name=\"blob\"; filename=\"blob\"\r\n\
+                     \r\n\
+                     \x89PNG\r\n\x1a\n\0\0\0\rIHDR\r\n\
+                     --XyZ--\r\n";
+
+        let state = parse(Body::from(&body[..]), &UploadConfig::new()).unwrap();
+        let multipart = Multipart::borrow_from(&state);
+
+        let notes = multipart.file("notes").unwrap();
+        assert_eq!(notes.content_type(), &mime::TEXT_PLAIN);
+
+        let expected = if cfg!(feature = "infer") {
+            mime::IMAGE_PNG
+        } else {
+            mime::APPLICATION_OCTET_STREAM
+        };
+        assert_eq!(multipart.file("blob").unwrap().content_type(), &expected);
+
+        // generic types are checked against the allowed types once detected
+        let config = UploadConfig::new().allowed_types(vec![mime::TEXT_PLAIN, mime::IMAGE_PNG]);
+        let result = parse(Body::from(&body[..]), &config).map(|_| ());
+        if cfg!(feature = "infer") {
+            assert!(result.is_ok());
+        } else {
+            assert_eq!(result.err(), Some(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_uploads() {
+        let config = UploadConfig::new().max_file_size(4);
+        assert_eq!(
+            parse(Body::from(BODY), &config).err(),
+            Some(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+
+        let config = UploadConfig::new().max_field_size(4);
+        assert_eq!(
+            parse(Body::from(BODY), &config).err(),
+            Some(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+
+        let config = UploadConfig::new().allowed_types(vec![mime::IMAGE_JPEG]);
+        assert_eq!(
+            parse(Body::from(BODY), &config).err(),
+            Some(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+        );
+
+        let truncated = &BODY[..BODY.len() - 20];
+        assert_eq!(
+            parse(Body::from(truncated), &UploadConfig::new()).err(),
+            Some(StatusCode::BAD_REQUEST)
+        );
+
+        let mut state = state(Body::from(BODY));
+        HeaderMap::borrow_mut_from(&mut state).remove(CONTENT_TYPE);
+        assert_eq!(
+            parse_multipart(state, &UploadConfig::new())
+                .wait()
+                .map_err(|(_, err)| err.status())
+                .err(),
+            Some(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+        );
+    }
+
+    #[test]
+    fn removes_temporary_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let count = || fs::read_dir(temp_dir.path()).unwrap().count();
+
+        let config = UploadConfig::new().temp_dir(temp_dir.path());
+        let state = parse(Body::from(BODY), &config).unwrap();
+
+        let file = Multipart::borrow_from(&state).file("photo").unwrap();
+        assert!(file.path().starts_with(temp_dir.path()));
+        assert_eq!(count(), 1);
+
+        let mut contents = Vec::new();
+        file.open().unwrap().read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, b"\x89PNG\r\n--XY");
+
+        drop(state);
+        assert_eq!(count(), 0);
+
+        // files are removed when the cap is hit part way through a file
+        let config = config.max_file_size(4);
+        assert_eq!(
+            parse(Body::from(BODY), &config).err(),
+            Some(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+        assert_eq!(count(), 0);
+
+        // and when the body ends part way through a file
+        let truncated = &BODY[..BODY.len() - 20];
+        assert_eq!(
+            parse(
+                Body::from(truncated),
+                &UploadConfig::new().temp_dir(temp_dir.path())
+            )
+            .err(),
+            Some(StatusCode::BAD_REQUEST)
+        );
+        assert_eq!(count(), 0);
+    }
+}
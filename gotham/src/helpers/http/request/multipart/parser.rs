@@ -0,0 +1,349 @@
+//! A streaming parser for `multipart/form-data` bodies.
+use std::io::{self, Write};
+use std::path::Path;
+
+use futures::{future, Async, Future};
+use hyper::Chunk;
+use mime::Mime;
+use tempfile::NamedTempFile;
+use tokio_threadpool::blocking;
+
+use crate::helpers::http::header::content_disposition::{ContentDisposition, DispositionType};
+use crate::helpers::mime::{detect_from_bytes, detect_from_extension};
+
+use super::file::UploadedFile;
+use super::{Multipart, UploadConfig};
+
+// the largest block of part headers accepted, to bound buffering
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+
+/// The reasons a multipart body may fail to parse.
+#[derive(Debug)]
+pub(super) enum Failure {
+    Read(hyper::Error),
+    Io(io::Error),
+    Invalid(&'static str),
+    TooLarge,
+    UnsupportedType,
+}
+
+/// The position of the parser within the body.
+enum Stage {
+    Preamble,
+    Delimiter,
+    Headers,
+    Body(Part),
+    Done,
+}
+
+/// A part which is currently being received.
+enum Part {
+    Field {
+        name: String,
+        value: Vec<u8>,
+    },
+    File {
+        name: String,
+        file: NamedTempFile,
+        original_name: Option<String>,
+        content_type: Mime,
+        // whether the type is generic, and should be detected from the first bytes received
+        detect: bool,
+        size: u64,
+    },
+}
+
+/// Parses a multipart body as it arrives, writing files to disk as they're received.
+///
+/// Only the trailing bytes which might begin a delimiter are held back between chunks, so memory
+/// use is bounded by the chunk size rather than the size of the body.
+pub(super) struct Parser {
+    config: UploadConfig,
+    // `--boundary`, which starts the first delimiter
+    dash_boundary: Vec<u8>,
+    // `\r\n--boundary`, which ends the body of a part
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    stage: Stage,
+    output: Multipart,
+}
+
+impl Parser {
+    /// Creates a parser for a body using the provided boundary.
+    pub(super) fn new(boundary: &str, config: UploadConfig) -> Self {
+        let dash_boundary = format!("--{}", boundary).into_bytes();
+        let mut delimiter = b"\r\n".to_vec();
+        delimiter.extend_from_slice(&dash_boundary);
+
+        Parser {
+            config,
+            dash_boundary,
+            delimiter,
+            buf: Vec::new(),
+            stage: Stage::Preamble,
+            output: Multipart::default(),
+        }
+    }
+
+    /// Feeds the next chunk of the body into the parser, resolving to the parser once the chunk
+    /// has been handled.
+    ///
+    /// Files are written to disk as the chunk is parsed, so this runs via `blocking` to avoid
+    /// stalling other futures on the thread pool. Outside of a thread pool the chunk is parsed
+    /// in place.
+    pub(super) fn feed_chunk(self, chunk: Chunk) -> impl Future<Item = Self, Error = Failure> {
+        let mut parser = Some(self);
+
+        future::poll_fn(move || {
+            let result = {
+                let parser = parser.as_mut().expect("polled after completion");
+
+                match blocking(|| parser.feed(&chunk)) {
+                    Ok(Async::Ready(result)) => result,
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(_) => parser.feed(&chunk),
+                }
+            };
+
+            result.map(|()| Async::Ready(parser.take().unwrap()))
+        })
+    }
+
+    /// Feeds the next chunk of the body into the parser.
+    fn feed(&mut self, chunk: &[u8]) -> Result<(), Failure> {
+        self.buf.extend_from_slice(chunk);
+
+        loop {
+            match self.stage {
+                Stage::Preamble => match find(&self.buf, &self.dash_boundary) {
+                    Some(index) => {
+                        self.buf.drain(..index + self.dash_boundary.len());
+                        self.stage = Stage::Delimiter;
+                    }
+                    None => {
+                        let keep = self.dash_boundary.len() - 1;
+                        let discard = self.buf.len().saturating_sub(keep);
+                        self.buf.drain(..discard);
+                        return Ok(());
+                    }
+                },
+                Stage::Delimiter => {
+                    if self.buf.len() < 2 {
+                        return Ok(());
+                    }
+
+                    self.stage = match &self.buf[..2] {
+                        b"--" => Stage::Done,
+                        b"\r\n" => Stage::Headers,
+                        _ => return Err(Failure::Invalid("malformed multipart delimiter")),
+                    };
+                    self.buf.drain(..2);
+                }
+                Stage::Headers => {
+                    // a part without any headers starts with the blank line
+                    let (headers, consumed) = if self.buf.starts_with(b"\r\n") {
+                        (Vec::new(), 2)
+                    } else {
+                        match find(&self.buf, b"\r\n\r\n") {
+                            Some(index) => (self.buf[..index].to_vec(), index + 4),
+                            None if self.buf.len() > MAX_HEADER_SIZE => {
+                                return Err(Failure::Invalid("multipart headers too large"));
+                            }
+                            None => return Ok(()),
+                        }
+                    };
+
+                    self.buf.drain(..consumed);
+                    self.stage = Stage::Body(self.start(&headers)?);
+                }
+                Stage::Body(ref mut part) => match find(&self.buf, &self.delimiter) {
+                    Some(index) => {
+                        write(&self.config, part, &self.buf[..index])?;
+                        self.buf.drain(..index + self.delimiter.len());
+
+                        match std::mem::replace(&mut self.stage, Stage::Delimiter) {
+                            Stage::Body(part) => self.finish_part(part)?,
+                            _ => unreachable!(),
+                        }
+                    }
+                    None => {
+                        let keep = self.delimiter.len() - 1;
+                        let safe = self.buf.len().saturating_sub(keep);
+                        write(&self.config, part, &self.buf[..safe])?;
+                        self.buf.drain(..safe);
+                        return Ok(());
+                    }
+                },
+                Stage::Done => {
+                    // anything after the final delimiter is an epilogue, and is ignored
+                    self.buf.clear();
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Completes parsing once the body has ended, returning the parsed parts.
+    pub(super) fn finish(self) -> Result<Multipart, Failure> {
+        match self.stage {
+            Stage::Done => Ok(self.output),
+            _ => Err(Failure::Invalid("multipart body ended unexpectedly")),
+        }
+    }
+
+    /// Starts a new part from its headers, validating any file against the configuration.
+    fn start(&self, headers: &[u8]) -> Result<Part, Failure> {
+        let headers = std::str::from_utf8(headers)
+            .map_err(|_| Failure::Invalid("multipart headers are not valid UTF-8"))?;
+
+        let mut disposition = None;
+        let mut content_type = None;
+
+        for line in headers.split("\r\n") {
+            let (name, value) = match line.find(':') {
+                Some(index) => (&line[..index], line[index + 1..].trim()),
+                None => return Err(Failure::Invalid("malformed multipart header")),
+            };
+
+            if name.eq_ignore_ascii_case("content-disposition") {
+                disposition = Some(value);
+            } else if name.eq_ignore_ascii_case("content-type") {
+                content_type = Some(value);
+            }
+        }
+
+        let (name, filename) = disposition
+            .and_then(parse_disposition)
+            .ok_or(Failure::Invalid("multipart part without a form-data name"))?;
+
+        let filename = match filename {
+            Some(filename) => filename,
+            None => {
+                return Ok(Part::Field {
+                    name,
+                    value: Vec::new(),
+                })
+            }
+        };
+
+        let content_type = match content_type {
+            Some(value) => value
+                .parse::<Mime>()
+                .map_err(|_| Failure::Invalid("malformed multipart content type"))?,
+            None => mime::APPLICATION_OCTET_STREAM,
+        };
+
+        // clients often send a generic type, so fall back to the extension of the file name
+        let content_type = if content_type == mime::APPLICATION_OCTET_STREAM {
+            detect_from_extension(Path::new(&filename)).unwrap_or(content_type)
+        } else {
+            content_type
+        };
+
+        // a generic type is only validated once the contents have been checked
+        let detect = content_type == mime::APPLICATION_OCTET_STREAM;
+
+        if !detect && !self.config.accepts(&content_type) {
+            return Err(Failure::UnsupportedType);
+        }
+
+        Ok(Part::File {
+            name,
+            file: self.config.temp_file().map_err(Failure::Io)?,
+            original_name: Some(filename).filter(|filename| !filename.is_empty()),
+            content_type,
+            detect,
+            size: 0,
+        })
+    }
+
+    /// Stores a completed part in the output.
+    fn finish_part(&mut self, part: Part) -> Result<(), Failure> {
+        match part {
+            Part::Field { name, value } => {
+                let value = String::from_utf8(value)
+                    .map_err(|_| Failure::Invalid("multipart field is not valid UTF-8"))?;
+                self.output.fields.push((name, value));
+            }
+            Part::File {
+                name,
+                mut file,
+                original_name,
+                content_type,
+                detect,
+                size,
+            } => {
+                if detect && !self.config.accepts(&content_type) {
+                    return Err(Failure::UnsupportedType);
+                }
+
+                file.flush().map_err(Failure::Io)?;
+                let file = UploadedFile::new(file, original_name, content_type, size);
+                self.output.files.push((name, file));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Appends data to the part being received, enforcing the configured size limits.
+fn write(config: &UploadConfig, part: &mut Part, data: &[u8]) -> Result<(), Failure> {
+    if data.is_empty() {
+        return Ok(());
+    }
+
+    match *part {
+        Part::Field { ref mut value, .. } => {
+            if value.len() + data.len() > config.max_field_size {
+                return Err(Failure::TooLarge);
+            }
+            value.extend_from_slice(data);
+        }
+        Part::File {
+            ref mut file,
+            ref mut content_type,
+            ref mut detect,
+            ref mut size,
+            ..
+        } => {
+            if *detect {
+                *detect = false;
+
+                if let Some(detected) = detect_from_bytes(data) {
+                    *content_type = detected;
+                }
+
+                if !config.accepts(content_type) {
+                    return Err(Failure::UnsupportedType);
+                }
+            }
+
+            *size += data.len() as u64;
+            if *size > config.max_file_size {
+                return Err(Failure::TooLarge);
+            }
+            file.write_all(data).map_err(Failure::Io)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a `form-data` disposition into the field name and optional file name.
+fn parse_disposition(value: &str) -> Option<(String, Option<String>)> {
+    let disposition = value.parse::<ContentDisposition>().ok()?;
+
+    if *disposition.disposition() != DispositionType::FormData {
+        return None;
+    }
+
+    let filename = disposition.filename().map(str::to_owned);
+    disposition.name().map(|name| (name.to_owned(), filename))
+}
+
+/// Returns the index of the first occurrence of `needle` within `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
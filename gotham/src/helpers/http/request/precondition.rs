@@ -0,0 +1,350 @@
+//! Defines helpers for evaluating the `If-Match` and `If-None-Match` preconditions of a request,
+//! as described by [RFC 7232](https://tools.ietf.org/html/rfc7232).
+//!
+//! These allow handlers which modify a resource to implement optimistic concurrency control,
+//! refusing to apply a change made against an outdated copy of the resource.
+
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, ETAG, IF_MATCH, IF_NONE_MATCH};
+use hyper::{Body, Method, Response, StatusCode};
+
+use crate::helpers::http::response::create_empty_response;
+use crate::state::{FromState, State};
+
+/// Evaluates the preconditions of a request against the current entity tag of a resource.
+///
+/// Preconditions are evaluated in the order defined by RFC 7232:
+///
+/// * When `If-Match` is present and none of its tags match the current tag using the strong
+///   comparison, the request fails with a `412 Precondition Failed`. The `*` value matches
+///   whenever the resource exists.
+/// * When `If-Match` is absent but has been required, the request fails with a
+///   `428 Precondition Required`.
+/// * When `If-None-Match` is present and one of its tags matches the current tag using the weak
+///   comparison, the request fails with a `304 Not Modified` for `GET` and `HEAD` requests, and
+///   a `412 Precondition Failed` otherwise. The `*` value matches whenever the resource exists,
+///   which allows a `PUT` to only create a resource rather than replace it.
+///
+/// Malformed headers never match.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::IF_MATCH;
+/// # use gotham::helpers::http::request::precondition::Preconditions;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn update_document(state: State) -> (State, Response<Body>) {
+///     // typically loaded alongside the resource being modified
+///     let current = Some("\"v5\"");
+///
+///     if let Some(response) = Preconditions::new()
+///         .require_if_match(true)
+///         .check(&state, current)
+///     {
+///         return (state, response);
+///     }
+///
+///     // the update is applied here
+///     (state, Response::new(Body::empty()))
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(build_simple_router(|route| {
+/// #       route.put("/document").to(update_document);
+/// #   })).unwrap();
+/// #
+/// #   let response = test_server.client()
+/// #       .put("http://localhost/document", "", mime::TEXT_PLAIN)
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+/// #
+/// #   let response = test_server.client()
+/// #       .put("http://localhost/document", "", mime::TEXT_PLAIN)
+/// #       .with_header(IF_MATCH, "\"v4\"".parse().unwrap())
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+/// #
+/// #   let response = test_server.client()
+/// #       .put("http://localhost/document", "", mime::TEXT_PLAIN)
+/// #       .with_header(IF_MATCH, "\"v5\"".parse().unwrap())
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Preconditions {
+    require_if_match: bool,
+}
+
+impl Preconditions {
+    /// Creates a new `Preconditions`, where `If-Match` is optional.
+    pub fn new() -> Self {
+        Preconditions {
+            require_if_match: false,
+        }
+    }
+
+    /// Sets whether requests must provide an `If-Match` header, so that clients can't modify a
+    /// resource without stating which version they expect to modify.
+    pub fn require_if_match(mut self, required: bool) -> Self {
+        self.require_if_match = required;
+        self
+    }
+
+    /// Checks the preconditions of the request against the current entity tag of the resource,
+    /// such as `"v5"` or `W/"v5"`, or `None` when the resource doesn't exist.
+    ///
+    /// Returns the response to send when a precondition fails, or `None` when the request may
+    /// proceed. A tag which isn't quoted is treated as the opaque value of a strong tag.
+    pub fn check(&self, state: &State, etag: Option<&str>) -> Option<Response<Body>> {
+        let headers = HeaderMap::borrow_from(state);
+        let current = etag.map(|etag| match EntityTag::parse(etag) {
+            Some((tag, "")) => tag,
+            _ => EntityTag {
+                weak: false,
+                opaque: etag,
+            },
+        });
+
+        match Condition::parse(headers, &IF_MATCH) {
+            Some(condition) if !condition.matches(current.as_ref(), true) => {
+                return Some(create_empty_response(
+                    state,
+                    StatusCode::PRECONDITION_FAILED,
+                ));
+            }
+            None if self.require_if_match => {
+                return Some(create_empty_response(
+                    state,
+                    StatusCode::PRECONDITION_REQUIRED,
+                ));
+            }
+            _ => (),
+        }
+
+        if let Some(condition) = Condition::parse(headers, &IF_NONE_MATCH) {
+            if condition.matches(current.as_ref(), false) {
+                let method = Method::borrow_from(state);
+
+                if method == Method::GET || method == Method::HEAD {
+                    let mut response = create_empty_response(state, StatusCode::NOT_MODIFIED);
+                    if let Some(etag) = etag.and_then(|etag| HeaderValue::from_str(etag).ok()) {
+                        response.headers_mut().insert(ETAG, etag);
+                    }
+                    return Some(response);
+                }
+
+                return Some(create_empty_response(
+                    state,
+                    StatusCode::PRECONDITION_FAILED,
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+/// An entity tag, where the opaque value excludes its quotes.
+#[derive(Debug, PartialEq)]
+struct EntityTag<'a> {
+    weak: bool,
+    opaque: &'a str,
+}
+
+impl<'a> EntityTag<'a> {
+    /// Parses an entity tag from the start of `input`, returning it alongside the remainder.
+    fn parse(input: &'a str) -> Option<(Self, &'a str)> {
+        let (weak, input) = match input.strip_prefix("W/") {
+            Some(input) => (true, input),
+            None => (false, input),
+        };
+
+        if !input.starts_with('"') {
+            return None;
+        }
+
+        let end = input[1..].find('"')? + 1;
+        let opaque = &input[1..end];
+
+        if opaque.bytes().any(|b| b < 0x21 || b == 0x7f) {
+            return None;
+        }
+
+        Some((EntityTag { weak, opaque }, &input[end + 1..]))
+    }
+
+    /// Compares two tags using the strong comparison, where both tags must also be strong, or
+    /// the weak comparison, where only the opaque values must match.
+    fn matches(&self, other: &EntityTag<'_>, strong: bool) -> bool {
+        self.opaque == other.opaque && !(strong && (self.weak || other.weak))
+    }
+}
+
+/// The value of an `If-Match` or `If-None-Match` header.
+enum Condition<'a> {
+    /// The `*` value, matching any current representation.
+    Any,
+
+    /// A list of entity tags, which is cut short at the first malformed tag.
+    Tags(Vec<EntityTag<'a>>),
+}
+
+impl<'a> Condition<'a> {
+    /// Parses every instance of the header, returning `None` when it isn't present.
+    fn parse(headers: &'a HeaderMap, name: &HeaderName) -> Option<Self> {
+        let mut values = headers.get_all(name).iter().peekable();
+        values.peek()?;
+
+        let mut tags = Vec::new();
+
+        for value in values {
+            let mut input = match value.to_str() {
+                Ok(value) if value.trim() == "*" => return Some(Condition::Any),
+                Ok(value) => value,
+                Err(_) => break,
+            };
+
+            loop {
+                input = input.trim_start_matches(&[',', ' ', '\t'][..]);
+
+                if input.is_empty() {
+                    break;
+                }
+
+                match EntityTag::parse(input) {
+                    Some((tag, rest)) => {
+                        tags.push(tag);
+                        input = rest;
+                    }
+                    None => return Some(Condition::Tags(tags)),
+                }
+            }
+        }
+
+        Some(Condition::Tags(tags))
+    }
+
+    /// Determines whether the condition matches the current tag, using the strong or weak
+    /// comparison.
+    fn matches(&self, current: Option<&EntityTag<'_>>, strong: bool) -> bool {
+        match (self, current) {
+            (_, None) => false,
+            (Condition::Any, Some(_)) => true,
+            (Condition::Tags(tags), Some(current)) => {
+                tags.iter().any(|tag| tag.matches(current, strong))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(
+        method: Method,
+        headers: &[(HeaderName, &'static str)],
+        etag: Option<&str>,
+        required: bool,
+    ) -> StatusCode {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(name, HeaderValue::from_static(value));
+        }
+
+        let mut state = State::new();
+        state.put(method);
+        state.put(map);
+        crate::state::set_request_id(&mut state);
+
+        let preconditions = Preconditions::new().require_if_match(required);
+        match preconditions.check(&state, etag) {
+            Some(response) => response.status(),
+            None => StatusCode::OK,
+        }
+    }
+
+    #[test]
+    fn evaluates_if_match() {
+        let put =
+            |headers: &[(HeaderName, &'static str)], etag| check(Method::PUT, headers, etag, false);
+
+        assert_eq!(put(&[], Some("\"v1\"")), StatusCode::OK);
+        assert_eq!(put(&[(IF_MATCH, "\"v1\"")], Some("\"v1\"")), StatusCode::OK);
+        assert_eq!(
+            put(&[(IF_MATCH, "\"v0\", \"v1\"")], Some("\"v1\"")),
+            StatusCode::OK
+        );
+        assert_eq!(
+            put(
+                &[(IF_MATCH, "\"v0\""), (IF_MATCH, "\"v1\"")],
+                Some("\"v1\"")
+            ),
+            StatusCode::OK
+        );
+        assert_eq!(
+            put(&[(IF_MATCH, "\"a,b\"")], Some("\"a,b\"")),
+            StatusCode::OK
+        );
+        assert_eq!(put(&[(IF_MATCH, "\"v1\"")], Some("v1")), StatusCode::OK);
+        assert_eq!(put(&[(IF_MATCH, "*")], Some("\"v1\"")), StatusCode::OK);
+
+        let failed = StatusCode::PRECONDITION_FAILED;
+        assert_eq!(put(&[(IF_MATCH, "\"v0\"")], Some("\"v1\"")), failed);
+        assert_eq!(put(&[(IF_MATCH, "*")], None), failed);
+        assert_eq!(put(&[(IF_MATCH, "\"v1\"")], None), failed);
+        assert_eq!(put(&[(IF_MATCH, "v1")], Some("\"v1\"")), failed);
+        assert_eq!(put(&[(IF_MATCH, "\"v1")], Some("\"v1\"")), failed);
+
+        // weak tags never match using the strong comparison
+        assert_eq!(put(&[(IF_MATCH, "W/\"v1\"")], Some("\"v1\"")), failed);
+        assert_eq!(put(&[(IF_MATCH, "\"v1\"")], Some("W/\"v1\"")), failed);
+
+        let required = |headers: &[(HeaderName, &'static str)]| {
+            check(Method::DELETE, headers, Some("\"v1\""), true)
+        };
+
+        assert_eq!(required(&[]), StatusCode::PRECONDITION_REQUIRED);
+        assert_eq!(required(&[(IF_MATCH, "\"v1\"")]), StatusCode::OK);
+        assert_eq!(required(&[(IF_MATCH, "\"v0\"")]), failed);
+    }
+
+    #[test]
+    fn evaluates_if_none_match() {
+        let failed = StatusCode::PRECONDITION_FAILED;
+
+        // creating a resource only when it doesn't exist yet
+        assert_eq!(
+            check(Method::PUT, &[(IF_NONE_MATCH, "*")], None, false),
+            StatusCode::OK
+        );
+        assert_eq!(
+            check(Method::PUT, &[(IF_NONE_MATCH, "*")], Some("\"v1\""), false),
+            failed
+        );
+
+        // weak tags match using the weak comparison
+        let headers = [(IF_NONE_MATCH, "W/\"v1\"")];
+        assert_eq!(check(Method::POST, &headers, Some("\"v1\""), false), failed);
+        assert_eq!(
+            check(Method::POST, &headers, Some("\"v2\""), false),
+            StatusCode::OK
+        );
+        assert_eq!(
+            check(Method::GET, &headers, Some("\"v1\""), false),
+            StatusCode::NOT_MODIFIED
+        );
+
+        // If-Match is evaluated first
+        let headers = [(IF_MATCH, "\"v0\""), (IF_NONE_MATCH, "\"v0\"")];
+        assert_eq!(check(Method::PUT, &headers, Some("\"v1\""), false), failed);
+    }
+}
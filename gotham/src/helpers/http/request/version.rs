@@ -0,0 +1,139 @@
+//! Defines helpers for determining the API version requested by a client.
+
+use hyper::header::{HeaderMap, ACCEPT};
+use hyper::Uri;
+
+use crate::state::{FromState, State, StateData};
+
+/// The API version requested by the client.
+///
+/// This is stored in `State` by the `ApiVersionMiddleware`, allowing middleware and handlers which
+/// are shared between versions to determine which version was requested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RequestVersion(pub u32);
+
+impl StateData for RequestVersion {}
+
+/// The strategy used to determine the API version of a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VersionStrategy {
+    /// The version is taken from a `/v{n}` segment of the request path, as created by the
+    /// `VersionedRouter`. The first segment of this form is used.
+    UrlPrefix,
+
+    /// The version is taken from the `Accept` header, using either a vendor media type such as
+    /// `application/vnd.myapp.v2+json`, or a `version` parameter such as
+    /// `application/json; version=2`.
+    AcceptHeader,
+}
+
+/// Determines the API version requested by the client, using the provided strategy.
+///
+/// Returns `None` when the request doesn't specify a version in the expected form.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # use gotham::helpers::http::request::version::{api_version, VersionStrategy};
+/// # use gotham::state::State;
+/// # use hyper::header::{HeaderMap, ACCEPT};
+/// # use hyper::Uri;
+/// # fn main() {
+/// # State::with_new(|state| {
+/// let mut headers = HeaderMap::new();
+/// headers.insert(ACCEPT, "application/vnd.myapp.v2+json".parse().unwrap());
+/// state.put(headers);
+/// state.put("/v1/users".parse::<Uri>().unwrap());
+///
+/// assert_eq!(api_version(state, VersionStrategy::AcceptHeader), Some(2));
+/// assert_eq!(api_version(state, VersionStrategy::UrlPrefix), Some(1));
+/// # });
+/// # }
+/// ```
+pub fn api_version(state: &State, strategy: VersionStrategy) -> Option<u32> {
+    match strategy {
+        VersionStrategy::UrlPrefix => Uri::try_borrow_from(state)
+            .and_then(|uri| uri.path().split('/').filter_map(parse_version).next()),
+        VersionStrategy::AcceptHeader => HeaderMap::try_borrow_from(state).and_then(|headers| {
+            headers
+                .get_all(ACCEPT)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .filter_map(media_type_version)
+                .next()
+        }),
+    }
+}
+
+/// Parses a version from a single media type within an `Accept` header.
+fn media_type_version(media_type: &str) -> Option<u32> {
+    let mut parts = media_type.split(';').map(str::trim);
+    let essence = parts.next()?;
+
+    // `version=2` parameters take priority over the vendor subtype
+    let param = parts
+        .filter_map(|param| {
+            let mut pair = param.splitn(2, '=');
+            match (pair.next(), pair.next()) {
+                (Some(name), Some(value)) if name.trim().eq_ignore_ascii_case("version") => {
+                    value.trim().trim_matches('"').parse().ok()
+                }
+                _ => None,
+            }
+        })
+        .next();
+
+    param.or_else(|| {
+        let subtype = &essence[essence.find('/')? + 1..];
+        let subtype = subtype.split('+').next()?;
+
+        if !subtype.starts_with("vnd.") {
+            return None;
+        }
+
+        subtype.split('.').filter_map(parse_version).next()
+    })
+}
+
+/// Parses a `v{n}` version identifier.
+fn parse_version(segment: &str) -> Option<u32> {
+    if segment.len() < 2 || !segment.starts_with('v') {
+        return None;
+    }
+
+    let digits = &segment[1..];
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_versions_from_media_types() {
+        assert_eq!(media_type_version("application/vnd.myapp.v2+json"), Some(2));
+        assert_eq!(media_type_version("application/vnd.myapp.v10"), Some(10));
+        assert_eq!(media_type_version("application/json; version=3"), Some(3));
+        assert_eq!(
+            media_type_version("application/vnd.myapp.v2+json; version=\"4\""),
+            Some(4)
+        );
+        assert_eq!(media_type_version("application/json"), None);
+        assert_eq!(media_type_version("application/v2+json"), None);
+    }
+
+    #[test]
+    fn parses_versions_from_paths() {
+        let mut state = State::new();
+        state.put("/api/v3/users/v4".parse::<Uri>().unwrap());
+        assert_eq!(api_version(&state, VersionStrategy::UrlPrefix), Some(3));
+
+        state.put("/api/vx/users".parse::<Uri>().unwrap());
+        assert_eq!(api_version(&state, VersionStrategy::UrlPrefix), None);
+    }
+}
@@ -8,6 +8,10 @@ use std::borrow::Cow;
 use crate::helpers::http::header::X_REQUEST_ID;
 use crate::state::{request_id, FromState, State};
 
+mod problem;
+
+pub use self::problem::{problem_response, ProblemDetails};
+
 /// Creates a `Response` object and populates it with a set of default headers that help to improve
 /// security and conformance to best practice.
 ///
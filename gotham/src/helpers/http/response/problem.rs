@@ -0,0 +1,244 @@
+//! Defines `ProblemDetails`, for describing errors in HTTP APIs as specified by RFC 7807.
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Response, StatusCode};
+use serde::Serializer;
+use serde_derive::Serialize;
+
+use super::create_response;
+use crate::handler::IntoResponse;
+use crate::state::State;
+
+// the media type of problem details serialized as JSON
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// A machine readable description of an error, as specified by RFC 7807.
+///
+/// Problems are serialized to JSON using the member names of the specification, so `type_uri`
+/// is written as `type`. The `detail` and `instance` members are omitted when they're not set.
+///
+/// `ProblemDetails` implements `IntoResponse`, so it can be returned directly from handlers, or
+/// used as the error type of a `ResultHandler` to give every failure branch the same shape.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate futures;
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use futures::future;
+/// # use gotham::handler::ResultHandler;
+/// # use gotham::helpers::http::response::ProblemDetails;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use hyper::header::CONTENT_TYPE;
+/// # use hyper::StatusCode;
+/// #
+/// fn handler(state: State) -> future::FutureResult<(State, String), (State, ProblemDetails)> {
+///     let problem = ProblemDetails::new(StatusCode::FORBIDDEN)
+///         .type_uri("https://example.com/probs/out-of-credit")
+///         .title("You do not have enough credit.")
+///         .detail("Your current balance is 30, but that costs 50.")
+///         .instance("/account/12345/msgs/abc");
+///
+///     future::err((state, problem))
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route.post("/msgs").to(ResultHandler::new(handler));
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .post("http://localhost/msgs", "", mime::TEXT_PLAIN)
+/// #     .perform()
+/// #     .unwrap();
+/// #
+/// # assert_eq!(response.status(), StatusCode::FORBIDDEN);
+/// # assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+/// # assert_eq!(
+/// #     response.read_utf8_body().unwrap(),
+/// #     "{\"type\":\"https://example.com/probs/out-of-credit\",\
+/// #      \"title\":\"You do not have enough credit.\",\"status\":403,\
+/// #      \"detail\":\"Your current balance is 30, but that costs 50.\",\
+/// #      \"instance\":\"/account/12345/msgs/abc\"}"
+/// # );
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ProblemDetails {
+    /// A URI identifying the type of problem, which is `about:blank` by default.
+    #[serde(rename = "type")]
+    pub type_uri: String,
+
+    /// A short summary of the type of problem.
+    pub title: String,
+
+    /// The status of the response.
+    #[serde(serialize_with = "serialize_status")]
+    pub status: StatusCode,
+
+    /// An explanation specific to this occurrence of the problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+
+    /// A URI identifying this occurrence of the problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+}
+
+impl ProblemDetails {
+    /// Creates a new `ProblemDetails` with the provided status.
+    ///
+    /// The type is `about:blank`, and the title is the canonical reason of the status, as
+    /// recommended for problems without any further semantics.
+    pub fn new(status: StatusCode) -> Self {
+        ProblemDetails {
+            type_uri: "about:blank".to_owned(),
+            title: status.canonical_reason().unwrap_or("Unknown").to_owned(),
+            status,
+            detail: None,
+            instance: None,
+        }
+    }
+
+    /// Sets the URI identifying the type of problem.
+    pub fn type_uri<S>(mut self, type_uri: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.type_uri = type_uri.into();
+        self
+    }
+
+    /// Sets the short summary of the type of problem.
+    pub fn title<S>(mut self, title: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.title = title.into();
+        self
+    }
+
+    /// Sets the explanation specific to this occurrence of the problem.
+    pub fn detail<S>(mut self, detail: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Sets the URI identifying this occurrence of the problem.
+    pub fn instance<S>(mut self, instance: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Serializes the problem to its JSON representation.
+    fn to_json(&self) -> Vec<u8> {
+        // every member serializes to a string or a number, so this can't fail
+        serde_json::to_vec(self).expect("ProblemDetails serialized to JSON")
+    }
+}
+
+/// Serializes a status as its numeric code.
+fn serialize_status<S>(status: &StatusCode, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_u16(status.as_u16())
+}
+
+/// Creates a `Response` describing the provided problem, with a `Content-Type` of
+/// `application/problem+json`.
+///
+/// As with `create_response`, the default headers are populated, and the body is omitted for
+/// `HEAD` requests.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use gotham::helpers::http::response::{problem_response, ProblemDetails};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use hyper::{Body, Response, StatusCode};
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let problem = ProblemDetails::new(StatusCode::NOT_FOUND).detail("no such widget");
+///     let response = problem_response(&state, problem);
+///
+///     (state, response)
+/// }
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::NOT_FOUND);
+/// #     assert_eq!(
+/// #         response.read_utf8_body().unwrap(),
+/// #         r#"{"type":"about:blank","title":"Not Found","status":404,"detail":"no such widget"}"#
+/// #     );
+/// # }
+/// ```
+pub fn problem_response(state: &State, problem: ProblemDetails) -> Response<Body> {
+    let mime = PROBLEM_JSON.parse().expect("valid problem media type");
+    create_response(state, problem.status, mime, problem.to_json())
+}
+
+impl IntoResponse for ProblemDetails {
+    fn into_response(self, state: &State) -> Response<Body> {
+        problem_response(state, self)
+    }
+}
+
+/// Converts a problem into a `Response` without access to the request `State`.
+///
+/// This is useful where only a `Response` can be returned, but lacks the default headers added
+/// by `problem_response`, which should be preferred wherever the `State` is available.
+impl From<ProblemDetails> for Response<Body> {
+    fn from(problem: ProblemDetails) -> Self {
+        let mut response = Response::new(Body::from(problem.to_json()));
+
+        *response.status_mut() = problem.status;
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{Future, Stream};
+
+    #[test]
+    fn converts_problems_without_state() {
+        let problem = ProblemDetails::new(StatusCode::UNAUTHORIZED).title("Token expired");
+        let response = Response::from(problem);
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[CONTENT_TYPE], PROBLEM_JSON);
+
+        let body = response.into_body().concat2().wait().unwrap();
+        assert_eq!(
+            &body[..],
+            &br#"{"type":"about:blank","title":"Token expired","status":401}"#[..]
+        );
+    }
+}
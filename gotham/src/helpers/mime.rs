@@ -0,0 +1,119 @@
+//! Helpers for detecting the MIME type of files, shared by static assets and file uploads.
+//!
+//! Detection is primarily based on the file extension, via the `mime_guess` crate. When the
+//! `infer` feature is enabled, the leading bytes of a file are also inspected when the extension
+//! is missing or only maps to `application/octet-stream`.
+use std::path::Path;
+
+use mime::Mime;
+use mime_guess::from_path;
+
+// the number of leading bytes read from a file to detect its type
+#[cfg(feature = "infer")]
+const SNIFF_LENGTH: u64 = 8 * 1024;
+
+/// Detects the MIME type of the file at the provided path.
+///
+/// The extension of the path is checked first. If it's unknown or generic, and the `infer`
+/// feature is enabled, the leading bytes of the file are read to detect the type; a path which
+/// doesn't exist (such as the name of an uploaded file) is never an error. When no type can be
+/// found, `application/octet-stream` is returned.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate mime;
+/// # use std::path::Path;
+/// # use gotham::helpers::mime::detect;
+/// # fn main() {
+/// assert_eq!(detect(Path::new("index.html")), mime::TEXT_HTML);
+/// # }
+/// ```
+pub fn detect(path: &Path) -> Mime {
+    detect_from_extension(path)
+        .or_else(|| sniff_file(path))
+        .unwrap_or(mime::APPLICATION_OCTET_STREAM)
+}
+
+/// Detects the MIME type of a path from its extension alone, without touching the filesystem.
+///
+/// Extensions which are unknown, or only map to `application/octet-stream`, return `None`. This
+/// is suitable for untrusted names, such as those provided with an upload.
+pub fn detect_from_extension(path: &Path) -> Option<Mime> {
+    from_path(path)
+        .first()
+        .filter(|mime| *mime != mime::APPLICATION_OCTET_STREAM)
+}
+
+/// Detects the MIME type of a file from its leading bytes.
+///
+/// This is intended for streaming scenarios, where only the first chunk of a file is available.
+/// Detection requires the `infer` feature; without it, `None` is always returned.
+pub fn detect_from_bytes(buf: &[u8]) -> Option<Mime> {
+    #[cfg(feature = "infer")]
+    {
+        infer::get(buf).and_then(|kind| kind.mime_type().parse().ok())
+    }
+
+    #[cfg(not(feature = "infer"))]
+    {
+        let _ = buf;
+        None
+    }
+}
+
+/// Reads the leading bytes of a file to detect its type.
+#[cfg(feature = "infer")]
+fn sniff_file(path: &Path) -> Option<Mime> {
+    use std::fs::File;
+    use std::io::Read;
+
+    let mut buf = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(SNIFF_LENGTH).read_to_end(&mut buf))
+        .ok()?;
+
+    detect_from_bytes(&buf)
+}
+
+/// Without the `infer` feature there's nothing to detect from the file contents.
+#[cfg(not(feature = "infer"))]
+fn sniff_file(_path: &Path) -> Option<Mime> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn detects_by_extension() {
+        assert_eq!(detect(Path::new("styles/site.css")), mime::TEXT_CSS);
+        assert_eq!(detect(Path::new("logo.PNG")), mime::IMAGE_PNG);
+        assert_eq!(
+            detect(Path::new("does/not/exist")),
+            mime::APPLICATION_OCTET_STREAM
+        );
+        assert_eq!(detect_from_extension(Path::new("data.bin")), None);
+    }
+
+    #[cfg(feature = "infer")]
+    #[test]
+    fn detects_by_contents() {
+        use std::io::Write;
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(PNG).unwrap();
+
+        assert_eq!(detect(file.path()), mime::IMAGE_PNG);
+        assert_eq!(detect_from_bytes(PNG), Some(mime::IMAGE_PNG));
+        assert_eq!(detect_from_bytes(b"plain"), None);
+    }
+
+    #[cfg(not(feature = "infer"))]
+    #[test]
+    fn detects_nothing_from_contents() {
+        assert_eq!(detect_from_bytes(PNG), None);
+    }
+}
@@ -1,4 +1,5 @@
 //! Helpers, e.g. for HTTP request handling and response generation
 
 pub mod http;
+pub mod mime;
 pub(crate) mod timing;
@@ -1,6 +1,9 @@
 //! Defines types for timing requests and emitting timing information.
 use chrono::prelude::*;
 use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+
+use crate::state::{FromState, RequestStart, State};
 
 /// Timer struct used to record execution times of requests.
 ///
@@ -9,26 +12,40 @@ use std::fmt::{self, Display, Formatter};
 #[derive(Clone, Copy)]
 pub struct Timer {
     start: DateTime<Utc>,
+    instant: Instant,
 }
 
 impl Timer {
     /// Begins measuring from the current time.
     pub fn new() -> Timer {
-        Timer { start: Utc::now() }
+        Timer::from_start(RequestStart::now())
     }
 
-    /// Finishes measuring, and returns the elapsed time as a `Timing` value.
-    pub fn elapsed(&self) -> Timing {
-        let duration = Utc::now()
-            .signed_duration_since(self.start)
-            .num_microseconds();
+    /// Begins measuring from the `RequestStart` stored in `State`, so that all middleware
+    /// measure from the same origin. Falls back to the current time if none was stored.
+    pub fn from_state(state: &State) -> Timer {
+        match RequestStart::try_borrow_from(state) {
+            Some(start) => Timer::from_start(*start),
+            None => Timer::new(),
+        }
+    }
 
-        match duration {
-            Some(dur) => Timing::Microseconds(dur),
-            None => Timing::Invalid,
+    /// Begins measuring from the provided `RequestStart`.
+    fn from_start(start: RequestStart) -> Timer {
+        Timer {
+            start: start.time(),
+            instant: start.instant(),
         }
     }
 
+    /// Finishes measuring, and returns the elapsed time as a `Timing` value.
+    ///
+    /// The elapsed time is measured using a monotonic clock, so it's unaffected by changes to
+    /// the system time while the request is running.
+    pub fn elapsed(&self) -> Timing {
+        Timing(self.instant.elapsed())
+    }
+
     /// Retrieves the start time of this timer.
     pub fn start_time(&self) -> &DateTime<Utc> {
         &self.start
@@ -36,28 +53,56 @@ impl Timer {
 }
 
 /// Represents an elapsed time measured by `Timer`.
+///
+/// The `Display` implementation picks the most readable unit, such as `850ns`, `250µs`,
+/// `1.52ms` or `2.10s`.
 #[derive(Clone, Copy)]
-pub enum Timing {
-    /// A number of microseconds measured by `Timer`.
-    Microseconds(i64),
+pub struct Timing(pub Duration);
 
-    /// An invalid state, where the amount of time elapsed was unable to be measured.
-    Invalid,
+impl Timing {
+    /// Returns the elapsed time with its full precision.
+    pub fn as_duration(&self) -> Duration {
+        self.0
+    }
 }
 
 impl Display for Timing {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        match *self {
-            Timing::Microseconds(i) => {
-                if i < 1000 {
-                    write!(f, "{}µs", i)
-                } else if i < 1_000_000 {
-                    write!(f, "{:.2}ms", (i as f32) / 1000.0)
-                } else {
-                    write!(f, "{:.2}s", (i as f32) / 1_000_000.0)
-                }
-            }
-            Timing::Invalid => f.write_str("invalid"),
+        let ns = self.0.as_nanos();
+        if ns < 1000 {
+            write!(f, "{}ns", ns)
+        } else if ns < 1_000_000 {
+            write!(f, "{}µs", ns / 1000)
+        } else if ns < 1_000_000_000 {
+            write!(f, "{:.2}ms", (ns as f64) / 1_000_000.0)
+        } else {
+            write!(f, "{:.2}s", (ns as f64) / 1_000_000_000.0)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_adaptive_units() {
+        let format = |ns| Timing(Duration::from_nanos(ns)).to_string();
+
+        assert_eq!(format(850), "850ns");
+        assert_eq!(format(250_400), "250µs");
+        assert_eq!(format(1_523_000), "1.52ms");
+        assert_eq!(format(2_104_000_000), "2.10s");
+    }
+
+    #[test]
+    fn measures_from_request_start() {
+        State::with_new(|state| {
+            let start = RequestStart::now();
+            state.put(start);
+
+            let timer = Timer::from_state(state);
+            assert_eq!(timer.start_time(), &start.time());
+        });
+    }
+}
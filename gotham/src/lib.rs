@@ -32,6 +32,7 @@ pub mod helpers;
 pub mod middleware;
 pub mod pipeline;
 pub mod router;
+pub mod server;
 mod service;
 pub mod state;
 
@@ -44,12 +45,18 @@ pub mod plain;
 /// Functions for creating a Gotham service using HTTPS.
 pub mod tls;
 
+/// Functions for creating a Gotham service on a Unix domain socket.
+#[cfg(unix)]
+pub mod unix;
+
 use std::net::ToSocketAddrs;
 
 use tokio::net::TcpListener;
 use tokio::runtime::{self, Runtime};
 
 pub use plain::*;
+#[cfg(unix)]
+pub use unix::*;
 
 fn new_runtime(threads: usize) -> Runtime {
     runtime::Builder::new()
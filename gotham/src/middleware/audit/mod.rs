@@ -0,0 +1,333 @@
+//! Middleware to record mutating requests to an audit log.
+//!
+//! Each `POST`, `PUT`, `PATCH` and `DELETE` request is described by an `AuditEntry`, which is
+//! handed to an `AuditBackend` once the response is ready. Backends may write entries to a file,
+//! a database or a message queue; failing to record an entry never fails the request.
+use std::fmt::Write;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use futures::{future, Future};
+use hyper::{Method, StatusCode, Uri};
+use log::{error, trace};
+use ring::digest;
+use tokio::executor::{DefaultExecutor, Executor};
+
+use crate::handler::HandlerFuture;
+use crate::helpers::timing::Timer;
+use crate::middleware::body_replay::{self, BufferedBody};
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+// the default limit placed on audited request bodies, 10MB
+const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// The user a request was authenticated as, recorded in audit entries when present.
+///
+/// This is intended to be stored in `State` by authentication middleware, ahead of the
+/// `AuditLogMiddleware` in the pipeline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthenticatedUser(pub String);
+
+impl StateData for AuthenticatedUser {}
+
+/// A record of a single mutating request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditEntry {
+    /// The time the request was received.
+    pub timestamp: DateTime<Utc>,
+
+    /// The unique identifier of the request.
+    pub request_id: String,
+
+    /// The method of the request.
+    pub method: Method,
+
+    /// The path of the request, without the query string.
+    pub path: String,
+
+    /// The user the request was authenticated as, if any.
+    pub user: Option<String>,
+
+    /// The SHA-256 digest of the request body, in lowercase hex.
+    pub body_sha256: String,
+
+    /// The status of the response, or of the error the request failed with.
+    pub status: StatusCode,
+}
+
+/// A destination for audit entries.
+///
+/// Entries are recorded after the response has been produced, on the executor of the server,
+/// so a slow backend never delays the response. A backend which fails to record an entry should
+/// resolve to an error, which is logged by the `AuditLogMiddleware`.
+///
+/// ```rust
+/// # extern crate futures;
+/// # extern crate gotham;
+/// # extern crate log;
+/// #
+/// # use std::io;
+/// # use futures::{future, Future};
+/// # use gotham::middleware::audit::{AuditBackend, AuditEntry};
+/// # use log::info;
+/// #
+/// struct LogBackend;
+///
+/// impl AuditBackend for LogBackend {
+///     fn record(&self, entry: AuditEntry) -> Box<dyn Future<Item = (), Error = io::Error> + Send> {
+///         info!(target: "audit", "{} {} {} {}", entry.request_id, entry.method, entry.path, entry.status);
+///         Box::new(future::ok(()))
+///     }
+/// }
+/// # fn main() { let _ = LogBackend; }
+/// ```
+pub trait AuditBackend: Send + Sync + RefUnwindSafe {
+    /// Records an entry, resolving once it has been stored.
+    fn record(&self, entry: AuditEntry) -> Box<dyn Future<Item = (), Error = io::Error> + Send>;
+}
+
+/// Middleware binding to record each mutating request to an `AuditBackend`.
+///
+/// The request body is buffered (as by the `BodyReplayMiddleware`) so that it can be hashed
+/// without preventing the handler from reading it, unless it has already been buffered into a
+/// `BufferedBody`. Bodies larger than the configured maximum size are rejected with a
+/// `413 Payload Too Large`, and are still audited.
+///
+/// ```rust
+/// # extern crate futures;
+/// # extern crate gotham;
+/// #
+/// # use std::io;
+/// # use std::sync::Arc;
+/// # use futures::{future, Future};
+/// # use gotham::middleware::audit::{AuditBackend, AuditEntry, AuditLogMiddleware};
+/// # use gotham::pipeline::new_pipeline;
+/// #
+/// # struct DatabaseBackend;
+/// #
+/// # impl AuditBackend for DatabaseBackend {
+/// #     fn record(&self, _entry: AuditEntry) -> Box<dyn Future<Item = (), Error = io::Error> + Send> {
+/// #         Box::new(future::ok(()))
+/// #     }
+/// # }
+/// #
+/// let audit = AuditLogMiddleware::new(Arc::new(DatabaseBackend)).max_body_size(1024 * 1024);
+///
+/// let pipeline = new_pipeline().add(audit).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone)]
+pub struct AuditLogMiddleware {
+    backend: Arc<dyn AuditBackend>,
+    max_body_size: usize,
+}
+
+impl AuditLogMiddleware {
+    /// Creates a new `AuditLogMiddleware` recording to the provided backend, limiting audited
+    /// request bodies to 10MB.
+    pub fn new(backend: Arc<dyn AuditBackend>) -> Self {
+        AuditLogMiddleware {
+            backend,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+
+    /// Sets the maximum size, in bytes, of an audited request body.
+    pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    /// Builds the entry for a completed request, and hands it to the backend.
+    fn record(&self, state: &State, status: StatusCode) {
+        let body_sha256 = match BufferedBody::try_borrow_from(state) {
+            Some(body) => hex(digest::digest(&digest::SHA256, body.bytes()).as_ref()),
+            // the body was rejected before it could be buffered
+            None => "-".to_owned(),
+        };
+
+        let entry = AuditEntry {
+            timestamp: *Timer::from_state(state).start_time(),
+            request_id: request_id(state).to_owned(),
+            method: Method::borrow_from(state).clone(),
+            path: Uri::borrow_from(state).path().to_owned(),
+            user: AuthenticatedUser::try_borrow_from(state).map(|user| user.0.clone()),
+            body_sha256,
+            status,
+        };
+
+        let id = entry.request_id.clone();
+        let f = self.backend.record(entry).map_err(move |e| {
+            error!("[{}] unable to record audit entry: {}", id, e);
+        });
+
+        if let Err(e) = DefaultExecutor::current().spawn(Box::new(f)) {
+            error!(
+                "[{}] unable to spawn audit entry recording: {:?}",
+                request_id(state),
+                e
+            );
+        }
+    }
+}
+
+/// Determines whether requests using a method are audited.
+fn is_mutating(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    )
+}
+
+/// Formats bytes as lowercase hex.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for AuditLogMiddleware {
+    /// Records mutating requests once the rest of the chain has produced a response.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        if !is_mutating(Method::borrow_from(&state)) {
+            return chain(state);
+        }
+
+        trace!("[{}] auditing request", request_id(&state));
+
+        let f = if state.has::<BufferedBody>() {
+            chain(state)
+        } else {
+            body_replay::buffer(state, self.max_body_size, chain)
+        };
+
+        let f = f.then(move |result| {
+            match result {
+                Ok((ref state, ref response)) => self.record(state, response.status()),
+                Err((ref state, ref err)) => self.record(state, err.status()),
+            }
+            future::result(result)
+        });
+
+        Box::new(f)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for AuditLogMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use futures::Stream;
+    use hyper::{Body, HeaderMap, Response};
+    use tokio::runtime::current_thread;
+
+    #[derive(Default)]
+    struct Recording(Mutex<Vec<AuditEntry>>);
+
+    impl AuditBackend for Recording {
+        fn record(
+            &self,
+            entry: AuditEntry,
+        ) -> Box<dyn Future<Item = (), Error = io::Error> + Send> {
+            self.0.lock().unwrap().push(entry);
+            Box::new(future::ok(()))
+        }
+    }
+
+    struct Failing;
+
+    impl AuditBackend for Failing {
+        fn record(
+            &self,
+            _entry: AuditEntry,
+        ) -> Box<dyn Future<Item = (), Error = io::Error> + Send> {
+            Box::new(future::err(io::Error::new(
+                io::ErrorKind::Other,
+                "database unavailable",
+            )))
+        }
+    }
+
+    fn call(middleware: AuditLogMiddleware, method: Method, user: Option<&str>) -> Vec<u8> {
+        let mut state = State::new();
+        state.put(method);
+        state.put("/accounts/7?debug=1".parse::<Uri>().unwrap());
+        state.put(HeaderMap::new());
+        state.put(Body::from("{\"name\":\"Ada\"}"));
+        if let Some(user) = user {
+            state.put(AuthenticatedUser(user.to_owned()));
+        }
+        crate::state::set_request_id(&mut state);
+
+        let f = middleware.call(state, |mut state| {
+            // the handler can still read the body which was hashed
+            let f = state.take::<Body>().concat2().then(move |result| {
+                let response = Response::builder()
+                    .status(StatusCode::CREATED)
+                    .body(Body::from(result.unwrap()))
+                    .unwrap();
+                Ok((state, response))
+            });
+
+            Box::new(f)
+        });
+
+        let (_, response) = current_thread::block_on_all(f).map_err(|_| ()).unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        current_thread::block_on_all(response.into_body().concat2())
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn records_mutating_requests() {
+        let recording = Arc::new(Recording::default());
+        let middleware = AuditLogMiddleware::new(recording.clone());
+
+        let body = call(middleware.clone(), Method::PUT, Some("ada@example.com"));
+        assert_eq!(body, b"{\"name\":\"Ada\"}");
+
+        call(middleware, Method::GET, None);
+
+        let entries = recording.0.lock().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].method, Method::PUT);
+        assert_eq!(entries[0].path, "/accounts/7");
+        assert_eq!(entries[0].user.as_deref(), Some("ada@example.com"));
+        assert_eq!(entries[0].status, StatusCode::CREATED);
+        assert_eq!(
+            entries[0].body_sha256,
+            "88bab6d8f6dc68a877064d584cbb5b6c50e74f617ea50d81d3a53c2ee6ffbc4f"
+        );
+    }
+
+    #[test]
+    fn ignores_backend_failures() {
+        let middleware = AuditLogMiddleware::new(Arc::new(Failing));
+        assert_eq!(
+            call(middleware, Method::DELETE, None),
+            b"{\"name\":\"Ada\"}"
+        );
+    }
+}
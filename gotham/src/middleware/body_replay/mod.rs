@@ -0,0 +1,242 @@
+//! Middleware to buffer request bodies, so that they can be read more than once.
+use std::io;
+
+use bytes::Bytes;
+use futures::{future, Future, Stream};
+use hyper::header::{HeaderMap, CONTENT_LENGTH};
+use hyper::{Body, StatusCode};
+use log::trace;
+
+use crate::handler::{HandlerFuture, IntoHandlerError};
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+// the default limit placed on buffered bodies, 10MB
+const DEFAULT_MAX_SIZE: usize = 10 * 1024 * 1024;
+
+/// The request body, buffered in full by the `BodyReplayMiddleware`.
+///
+/// This can be borrowed from `State` any number of times, by middleware and handlers alike. As
+/// `Bytes` is reference counted, cloning the buffered body is cheap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BufferedBody(pub Bytes);
+
+impl BufferedBody {
+    /// Returns the buffered bytes of the request body.
+    pub fn bytes(&self) -> &Bytes {
+        &self.0
+    }
+
+    /// Creates a new `Body` which replays the buffered bytes, such as to put a fresh body back
+    /// into `State` after it has been read.
+    pub fn body(&self) -> Body {
+        Body::from(self.0.clone())
+    }
+}
+
+impl StateData for BufferedBody {}
+
+/// Middleware binding to buffer the entire request body ahead of the rest of the chain.
+///
+/// The buffered body is stored in `State` as a `BufferedBody`, and the `Body` in `State` is
+/// replaced by one which replays the buffered bytes. Later middleware (such as signature
+/// verification) can inspect the `BufferedBody`, while the handler still reads the `Body` as
+/// usual, without either having to pass the bytes along by other means.
+///
+/// Requests are rejected before reaching the handler when:
+///
+/// * the body exceeds the configured maximum size, with a `413 Payload Too Large`, which is
+///   checked against the `Content-Length` before any of the body is read;
+/// * the body can't be read, with a `400 Bad Request`.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::body_replay::BodyReplayMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// let replay = BodyReplayMiddleware::new().max_size(64 * 1024);
+///
+/// let pipeline = new_pipeline().add(replay).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct BodyReplayMiddleware {
+    max_size: usize,
+}
+
+impl BodyReplayMiddleware {
+    /// Creates a new `BodyReplayMiddleware`, limiting buffered bodies to 10MB.
+    pub fn new() -> Self {
+        BodyReplayMiddleware {
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+
+    /// Sets the maximum size, in bytes, of a buffered request body.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+impl Default for BodyReplayMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for BodyReplayMiddleware {
+    /// Buffers the request body ahead of the rest of the chain.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        buffer(state, self.max_size, chain)
+    }
+}
+
+/// Buffers the request body of up to `max_size` bytes into a `BufferedBody`, replacing the `Body`
+/// in `State` with a replay of it, before invoking the chain.
+///
+/// Bodies which are too large are rejected with a `413 Payload Too Large`, and bodies which can't
+/// be read with a `400 Bad Request`, without invoking the chain.
+pub(crate) fn buffer<Chain>(mut state: State, max_size: usize, chain: Chain) -> Box<HandlerFuture>
+where
+    Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+{
+    let declared = HeaderMap::borrow_from(&state)
+        .get(CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok())
+        .and_then(|len| len.parse::<u64>().ok());
+
+    if declared.map_or(false, |len| len > max_size as u64) {
+        trace!("[{}] declared request body too large", request_id(&state));
+        let response = create_empty_response(&state, StatusCode::PAYLOAD_TOO_LARGE);
+        return Box::new(future::ok((state, response)));
+    }
+
+    let body = state.take::<Body>();
+
+    let f = body
+        .map_err(Failure::Read)
+        .fold(Vec::new(), move |mut buffer, chunk| {
+            if buffer.len() + chunk.len() > max_size {
+                return Err(Failure::TooLarge);
+            }
+            buffer.extend_from_slice(&chunk);
+            Ok(buffer)
+        })
+        .then(move |result| match result {
+            Ok(buffer) => {
+                trace!(
+                    "[{}] buffered request body of {} bytes",
+                    request_id(&state),
+                    buffer.len()
+                );
+
+                let buffered = BufferedBody(Bytes::from(buffer));
+                state.put(buffered.body());
+                state.put(buffered);
+                chain(state)
+            }
+            Err(Failure::Read(e)) => {
+                let err = e.into_handler_error().with_status(StatusCode::BAD_REQUEST);
+                Box::new(future::err((state, err)))
+            }
+            Err(Failure::TooLarge) => {
+                trace!("[{}] request body too large", request_id(&state));
+                let response = create_empty_response(&state, StatusCode::PAYLOAD_TOO_LARGE);
+                Box::new(future::ok((state, response)))
+            }
+        });
+
+    Box::new(f)
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for BodyReplayMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+/// The reasons a body may fail to be buffered.
+enum Failure {
+    Read(hyper::Error),
+    TooLarge,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::Response;
+
+    fn call(
+        middleware: BodyReplayMiddleware,
+        body: Body,
+        declared: Option<usize>,
+    ) -> (StatusCode, Vec<u8>) {
+        let mut headers = HeaderMap::new();
+        if let Some(declared) = declared {
+            headers.insert(CONTENT_LENGTH, declared.into());
+        }
+
+        let mut state = State::new();
+        state.put(headers);
+        state.put(body);
+        crate::state::set_request_id(&mut state);
+
+        let (_, response) = middleware
+            .call(state, |mut state| {
+                // the middleware sees the buffered body, and the handler still reads the body
+                let buffered = BufferedBody::borrow_from(&state).clone();
+                let f = state.take::<Body>().concat2().then(move |result| {
+                    let body = result.unwrap();
+                    assert_eq!(&body[..], &buffered.bytes()[..]);
+                    assert_eq!(&BufferedBody::borrow_from(&state).bytes()[..], &body[..]);
+
+                    let response = Response::new(Body::from(body));
+                    Ok((state, response))
+                });
+
+                Box::new(f)
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap();
+
+        let status = response.status();
+        let body = response.into_body().concat2().wait().unwrap().to_vec();
+        (status, body)
+    }
+
+    #[test]
+    fn replays_buffered_bodies() {
+        let chunks = vec!["sig", "ned ", "payload"];
+        let body = Body::wrap_stream(futures::stream::iter_ok::<_, hyper::Error>(chunks));
+
+        let (status, body) = call(BodyReplayMiddleware::new(), body, None);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"signed payload");
+    }
+
+    #[test]
+    fn rejects_large_bodies() {
+        let middleware = BodyReplayMiddleware::new().max_size(4);
+
+        let (status, _) = call(middleware, Body::from("too long"), None);
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        let (status, _) = call(middleware, Body::empty(), Some(8));
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        let (status, body) = call(middleware, Body::from("fits"), Some(4));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"fits");
+    }
+}
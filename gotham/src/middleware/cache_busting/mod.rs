@@ -0,0 +1,390 @@
+//! Middleware to provide fingerprinted ("cache busted") URLs for static assets.
+//!
+//! Build tools for single page applications typically emit a manifest mapping each asset to a
+//! name containing a hash of its contents, such as `{"main.js": "main.abc123.js"}`. Linking to
+//! the fingerprinted name allows assets to be cached indefinitely, as every deploy which changes
+//! an asset also changes its URL.
+//!
+//! The `CacheBustingMiddleware` loads such a manifest and stores it in `State` as an
+//! `AssetManifest`, which handlers use to create asset URLs. Requests for fingerprinted names
+//! are served from the original files by a `to_dir` route running the middleware, so the
+//! assets don't need to be renamed on disk.
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockReadGuard, Weak};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use futures::future;
+use hyper::{Method, StatusCode, Uri};
+use log::{trace, warn};
+
+use crate::handler::assets::AssetPathRewrite;
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+// the interval at which the manifest file is checked for changes, 2s
+const RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Middleware binding to load an asset manifest and store it in `State`.
+///
+/// The manifest is a JSON object mapping asset paths to their fingerprinted paths, both
+/// relative to the directory the assets are served from. It's read when the middleware is
+/// created, and then checked for changes every 2 seconds by a background thread, so that a
+/// deploy can replace the manifest without restarting the application. A manifest which fails
+/// to load is ignored, keeping the previous mappings.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate tempfile;
+/// #
+/// # use hyper::StatusCode;
+/// # use gotham::middleware::cache_busting::CacheBustingMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::test::TestServer;
+/// # use std::fs;
+/// #
+/// # fn main() -> std::io::Result<()> {
+/// # let dir = tempfile::tempdir()?;
+/// # let (assets, manifest) = (dir.path().to_owned(), dir.path().join("manifest.json"));
+/// # fs::write(&manifest, r#"{"main.js": "main.abc123.js"}"#)?;
+/// # fs::write(assets.join("main.js"), "console.log('hello');")?;
+/// let cache_busting = CacheBustingMiddleware::new(manifest)?
+///     .url_prefix("/assets")
+///     .manifest_url("/assets/manifest.json");
+///
+/// let (chain, pipelines) = single_pipeline(new_pipeline().add(cache_busting).build());
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     // serves `/assets/main.abc123.js` from `main.js`
+///     route.get("/assets/*").to_dir(assets);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/assets/main.abc123.js")
+/// #     .perform()
+/// #     .unwrap();
+/// #
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert_eq!(response.read_utf8_body().unwrap(), "console.log('hello');");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CacheBustingMiddleware {
+    manifest: AssetManifest,
+    manifest_url: Option<String>,
+}
+
+impl CacheBustingMiddleware {
+    /// Creates a new `CacheBustingMiddleware`, loading the manifest at the provided path and
+    /// starting the thread which reloads it on change.
+    ///
+    /// Fails if the manifest can't be read, or isn't a JSON object of strings.
+    pub fn new<P>(manifest_path: P) -> io::Result<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = manifest_path.into();
+        let (mappings, modified) = load(&path)?;
+
+        let shared = Arc::new(RwLock::new(mappings));
+        let watched = Arc::downgrade(&shared);
+
+        thread::Builder::new()
+            .name("gotham-asset-manifest".to_owned())
+            .spawn(move || watch(&path, &watched, modified))?;
+
+        Ok(CacheBustingMiddleware {
+            manifest: AssetManifest {
+                mappings: shared,
+                prefix: Arc::new(String::new()),
+            },
+            manifest_url: None,
+        })
+    }
+
+    /// Sets the path which asset URLs are created beneath, such as `/assets`, matching the
+    /// route serving the asset directory.
+    pub fn url_prefix(mut self, prefix: &str) -> Self {
+        self.manifest.prefix = Arc::new(prefix.trim_end_matches('/').to_owned());
+        self
+    }
+
+    /// Serves the current manifest as JSON at the provided path, for client-side use.
+    ///
+    /// The path must be routed to a handler using a pipeline containing the middleware, such as
+    /// the `to_dir` route serving the assets themselves.
+    pub fn manifest_url(mut self, path: &str) -> Self {
+        self.manifest_url = Some(path.to_owned());
+        self
+    }
+
+    /// Returns the manifest which the middleware stores in `State`, for use outside of requests.
+    pub fn manifest(&self) -> AssetManifest {
+        self.manifest.clone()
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for CacheBustingMiddleware {
+    /// Stores the `AssetManifest` and an `AssetPathRewrite` in `State`, or responds with the
+    /// manifest itself.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        let serve_manifest = match self.manifest_url {
+            Some(ref url) => {
+                let method = Method::borrow_from(&state);
+                (method == Method::GET || method == Method::HEAD)
+                    && Uri::borrow_from(&state).path() == url
+            }
+            None => false,
+        };
+
+        if serve_manifest {
+            trace!("[{}] serving asset manifest", request_id(&state));
+
+            let body = serde_json::to_string(&*self.manifest.read())
+                .expect("string maps always serialize");
+            let response = create_response(&state, StatusCode::OK, mime::APPLICATION_JSON, body);
+
+            return Box::new(future::ok((state, response)));
+        }
+
+        // fingerprinted paths are served from the original file by `to_dir` routes
+        let manifest = self.manifest.clone();
+        state.put(AssetPathRewrite::new(move |path| {
+            manifest.original_path(path)
+        }));
+
+        state.put(self.manifest);
+        chain(state)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for CacheBustingMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance, sharing the manifest.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// The asset manifest loaded by the `CacheBustingMiddleware`.
+///
+/// This is stored in `State` by the middleware, and can be retrieved via
+/// `AssetManifest::borrow_from(&state)` in order to link to assets.
+#[derive(Clone)]
+pub struct AssetManifest {
+    mappings: Arc<RwLock<Mappings>>,
+    prefix: Arc<String>,
+}
+
+/// The manifest entries, along with the reverse mapping used to serve fingerprinted paths.
+struct Mappings {
+    forward: HashMap<String, String>,
+    reverse: HashMap<String, String>,
+}
+
+impl AssetManifest {
+    /// Returns the URL of an asset, using the fingerprinted path from the manifest.
+    ///
+    /// Paths are relative to the asset directory; a leading `/` is ignored. Assets missing from
+    /// the manifest keep their original path.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate tempfile;
+    /// # use gotham::middleware::cache_busting::CacheBustingMiddleware;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let dir = tempfile::tempdir()?;
+    /// # let path = dir.path().join("manifest.json");
+    /// # std::fs::write(&path, r#"{"main.js": "main.abc123.js"}"#)?;
+    /// let manifest = CacheBustingMiddleware::new(path)?
+    ///     .url_prefix("/assets/")
+    ///     .manifest();
+    ///
+    /// assert_eq!(manifest.asset_url("main.js"), "/assets/main.abc123.js");
+    /// assert_eq!(manifest.asset_url("/logo.png"), "/assets/logo.png");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn asset_url(&self, path: &str) -> String {
+        let path = path.trim_start_matches('/');
+
+        let mappings = self.read();
+        let resolved = mappings.forward.get(path).map_or(path, String::as_str);
+
+        format!("{}/{}", self.prefix, resolved)
+    }
+
+    /// Returns the original path of a fingerprinted path, relative to the asset directory.
+    fn original_path(&self, fingerprinted: &str) -> Option<String> {
+        self.read().reverse.get(fingerprinted).cloned()
+    }
+
+    /// Locks the mappings for reading, ignoring poisoning as they're replaced in one step.
+    fn read(&self) -> RwLockReadGuard<'_, Mappings> {
+        match self.mappings.read() {
+            Ok(mappings) => mappings,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl StateData for AssetManifest {}
+
+impl serde::Serialize for Mappings {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.forward.serialize(serializer)
+    }
+}
+
+/// Reads and parses the manifest at `path`, along with the time it was last modified.
+fn load(path: &Path) -> io::Result<(Mappings, Option<SystemTime>)> {
+    let modified = fs::metadata(path)?.modified().ok();
+    let contents = fs::read(path)?;
+
+    let forward: HashMap<String, String> = serde_json::from_slice(&contents)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let reverse = forward
+        .iter()
+        .map(|(original, fingerprinted)| (fingerprinted.clone(), original.clone()))
+        .collect();
+
+    Ok((Mappings { forward, reverse }, modified))
+}
+
+/// Reloads the manifest whenever it's modified, until the middleware is dropped.
+fn watch(path: &Path, mappings: &Weak<RwLock<Mappings>>, mut modified: Option<SystemTime>) {
+    loop {
+        thread::sleep(RELOAD_INTERVAL);
+
+        match mappings.upgrade() {
+            Some(mappings) => reload(path, &mappings, &mut modified),
+            None => return,
+        }
+    }
+}
+
+/// Replaces the mappings with the contents of the manifest, if it was modified since the last
+/// time it was loaded.
+fn reload(path: &Path, mappings: &RwLock<Mappings>, modified: &mut Option<SystemTime>) {
+    let current = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    if current.is_none() || current == *modified {
+        return;
+    }
+
+    match load(path) {
+        Ok((loaded, loaded_at)) => {
+            *modified = loaded_at;
+            match mappings.write() {
+                Ok(mut mappings) => *mappings = loaded,
+                Err(poisoned) => *poisoned.into_inner() = loaded,
+            }
+        }
+        Err(e) => {
+            *modified = current;
+            warn!("unable to reload asset manifest {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+
+    use futures::Future;
+    use hyper::{Body, HeaderMap, Response};
+    use tempfile::tempdir;
+
+    fn request(middleware: CacheBustingMiddleware, path: &str) -> (State, Response<Body>) {
+        let mut state = State::new();
+        state.put(Method::GET);
+        state.put(path.parse::<Uri>().unwrap());
+        state.put(HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+
+        middleware
+            .call(state, |state| {
+                Box::new(future::ok((state, Response::new(Body::empty()))))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap()
+    }
+
+    #[test]
+    fn serves_and_reloads_manifests() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("manifest.json");
+        fs::write(&path, r#"{"main.js": "main.abc123.js"}"#).unwrap();
+
+        let middleware = CacheBustingMiddleware::new(path.clone())
+            .unwrap()
+            .url_prefix("/assets")
+            .manifest_url("/assets/manifest.json");
+
+        let (state, _) = request(middleware.clone(), "/assets/main.abc123.js");
+        let manifest = AssetManifest::borrow_from(&state);
+        assert_eq!(manifest.asset_url("main.js"), "/assets/main.abc123.js");
+        assert_eq!(
+            manifest.original_path("main.abc123.js"),
+            Some("main.js".to_owned())
+        );
+
+        let rewrite = AssetPathRewrite::borrow_from(&state);
+        assert_eq!(
+            rewrite.rewrite("main.abc123.js"),
+            Some("main.js".to_owned())
+        );
+        assert_eq!(rewrite.rewrite("logo.png"), None);
+
+        let (state, response) = request(middleware.clone(), "/assets/manifest.json");
+        assert!(!state.has::<AssetManifest>());
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // a changed manifest replaces every mapping
+        let mut modified = fs::metadata(&path).unwrap().modified().ok();
+        fs::write(&path, r#"{"main.js": "main.def456.js"}"#).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        reload(&path, &middleware.manifest.mappings, &mut modified);
+        assert_eq!(manifest.asset_url("main.js"), "/assets/main.def456.js");
+        assert_eq!(manifest.original_path("main.abc123.js"), None);
+
+        // an invalid manifest is ignored
+        fs::write(&path, "not json").unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(120))
+            .unwrap();
+
+        reload(&path, &middleware.manifest.mappings, &mut modified);
+        assert_eq!(manifest.asset_url("main.js"), "/assets/main.def456.js");
+        assert!(CacheBustingMiddleware::new(path).is_err());
+    }
+}
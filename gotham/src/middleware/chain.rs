@@ -19,6 +19,8 @@ pub unsafe trait NewMiddlewareChain: RefUnwindSafe + Sized {
 
     /// Create and return a new `MiddlewareChain` value.
     fn construct(&self) -> io::Result<Self::Instance>;
+
+    fn middleware_names(&self, names: &mut Vec<&'static str>);
 }
 
 unsafe impl<T, U> NewMiddlewareChain for (T, U)
@@ -38,6 +40,13 @@ where
         let (ref nm, ref tail) = *self;
         Ok((nm.new_middleware()?, tail.construct()?))
     }
+
+    fn middleware_names(&self, names: &mut Vec<&'static str>) {
+        // The list is reversed, so the tail is visited first to report invocation order.
+        let (ref nm, ref tail) = *self;
+        tail.middleware_names(names);
+        names.push(nm.middleware_name());
+    }
 }
 
 unsafe impl NewMiddlewareChain for () {
@@ -48,6 +57,8 @@ unsafe impl NewMiddlewareChain for () {
         trace!(" completed middleware pipeline construction");
         Ok(())
     }
+
+    fn middleware_names(&self, _names: &mut Vec<&'static str>) {}
 }
 
 /// A recursive type representing an instance of a pipeline, which is used to process a single
@@ -0,0 +1,321 @@
+//! Middleware to limit the number of requests handled concurrently by specific routes.
+//!
+//! A slow, resource-heavy endpoint can tie up enough of the server to starve every other route.
+//! Adding this middleware to the pipeline of such routes (or to a single route via
+//! `with_middleware`) bounds how many of their requests are in flight at once, independently of
+//! the rest of the application. This differs from the `ConnectionLimitMiddleware`, which applies
+//! to every request passing through it and never queues.
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use futures::sync::oneshot;
+use futures::{future, Future};
+use hyper::header::{HeaderValue, RETRY_AFTER};
+use hyper::StatusCode;
+use log::{debug, trace};
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, State};
+
+/// Middleware binding to limit the number of requests handled concurrently by the routes it
+/// guards.
+///
+/// Each request holds a permit from a semaphore shared by every instance of the middleware, from
+/// when it reaches the middleware until the rest of the chain has produced a response. The permit
+/// is also released if the request is abandoned, such as when the client disconnects and the
+/// handler future is dropped.
+///
+/// When no permit is available, requests wait for one in arrival order, up to the queue bound.
+/// Requests arriving while the queue is full (or immediately, when no queue is configured)
+/// receive a `503 Service Unavailable` with a `Retry-After: 1` header.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::concurrency::ConcurrencyLimitMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// // at most 4 reports are generated at once, with up to 16 more waiting their turn
+/// let limit = ConcurrencyLimitMiddleware::new(4).queue(16);
+///
+/// let pipeline = new_pipeline().add(limit).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone)]
+pub struct ConcurrencyLimitMiddleware {
+    semaphore: Arc<Semaphore>,
+    max_queued: usize,
+}
+
+impl ConcurrencyLimitMiddleware {
+    /// Creates a new `ConcurrencyLimitMiddleware`, handling at most `max_concurrent` requests at
+    /// once and rejecting any others.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_concurrent` is zero.
+    pub fn new(max_concurrent: usize) -> Self {
+        assert!(
+            max_concurrent > 0,
+            "max_concurrent must be greater than zero"
+        );
+
+        ConcurrencyLimitMiddleware {
+            semaphore: Arc::new(Semaphore {
+                slots: Mutex::new(Slots {
+                    available: max_concurrent,
+                    waiters: VecDeque::new(),
+                }),
+            }),
+            max_queued: 0,
+        }
+    }
+
+    /// Sets the number of requests which may wait for a permit, rather than being rejected, once
+    /// the limit is reached. This is zero by default.
+    pub fn queue(mut self, max_queued: usize) -> Self {
+        self.max_queued = max_queued;
+        self
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for ConcurrencyLimitMiddleware {
+    /// Runs the chain once a permit is available, and otherwise responds with a `503`.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        let run = |permit: Permit, state: State| -> Box<HandlerFuture> {
+            // the permit is dropped alongside the chain, whether it completes or not
+            Box::new(chain(state).then(move |result| {
+                drop(permit);
+                result
+            }))
+        };
+
+        match self.semaphore.acquire(self.max_queued) {
+            Acquire::Ready(permit) => run(permit, state),
+            Acquire::Queued(waiting) => {
+                trace!("[{}] waiting for a concurrency permit", request_id(&state));
+
+                // the sender is only dropped without a permit if the semaphore is dropped
+                Box::new(waiting.then(move |permit| match permit {
+                    Ok(permit) => run(permit, state),
+                    Err(_) => reject(state),
+                }))
+            }
+            Acquire::Full => {
+                debug!(
+                    "[{}] rejecting request, concurrency limit reached",
+                    request_id(&state)
+                );
+                reject(state)
+            }
+        }
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for ConcurrencyLimitMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance, sharing the semaphore.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// Responds with a `503 Service Unavailable`, asking the client to retry shortly.
+fn reject(state: State) -> Box<HandlerFuture> {
+    let mut response = create_empty_response(&state, StatusCode::SERVICE_UNAVAILABLE);
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from_static("1"));
+
+    Box::new(future::ok((state, response)))
+}
+
+/// A counting semaphore which hands permits to waiting requests in arrival order.
+struct Semaphore {
+    slots: Mutex<Slots>,
+}
+
+/// The permits which are available, and the requests waiting for one.
+struct Slots {
+    available: usize,
+    waiters: VecDeque<oneshot::Sender<Permit>>,
+}
+
+/// The outcome of requesting a permit from a `Semaphore`.
+enum Acquire {
+    /// A permit was available.
+    Ready(Permit),
+
+    /// The request has been queued, and will receive a permit once one is released.
+    Queued(oneshot::Receiver<Permit>),
+
+    /// No permit was available, and the queue was full.
+    Full,
+}
+
+impl Semaphore {
+    /// Locks the slots, ignoring poisoning as the counts are always left consistent.
+    fn lock(&self) -> MutexGuard<'_, Slots> {
+        self.slots
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Requests a permit, queueing the request when none is available and the queue has room.
+    fn acquire(self: &Arc<Self>, max_queued: usize) -> Acquire {
+        let mut slots = self.lock();
+
+        if slots.available > 0 {
+            slots.available -= 1;
+            return Acquire::Ready(Permit {
+                semaphore: Some(self.clone()),
+            });
+        }
+
+        // abandoned requests no longer count towards the queue
+        slots.waiters.retain(|waiter| !waiter.is_canceled());
+
+        if slots.waiters.len() >= max_queued {
+            return Acquire::Full;
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        slots.waiters.push_back(sender);
+        Acquire::Queued(receiver)
+    }
+
+    /// Passes a released permit to the first waiting request, or makes it available again.
+    fn release(self: &Arc<Self>) {
+        loop {
+            let waiter = {
+                let mut slots = self.lock();
+                match slots.waiters.pop_front() {
+                    Some(waiter) => waiter,
+                    None => {
+                        slots.available += 1;
+                        return;
+                    }
+                }
+            };
+
+            let permit = Permit {
+                semaphore: Some(self.clone()),
+            };
+
+            match waiter.send(permit) {
+                Ok(()) => return,
+                // the request was abandoned, so the permit moves on without being released
+                Err(mut permit) => {
+                    permit.semaphore = None;
+                }
+            }
+        }
+    }
+}
+
+/// A permit held by a request in progress, released when dropped.
+struct Permit {
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(semaphore) = self.semaphore.take() {
+            semaphore.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, HeaderMap, Response};
+
+    fn state() -> State {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+        state
+    }
+
+    fn ok(state: State) -> Box<HandlerFuture> {
+        Box::new(future::ok((state, Response::new(Body::empty()))))
+    }
+
+    fn status(f: Box<HandlerFuture>) -> StatusCode {
+        f.wait().map_err(|_| ()).unwrap().1.status()
+    }
+
+    fn available(middleware: &ConcurrencyLimitMiddleware) -> usize {
+        middleware.semaphore.lock().available
+    }
+
+    #[test]
+    fn rejects_requests_beyond_the_limit() {
+        let middleware = ConcurrencyLimitMiddleware::new(1);
+        let (release, released) = oneshot::channel::<()>();
+
+        let slow = middleware.clone().call(state(), move |state| {
+            Box::new(released.then(|_| Ok((state, Response::new(Body::empty())))))
+        });
+
+        let rejected = middleware.clone().call(state(), ok);
+        let response = rejected.wait().map_err(|_| ()).unwrap().1;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[RETRY_AFTER], "1");
+
+        release.send(()).unwrap();
+        assert_eq!(status(slow), StatusCode::OK);
+        assert_eq!(status(middleware.clone().call(state(), ok)), StatusCode::OK);
+        assert_eq!(available(&middleware), 1);
+    }
+
+    #[test]
+    fn queues_requests_up_to_the_bound() {
+        let middleware = ConcurrencyLimitMiddleware::new(1).queue(1);
+        let (release, released) = oneshot::channel::<()>();
+
+        let slow = middleware.clone().call(state(), move |state| {
+            Box::new(released.then(|_| Ok((state, Response::new(Body::empty())))))
+        });
+
+        let queued = middleware.clone().call(state(), ok);
+        let rejected = middleware.clone().call(state(), ok);
+        assert_eq!(status(rejected), StatusCode::SERVICE_UNAVAILABLE);
+
+        // the permit passes straight to the queued request
+        release.send(()).unwrap();
+        assert_eq!(status(slow), StatusCode::OK);
+        assert_eq!(available(&middleware), 0);
+
+        assert_eq!(status(queued), StatusCode::OK);
+        assert_eq!(available(&middleware), 1);
+    }
+
+    #[test]
+    fn releases_permits_of_dropped_requests() {
+        let middleware = ConcurrencyLimitMiddleware::new(1).queue(1);
+
+        let (_release, released) = oneshot::channel::<()>();
+        let slow = middleware.clone().call(state(), move |state| {
+            Box::new(released.then(|_| Ok((state, Response::new(Body::empty())))))
+        });
+
+        // abandoned while waiting, so the queue has room again
+        drop(middleware.clone().call(state(), ok));
+        let queued = middleware.clone().call(state(), ok);
+
+        // abandoned while holding the permit
+        drop(slow);
+        assert_eq!(status(queued), StatusCode::OK);
+        assert_eq!(available(&middleware), 1);
+    }
+}
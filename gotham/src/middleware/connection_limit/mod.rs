@@ -0,0 +1,276 @@
+//! Middleware to shed load by capping the number of requests handled at once.
+//!
+//! Under extreme load it's better to reject requests cleanly than to queue them until memory runs
+//! out. This middleware counts the requests currently passing through it, and responds to any
+//! arriving beyond the limit with a `503 Service Unavailable` without running the rest of the
+//! chain. Unlike `ServerOptions::max_connections`, idle keep-alive connections don't count
+//! towards the limit.
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures::{future, Future, Poll, Stream};
+use hyper::body::Payload;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, RETRY_AFTER};
+use hyper::{Body, Chunk, Response, StatusCode};
+use log::debug;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, State};
+
+// the header carrying the number of requests in progress, when enabled
+const X_CONCURRENT_REQUESTS: &str = "x-concurrent-requests";
+
+/// Middleware binding to limit the number of requests handled concurrently.
+///
+/// A request counts towards the limit from when it reaches the middleware until its response
+/// body has been sent (or the connection is dropped), so slow clients downloading large bodies
+/// hold their slot. Requests arriving while the limit is reached immediately receive a
+/// `503 Service Unavailable` with a `Retry-After: 1` header.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::connection_limit::ConnectionLimitMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// let limit = ConnectionLimitMiddleware::new(512).include_concurrency_header(true);
+///
+/// let pipeline = new_pipeline().add(limit).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone)]
+pub struct ConnectionLimitMiddleware {
+    max_concurrent: usize,
+    active: Arc<AtomicUsize>,
+    header: bool,
+}
+
+impl ConnectionLimitMiddleware {
+    /// Creates a new `ConnectionLimitMiddleware`, handling at most `max_concurrent` requests at
+    /// once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_concurrent` is zero.
+    pub fn new(max_concurrent: usize) -> Self {
+        assert!(
+            max_concurrent > 0,
+            "max_concurrent must be greater than zero"
+        );
+
+        ConnectionLimitMiddleware {
+            max_concurrent,
+            active: Arc::new(AtomicUsize::new(0)),
+            header: false,
+        }
+    }
+
+    /// Sets whether each response carries an `X-Concurrent-Requests` header, containing the
+    /// number of requests in progress (including itself) when the request arrived. This is
+    /// disabled by default.
+    pub fn include_concurrency_header(mut self, include: bool) -> Self {
+        self.header = include;
+        self
+    }
+
+    /// Claims a slot for a request, returning the number of requests in progress when the limit
+    /// has been reached.
+    fn acquire(&self) -> Result<ActiveRequest, usize> {
+        let mut current = self.active.load(Ordering::SeqCst);
+
+        loop {
+            if current >= self.max_concurrent {
+                return Err(current);
+            }
+
+            match self.active.compare_exchange(
+                current,
+                current + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => {
+                    return Ok(ActiveRequest {
+                        active: self.active.clone(),
+                        count: current + 1,
+                    })
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Adds the concurrency header to a response, when enabled.
+    fn stamp(&self, headers: &mut HeaderMap, count: usize) {
+        if self.header {
+            headers.insert(
+                HeaderName::from_static(X_CONCURRENT_REQUESTS),
+                HeaderValue::from(count),
+            );
+        }
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for ConnectionLimitMiddleware {
+    /// Runs the chain when below the limit, and otherwise responds with a `503`.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        let request = match self.acquire() {
+            Ok(request) => request,
+            Err(count) => {
+                debug!(
+                    "[{}] rejecting request, {} requests in progress",
+                    request_id(&state),
+                    count
+                );
+
+                let mut response = create_empty_response(&state, StatusCode::SERVICE_UNAVAILABLE);
+                let headers = response.headers_mut();
+                headers.insert(RETRY_AFTER, HeaderValue::from_static("1"));
+                self.stamp(headers, count);
+
+                return Box::new(future::ok((state, response)));
+            }
+        };
+
+        let f = chain(state).map(move |(state, response)| {
+            let (mut parts, body) = response.into_parts();
+            self.stamp(&mut parts.headers, request.count);
+
+            // empty bodies are sent straight away, so there's nothing to wait for
+            if body.is_end_stream() {
+                return (state, Response::from_parts(parts, body));
+            }
+
+            // wrapping the body hides its length from hyper, so keep it in a header
+            if let Some(length) = body.content_length() {
+                parts
+                    .headers
+                    .entry(CONTENT_LENGTH)
+                    .unwrap()
+                    .or_insert_with(|| HeaderValue::from(length));
+            }
+
+            let body = Body::wrap_stream(HoldingBody {
+                body,
+                _request: request,
+            });
+            (state, Response::from_parts(parts, body))
+        });
+
+        Box::new(f)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for ConnectionLimitMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance, sharing the request count.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// A slot claimed by a request in progress, released when dropped.
+struct ActiveRequest {
+    active: Arc<AtomicUsize>,
+    count: usize,
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A response body which holds the slot of its request until it has been sent.
+struct HoldingBody {
+    body: Body,
+    _request: ActiveRequest,
+}
+
+impl Stream for HoldingBody {
+    type Item = Chunk;
+    type Error = hyper::Error;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, hyper::Error> {
+        self.body.poll()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::sync::oneshot;
+
+    use crate::handler::IntoHandlerError;
+
+    fn state() -> State {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+        state
+    }
+
+    fn ok(state: State) -> Box<HandlerFuture> {
+        Box::new(future::ok((state, Response::new(Body::from("done")))))
+    }
+
+    fn run(f: Box<HandlerFuture>) -> Response<Body> {
+        f.wait().map_err(|_| ()).unwrap().1
+    }
+
+    #[test]
+    fn rejects_requests_beyond_the_limit() {
+        let middleware = ConnectionLimitMiddleware::new(1).include_concurrency_header(true);
+        let (release, released) = oneshot::channel::<()>();
+
+        let first = middleware.clone().call(state(), move |state| {
+            Box::new(released.then(|_| Ok((state, Response::new(Body::from("slow"))))))
+        });
+
+        let rejected = run(middleware.clone().call(state(), ok));
+        assert_eq!(rejected.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(rejected.headers()[RETRY_AFTER], "1");
+        assert_eq!(rejected.headers()[X_CONCURRENT_REQUESTS], "1");
+
+        release.send(()).unwrap();
+        let first = run(first);
+        assert_eq!(first.headers()[X_CONCURRENT_REQUESTS], "1");
+        assert_eq!(first.headers()[CONTENT_LENGTH], "4");
+
+        // the slot is held until the body has been sent
+        let rejected = run(middleware.clone().call(state(), ok));
+        assert_eq!(rejected.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = first.into_body().concat2().wait().unwrap();
+        assert_eq!(&body[..], b"slow");
+
+        let accepted = run(middleware.clone().call(state(), ok));
+        assert_eq!(accepted.status(), StatusCode::OK);
+        drop(accepted);
+
+        assert_eq!(middleware.active.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn releases_slots_on_errors() {
+        let middleware = ConnectionLimitMiddleware::new(1);
+
+        let f = middleware.clone().call(state(), |state| {
+            let err = io::Error::from(io::ErrorKind::Other).into_handler_error();
+            Box::new(future::err((state, err)))
+        });
+        assert!(f.wait().is_err());
+
+        let response = run(middleware.clone().call(state(), ok));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(X_CONCURRENT_REQUESTS).is_none());
+    }
+}
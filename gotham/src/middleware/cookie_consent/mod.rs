@@ -0,0 +1,283 @@
+//! Middleware to withhold non-essential cookies until a visitor has consented to them.
+//!
+//! Regulations such as the GDPR require sites to obtain consent before setting tracking cookies.
+//! This middleware sends visitors without consent to a consent page, and prevents cookies from
+//! being set on the pages which may be visited before consent has been given.
+use std::io;
+use std::sync::Arc;
+
+use cookie::{Cookie, SameSite};
+use futures::{future, Future};
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_SECURITY_POLICY, LOCATION, SET_COOKIE};
+use hyper::{Body, Response, StatusCode, Uri};
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::cookie::CookieParser;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State};
+
+// the page visitors are sent to when no consent URL is configured
+const DEFAULT_CONSENT_URL: &str = "/cookie-consent";
+
+// the policy applied before consent, allowing only scripts served by the site itself
+const SCRIPT_POLICY: &str = "script-src 'self'";
+
+/// Middleware binding to require consent before non-essential cookies are set.
+///
+/// Consent is indicated by the presence of the consent cookie on the request, which is expected
+/// to be set by the consent page once the visitor accepts. For requests without it:
+///
+/// * requests to paths other than the exempt paths (and the consent URL) are redirected to the
+///   consent URL with a `303 See Other`;
+/// * requests to exempt paths are handled as usual, but any `Set-Cookie` headers are removed
+///   from the response unless the cookie is always allowed (such as a session cookie which the
+///   site can't function without), and a `Content-Security-Policy` of `script-src 'self'` is
+///   added to block third-party scripts. Policies set by the application still apply as well.
+///
+/// Exempt paths match themselves and any path beneath them, so `/static` exempts
+/// `/static/app.css`. Whenever the consent cookie is set by a response, it's set with the
+/// `SameSite=Strict` and `Secure` attributes.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::cookie_consent::CookieConsentMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// let consent = CookieConsentMiddleware::new(vec!["/static", "/privacy"], "cookie_consent")
+///     .consent_url("/consent")
+///     .always_allowed(vec!["_gotham_session"]);
+///
+/// let pipeline = new_pipeline().add(consent).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone)]
+pub struct CookieConsentMiddleware {
+    exempt_paths: Arc<Vec<String>>,
+    consent_cookie: String,
+    consent_url: String,
+    always_allowed: Arc<Vec<String>>,
+}
+
+impl CookieConsentMiddleware {
+    /// Creates a new `CookieConsentMiddleware`, where `consent_cookie` is the name of the cookie
+    /// indicating consent and `exempt_paths` can be visited without consent.
+    pub fn new(exempt_paths: Vec<&str>, consent_cookie: &str) -> Self {
+        CookieConsentMiddleware {
+            exempt_paths: Arc::new(exempt_paths.into_iter().map(str::to_owned).collect()),
+            consent_cookie: consent_cookie.to_owned(),
+            consent_url: DEFAULT_CONSENT_URL.to_owned(),
+            always_allowed: Arc::new(Vec::new()),
+        }
+    }
+
+    /// Sets the URL which visitors without consent are redirected to, which is
+    /// `/cookie-consent` by default. The path of the URL is always exempt.
+    pub fn consent_url(mut self, url: &str) -> Self {
+        self.consent_url = url.to_owned();
+        self
+    }
+
+    /// Sets the names of cookies which may be set before consent has been given.
+    pub fn always_allowed(mut self, names: Vec<&str>) -> Self {
+        self.always_allowed = Arc::new(names.into_iter().map(str::to_owned).collect());
+        self
+    }
+
+    /// Determines whether a path may be visited without consent.
+    fn is_exempt(&self, path: &str) -> bool {
+        let consent_path = self
+            .consent_url
+            .parse::<Uri>()
+            .map(|uri| uri.path().to_owned())
+            .unwrap_or_else(|_| self.consent_url.clone());
+
+        self.exempt_paths
+            .iter()
+            .chain(Some(&consent_path))
+            .any(|exempt| {
+                let exempt = exempt.trim_end_matches('/');
+                path.starts_with(exempt) && {
+                    let rest = &path[exempt.len()..];
+                    rest.is_empty() || rest.starts_with('/')
+                }
+            })
+    }
+
+    /// Updates the `Set-Cookie` headers of a response, removing those which aren't allowed
+    /// without consent and securing the consent cookie.
+    fn filter_cookies(&self, headers: &mut HeaderMap, consented: bool) {
+        let values: Vec<HeaderValue> = headers.get_all(SET_COOKIE).iter().cloned().collect();
+        headers.remove(SET_COOKIE);
+
+        for value in values {
+            let mut cookie = match value.to_str().ok().and_then(|v| Cookie::parse(v).ok()) {
+                Some(cookie) => cookie,
+                None if consented => {
+                    headers.append(SET_COOKIE, value);
+                    continue;
+                }
+                None => continue,
+            };
+
+            if cookie.name() == self.consent_cookie {
+                cookie.set_same_site(SameSite::Strict);
+                cookie.set_secure(true);
+
+                if let Ok(value) = HeaderValue::from_str(&cookie.to_string()) {
+                    headers.append(SET_COOKIE, value);
+                }
+            } else if consented || self.always_allowed.iter().any(|name| name == cookie.name()) {
+                headers.append(SET_COOKIE, value);
+            }
+        }
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for CookieConsentMiddleware {
+    /// Redirects or restricts requests without consent.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        let consented = CookieParser::from_state(&state)
+            .get(&self.consent_cookie)
+            .is_some();
+
+        if !consented && !self.is_exempt(Uri::borrow_from(&state).path()) {
+            trace!("[{}] redirecting to cookie consent", request_id(&state));
+
+            let response = match HeaderValue::from_str(&self.consent_url) {
+                Ok(location) => {
+                    let mut response = create_empty_response(&state, StatusCode::SEE_OTHER);
+                    response.headers_mut().insert(LOCATION, location);
+                    response
+                }
+                Err(_) => create_empty_response(&state, StatusCode::INTERNAL_SERVER_ERROR),
+            };
+
+            return Box::new(future::ok((state, response)));
+        }
+
+        let f = chain(state).map(move |(state, mut response): (State, Response<Body>)| {
+            self.filter_cookies(response.headers_mut(), consented);
+
+            if !consented {
+                response.headers_mut().append(
+                    CONTENT_SECURITY_POLICY,
+                    HeaderValue::from_static(SCRIPT_POLICY),
+                );
+            }
+
+            (state, response)
+        });
+
+        Box::new(f)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for CookieConsentMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::COOKIE;
+    use hyper::Method;
+
+    fn middleware() -> CookieConsentMiddleware {
+        CookieConsentMiddleware::new(vec!["/static/", "/privacy"], "consent")
+            .consent_url("/consent?next=%2F")
+            .always_allowed(vec!["session"])
+    }
+
+    fn call(path: &str, cookie: Option<&'static str>) -> Response<Body> {
+        let mut headers = HeaderMap::new();
+        if let Some(cookie) = cookie {
+            headers.insert(COOKIE, HeaderValue::from_static(cookie));
+        }
+
+        let mut state = State::new();
+        state.put(Method::GET);
+        state.put(path.parse::<Uri>().unwrap());
+        state.put(headers);
+        crate::state::set_request_id(&mut state);
+
+        middleware()
+            .call(state, |state| {
+                let response = Response::builder()
+                    .header(SET_COOKIE, "session=abc; HttpOnly")
+                    .header(SET_COOKIE, "tracker=xyz")
+                    .header(SET_COOKIE, "consent=yes; Path=/")
+                    .body(Body::empty())
+                    .unwrap();
+
+                Box::new(future::ok((state, response)))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap()
+            .1
+    }
+
+    fn set_cookies(response: &Response<Body>) -> Vec<&str> {
+        response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn redirects_without_consent() {
+        for path in &["/", "/account", "/privacy-settings", "/staticfile"] {
+            let response = call(path, None);
+            assert_eq!(response.status(), StatusCode::SEE_OTHER);
+            assert_eq!(response.headers()[LOCATION], "/consent?next=%2F");
+        }
+
+        let response = call("/account", Some("other=1"));
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    }
+
+    #[test]
+    fn restricts_exempt_paths_without_consent() {
+        for path in &["/consent", "/privacy", "/privacy/", "/static/app.css"] {
+            let response = call(path, None);
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[CONTENT_SECURITY_POLICY], SCRIPT_POLICY);
+
+            let cookies = set_cookies(&response);
+            assert_eq!(cookies.len(), 2);
+            assert_eq!(cookies[0], "session=abc; HttpOnly");
+            assert!(cookies[1].starts_with("consent=yes"));
+        }
+    }
+
+    #[test]
+    fn passes_requests_with_consent() {
+        let response = call("/account", Some("consent=yes; other=1"));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(CONTENT_SECURITY_POLICY).is_none());
+
+        let cookies = set_cookies(&response);
+        assert_eq!(cookies.len(), 3);
+        assert_eq!(cookies[1], "tracker=xyz");
+
+        let consent = cookies[2];
+        assert!(consent.starts_with("consent=yes"));
+        assert!(consent.contains("SameSite=Strict"));
+        assert!(consent.contains("Secure"));
+        assert!(consent.contains("Path=/"));
+    }
+}
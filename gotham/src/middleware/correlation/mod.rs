@@ -0,0 +1,191 @@
+//! Middleware to propagate correlation identifiers between services.
+//!
+//! Unlike the request ID, which is local to a single Gotham application, a correlation ID is
+//! expected to be forwarded by each service in a call chain so that every request made on behalf
+//! of a single user request can be tied back together.
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+use futures::{future, Future};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use log::trace;
+use uuid::Uuid;
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+// the header used when no custom name is configured
+const X_CORRELATION_ID: &str = "x-correlation-id";
+
+/// The correlation ID associated with the current request.
+///
+/// This is stored in `State` by the `CorrelationIdMiddleware`, and can be retrieved via
+/// `CorrelationId::borrow_from(&state)` in order to forward it to downstream services.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorrelationId(String);
+
+impl CorrelationId {
+    /// Returns the correlation ID as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for CorrelationId {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl StateData for CorrelationId {}
+
+/// Middleware binding to read, store and propagate a correlation ID.
+///
+/// The ID is read from the `X-Correlation-ID` request header, or generated as a UUID v4 when the
+/// header is absent or not valid. It is then stored in `State` as a `CorrelationId`, and set on
+/// the response using the same header.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::correlation::CorrelationIdMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// let correlation = CorrelationIdMiddleware::new().header_name("X-Trace-ID");
+///
+/// let pipeline = new_pipeline().add(correlation).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone)]
+pub struct CorrelationIdMiddleware {
+    header: HeaderName,
+}
+
+impl CorrelationIdMiddleware {
+    /// Creates a new `CorrelationIdMiddleware` using the `X-Correlation-ID` header.
+    pub fn new() -> Self {
+        CorrelationIdMiddleware {
+            header: HeaderName::from_static(X_CORRELATION_ID),
+        }
+    }
+
+    /// Sets the name of the header used to read and write the correlation ID.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn header_name(mut self, name: &str) -> Self {
+        self.header = HeaderName::from_bytes(name.as_bytes()).expect("invalid header name");
+        self
+    }
+}
+
+impl Default for CorrelationIdMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for CorrelationIdMiddleware {
+    /// Stores the correlation ID in `State` and attaches it to the response.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        let correlation_id = HeaderMap::borrow_from(&state)
+            .get(&self.header)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.is_empty())
+            .map(ToOwned::to_owned);
+
+        let correlation_id = match correlation_id {
+            Some(id) => {
+                trace!("[{}] correlation id provided: {}", request_id(&state), id);
+                id
+            }
+            None => {
+                let id = Uuid::new_v4().to_hyphenated().to_string();
+                trace!("[{}] correlation id generated: {}", request_id(&state), id);
+                id
+            }
+        };
+
+        // this only came from a valid header, or a uuid, so it's always a valid value
+        let value = HeaderValue::from_str(&correlation_id).unwrap();
+        state.put(CorrelationId(correlation_id));
+
+        let header = self.header;
+        let f = chain(state).and_then(move |(state, mut response)| {
+            response.headers_mut().insert(header, value);
+            future::ok((state, response))
+        });
+
+        Box::new(f)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for CorrelationIdMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, Response};
+
+    fn run(middleware: CorrelationIdMiddleware, headers: HeaderMap) -> (String, Response<Body>) {
+        let mut state = State::new();
+        state.put(headers);
+        crate::state::set_request_id(&mut state);
+
+        let (state, response) = middleware
+            .call(state, |state| {
+                Box::new(future::ok((state, Response::new(Body::empty()))))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap();
+
+        (CorrelationId::borrow_from(&state).to_string(), response)
+    }
+
+    #[test]
+    fn forwards_provided_correlation_id() {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_CORRELATION_ID, "abc-123".parse().unwrap());
+
+        let (id, response) = run(CorrelationIdMiddleware::new(), headers);
+
+        assert_eq!(id, "abc-123");
+        assert_eq!(response.headers().get(X_CORRELATION_ID).unwrap(), "abc-123");
+    }
+
+    #[test]
+    fn generates_missing_correlation_id() {
+        let (id, response) = run(CorrelationIdMiddleware::new(), HeaderMap::new());
+
+        assert_eq!(Uuid::parse_str(&id).unwrap().get_version_num(), 4);
+        assert_eq!(response.headers().get(X_CORRELATION_ID).unwrap(), &id[..]);
+    }
+
+    #[test]
+    fn supports_custom_header_names() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-trace-id", "trace".parse().unwrap());
+        headers.insert(X_CORRELATION_ID, "ignored".parse().unwrap());
+
+        let middleware = CorrelationIdMiddleware::new().header_name("X-Trace-ID");
+        let (id, response) = run(middleware, headers);
+
+        assert_eq!(id, "trace");
+        assert_eq!(response.headers().get("x-trace-id").unwrap(), "trace");
+        assert!(response.headers().get(X_CORRELATION_ID).is_none());
+    }
+}
@@ -0,0 +1,296 @@
+//! Server side caching of CORS preflight responses.
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use futures::{future, Future};
+use hyper::header::{
+    HeaderMap, HeaderValue, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS,
+    ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+};
+use hyper::{StatusCode, Uri};
+use log::trace;
+
+use super::is_preflight;
+use crate::handler::HandlerFuture;
+use crate::helpers::http::header::X_REQUEST_ID;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::router::non_match::CorsPreflight;
+use crate::state::{request_id, FromState, State};
+
+// the number of preflight responses cached when no limit is configured
+const DEFAULT_MAX_ENTRIES: usize = 1024;
+
+/// Middleware binding to cache the responses to CORS preflight requests on the server.
+///
+/// Preflight responses carrying an `Access-Control-Max-Age` header are cached for that duration,
+/// keyed by the `Origin`, request path, `Access-Control-Request-Method` and
+/// `Access-Control-Request-Headers` of the preflight. Identical preflights are then answered from
+/// the cache without invoking the rest of the pipeline chain. Responses without a maximum age,
+/// or which aren't successful, are never cached.
+///
+/// This complements the `CorsMiddleware`, and should be placed before it in the same pipeline so
+/// that the responses it builds are the ones cached. As the cache is shared by every instance of
+/// the middleware, each pipeline should be given its own `PreflightCacheMiddleware`.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use std::time::Duration;
+/// # use gotham::middleware::cors::{CorsMiddleware, PreflightCacheMiddleware};
+/// # use gotham::pipeline::new_pipeline;
+/// let pipeline = new_pipeline()
+///     .add(PreflightCacheMiddleware::new())
+///     .add(CorsMiddleware::new().max_age(Duration::from_secs(600)))
+///     .build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone)]
+pub struct PreflightCacheMiddleware {
+    entries: Arc<Mutex<HashMap<CacheKey, CachedPreflight>>>,
+    max_entries: usize,
+}
+
+/// Identifies preflight requests which receive the same response.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct CacheKey {
+    origin: HeaderValue,
+    path: String,
+    method: HeaderValue,
+    headers: Option<HeaderValue>,
+}
+
+/// A cached preflight response, without its request ID.
+struct CachedPreflight {
+    status: StatusCode,
+    headers: HeaderMap,
+    expires: Instant,
+}
+
+impl PreflightCacheMiddleware {
+    /// Creates a new `PreflightCacheMiddleware`, caching up to 1024 responses.
+    pub fn new() -> Self {
+        PreflightCacheMiddleware {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+
+    /// Sets the maximum number of responses cached at once. Once it's reached, responses are only
+    /// cached after others have expired.
+    pub fn max_entries(mut self, max: usize) -> Self {
+        self.max_entries = max;
+        self
+    }
+
+    /// Locks the cache, ignoring poisoning as entries are always inserted whole.
+    fn lock(&self) -> MutexGuard<'_, HashMap<CacheKey, CachedPreflight>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Caches a response for its maximum age, if it has one.
+    fn store(&self, key: CacheKey, status: StatusCode, headers: &HeaderMap) {
+        let max_age = headers
+            .get(ACCESS_CONTROL_MAX_AGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok());
+
+        let max_age = match max_age {
+            Some(max_age) if max_age > 0 && status.is_success() => max_age,
+            _ => return,
+        };
+
+        let mut headers = headers.clone();
+        headers.remove(X_REQUEST_ID);
+
+        let now = Instant::now();
+        let mut entries = self.lock();
+
+        if entries.len() >= self.max_entries {
+            entries.retain(|_, cached| cached.expires > now);
+
+            if entries.len() >= self.max_entries {
+                return;
+            }
+        }
+
+        entries.insert(
+            key,
+            CachedPreflight {
+                status,
+                headers,
+                expires: now + Duration::from_secs(max_age),
+            },
+        );
+    }
+}
+
+impl Default for PreflightCacheMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds the cache key of a preflight request.
+fn cache_key(state: &State) -> Option<CacheKey> {
+    let headers = HeaderMap::borrow_from(state);
+
+    Some(CacheKey {
+        origin: headers.get(ORIGIN)?.clone(),
+        path: Uri::borrow_from(state).path().to_owned(),
+        method: headers.get(ACCESS_CONTROL_REQUEST_METHOD)?.clone(),
+        headers: headers.get(ACCESS_CONTROL_REQUEST_HEADERS).cloned(),
+    })
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for PreflightCacheMiddleware {
+    /// Answers cached preflights, and caches the responses to others.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        let key = match cache_key(&state) {
+            Some(ref key) if is_preflight(&state) => key.clone(),
+            _ => return chain(state),
+        };
+
+        let cached = {
+            let mut entries = self.lock();
+
+            match entries.get(&key) {
+                Some(cached) if cached.expires > Instant::now() => {
+                    Some((cached.status, cached.headers.clone()))
+                }
+                Some(_) => {
+                    entries.remove(&key);
+                    None
+                }
+                None => None,
+            }
+        };
+
+        if let Some((status, headers)) = cached {
+            trace!("[{}] answering preflight from cache", request_id(&state));
+
+            // answered here, so the router mustn't treat it as unanswered
+            state.try_take::<CorsPreflight>();
+
+            let mut response = create_empty_response(&state, status);
+            response.headers_mut().extend(headers);
+            return Box::new(future::ok((state, response)));
+        }
+
+        let f = chain(state).map(move |(state, response)| {
+            self.store(key, response.status(), response.headers());
+            (state, response)
+        });
+
+        Box::new(f)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for PreflightCacheMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance, sharing the cache.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use hyper::header::ACCESS_CONTROL_ALLOW_ORIGIN;
+    use hyper::{Body, Method, Response};
+
+    fn preflight(
+        middleware: &PreflightCacheMiddleware,
+        calls: &Arc<AtomicUsize>,
+        request_headers: &'static str,
+        max_age: &'static str,
+    ) -> Response<Body> {
+        let mut headers = HeaderMap::new();
+        headers.insert(ORIGIN, HeaderValue::from_static("https://app.example.com"));
+        headers.insert(
+            ACCESS_CONTROL_REQUEST_METHOD,
+            HeaderValue::from_static("PUT"),
+        );
+        headers.insert(
+            ACCESS_CONTROL_REQUEST_HEADERS,
+            HeaderValue::from_static(request_headers),
+        );
+
+        let mut state = State::new();
+        state.put(Method::OPTIONS);
+        state.put("/documents".parse::<Uri>().unwrap());
+        state.put(headers);
+        crate::state::set_request_id(&mut state);
+
+        let calls = calls.clone();
+        middleware
+            .clone()
+            .call(state, move |state| {
+                calls.fetch_add(1, Ordering::SeqCst);
+
+                let mut response = create_empty_response(&state, StatusCode::NO_CONTENT);
+                let headers = response.headers_mut();
+                headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+                headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from_static(max_age));
+
+                Box::new(future::ok((state, response)))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn answers_identical_preflights_from_cache() {
+        let middleware = PreflightCacheMiddleware::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let first = preflight(&middleware, &calls, "content-type", "600");
+        let second = preflight(&middleware, &calls, "content-type", "600");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        assert_eq!(second.status(), StatusCode::NO_CONTENT);
+        assert_eq!(second.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert_eq!(second.headers()[ACCESS_CONTROL_MAX_AGE], "600");
+
+        // each response carries the ID of its own request
+        assert_eq!(second.headers().get_all(X_REQUEST_ID).iter().count(), 1);
+        assert_ne!(
+            first.headers()[X_REQUEST_ID],
+            second.headers()[X_REQUEST_ID]
+        );
+
+        // a different preflight isn't answered by the cached response
+        preflight(&middleware, &calls, "authorization", "600");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn skips_responses_without_a_max_age() {
+        let middleware = PreflightCacheMiddleware::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        preflight(&middleware, &calls, "content-type", "0");
+        preflight(&middleware, &calls, "content-type", "0");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let full = PreflightCacheMiddleware::new().max_entries(0);
+        preflight(&full, &calls, "content-type", "600");
+        preflight(&full, &calls, "content-type", "600");
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+}
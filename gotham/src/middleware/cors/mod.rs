@@ -0,0 +1,458 @@
+//! Cross-Origin Resource Sharing (CORS) middleware.
+//!
+//! The `CorsMiddleware` is configured per pipeline, so different parts of an application can
+//! allow different origins and methods by dispatching them through different pipelines. For
+//! example, `/public` routes may allow any origin, while `/admin` routes only allow the origin
+//! of the admin interface.
+//!
+//! Browsers send a preflight `OPTIONS` request before most cross-origin requests. When no route
+//! handles `OPTIONS` for the requested path, the `Router` selects the route using the method
+//! named in the `Access-Control-Request-Method` header instead, and dispatches the preflight
+//! through the pipelines of that route. The preflight response therefore reflects the
+//! configuration of the route the actual request will be sent to.
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{future, Future};
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCESS_CONTROL_ALLOW_CREDENTIALS,
+    ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+    ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS,
+    ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use hyper::{Body, Method, Response, StatusCode};
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::router::non_match::CorsPreflight;
+use crate::state::{request_id, FromState, State};
+
+mod cache;
+
+pub use self::cache::PreflightCacheMiddleware;
+
+/// Middleware binding to handle CORS requests for the routes of a pipeline.
+///
+/// Preflight requests from an allowed origin are answered directly with `204 No Content`,
+/// without invoking the rest of the pipeline chain; other requests from an allowed origin have
+/// the appropriate headers attached to their response. Requests from any other origin are passed
+/// through untouched, leaving the browser to reject the response. For this reason the
+/// `CorsMiddleware` should generally be the first middleware of its pipeline, so preflights
+/// aren't rejected by other middleware such as authentication.
+///
+/// By default any origin is allowed, along with the `GET`, `HEAD` and `POST` methods and any
+/// headers the client requests.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Method, Response};
+/// # use gotham::middleware::cors::CorsMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::set::{finalize_pipeline_set, new_pipeline_set};
+/// # use gotham::router::builder::*;
+/// # use gotham::router::Router;
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// fn router() -> Router {
+///     let pipelines = new_pipeline_set();
+///     let (pipelines, public) = pipelines.add(new_pipeline().add(CorsMiddleware::new()).build());
+///     let (pipelines, admin) = pipelines.add(
+///         new_pipeline()
+///             .add(
+///                 CorsMiddleware::new()
+///                     .allow_origin("https://admin.example.com")
+///                     .allow_methods(vec![Method::GET, Method::DELETE])
+///                     .allow_credentials(true),
+///             )
+///             .build(),
+///     );
+///     let pipelines = finalize_pipeline_set(pipelines);
+///
+///     build_router((), pipelines, |route| {
+///         route.with_pipeline_chain((public, ()), |route| {
+///             route.get("/public/articles").to(handler);
+///         });
+///
+///         route.with_pipeline_chain((admin, ()), |route| {
+///             route.delete("/admin/articles/:id").to(handler);
+///         });
+///     })
+/// }
+/// # fn main() { let _ = router(); }
+/// ```
+#[derive(Clone)]
+pub struct CorsMiddleware {
+    config: Arc<CorsConfig>,
+}
+
+/// Options configured on a `CorsMiddleware`.
+#[derive(Clone)]
+struct CorsConfig {
+    origins: Option<Vec<HeaderValue>>,
+    methods: Vec<Method>,
+    headers: Option<Vec<HeaderName>>,
+    expose_headers: Vec<HeaderName>,
+    credentials: bool,
+    max_age: Option<Duration>,
+}
+
+impl CorsMiddleware {
+    /// Creates a new `CorsMiddleware` using the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows requests from the provided origin, such as `https://example.com`.
+    ///
+    /// Once any origin has been provided, requests from all other origins are no longer allowed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the origin is not valid within a header.
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        let origin = HeaderValue::from_str(origin).expect("invalid origin");
+        let config = Arc::make_mut(&mut self.config);
+
+        config.origins.get_or_insert_with(Vec::new).push(origin);
+        self
+    }
+
+    /// Sets the methods which may be used by cross-origin requests.
+    pub fn allow_methods(mut self, methods: Vec<Method>) -> Self {
+        Arc::make_mut(&mut self.config).methods = methods;
+        self
+    }
+
+    /// Sets the headers which may be sent by cross-origin requests, in place of allowing any
+    /// headers the client requests.
+    pub fn allow_headers(mut self, headers: Vec<HeaderName>) -> Self {
+        Arc::make_mut(&mut self.config).headers = Some(headers);
+        self
+    }
+
+    /// Sets the response headers which are made visible to the client, beyond those which are
+    /// always visible.
+    pub fn expose_headers(mut self, headers: Vec<HeaderName>) -> Self {
+        Arc::make_mut(&mut self.config).expose_headers = headers;
+        self
+    }
+
+    /// Sets whether cross-origin requests may include credentials, such as cookies.
+    ///
+    /// When enabled, the origin of the request is always sent back in place of `*`, as
+    /// browsers reject credentialed responses which allow any origin.
+    pub fn allow_credentials(mut self, credentials: bool) -> Self {
+        Arc::make_mut(&mut self.config).credentials = credentials;
+        self
+    }
+
+    /// Sets how long the client may cache the result of a preflight request.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        Arc::make_mut(&mut self.config).max_age = Some(max_age);
+        self
+    }
+
+    /// Determines whether requests from the provided origin are allowed.
+    fn allows(&self, origin: &HeaderValue) -> bool {
+        match self.config.origins {
+            Some(ref origins) => origins.iter().any(|allowed| allowed == origin),
+            None => true,
+        }
+    }
+
+    /// Attaches the headers sent in response to every request from an allowed origin.
+    fn origin_headers(&self, origin: HeaderValue, headers: &mut HeaderMap) {
+        let config = &self.config;
+
+        if config.origins.is_none() && !config.credentials {
+            headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+        } else {
+            headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+            headers.append(VARY, HeaderValue::from_static("Origin"));
+        }
+
+        if config.credentials {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+
+    /// Builds the response to a preflight request.
+    fn preflight(&self, state: &State, origin: HeaderValue) -> Response<Body> {
+        let mut response = create_empty_response(state, StatusCode::NO_CONTENT);
+
+        if !self.allows(&origin) {
+            return response;
+        }
+
+        let config = &self.config;
+        let headers = response.headers_mut();
+
+        self.origin_headers(origin, headers);
+
+        if let Some(methods) = join(config.methods.iter().map(Method::as_str)) {
+            headers.insert(ACCESS_CONTROL_ALLOW_METHODS, methods);
+        }
+
+        let allowed = match config.headers {
+            Some(ref allowed) => join(allowed.iter().map(HeaderName::as_str)),
+            None => HeaderMap::borrow_from(state)
+                .get(ACCESS_CONTROL_REQUEST_HEADERS)
+                .cloned(),
+        };
+
+        if let Some(allowed) = allowed {
+            headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, allowed);
+        }
+
+        if let Some(max_age) = config.max_age {
+            headers.insert(ACCESS_CONTROL_MAX_AGE, max_age.as_secs().into());
+        }
+
+        response
+    }
+}
+
+impl Default for CorsMiddleware {
+    fn default() -> Self {
+        CorsMiddleware {
+            config: Arc::new(CorsConfig {
+                origins: None,
+                methods: vec![Method::GET, Method::HEAD, Method::POST],
+                headers: None,
+                expose_headers: Vec::new(),
+                credentials: false,
+                max_age: None,
+            }),
+        }
+    }
+}
+
+/// Joins values into a single comma separated header value.
+fn join<'a, I>(values: I) -> Option<HeaderValue>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let values: Vec<&str> = values.collect();
+
+    if values.is_empty() {
+        return None;
+    }
+
+    HeaderValue::from_str(&values.join(", ")).ok()
+}
+
+/// Determines whether the request is a CORS preflight request.
+fn is_preflight(state: &State) -> bool {
+    *Method::borrow_from(state) == Method::OPTIONS
+        && HeaderMap::borrow_from(state).contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for CorsMiddleware {
+    /// Answers preflight requests, and attaches CORS headers to the responses of other requests.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        let origin = match HeaderMap::borrow_from(&state).get(ORIGIN) {
+            Some(origin) => origin.clone(),
+            None => return chain(state),
+        };
+
+        if is_preflight(&state) {
+            trace!("[{}] responding to cors preflight", request_id(&state));
+
+            state.try_take::<CorsPreflight>();
+
+            let response = self.preflight(&state, origin);
+            return Box::new(future::ok((state, response)));
+        }
+
+        if !self.allows(&origin) {
+            trace!("[{}] cors origin not allowed", request_id(&state));
+            return chain(state);
+        }
+
+        let f = chain(state).and_then(move |(state, mut response)| {
+            {
+                let headers = response.headers_mut();
+                self.origin_headers(origin, headers);
+
+                if let Some(exposed) =
+                    join(self.config.expose_headers.iter().map(HeaderName::as_str))
+                {
+                    headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, exposed);
+                }
+            }
+            future::ok((state, response))
+        });
+
+        Box::new(f)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for CorsMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::pipeline::new_pipeline;
+    use crate::pipeline::set::{finalize_pipeline_set, new_pipeline_set};
+    use crate::router::builder::*;
+    use crate::router::Router;
+    use crate::test::{TestResponse, TestServer};
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new(Body::from("handled")))
+    }
+
+    fn router() -> Router {
+        let pipelines = new_pipeline_set();
+        let (pipelines, public) = pipelines.add(new_pipeline().add(CorsMiddleware::new()).build());
+        let (pipelines, admin) = pipelines.add(
+            new_pipeline()
+                .add(
+                    CorsMiddleware::new()
+                        .allow_origin("https://admin.example.com")
+                        .allow_methods(vec![Method::GET, Method::DELETE])
+                        .allow_headers(vec![hyper::header::AUTHORIZATION])
+                        .expose_headers(vec![hyper::header::ETAG])
+                        .allow_credentials(true)
+                        .max_age(Duration::from_secs(600)),
+                )
+                .build(),
+        );
+        let pipelines = finalize_pipeline_set(pipelines);
+
+        build_router((), pipelines, |route| {
+            route.with_pipeline_chain((public, ()), |route| {
+                route.get("/public").to(handler);
+            });
+
+            route.with_pipeline_chain((admin, ()), |route| {
+                route.get("/admin").to(handler);
+                route.delete("/admin").to(handler);
+            });
+
+            route.get("/plain").to(handler);
+        })
+    }
+
+    fn preflight(server: &TestServer, path: &str, origin: &str, method: &str) -> TestResponse {
+        server
+            .client()
+            .options(format!("http://localhost{}", path))
+            .with_header(ORIGIN, HeaderValue::from_str(origin).unwrap())
+            .with_header(
+                ACCESS_CONTROL_REQUEST_METHOD,
+                HeaderValue::from_str(method).unwrap(),
+            )
+            .with_header(
+                ACCESS_CONTROL_REQUEST_HEADERS,
+                HeaderValue::from_static("x-requested-with"),
+            )
+            .perform()
+            .unwrap()
+    }
+
+    #[test]
+    fn answers_preflights_using_route_config() {
+        let server = TestServer::new(router()).unwrap();
+
+        let response = preflight(&server, "/public", "https://any.example.com", "GET");
+        let headers = response.headers();
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_METHODS], "GET, HEAD, POST");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "x-requested-with");
+        assert!(headers.get(VARY).is_none());
+
+        let response = preflight(&server, "/admin", "https://admin.example.com", "DELETE");
+        let headers = response.headers();
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            headers[ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://admin.example.com"
+        );
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_METHODS], "GET, DELETE");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "authorization");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[ACCESS_CONTROL_MAX_AGE], "600");
+        assert_eq!(headers[VARY], "Origin");
+
+        let response = preflight(&server, "/admin", "https://any.example.com", "DELETE");
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(response
+            .headers()
+            .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+    }
+
+    #[test]
+    fn skips_handler_for_unanswered_preflights() {
+        let server = TestServer::new(router()).unwrap();
+
+        let response = preflight(&server, "/plain", "https://any.example.com", "GET");
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[hyper::header::ALLOW], "GET");
+        assert_eq!(response.read_utf8_body().unwrap(), "");
+
+        let response = preflight(&server, "/public", "https://any.example.com", "PUT");
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[test]
+    fn attaches_headers_to_allowed_requests() {
+        let server = TestServer::new(router()).unwrap();
+
+        let get = |origin: &str| {
+            server
+                .client()
+                .get("http://localhost/admin")
+                .with_header(ORIGIN, HeaderValue::from_str(origin).unwrap())
+                .perform()
+                .unwrap()
+        };
+
+        let response = get("https://admin.example.com");
+        let headers = response.headers();
+
+        assert_eq!(
+            headers[ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://admin.example.com"
+        );
+        assert_eq!(headers[ACCESS_CONTROL_EXPOSE_HEADERS], "etag");
+        assert!(headers.get(ACCESS_CONTROL_ALLOW_METHODS).is_none());
+
+        let response = get("https://any.example.com");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response
+            .headers()
+            .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+    }
+}
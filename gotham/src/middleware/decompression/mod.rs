@@ -0,0 +1,365 @@
+//! Middleware to decompress request bodies sent with a `Content-Encoding`.
+//!
+//! Handlers generally expect to read a plain request body, so compressed bodies are decoded
+//! before the handler is invoked. Decoding is bounded by a configurable limit, to protect
+//! against small payloads which expand to enormous sizes ("zip bombs").
+use std::io::{self, Write};
+
+use flate2::write::{GzDecoder, ZlibDecoder};
+use futures::{future, Future, Stream};
+use hyper::header::{HeaderMap, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH};
+use hyper::{Body, StatusCode};
+use log::trace;
+
+use crate::handler::{HandlerFuture, IntoHandlerError};
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State};
+
+// the default limit placed on decompressed bodies, 10MB
+const DEFAULT_MAX_SIZE: usize = 10 * 1024 * 1024;
+
+/// Middleware binding to decompress `gzip` and `deflate` encoded request bodies.
+///
+/// When a request declares one of the supported encodings, the body in `State` is replaced with
+/// the decompressed body, the `Content-Encoding` header is removed and the `Content-Length`
+/// header is updated to match. Requests without a `Content-Encoding` (or using `identity`) are
+/// passed through untouched.
+///
+/// Requests are rejected before reaching the handler when:
+///
+/// * the encoding is unsupported, including stacked encodings such as `gzip, deflate`, with
+///   a `415 Unsupported Media Type` listing the supported encodings in `Accept-Encoding`;
+/// * the decompressed body exceeds the configured maximum size, with a
+///   `413 Payload Too Large`;
+/// * the body can't be decoded, with a `400 Bad Request`.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::decompression::DecompressionMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// let decompression = DecompressionMiddleware::new().max_size(1024 * 1024);
+///
+/// let pipeline = new_pipeline().add(decompression).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct DecompressionMiddleware {
+    max_size: usize,
+}
+
+impl DecompressionMiddleware {
+    /// Creates a new `DecompressionMiddleware`, limiting decompressed bodies to 10MB.
+    pub fn new() -> Self {
+        DecompressionMiddleware {
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+
+    /// Sets the maximum size, in bytes, of a decompressed request body.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+impl Default for DecompressionMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for DecompressionMiddleware {
+    /// Decompresses the request body ahead of the rest of the chain.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        let decoder = match Encoding::from_headers(HeaderMap::borrow_from(&state)) {
+            Ok(Encoding::Identity) => return chain(state),
+            Ok(encoding) => Decoder::new(encoding, self.max_size),
+            Err(()) => {
+                trace!("[{}] unsupported content encoding", request_id(&state));
+
+                let mut response =
+                    create_empty_response(&state, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+                response
+                    .headers_mut()
+                    .insert(ACCEPT_ENCODING, HeaderValue::from_static("gzip, deflate"));
+
+                return Box::new(future::ok((state, response)));
+            }
+        };
+
+        let body = state.take::<Body>();
+        let f = body
+            .map_err(Failure::Read)
+            .fold(decoder, |mut decoder, chunk| {
+                decoder.write(&chunk).map(|_| decoder)
+            })
+            .and_then(Decoder::finish)
+            .then(move |result| match result {
+                Ok(decoded) => {
+                    trace!(
+                        "[{}] decompressed request body to {} bytes",
+                        request_id(&state),
+                        decoded.len()
+                    );
+
+                    {
+                        let headers = HeaderMap::borrow_mut_from(&mut state);
+                        headers.remove(CONTENT_ENCODING);
+                        headers.insert(CONTENT_LENGTH, decoded.len().into());
+                    }
+
+                    state.put(Body::from(decoded));
+                    chain(state)
+                }
+                Err(Failure::Read(e)) => {
+                    let err = e.into_handler_error().with_status(StatusCode::BAD_REQUEST);
+                    Box::new(future::err((state, err)))
+                }
+                Err(Failure::TooLarge) => {
+                    let response = create_empty_response(&state, StatusCode::PAYLOAD_TOO_LARGE);
+                    Box::new(future::ok((state, response)))
+                }
+                Err(Failure::Invalid(e)) => {
+                    trace!(
+                        "[{}] unable to decompress request body: {}",
+                        request_id(&state),
+                        e
+                    );
+
+                    let response = create_empty_response(&state, StatusCode::BAD_REQUEST);
+                    Box::new(future::ok((state, response)))
+                }
+            });
+
+        Box::new(f)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for DecompressionMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+/// The encodings supported by the `DecompressionMiddleware`.
+enum Encoding {
+    Identity,
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    /// Determines the encoding declared by a request, if supported.
+    fn from_headers(headers: &HeaderMap) -> Result<Encoding, ()> {
+        let mut values = headers.get_all(CONTENT_ENCODING).iter();
+
+        let value = match (values.next(), values.next()) {
+            (None, _) => return Ok(Encoding::Identity),
+            (Some(value), None) => value.to_str().map_err(|_| ())?,
+            (Some(_), Some(_)) => return Err(()),
+        };
+
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "identity" => Ok(Encoding::Identity),
+            "gzip" | "x-gzip" => Ok(Encoding::Gzip),
+            "deflate" => Ok(Encoding::Deflate),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The reasons a body may fail to decompress.
+enum Failure {
+    Read(hyper::Error),
+    Invalid(io::Error),
+    TooLarge,
+}
+
+/// A streaming decoder for a supported encoding, writing into a bounded buffer.
+enum Decoder {
+    Gzip(GzDecoder<Bounded>),
+    Deflate(ZlibDecoder<Bounded>),
+}
+
+impl Decoder {
+    fn new(encoding: Encoding, max_size: usize) -> Decoder {
+        let buffer = Bounded {
+            buf: Vec::new(),
+            max_size,
+            exceeded: false,
+        };
+
+        match encoding {
+            Encoding::Gzip => Decoder::Gzip(GzDecoder::new(buffer)),
+            // HTTP's "deflate" is the zlib format, rather than a raw deflate stream
+            Encoding::Deflate | Encoding::Identity => Decoder::Deflate(ZlibDecoder::new(buffer)),
+        }
+    }
+
+    /// Decodes a chunk of the body.
+    fn write(&mut self, chunk: &[u8]) -> Result<(), Failure> {
+        let result = match *self {
+            Decoder::Gzip(ref mut decoder) => decoder.write_all(chunk),
+            Decoder::Deflate(ref mut decoder) => decoder.write_all(chunk),
+        };
+        self.check(result)
+    }
+
+    /// Completes decoding, returning the decompressed body.
+    fn finish(mut self) -> Result<Vec<u8>, Failure> {
+        let result = match self {
+            Decoder::Gzip(ref mut decoder) => decoder.try_finish(),
+            Decoder::Deflate(ref mut decoder) => decoder.try_finish(),
+        };
+        self.check(result)?;
+
+        let buffer = match self {
+            Decoder::Gzip(decoder) => decoder.finish(),
+            Decoder::Deflate(decoder) => decoder.finish(),
+        };
+        buffer.map(|buffer| buffer.buf).map_err(Failure::Invalid)
+    }
+
+    /// Classifies a decoding error, distinguishing between bad input and an exceeded limit.
+    fn check(&self, result: io::Result<()>) -> Result<(), Failure> {
+        let exceeded = match *self {
+            Decoder::Gzip(ref decoder) => decoder.get_ref().exceeded,
+            Decoder::Deflate(ref decoder) => decoder.get_ref().exceeded,
+        };
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(_) if exceeded => Err(Failure::TooLarge),
+            Err(e) => Err(Failure::Invalid(e)),
+        }
+    }
+}
+
+/// A buffer which refuses writes beyond a maximum size.
+struct Bounded {
+    buf: Vec<u8>,
+    max_size: usize,
+    exceeded: bool,
+}
+
+impl Write for Bounded {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.buf.len() + data.len() > self.max_size {
+            self.exceeded = true;
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use hyper::Response;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn send(
+        middleware: DecompressionMiddleware,
+        encoding: Option<&str>,
+        body: Vec<u8>,
+    ) -> (StatusCode, HeaderMap, Vec<u8>) {
+        let mut headers = HeaderMap::new();
+        if let Some(encoding) = encoding {
+            headers.insert(CONTENT_ENCODING, encoding.parse().unwrap());
+        }
+
+        let mut state = State::new();
+        state.put(headers);
+        state.put(Body::from(body));
+        crate::state::set_request_id(&mut state);
+
+        let (mut state, response) = middleware
+            .call(state, |mut state| {
+                let body = state.take::<Body>().concat2().wait().unwrap().to_vec();
+                Box::new(future::ok((state, Response::new(Body::from(body)))))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap();
+
+        let status = response.status();
+        let body = response.into_body().concat2().wait().unwrap().to_vec();
+
+        (status, state.take::<HeaderMap>(), body)
+    }
+
+    #[test]
+    fn decompresses_supported_encodings() {
+        let middleware = DecompressionMiddleware::new();
+
+        let (status, headers, body) = send(middleware, Some("gzip"), gzip(b"hello gzip"));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"hello gzip");
+        assert_eq!(headers.get(CONTENT_ENCODING), None);
+        assert_eq!(headers[CONTENT_LENGTH], "10");
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"hello deflate").unwrap();
+
+        let (status, _, body) = send(middleware, Some("Deflate"), encoder.finish().unwrap());
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"hello deflate");
+
+        let (status, _, body) = send(middleware, None, b"plain".to_vec());
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"plain");
+    }
+
+    #[test]
+    fn rejects_bodies_over_the_limit() {
+        let middleware = DecompressionMiddleware::new().max_size(1024);
+
+        let (status, _, _) = send(middleware, Some("gzip"), gzip(&[0; 1024]));
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _, _) = send(middleware, Some("gzip"), gzip(&[0; 1024 * 1024]));
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn rejects_invalid_and_unsupported_bodies() {
+        let middleware = DecompressionMiddleware::new();
+
+        let (status, _, _) = send(middleware, Some("gzip"), b"not gzip".to_vec());
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let mut truncated = gzip(b"hello gzip");
+        truncated.truncate(12);
+
+        let (status, _, _) = send(middleware, Some("gzip"), truncated);
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _, _) = send(middleware, Some("br"), b"data".to_vec());
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let (status, _, _) = send(middleware, Some("gzip, deflate"), b"data".to_vec());
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
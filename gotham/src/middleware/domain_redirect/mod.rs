@@ -0,0 +1,247 @@
+//! Middleware to redirect every request for a retired domain to its replacement.
+//!
+//! When a site moves from one domain to another, links to the old domain should keep working.
+//! This middleware redirects each request arriving for the old host to the same path and query on
+//! the new one, without any per-route configuration.
+use std::io;
+
+use futures::future;
+use hyper::header::{HeaderMap, HeaderValue, HOST, LOCATION};
+use hyper::{StatusCode, Uri};
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State};
+
+/// Middleware binding to redirect requests for one host to another base URL.
+///
+/// Requests whose `Host` header (or absolute URI) matches `from_host` are answered with the
+/// configured redirect status and a `Location` made of `to_base` followed by the full path and
+/// query of the request, exactly as sent. Hosts are compared case-insensitively, and ignore the
+/// port of the request unless `from_host` includes one. Other requests pass through untouched.
+///
+/// When `to_base` has no scheme, such as `new.example.com`, the scheme of the request is kept
+/// (assumed to be `http` when the request doesn't state one). Setting `upgrade_scheme` redirects
+/// to `https` in either case.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # use gotham::middleware::domain_redirect::DomainRedirectMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// # use hyper::StatusCode;
+/// // `http://old.example.com/blog?page=2` moves to `https://new.example.com/blog?page=2`
+/// let migration = DomainRedirectMiddleware::new(
+///     "old.example.com",
+///     "new.example.com",
+///     StatusCode::MOVED_PERMANENTLY,
+/// )
+/// .upgrade_scheme(true);
+///
+/// let pipeline = new_pipeline().add(migration).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone, Debug)]
+pub struct DomainRedirectMiddleware {
+    from_host: String,
+    to_base: String,
+    status: StatusCode,
+    upgrade_scheme: bool,
+}
+
+impl DomainRedirectMiddleware {
+    /// Creates a new `DomainRedirectMiddleware`, redirecting requests for `from_host` to
+    /// `to_base` using `status`, which is typically `301 Moved Permanently` or
+    /// `308 Permanent Redirect`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `status` isn't a redirection status.
+    pub fn new(from_host: &str, to_base: &str, status: StatusCode) -> Self {
+        assert!(
+            status.is_redirection(),
+            "status must be a redirection status"
+        );
+
+        DomainRedirectMiddleware {
+            from_host: from_host.to_ascii_lowercase(),
+            to_base: to_base.trim_end_matches('/').to_owned(),
+            status,
+            upgrade_scheme: false,
+        }
+    }
+
+    /// Sets whether redirects always use `https`, upgrading requests made over `http` as part of
+    /// the migration. This is disabled by default.
+    pub fn upgrade_scheme(mut self, upgrade: bool) -> Self {
+        self.upgrade_scheme = upgrade;
+        self
+    }
+
+    /// Determines whether a request was made for the migrated host.
+    fn matches(&self, state: &State) -> bool {
+        let uri = Uri::borrow_from(state);
+        let host = match HeaderMap::borrow_from(state).get(HOST) {
+            Some(host) => host.to_str().ok(),
+            None => uri.authority_part().map(|authority| authority.as_str()),
+        };
+
+        let host = match host {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+
+        if host == self.from_host {
+            return true;
+        }
+
+        // compare without the port when the configured host doesn't specify one
+        !self.from_host.contains(':')
+            && host.rfind(':').map_or(false, |i| {
+                host[..i] == self.from_host && !host[i + 1..].contains(']')
+            })
+    }
+
+    /// Builds the redirect target for a request.
+    fn location(&self, uri: &Uri) -> String {
+        let (scheme, rest) = match self.to_base.find("://") {
+            Some(index) => (&self.to_base[..index], &self.to_base[index + 3..]),
+            None => (
+                uri.scheme_str().unwrap_or("http"),
+                self.to_base.trim_start_matches('/'),
+            ),
+        };
+
+        let scheme = if self.upgrade_scheme && scheme.eq_ignore_ascii_case("http") {
+            "https"
+        } else {
+            scheme
+        };
+
+        let path_and_query = uri.path_and_query().map_or("/", |pq| pq.as_str());
+        format!("{}://{}{}", scheme, rest, path_and_query)
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for DomainRedirectMiddleware {
+    /// Redirects requests for the migrated host, passing others through to the chain.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        if !self.matches(&state) {
+            return chain(state);
+        }
+
+        let location = self.location(Uri::borrow_from(&state));
+        trace!("[{}] redirecting to {}", request_id(&state), location);
+
+        let response = match HeaderValue::from_str(&location) {
+            Ok(location) => {
+                let mut response = create_empty_response(&state, self.status);
+                response.headers_mut().insert(LOCATION, location);
+                response
+            }
+            Err(_) => create_empty_response(&state, StatusCode::INTERNAL_SERVER_ERROR),
+        };
+
+        Box::new(future::ok((state, response)))
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for DomainRedirectMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::Future;
+    use hyper::{Body, Response};
+
+    fn call(middleware: DomainRedirectMiddleware, uri: &str, host: &str) -> Response<Body> {
+        let mut headers = HeaderMap::new();
+        headers.insert(HOST, host.parse().unwrap());
+
+        let mut state = State::new();
+        state.put(uri.parse::<Uri>().unwrap());
+        state.put(headers);
+        crate::state::set_request_id(&mut state);
+
+        middleware
+            .call(state, |state| {
+                Box::new(future::ok((state, Response::new(Body::empty()))))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap()
+            .1
+    }
+
+    fn location(response: &Response<Body>) -> &str {
+        response.headers()[LOCATION].to_str().unwrap()
+    }
+
+    #[test]
+    fn redirects_matching_hosts() {
+        let middleware = DomainRedirectMiddleware::new(
+            "old.example.com",
+            "https://new.example.com/",
+            StatusCode::MOVED_PERMANENTLY,
+        );
+
+        let response = call(
+            middleware.clone(),
+            "/blog/post?page=2&q=a%20b",
+            "old.example.com",
+        );
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            location(&response),
+            "https://new.example.com/blog/post?page=2&q=a%20b"
+        );
+
+        let response = call(middleware.clone(), "/", "OLD.example.com:8080");
+        assert_eq!(location(&response), "https://new.example.com/");
+
+        for host in &[
+            "new.example.com",
+            "sub.old.example.com",
+            "old.example.com.evil",
+        ] {
+            let response = call(middleware.clone(), "/", host);
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[test]
+    fn keeps_or_upgrades_the_scheme() {
+        let middleware = DomainRedirectMiddleware::new(
+            "old.example.com:8080",
+            "new.example.com",
+            StatusCode::PERMANENT_REDIRECT,
+        );
+
+        let response = call(middleware.clone(), "/a?b", "old.example.com:8080");
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(location(&response), "http://new.example.com/a?b");
+
+        // the port is part of the configured host
+        let response = call(middleware.clone(), "/a?b", "old.example.com");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let upgraded = middleware.upgrade_scheme(true);
+        let response = call(upgraded, "/a?b", "old.example.com:8080");
+        assert_eq!(location(&response), "https://new.example.com/a?b");
+    }
+}
@@ -0,0 +1,197 @@
+//! Middleware to forward tracing headers from the incoming request to outbound requests.
+//!
+//! Service meshes such as Envoy and Istio rely on applications copying certain headers (such as
+//! the B3 trace headers) from each incoming request onto any requests made on its behalf, so that
+//! the calls can be tied together. This middleware captures those headers, leaving them ready to
+//! be applied when building outbound requests.
+use std::io;
+use std::sync::Arc;
+
+use hyper::header::{HeaderMap, HeaderName};
+use hyper::Request;
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{FromState, State, StateData};
+
+// the headers used by B3 propagation, as used by Zipkin
+const B3_HEADERS: [&str; 5] = [
+    "x-b3-traceid",
+    "x-b3-spanid",
+    "x-b3-parentspanid",
+    "x-b3-sampled",
+    "x-b3-flags",
+];
+
+/// The headers of the current request which should be forwarded to outbound requests.
+///
+/// This is stored in `State` by the `HeaderForwardingMiddleware`, and can be retrieved via
+/// `ForwardedHeaders::borrow_from(&state)`.
+#[derive(Clone, Debug, Default)]
+pub struct ForwardedHeaders(HeaderMap);
+
+impl ForwardedHeaders {
+    /// Copies the forwarded headers onto an outbound request, replacing any values the request
+    /// already has for the same names.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # use gotham::middleware::header_forwarding::ForwardedHeaders;
+    /// # use gotham::state::{FromState, State};
+    /// # use hyper::{Body, Request};
+    /// fn inventory_request(state: &State) -> Request<Body> {
+    ///     let mut request = Request::get("http://inventory/items")
+    ///         .body(Body::empty())
+    ///         .unwrap();
+    ///
+    ///     ForwardedHeaders::borrow_from(state).apply_to(&mut request);
+    ///     request
+    /// }
+    /// # fn main() {
+    /// #     State::with_new(|state| {
+    /// #         state.put(ForwardedHeaders::default());
+    /// #         assert!(inventory_request(state).headers().is_empty());
+    /// #     })
+    /// # }
+    /// ```
+    pub fn apply_to<B>(&self, request: &mut Request<B>) {
+        let headers = request.headers_mut();
+
+        for name in self.0.keys() {
+            headers.remove(name);
+
+            for value in self.0.get_all(name) {
+                headers.append(name.clone(), value.clone());
+            }
+        }
+    }
+
+    /// Returns the forwarded headers.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.0
+    }
+}
+
+impl StateData for ForwardedHeaders {}
+
+/// Middleware binding to capture the headers to forward to outbound requests.
+///
+/// Each of the listed headers which is present on the request is copied into a
+/// `ForwardedHeaders` value stored in `State`; headers which are missing are skipped.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # use gotham::middleware::header_forwarding::HeaderForwardingMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// # use hyper::header::HeaderName;
+/// let forwarding = HeaderForwardingMiddleware::new(vec![
+///     HeaderName::from_static("x-request-id"),
+///     HeaderName::from_static("x-b3-traceid"),
+///     HeaderName::from_static("x-b3-spanid"),
+/// ]);
+///
+/// let pipeline = new_pipeline().add(forwarding).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone)]
+pub struct HeaderForwardingMiddleware {
+    headers: Arc<Vec<HeaderName>>,
+}
+
+impl HeaderForwardingMiddleware {
+    /// Creates a new `HeaderForwardingMiddleware` forwarding the provided headers.
+    pub fn new(headers: Vec<HeaderName>) -> Self {
+        HeaderForwardingMiddleware {
+            headers: Arc::new(headers),
+        }
+    }
+
+    /// Creates a new `HeaderForwardingMiddleware` forwarding the standard B3 headers:
+    /// `X-B3-TraceId`, `X-B3-SpanId`, `X-B3-ParentSpanId`, `X-B3-Sampled` and `X-B3-Flags`.
+    pub fn all_b3_headers() -> Self {
+        Self::new(
+            B3_HEADERS
+                .iter()
+                .map(|name| HeaderName::from_static(name))
+                .collect(),
+        )
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for HeaderForwardingMiddleware {
+    /// Stores the headers to forward in `State`.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        let mut forwarded = HeaderMap::new();
+        {
+            let headers = HeaderMap::borrow_from(&state);
+
+            for name in self.headers.iter() {
+                for value in headers.get_all(name) {
+                    forwarded.append(name.clone(), value.clone());
+                }
+            }
+        }
+
+        state.put(ForwardedHeaders(forwarded));
+        chain(state)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for HeaderForwardingMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{future, Future};
+    use hyper::{Body, Response};
+
+    #[test]
+    fn forwards_listed_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-b3-traceid", "463ac35c9f6413ad".parse().unwrap());
+        headers.insert("x-b3-sampled", "1".parse().unwrap());
+        headers.insert("authorization", "Bearer secret".parse().unwrap());
+
+        let mut state = State::new();
+        state.put(headers);
+
+        let (state, _) = HeaderForwardingMiddleware::all_b3_headers()
+            .call(state, |state| {
+                Box::new(future::ok((state, Response::new(Body::empty()))))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap();
+
+        let forwarded = ForwardedHeaders::borrow_from(&state);
+        assert_eq!(forwarded.headers().len(), 2);
+
+        let mut request = Request::get("http://downstream/")
+            .header("x-b3-sampled", "0")
+            .body(Body::empty())
+            .unwrap();
+
+        forwarded.apply_to(&mut request);
+
+        let headers = request.headers();
+        assert_eq!(headers["x-b3-traceid"], "463ac35c9f6413ad");
+        assert_eq!(headers.get_all("x-b3-sampled").iter().count(), 1);
+        assert_eq!(headers["x-b3-sampled"], "1");
+        assert!(headers.get("authorization").is_none());
+    }
+}
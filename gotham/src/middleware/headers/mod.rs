@@ -0,0 +1,154 @@
+//! Middleware to attach a fixed set of headers to every response.
+//!
+//! Unlike the `SecurityMiddleware`, this middleware makes no decisions about which headers
+//! should be sent; it simply injects whatever headers it has been configured with.
+use std::io;
+use std::sync::Arc;
+
+use futures::{future, Future};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::State;
+
+/// Middleware binding to inject static headers into each response.
+///
+/// Headers which are already present on the response (such as those set by a handler) are left
+/// untouched, unless `override_existing(true)` is used.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::headers::StaticHeadersMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// let headers = StaticHeadersMiddleware::from_pairs(&[
+///     ("X-API-Version", "2"),
+///     ("X-Environment", "staging"),
+/// ]);
+///
+/// let pipeline = new_pipeline().add(headers).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone)]
+pub struct StaticHeadersMiddleware {
+    headers: Arc<HeaderMap>,
+    override_existing: bool,
+}
+
+impl StaticHeadersMiddleware {
+    /// Creates a new `StaticHeadersMiddleware` injecting every entry of `headers`.
+    pub fn new(headers: HeaderMap) -> Self {
+        StaticHeadersMiddleware {
+            headers: Arc::new(headers),
+            override_existing: false,
+        }
+    }
+
+    /// Creates a new `StaticHeadersMiddleware` from a list of name and value pairs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any name or value is not valid within a header.
+    pub fn from_pairs(pairs: &[(&str, &str)]) -> Self {
+        let mut headers = HeaderMap::with_capacity(pairs.len());
+
+        for (name, value) in pairs {
+            let name = HeaderName::from_bytes(name.as_bytes()).expect("invalid header name");
+            let value = HeaderValue::from_str(value).expect("invalid header value");
+            headers.append(name, value);
+        }
+
+        Self::new(headers)
+    }
+
+    /// Configures whether headers already present on the response are replaced.
+    pub fn override_existing(mut self, override_existing: bool) -> Self {
+        self.override_existing = override_existing;
+        self
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for StaticHeadersMiddleware {
+    /// Attaches the configured headers to the response.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        let f = chain(state).and_then(move |(state, mut response)| {
+            {
+                let headers = response.headers_mut();
+
+                for name in self.headers.keys() {
+                    if !self.override_existing && headers.contains_key(name) {
+                        continue;
+                    }
+
+                    headers.remove(name);
+                    for value in self.headers.get_all(name) {
+                        headers.append(name.clone(), value.clone());
+                    }
+                }
+            }
+            future::ok((state, response))
+        });
+
+        Box::new(f)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for StaticHeadersMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::SERVER;
+    use hyper::{Body, Response};
+
+    fn run(middleware: StaticHeadersMiddleware) -> Response<Body> {
+        let (_, response) = middleware
+            .call(State::new(), |state| {
+                let response = Response::builder()
+                    .header(SERVER, "gotham")
+                    .body(Body::empty())
+                    .unwrap();
+                Box::new(future::ok((state, response)))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap();
+
+        response
+    }
+
+    #[test]
+    fn injects_missing_headers() {
+        let response = run(StaticHeadersMiddleware::from_pairs(&[
+            ("X-Environment", "test"),
+            ("Server", "other"),
+        ]));
+
+        assert_eq!(response.headers().get("x-environment").unwrap(), "test");
+        assert_eq!(response.headers().get(SERVER).unwrap(), "gotham");
+    }
+
+    #[test]
+    fn overrides_existing_headers_when_enabled() {
+        let middleware =
+            StaticHeadersMiddleware::from_pairs(&[("Server", "other")]).override_existing(true);
+
+        let response = run(middleware);
+        let servers: Vec<_> = response.headers().get_all(SERVER).iter().collect();
+
+        assert_eq!(servers, vec!["other"]);
+    }
+}
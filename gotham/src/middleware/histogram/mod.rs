@@ -0,0 +1,438 @@
+//! Middleware to track the distribution of request durations per route.
+//!
+//! Access lines record the duration of each request individually, which makes percentiles
+//! impractical to compute without post-processing them. The `DurationHistogramMiddleware`
+//! instead records every duration into a histogram per route, which can be summarised on demand
+//! via the `latency_handler`.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use futures::Future;
+use hyper::{Body, Method, Response, StatusCode};
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::{create_empty_response, create_response};
+use crate::helpers::timing::Timer;
+use crate::middleware::logger::{bucket, quantile, RouteTemplate, BUCKETS};
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{FromState, State, StateData};
+
+/// A histogram of durations, counted into the log-linear buckets also used by the latency
+/// summaries of the `RequestLogger`.
+///
+/// Each power of two is split into 8 buckets, so percentiles are reported within 12.5% of the
+/// recorded durations; they never exceed the largest duration recorded, which is tracked exactly.
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    // a count per bucket, of durations in microseconds
+    counts: Vec<u64>,
+    count: u64,
+    max: u64,
+}
+
+impl Histogram {
+    /// Creates a new, empty `Histogram`.
+    pub fn new() -> Self {
+        Histogram {
+            counts: vec![0; BUCKETS],
+            count: 0,
+            max: 0,
+        }
+    }
+
+    /// Records a duration, at microsecond precision.
+    pub fn record(&mut self, duration: Duration) {
+        let us = duration.as_micros().min(u128::from(u64::MAX)) as u64;
+
+        self.counts[bucket(us)] += 1;
+        self.count += 1;
+        self.max = self.max.max(us);
+    }
+
+    /// Returns the number of durations recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the largest duration recorded.
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max)
+    }
+
+    /// Returns the duration at the provided percentile, such as `99.9`, or zero when nothing
+    /// has been recorded.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        let us = quantile(&self.counts, percentile).map_or(0, |us| us.min(self.max));
+        Duration::from_micros(us)
+    }
+
+    /// Clears every recorded duration.
+    pub fn reset(&mut self) {
+        for count in self.counts.iter_mut() {
+            *count = 0;
+        }
+        self.count = 0;
+        self.max = 0;
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The histograms shared by every instance of a `DurationHistogramMiddleware`, keyed by route.
+///
+/// The middleware places a handle into `State`, so handlers can record the durations of their
+/// own work (such as calls to other services) alongside the routes:
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::time::Instant;
+/// # use hyper::{Body, Response};
+/// # use gotham::middleware::histogram::GlobalHistogram;
+/// # use gotham::state::{FromState, State};
+/// #
+/// fn my_handler(state: State) -> (State, Response<Body>) {
+///     let start = Instant::now();
+///     // call the inventory service...
+///     GlobalHistogram::borrow_from(&state).record("inventory", start.elapsed());
+///     # (state, Response::new(Body::empty()))
+/// }
+/// # fn main() { let _ = my_handler; }
+/// ```
+#[derive(Clone)]
+pub struct GlobalHistogram {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    histograms: Mutex<BTreeMap<String, Histogram>>,
+    reset: AtomicBool,
+}
+
+impl GlobalHistogram {
+    /// Creates a new `GlobalHistogram`, without any histograms.
+    fn new() -> Self {
+        GlobalHistogram {
+            shared: Arc::new(Shared {
+                histograms: Mutex::new(BTreeMap::new()),
+                reset: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Records a duration under the provided name, creating its histogram on first use.
+    pub fn record(&self, name: &str, duration: Duration) {
+        let mut histograms = self.lock();
+
+        if let Some(histogram) = histograms.get_mut(name) {
+            histogram.record(duration);
+            return;
+        }
+
+        let mut histogram = Histogram::new();
+        histogram.record(duration);
+        histograms.insert(name.to_owned(), histogram);
+    }
+
+    /// Returns a copy of the histogram recorded under the provided name, if any.
+    pub fn histogram(&self, name: &str) -> Option<Histogram> {
+        self.lock().get(name).cloned()
+    }
+
+    /// Discards every recorded duration.
+    ///
+    /// This only sets a flag, so it never waits for the histograms to be unlocked; they're
+    /// cleared before the next duration is recorded or summarised.
+    pub fn reset(&self) {
+        self.shared.reset.store(true, Ordering::Release);
+    }
+
+    /// Summarises each histogram as a line, ordered by name, such as:
+    ///
+    /// ```plain
+    /// GET /users/:id: 1203 requests, p50 5ms, p95 25ms, p99 50ms, p999 100ms, max 184.2ms
+    /// ```
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+
+        for (name, histogram) in self.lock().iter() {
+            let _ = writeln!(
+                summary,
+                "{}: {} requests, p50 {}, p95 {}, p99 {}, p999 {}, max {}",
+                name,
+                histogram.count(),
+                millis(histogram.percentile(50.0)),
+                millis(histogram.percentile(95.0)),
+                millis(histogram.percentile(99.0)),
+                millis(histogram.percentile(99.9)),
+                millis(histogram.max()),
+            );
+        }
+
+        summary
+    }
+
+    /// Locks the histograms, applying any pending reset.
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, Histogram>> {
+        // a poisoned lock only means another request panicked mid-record
+        let mut histograms = self
+            .shared
+            .histograms
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+
+        if self.shared.reset.swap(false, Ordering::Acquire) {
+            histograms.clear();
+        }
+
+        histograms
+    }
+}
+
+impl StateData for GlobalHistogram {}
+
+/// Formats a duration in milliseconds, with up to three decimal places.
+fn millis(duration: Duration) -> String {
+    let us = duration.as_micros();
+    let formatted = format!("{}.{:03}", us / 1000, us % 1000);
+    format!(
+        "{}ms",
+        formatted.trim_end_matches('0').trim_end_matches('.')
+    )
+}
+
+/// Middleware binding to record the duration of each request into a histogram per route.
+///
+/// Routes are identified by their method and `RouteTemplate` (such as `GET /users/:id`), so
+/// templates should be attached to the routes of interest; requests without a template are
+/// recorded per method under `-`. Durations are measured from the time the request was
+/// received, until the response is ready.
+///
+/// The histograms are shared by every instance of the middleware, and are placed into `State`
+/// as a `GlobalHistogram`. They can be summarised on demand by routing to the
+/// `latency_handler` through a pipeline containing the middleware:
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::histogram::{latency_handler, DurationHistogramMiddleware};
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// let histogram = DurationHistogramMiddleware::new();
+/// let (chain, pipelines) = single_pipeline(new_pipeline().add(histogram).build());
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/internal/latency").to(latency_handler);
+/// });
+/// # let _ = router;
+/// ```
+#[derive(Clone)]
+pub struct DurationHistogramMiddleware {
+    histogram: GlobalHistogram,
+}
+
+impl DurationHistogramMiddleware {
+    /// Creates a new `DurationHistogramMiddleware`, with an empty histogram per route.
+    pub fn new() -> Self {
+        DurationHistogramMiddleware {
+            histogram: GlobalHistogram::new(),
+        }
+    }
+
+    /// Returns a handle to the histograms, such as for exporting them periodically.
+    pub fn histogram(&self) -> GlobalHistogram {
+        self.histogram.clone()
+    }
+}
+
+impl Default for DurationHistogramMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for DurationHistogramMiddleware {
+    /// Records the duration of the request once its response is ready.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        // measure from the time the request was received
+        let timer = Timer::from_state(&state);
+        let histogram = self.histogram;
+
+        state.put(histogram.clone());
+
+        let f = chain(state).then(move |result| {
+            let state = match result {
+                Ok((ref state, _)) | Err((ref state, _)) => state,
+            };
+
+            // the template is usually attached by the route, so read it once it's handled
+            let route = match RouteTemplate::try_borrow_from(state) {
+                Some(template) => format!("{} {}", Method::borrow_from(state), template.as_str()),
+                None => format!("{} -", Method::borrow_from(state)),
+            };
+
+            histogram.record(&route, timer.elapsed().as_duration());
+            result
+        });
+
+        Box::new(f)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for DurationHistogramMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// Writes the `GlobalHistogram` summary as plain text, one line per route.
+///
+/// This is intended to be routed to a path such as `/internal/latency` through a pipeline
+/// containing a `DurationHistogramMiddleware`, and kept private to the network. Without the
+/// middleware, there's nothing to summarise and a `404 Not Found` is returned.
+pub fn latency_handler(state: State) -> (State, Response<Body>) {
+    let response = match GlobalHistogram::try_borrow_from(&state) {
+        Some(histogram) => create_response(
+            &state,
+            StatusCode::OK,
+            mime::TEXT_PLAIN,
+            histogram.summary(),
+        ),
+        None => create_empty_response(&state, StatusCode::NOT_FOUND),
+    };
+
+    (state, response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{future, Stream};
+    use hyper::{HeaderMap, Uri};
+
+    #[test]
+    fn reports_percentiles_from_buckets() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.percentile(50.0), Duration::from_micros(0));
+
+        for _ in 0..90 {
+            histogram.record(Duration::from_micros(800));
+        }
+        for _ in 0..9 {
+            histogram.record(Duration::from_millis(4));
+        }
+        histogram.record(Duration::from_millis(42));
+
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(histogram.percentile(50.0), Duration::from_micros(831));
+        assert_eq!(histogram.percentile(95.0), Duration::from_micros(4095));
+        assert_eq!(histogram.percentile(99.0), Duration::from_micros(4095));
+        assert_eq!(histogram.percentile(99.9), Duration::from_millis(42));
+        assert_eq!(histogram.max(), Duration::from_millis(42));
+
+        histogram.reset();
+        assert_eq!(histogram.count(), 0);
+        assert_eq!(histogram.max(), Duration::from_micros(0));
+    }
+
+    #[test]
+    fn records_durations_per_route() {
+        let middleware = DurationHistogramMiddleware::new();
+        let histogram = middleware.histogram();
+
+        for template in &[Some("/users/:id"), Some("/users/:id"), None] {
+            let mut state = State::new();
+            state.put(Method::GET);
+            state.put("/users/1".parse::<Uri>().unwrap());
+            state.put(HeaderMap::new());
+            crate::state::set_request_id(&mut state);
+
+            let template = *template;
+            let (state, _) = middleware
+                .clone()
+                .new_middleware()
+                .unwrap()
+                .call(state, move |mut state| {
+                    if let Some(template) = template {
+                        state.put(RouteTemplate::new(template));
+                    }
+                    GlobalHistogram::borrow_from(&state)
+                        .record("inventory", Duration::from_millis(3));
+                    Box::new(future::ok((state, Response::new(Body::empty()))))
+                })
+                .wait()
+                .map_err(|_| ())
+                .unwrap();
+
+            assert!(state.has::<GlobalHistogram>());
+        }
+
+        assert_eq!(histogram.histogram("GET /users/:id").unwrap().count(), 2);
+        assert_eq!(histogram.histogram("GET -").unwrap().count(), 1);
+
+        let inventory = histogram.histogram("inventory").unwrap();
+        assert_eq!(inventory.percentile(50.0), Duration::from_millis(3));
+
+        let summary = histogram.summary();
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("GET -: 1 requests, p50 "));
+        assert!(lines[1].starts_with("GET /users/:id: 2 requests, p50 "));
+        assert_eq!(
+            lines[2],
+            "inventory: 3 requests, p50 3ms, p95 3ms, p99 3ms, p999 3ms, max 3ms"
+        );
+
+        histogram.reset();
+        assert_eq!(histogram.summary(), "");
+    }
+
+    #[test]
+    fn writes_latency_summaries() {
+        let middleware = DurationHistogramMiddleware::new();
+        middleware
+            .histogram()
+            .record("GET /", Duration::from_micros(1500));
+
+        let mut state = State::new();
+        state.put(Method::GET);
+        state.put(HeaderMap::new());
+        state.put(middleware.histogram());
+        crate::state::set_request_id(&mut state);
+
+        let (_, response) = latency_handler(state);
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().concat2().wait().unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            "GET /: 1 requests, p50 1.5ms, p95 1.5ms, p99 1.5ms, p999 1.5ms, max 1.5ms\n"
+        );
+
+        let mut state = State::new();
+        state.put(Method::GET);
+        state.put(HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+
+        let (_, response) = latency_handler(state);
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
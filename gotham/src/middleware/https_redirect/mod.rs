@@ -0,0 +1,245 @@
+//! Middleware to redirect requests made over `http` to `https`.
+//!
+//! When an application serves both `http` and `https`, plaintext requests should be upgraded
+//! rather than answered. This middleware redirects them to the same host, path and query over
+//! `https`, and marks `https` responses with `Strict-Transport-Security` so that browsers skip
+//! the plaintext request altogether on later visits.
+use std::io;
+
+use futures::{future, Future};
+use hyper::header::{HeaderMap, HeaderValue, HOST, LOCATION, STRICT_TRANSPORT_SECURITY};
+use hyper::{StatusCode, Uri};
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State};
+use crate::tls::TlsConnection;
+
+// the header set by proxies which terminate TLS
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+// the default lifetime of the HSTS policy, one year
+const DEFAULT_HSTS_MAX_AGE: u64 = 365 * 24 * 60 * 60;
+
+/// Middleware binding to redirect `http` requests to `https`, and to set
+/// `Strict-Transport-Security` on `https` responses.
+///
+/// The scheme of a request is taken from the `X-Forwarded-Proto` header when present, as set by
+/// proxies terminating TLS, and otherwise from whether the request was made over a TLS
+/// connection (see `TlsConnection`). Requests made over `http` are answered with a
+/// `301 Moved Permanently` to the same host, path and query on the configured `https` port,
+/// without reaching the rest of the chain. Requests already made over `https` pass through, so
+/// the middleware never causes a redirect loop.
+///
+/// The `X-Forwarded-Proto` header should only be relied upon behind a proxy which sets it, as a
+/// client sending it directly can opt out of the redirect.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::https_redirect::HttpsRedirectMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// // `http://example.com/login?next=/` moves to `https://example.com:8443/login?next=/`
+/// let https = HttpsRedirectMiddleware::new(8443).hsts_max_age(24 * 60 * 60);
+///
+/// let pipeline = new_pipeline().add(https).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct HttpsRedirectMiddleware {
+    https_port: u16,
+    hsts_max_age: u64,
+}
+
+impl HttpsRedirectMiddleware {
+    /// Creates a new `HttpsRedirectMiddleware`, redirecting to `https` on the provided port and
+    /// setting an HSTS policy lasting one year.
+    ///
+    /// The port is omitted from redirects when it's the default `https` port of 443.
+    pub fn new(https_port: u16) -> Self {
+        HttpsRedirectMiddleware {
+            https_port,
+            hsts_max_age: DEFAULT_HSTS_MAX_AGE,
+        }
+    }
+
+    /// Sets the `max-age` of the `Strict-Transport-Security` header, in seconds.
+    ///
+    /// Browsers only remember the policy for this long after their last `https` response, so a
+    /// `max-age` of `0` removes a policy set previously.
+    pub fn hsts_max_age(mut self, seconds: u64) -> Self {
+        self.hsts_max_age = seconds;
+        self
+    }
+
+    /// Builds the `https` redirect target for a request, if the request states its host.
+    fn location(&self, state: &State) -> Option<String> {
+        let uri = Uri::borrow_from(state);
+        let host = match HeaderMap::borrow_from(state).get(HOST) {
+            Some(host) => host.to_str().ok()?,
+            None => uri.authority_part()?.as_str(),
+        };
+
+        // replace any port of the request, keeping the brackets of IPv6 addresses
+        let host = match host.rfind(':') {
+            Some(i) if !host[i + 1..].contains(']') => &host[..i],
+            _ => host,
+        };
+
+        let path_and_query = uri.path_and_query().map_or("/", |pq| pq.as_str());
+
+        Some(match self.https_port {
+            443 => format!("https://{}{}", host, path_and_query),
+            port => format!("https://{}:{}{}", host, port, path_and_query),
+        })
+    }
+}
+
+/// Determines whether a request was made over `https`.
+fn is_secure(state: &State) -> bool {
+    let forwarded = HeaderMap::borrow_from(state)
+        .get(X_FORWARDED_PROTO)
+        .and_then(|proto| proto.to_str().ok())
+        // proxies may append to an existing value, the first of which is the client's
+        .and_then(|proto| proto.split(',').next())
+        .map(str::trim);
+
+    match forwarded {
+        Some(proto) => proto.eq_ignore_ascii_case("https"),
+        None => {
+            state.has::<TlsConnection>() || Uri::borrow_from(state).scheme_str() == Some("https")
+        }
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for HttpsRedirectMiddleware {
+    /// Redirects `http` requests, and sets `Strict-Transport-Security` on `https` responses.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        if is_secure(&state) {
+            let hsts = HeaderValue::from_str(&format!("max-age={}", self.hsts_max_age))
+                .expect("digits are a valid header value");
+
+            let f = chain(state).map(move |(state, mut response)| {
+                let headers = response.headers_mut();
+                if !headers.contains_key(STRICT_TRANSPORT_SECURITY) {
+                    headers.insert(STRICT_TRANSPORT_SECURITY, hsts);
+                }
+                (state, response)
+            });
+
+            return Box::new(f);
+        }
+
+        let location = self
+            .location(&state)
+            .and_then(|location| HeaderValue::from_str(&location).ok());
+
+        let response = match location {
+            Some(location) => {
+                trace!("[{}] redirecting to https", request_id(&state));
+                let mut response = create_empty_response(&state, StatusCode::MOVED_PERMANENTLY);
+                response.headers_mut().insert(LOCATION, location);
+                response
+            }
+            // without a host, there's nowhere to redirect to
+            None => create_empty_response(&state, StatusCode::BAD_REQUEST),
+        };
+
+        Box::new(future::ok((state, response)))
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for HttpsRedirectMiddleware {
+    type Instance = Self;
+
+    /// Copies the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, Response};
+
+    fn call(
+        middleware: HttpsRedirectMiddleware,
+        uri: &str,
+        headers: &[(&'static str, &'static str)],
+        tls: bool,
+    ) -> Response<Body> {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(*name, value.parse().unwrap());
+        }
+
+        let mut state = State::new();
+        state.put(uri.parse::<Uri>().unwrap());
+        state.put(map);
+        if tls {
+            state.put(TlsConnection);
+        }
+        crate::state::set_request_id(&mut state);
+
+        middleware
+            .call(state, |state| {
+                Box::new(future::ok((state, Response::new(Body::empty()))))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn redirects_insecure_requests() {
+        let middleware = HttpsRedirectMiddleware::new(443);
+
+        let response = call(
+            middleware,
+            "/a/b?c=d",
+            &[("host", "example.com:8080")],
+            false,
+        );
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.headers()[LOCATION], "https://example.com/a/b?c=d");
+        assert!(!response.headers().contains_key(STRICT_TRANSPORT_SECURITY));
+
+        let middleware = HttpsRedirectMiddleware::new(8443);
+
+        let response = call(middleware, "/", &[("host", "[::1]:8080")], false);
+        assert_eq!(response.headers()[LOCATION], "https://[::1]:8443/");
+
+        let headers = [("host", "example.com"), ("x-forwarded-proto", "http")];
+        let response = call(middleware, "/", &headers, true);
+        assert_eq!(response.headers()[LOCATION], "https://example.com:8443/");
+
+        let response = call(middleware, "/", &[], false);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn marks_secure_responses() {
+        let middleware = HttpsRedirectMiddleware::new(443).hsts_max_age(600);
+
+        let response = call(middleware, "/", &[("host", "example.com")], true);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[STRICT_TRANSPORT_SECURITY], "max-age=600");
+
+        let headers = [
+            ("host", "example.com"),
+            ("x-forwarded-proto", "HTTPS, http"),
+        ];
+        let response = call(middleware, "/", &headers, false);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[STRICT_TRANSPORT_SECURITY], "max-age=600");
+    }
+}
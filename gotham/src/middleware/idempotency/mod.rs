@@ -0,0 +1,470 @@
+//! Middleware to de-duplicate retried requests using an `Idempotency-Key` header.
+//!
+//! Clients of endpoints with side effects, such as taking a payment, send a unique key with each
+//! logical request and reuse it when retrying. The first response for a key is recorded, and
+//! later requests with the same key receive the recorded response rather than running the
+//! handler again.
+mod store;
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use futures::sync::oneshot;
+use futures::{future, Future, Stream};
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::{Body, HeaderMap, Method, Response, Uri};
+use log::{trace, warn};
+
+use crate::handler::{HandlerFuture, IntoHandlerError};
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State};
+
+pub use self::store::{CachedResponse, IdempotencyStore, MemoryStore, StoreFuture};
+
+// the header carrying the key when no custom name is configured
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+// the header marking responses which were replayed from the store
+const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+/// The requests waiting on the request currently running for each key, in arrival order.
+type InFlight = Mutex<HashMap<String, Vec<oneshot::Sender<Outcome>>>>;
+
+/// What a waiting request receives once the request it's waiting on has finished.
+enum Outcome {
+    /// The recorded response, to be replayed.
+    Replay(CachedResponse),
+    /// No response was recorded, so the waiting request runs in its place.
+    Lead(Leader),
+}
+
+/// Middleware binding to record and replay responses for requests carrying an idempotency key.
+///
+/// Keys are scoped to the method and path of the request, so the same key may be used against
+/// different endpoints. Requests without a key are passed through untouched.
+///
+/// The first response for a key is recorded in the `IdempotencyStore` with its body buffered in
+/// full, unless it's a server error, which a client is expected to retry. Later requests with
+/// the same key receive the recorded response, marked with an `Idempotent-Replayed: true`
+/// header, without the rest of the pipeline or the handler running. Requests arriving while the
+/// first is still running wait for it to complete, and then receive its response. When it
+/// fails, or responds with a server error, the earliest waiting request runs in its place while
+/// the others keep waiting.
+///
+/// The request body isn't compared against the first request, so clients must not reuse keys
+/// for different requests.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use std::time::Duration;
+/// # use gotham::middleware::idempotency::{IdempotencyMiddleware, MemoryStore};
+/// # use gotham::pipeline::new_pipeline;
+/// let idempotency = IdempotencyMiddleware::new(MemoryStore::new(Duration::from_secs(3600)));
+///
+/// let pipeline = new_pipeline().add(idempotency).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone)]
+pub struct IdempotencyMiddleware {
+    store: Arc<dyn IdempotencyStore>,
+    in_flight: Arc<InFlight>,
+    header: HeaderName,
+}
+
+impl IdempotencyMiddleware {
+    /// Creates a new `IdempotencyMiddleware` recording responses in the provided store.
+    pub fn new<S>(store: S) -> Self
+    where
+        S: IdempotencyStore + 'static,
+    {
+        IdempotencyMiddleware {
+            store: Arc::new(store),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            header: HeaderName::from_static(IDEMPOTENCY_KEY),
+        }
+    }
+
+    /// Sets the name of the header carrying the idempotency key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn header_name(mut self, name: &str) -> Self {
+        self.header = HeaderName::from_bytes(name.as_bytes()).expect("invalid header name");
+        self
+    }
+}
+
+impl Default for IdempotencyMiddleware {
+    fn default() -> Self {
+        Self::new(MemoryStore::default())
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for IdempotencyMiddleware {
+    /// Replays the recorded response for the request key, or records the response.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        let key = match HeaderMap::borrow_from(&state)
+            .get(&self.header)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.is_empty())
+        {
+            Some(key) => format!(
+                "{} {} {}",
+                Method::borrow_from(&state),
+                Uri::borrow_from(&state).path(),
+                key
+            ),
+            None => return chain(state),
+        };
+
+        // wait for a request already running with the same key
+        let waiting = {
+            let mut in_flight = lock(&self.in_flight);
+            match in_flight.get_mut(&key) {
+                Some(waiters) => {
+                    let (sender, receiver) = oneshot::channel();
+                    waiters.push(sender);
+                    Some(receiver)
+                }
+                None => {
+                    in_flight.insert(key.clone(), Vec::new());
+                    None
+                }
+            }
+        };
+
+        let store = self.store;
+
+        if let Some(receiver) = waiting {
+            trace!("[{}] waiting on idempotency key", request_id(&state));
+
+            let f = receiver.then(move |result| match result {
+                Ok(Outcome::Replay(cached)) => Box::new(future::ok((state, replay(cached)))),
+                Ok(Outcome::Lead(leader)) => {
+                    trace!(
+                        "[{}] running in place of failed request",
+                        request_id(&state)
+                    );
+                    record(leader, store, state, chain)
+                }
+                // the waiting requests were abandoned, so this one runs as usual
+                Err(_) => chain(state),
+            });
+
+            return Box::new(f);
+        }
+
+        let leader = Leader {
+            key,
+            in_flight: self.in_flight,
+            finished: false,
+        };
+
+        let f = store.get(&leader.key).then(move |result| {
+            let cached = match result {
+                Ok(cached) => cached,
+                Err(e) => {
+                    warn!(
+                        "[{}] unable to read idempotency store: {}",
+                        request_id(&state),
+                        e
+                    );
+                    None
+                }
+            };
+
+            if let Some(cached) = cached {
+                trace!("[{}] replaying recorded response", request_id(&state));
+                leader.finish(Some(&cached));
+                return Box::new(future::ok((state, replay(cached)))) as Box<HandlerFuture>;
+            }
+
+            record(leader, store, state, chain)
+        });
+
+        Box::new(f)
+    }
+}
+
+/// Runs the request for the key held by `leader`, recording its response in the store and
+/// passing it on to the waiting requests.
+fn record<Chain>(
+    leader: Leader,
+    store: Arc<dyn IdempotencyStore>,
+    state: State,
+    chain: Chain,
+) -> Box<HandlerFuture>
+where
+    Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+{
+    let f = chain(state).and_then(move |(state, response)| {
+        if response.status().is_server_error() {
+            leader.finish(None);
+            return future::Either::A(future::ok((state, response)));
+        }
+
+        let (parts, body) = response.into_parts();
+        let f = body.concat2().then(move |result| match result {
+            Ok(body) => {
+                let cached = CachedResponse {
+                    status: parts.status,
+                    headers: parts.headers.clone(),
+                    body: body.into_bytes(),
+                };
+
+                if let Err(e) = store.put(&leader.key, &cached) {
+                    warn!("[{}] unable to record response: {}", request_id(&state), e);
+                }
+
+                leader.finish(Some(&cached));
+                let response = Response::from_parts(parts, Body::from(cached.body));
+                Ok((state, response))
+            }
+            Err(e) => Err((state, e.into_handler_error())),
+        });
+
+        future::Either::B(f)
+    });
+
+    Box::new(f)
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for IdempotencyMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance, sharing the store.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// The request running for a key, which releases the requests waiting on it once finished
+/// (or dropped).
+struct Leader {
+    key: String,
+    in_flight: Arc<InFlight>,
+    finished: bool,
+}
+
+impl Leader {
+    /// Sends the recorded response to each waiting request, or promotes the earliest waiting
+    /// request to run in its place when there is none.
+    fn finish(mut self, response: Option<&CachedResponse>) {
+        self.finished = true;
+
+        let response = match response {
+            Some(response) => response,
+            None => return self.promote(),
+        };
+
+        if let Some(waiters) = lock(&self.in_flight).remove(&self.key) {
+            for waiter in waiters {
+                let _ = waiter.send(Outcome::Replay(response.clone()));
+            }
+        }
+    }
+
+    /// Hands the key to the earliest waiting request still listening, leaving the rest waiting
+    /// on it, or releases the key when no requests are waiting.
+    fn promote(&self) {
+        let mut in_flight = lock(&self.in_flight);
+
+        loop {
+            let waiter = match in_flight.get_mut(&self.key) {
+                Some(waiters) if !waiters.is_empty() => waiters.remove(0),
+                _ => {
+                    in_flight.remove(&self.key);
+                    return;
+                }
+            };
+
+            let leader = Leader {
+                key: self.key.clone(),
+                in_flight: self.in_flight.clone(),
+                finished: false,
+            };
+
+            match waiter.send(Outcome::Lead(leader)) {
+                Ok(()) => return,
+                // the waiting request was abandoned, so the next one is tried while still locked
+                Err(Outcome::Lead(mut leader)) => leader.finished = true,
+                Err(Outcome::Replay(_)) => unreachable!(),
+            }
+        }
+    }
+}
+
+impl Drop for Leader {
+    fn drop(&mut self) {
+        // the request failed or was abandoned, so another request runs in its place
+        if !self.finished {
+            self.promote();
+        }
+    }
+}
+
+/// Locks the in-flight requests, ignoring poisoning as entries are changed in one step.
+fn lock(in_flight: &InFlight) -> MutexGuard<'_, HashMap<String, Vec<oneshot::Sender<Outcome>>>> {
+    match in_flight.lock() {
+        Ok(in_flight) => in_flight,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Creates a response from a recorded response, marked as replayed.
+fn replay(cached: CachedResponse) -> Response<Body> {
+    let mut response = Response::new(Body::from(cached.body));
+    *response.status_mut() = cached.status;
+    *response.headers_mut() = cached.headers;
+
+    response.headers_mut().insert(
+        HeaderName::from_static(IDEMPOTENT_REPLAYED),
+        HeaderValue::from_static("true"),
+    );
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use hyper::StatusCode;
+
+    fn state(key: Option<&'static str>) -> State {
+        let mut headers = HeaderMap::new();
+        if let Some(key) = key {
+            headers.insert(IDEMPOTENCY_KEY, HeaderValue::from_static(key));
+        }
+
+        let mut state = State::new();
+        state.put(Method::POST);
+        state.put("/payments".parse::<Uri>().unwrap());
+        state.put(headers);
+        crate::state::set_request_id(&mut state);
+        state
+    }
+
+    fn charge(calls: &Arc<AtomicUsize>) -> impl FnOnce(State) -> Box<HandlerFuture> + Send {
+        let calls = calls.clone();
+        move |state| {
+            let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            let response = Response::builder()
+                .status(StatusCode::CREATED)
+                .body(Body::from(format!("charge {}", call)))
+                .unwrap();
+
+            Box::new(future::ok((state, response)))
+        }
+    }
+
+    fn body(response: Response<Body>) -> String {
+        let body = response.into_body().concat2().wait().unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    fn run(f: Box<HandlerFuture>) -> Response<Body> {
+        f.wait().map_err(|_| ()).unwrap().1
+    }
+
+    #[test]
+    fn replays_recorded_responses() {
+        let middleware = IdempotencyMiddleware::new(MemoryStore::new(Duration::from_secs(60)));
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let call = |key| run(middleware.clone().call(state(key), charge(&calls)));
+
+        let first = call(Some("abc"));
+        assert_eq!(first.status(), StatusCode::CREATED);
+        assert!(first.headers().get(IDEMPOTENT_REPLAYED).is_none());
+        assert_eq!(body(first), "charge 1");
+
+        let second = call(Some("abc"));
+        assert_eq!(second.status(), StatusCode::CREATED);
+        assert_eq!(second.headers()[IDEMPOTENT_REPLAYED], "true");
+        assert_eq!(body(second), "charge 1");
+
+        assert_eq!(body(call(Some("def"))), "charge 2");
+        assert_eq!(body(call(None)), "charge 3");
+        assert_eq!(body(call(None)), "charge 4");
+    }
+
+    #[test]
+    fn coalesces_concurrent_requests() {
+        let middleware = IdempotencyMiddleware::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (release, released) = oneshot::channel::<()>();
+
+        let first = middleware.clone().call(state(Some("abc")), {
+            let calls = calls.clone();
+            move |state| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                let f = released.then(|_| {
+                    let response = Response::new(Body::from("charged once"));
+                    Ok((state, response))
+                });
+                Box::new(f)
+            }
+        });
+
+        // registered while the first request is still running
+        let second = middleware.clone().call(state(Some("abc")), charge(&calls));
+
+        release.send(()).unwrap();
+
+        assert_eq!(body(run(first)), "charged once");
+        assert_eq!(body(run(second)), "charged once");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(lock(&middleware.in_flight).is_empty());
+    }
+
+    #[test]
+    fn promotes_a_waiting_request_when_the_first_fails() {
+        let middleware = IdempotencyMiddleware::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (release, released) = oneshot::channel::<()>();
+
+        let first = middleware.clone().call(state(Some("abc")), move |state| {
+            let f = released.then(|_| {
+                let response = Response::builder()
+                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                    .body(Body::empty())
+                    .unwrap();
+                Ok((state, response))
+            });
+            Box::new(f)
+        });
+
+        // both registered while the first request is still running
+        let second = middleware.clone().call(state(Some("abc")), charge(&calls));
+        let third = middleware.clone().call(state(Some("abc")), charge(&calls));
+
+        release.send(()).unwrap();
+        assert_eq!(run(first).status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // the second request runs in place of the first, while the third keeps waiting on it
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert!(!lock(&middleware.in_flight).is_empty());
+
+        let second = run(second);
+        assert!(second.headers().get(IDEMPOTENT_REPLAYED).is_none());
+        assert_eq!(body(second), "charge 1");
+
+        let third = run(third);
+        assert_eq!(third.headers()[IDEMPOTENT_REPLAYED], "true");
+        assert_eq!(body(third), "charge 1");
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(lock(&middleware.in_flight).is_empty());
+    }
+}
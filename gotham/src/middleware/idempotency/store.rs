@@ -0,0 +1,137 @@
+//! Defines the `IdempotencyStore` trait, and the in-memory store used by default.
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::{future, Future};
+use hyper::{HeaderMap, StatusCode};
+use linked_hash_map::LinkedHashMap;
+
+// the default time a response is kept for by the `MemoryStore`, 24h
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A response recorded for an idempotency key, replayed to later requests using the same key.
+#[derive(Clone, Debug)]
+pub struct CachedResponse {
+    /// The status of the response.
+    pub status: StatusCode,
+
+    /// The headers of the response.
+    pub headers: HeaderMap,
+
+    /// The complete body of the response.
+    pub body: Bytes,
+}
+
+/// Type alias for the futures returned by `IdempotencyStore::get`.
+pub type StoreFuture = dyn Future<Item = Option<CachedResponse>, Error = io::Error> + Send;
+
+/// Storage for the responses recorded by an `IdempotencyMiddleware`.
+///
+/// Keys are opaque to the store, which decides how long responses are kept for. Stores shared
+/// between instances of an application (such as one backed by Redis) allow retries to be
+/// de-duplicated wherever they arrive, although concurrent requests are only coalesced within
+/// a single instance.
+pub trait IdempotencyStore: Send + Sync + RefUnwindSafe {
+    /// Retrieves the response recorded for a key, resolving to `None` when there is none.
+    fn get(&self, key: &str) -> Box<StoreFuture>;
+
+    /// Records the response for a key.
+    fn put(&self, key: &str, response: &CachedResponse) -> io::Result<()>;
+}
+
+/// Type alias for the `MemoryStore` storage container.
+type MemoryMap = Mutex<LinkedHashMap<String, (Instant, CachedResponse)>>;
+
+/// An in-process `IdempotencyStore`, where responses expire after a fixed time to live.
+///
+/// This is the store used by `IdempotencyMiddleware::default()`, with a time to live of 24 hours.
+#[derive(Clone)]
+pub struct MemoryStore {
+    ttl: Duration,
+    storage: Arc<MemoryMap>,
+}
+
+impl MemoryStore {
+    /// Creates a new `MemoryStore` where responses expire once the `ttl` has elapsed.
+    pub fn new(ttl: Duration) -> Self {
+        MemoryStore {
+            ttl,
+            storage: Arc::new(Mutex::new(LinkedHashMap::new())),
+        }
+    }
+
+    /// Locks the storage, ignoring poisoning as entries are replaced in one step.
+    fn lock(&self) -> MutexGuard<'_, LinkedHashMap<String, (Instant, CachedResponse)>> {
+        match self.storage.lock() {
+            Ok(storage) => storage,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL)
+    }
+}
+
+impl IdempotencyStore for MemoryStore {
+    fn get(&self, key: &str) -> Box<StoreFuture> {
+        let response = match self.lock().get(key) {
+            Some((expiry, response)) if *expiry > Instant::now() => Some(response.clone()),
+            _ => None,
+        };
+
+        Box::new(future::ok(response))
+    }
+
+    fn put(&self, key: &str, response: &CachedResponse) -> io::Result<()> {
+        let now = Instant::now();
+        let mut storage = self.lock();
+
+        // every entry has the same ttl, so expired entries are always at the front
+        while let Some((_, (expiry, _))) = storage.front() {
+            if *expiry > now {
+                break;
+            }
+            storage.pop_front();
+        }
+
+        storage.insert(key.to_owned(), (now + self.ttl, response.clone()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response() -> CachedResponse {
+        CachedResponse {
+            status: StatusCode::CREATED,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(b"charged"),
+        }
+    }
+
+    #[test]
+    fn expires_responses() {
+        let store = MemoryStore::new(Duration::from_secs(60));
+        store.put("abc", &response()).unwrap();
+
+        let cached = store.get("abc").wait().unwrap().unwrap();
+        assert_eq!(cached.status, StatusCode::CREATED);
+        assert_eq!(cached.body, "charged");
+        assert!(store.get("def").wait().unwrap().is_none());
+
+        let store = MemoryStore::new(Duration::from_secs(0));
+        store.put("abc", &response()).unwrap();
+        assert!(store.get("abc").wait().unwrap().is_none());
+
+        store.put("def", &response()).unwrap();
+        assert_eq!(store.lock().len(), 1);
+    }
+}
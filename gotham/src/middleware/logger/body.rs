@@ -0,0 +1,218 @@
+//! Body capturing used by the `log_request_body` and `log_error_response_body` options.
+//!
+//! Capturing a body means reading it ahead of the handler, so everything here
+//! is careful to read no more than the configured limit and to hand back a
+//! `Body` which still yields the full original content.
+use futures::future::{self, Loop};
+use futures::{stream, Future, Stream};
+use hyper::header::{HeaderMap, CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{Body, Chunk, StatusCode};
+use mime::Mime;
+
+/// Options controlling which request bodies are captured for logging.
+#[derive(Clone, Debug)]
+pub(super) struct BodyLogging {
+    pub(super) limit: usize,
+    pub(super) content_types: Vec<Mime>,
+}
+
+impl BodyLogging {
+    /// Determines whether the request described by `headers` should have its body captured.
+    ///
+    /// Only the type and subtype are compared, so parameters such as `charset` are ignored.
+    pub(super) fn accepts(&self, headers: &HeaderMap) -> bool {
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .and_then(|ct| ct.parse::<Mime>().ok());
+
+        match content_type {
+            Some(ct) => self
+                .content_types
+                .iter()
+                .any(|m| m.type_() == ct.type_() && m.subtype() == ct.subtype()),
+            None => false,
+        }
+    }
+}
+
+/// Options controlling which response bodies are captured for logging.
+#[derive(Clone, Debug)]
+pub(super) struct ErrorBodyLogging {
+    pub(super) limit: usize,
+    pub(super) threshold: StatusCode,
+}
+
+/// A captured prefix of a request or response body, ready to be written to the log.
+#[derive(Clone, Debug)]
+pub struct CapturedBody {
+    prefix: Vec<u8>,
+    total: Option<u64>,
+    truncated: bool,
+}
+
+impl CapturedBody {
+    /// Returns the captured bytes, which may be a prefix of the full body.
+    pub fn bytes(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    /// Returns the captured bytes as a sanitized string, safe to embed inside a log line.
+    pub fn content(&self) -> String {
+        sanitize(&self.prefix)
+    }
+
+    /// Returns whether the body was longer than the capture limit.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Returns the total length of the body, when known.
+    pub fn total(&self) -> Option<u64> {
+        self.total
+    }
+
+    /// Formats the captured body as a quoted, sanitized log field.
+    pub(super) fn to_field(&self) -> String {
+        let mut field = format!("\"{}\"", self.content());
+
+        if self.truncated {
+            match self.total {
+                Some(total) => field.push_str(&format!(" [truncated, {} bytes]", total)),
+                None => field.push_str(" [truncated]"),
+            }
+        }
+
+        field
+    }
+}
+
+/// Reads at most `limit` bytes (rounded up to the end of the current chunk) from the body.
+///
+/// The returned `Body` replays the buffered chunks before continuing with the remainder of
+/// the original stream, so the reader receives exactly what was originally sent.
+pub(super) fn capture(
+    body: Body,
+    headers: &HeaderMap,
+    limit: usize,
+) -> impl Future<Item = (Body, CapturedBody), Error = hyper::Error> + Send {
+    let declared = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok())
+        .and_then(|len| len.parse::<u64>().ok());
+
+    future::loop_fn((body, Vec::new()), move |(body, mut buf)| {
+        body.into_future()
+            .map_err(|(err, _)| err)
+            .map(move |(chunk, rest)| match chunk {
+                Some(chunk) => {
+                    buf.extend_from_slice(&chunk);
+                    if buf.len() > limit {
+                        Loop::Break((rest, buf, false))
+                    } else {
+                        Loop::Continue((rest, buf))
+                    }
+                }
+                None => Loop::Break((rest, buf, true)),
+            })
+    })
+    .map(move |(rest, buf, complete)| {
+        let truncated = buf.len() > limit;
+        let total = if complete {
+            Some(buf.len() as u64)
+        } else {
+            declared
+        };
+
+        let captured = CapturedBody {
+            prefix: buf[..buf.len().min(limit)].to_vec(),
+            total,
+            truncated,
+        };
+
+        let body = if complete {
+            Body::from(buf)
+        } else {
+            let head = stream::once::<Chunk, hyper::Error>(Ok(Chunk::from(buf)));
+            Body::wrap_stream(head.chain(rest))
+        };
+
+        (body, captured)
+    })
+}
+
+/// Escapes a byte string so that it is safe to embed inside a quoted log field.
+///
+/// Quotes and backslashes are backslash-escaped, and anything outside of printable
+/// ASCII is written as a `\xNN` escape so a body can never break the log line.
+pub(super) fn sanitize(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+
+    for &b in bytes {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunked(parts: Vec<&'static str>) -> Body {
+        Body::wrap_stream(stream::iter_ok::<_, hyper::Error>(parts))
+    }
+
+    #[test]
+    fn captures_small_bodies_completely() {
+        let (body, captured) = capture(Body::from("{\"a\":1}"), &HeaderMap::new(), 64)
+            .wait()
+            .unwrap();
+
+        assert_eq!(captured.to_field(), "\"{\\\"a\\\":1}\"");
+        assert_eq!(&body.concat2().wait().unwrap()[..], b"{\"a\":1}");
+    }
+
+    #[test]
+    fn truncates_large_bodies_and_replays_everything() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, "12".parse().unwrap());
+
+        let (body, captured) = capture(chunked(vec!["abcd", "efgh", "ijkl"]), &headers, 6)
+            .wait()
+            .unwrap();
+
+        assert_eq!(captured.to_field(), "\"abcdef\" [truncated, 12 bytes]");
+        assert_eq!(&body.concat2().wait().unwrap()[..], b"abcdefghijkl");
+    }
+
+    #[test]
+    fn matches_allowed_content_types() {
+        let options = BodyLogging {
+            limit: 16,
+            content_types: vec![mime::APPLICATION_JSON],
+        };
+
+        let mut headers = HeaderMap::new();
+        assert!(!options.accepts(&headers));
+
+        headers.insert(
+            CONTENT_TYPE,
+            "application/json; charset=utf-8".parse().unwrap(),
+        );
+        assert!(options.accepts(&headers));
+
+        headers.insert(CONTENT_TYPE, "text/html".parse().unwrap());
+        assert!(!options.accepts(&headers));
+    }
+
+    #[test]
+    fn sanitizes_unprintable_bytes() {
+        assert_eq!(sanitize(b"a\"b\\c\n\xff"), "a\\\"b\\\\c\\x0a\\xff");
+    }
+}
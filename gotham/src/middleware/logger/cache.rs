@@ -0,0 +1,56 @@
+//! Extraction of the cache outcome written to access lines, used by the `cache_status_header`
+//! and `cache_status_normalization` options.
+use hyper::header::{HeaderMap, HeaderName};
+
+/// Returns the cache outcome from the named response header, or `-` when there is none.
+///
+/// The first row of `table` whose pattern is a prefix of the value, ignoring case, replaces the
+/// value with its normalized form. Values which don't match any row are returned as sent.
+pub(super) fn status(headers: &HeaderMap, name: &HeaderName, table: &[(String, String)]) -> String {
+    let value = match headers.get(name) {
+        Some(value) => String::from_utf8_lossy(value.as_bytes()).trim().to_owned(),
+        None => return "-".to_owned(),
+    };
+
+    if value.is_empty() {
+        return "-".to_owned();
+    }
+
+    let normalized = table.iter().find(|(pattern, _)| {
+        value
+            .get(..pattern.len())
+            .map_or(false, |prefix| prefix.eq_ignore_ascii_case(pattern))
+    });
+
+    match normalized {
+        Some((_, normalized)) => normalized.clone(),
+        None => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_cache_outcomes() {
+        let name = HeaderName::from_static("x-cache");
+        let table = vec![
+            ("hit".to_owned(), "HIT".to_owned()),
+            ("TCP_MISS".to_owned(), "MISS".to_owned()),
+        ];
+
+        let status = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name.clone(), value.parse().unwrap());
+            status(&headers, &name, &table)
+        };
+
+        assert_eq!(status("HIT from memory"), "HIT");
+        assert_eq!(status("Hit"), "HIT");
+        assert_eq!(status("TCP_MISS/200"), "MISS");
+        assert_eq!(status("STALE"), "STALE");
+        assert_eq!(status(" "), "-");
+        assert_eq!(super::status(&HeaderMap::new(), &name, &table), "-");
+    }
+}
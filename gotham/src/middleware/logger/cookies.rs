@@ -0,0 +1,107 @@
+//! Extraction of cookie names for access lines, used by the `log_cookie_names` and
+//! `log_set_cookie_names` options. Cookie values are never read.
+use hyper::header::{HeaderMap, HeaderName, COOKIE, SET_COOKIE};
+
+// the placeholder written when there are no cookies, or the headers are malformed
+const NONE: &str = "-";
+
+/// Returns the sorted, de-duplicated names of the cookies sent with a request, separated by
+/// commas, or `-` when there are none or a `Cookie` header is malformed.
+pub(super) fn request_names(headers: &HeaderMap) -> String {
+    names(headers, &COOKIE, |value| value.split(';').collect())
+}
+
+/// Returns the sorted, de-duplicated names of the cookies set by a response, separated by
+/// commas, or `-` when there are none or a `Set-Cookie` header is malformed.
+pub(super) fn response_names(headers: &HeaderMap) -> String {
+    // everything after the first `;` is an attribute
+    names(headers, &SET_COOKIE, |value| {
+        value.split(';').take(1).collect()
+    })
+}
+
+/// Collects the names from each instance of a header, split into `name=value` pairs.
+fn names<F>(headers: &HeaderMap, header: &HeaderName, pairs: F) -> String
+where
+    F: Fn(&str) -> Vec<&str>,
+{
+    let mut names = Vec::new();
+
+    for value in headers.get_all(header) {
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => return NONE.to_owned(),
+        };
+
+        for pair in pairs(value) {
+            let pair = pair.trim();
+
+            // tolerate a trailing separator
+            if pair.is_empty() {
+                continue;
+            }
+
+            match pair.find('=').map(|i| pair[..i].trim()) {
+                Some(name) if is_token(name) => names.push(name),
+                _ => return NONE.to_owned(),
+            }
+        }
+    }
+
+    if names.is_empty() {
+        return NONE.to_owned();
+    }
+
+    names.sort_unstable();
+    names.dedup();
+    names.join(",")
+}
+
+/// Determines whether a cookie name is a valid token, as defined by RFC 6265.
+fn is_token(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b > 0x20 && b < 0x7f && !b"()<>@,;:\\\"/[]?={}".contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(name: HeaderName, values: &[&'static str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(name.clone(), value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn extracts_request_cookie_names() {
+        let names = |values: &[&'static str]| request_names(&headers(COOKIE, values));
+
+        assert_eq!(names(&[]), "-");
+        assert_eq!(names(&["session=abc; theme=dark"]), "session,theme");
+        assert_eq!(names(&["b=1; a=2;", "a=3"]), "a,b");
+        assert_eq!(names(&["empty="]), "empty");
+
+        // malformed headers reveal nothing
+        assert_eq!(names(&["session=abc; broken"]), "-");
+        assert_eq!(names(&["=abc"]), "-");
+        assert_eq!(names(&["se ssion=abc"]), "-");
+    }
+
+    #[test]
+    fn extracts_response_cookie_names() {
+        let names = |values: &[&'static str]| response_names(&headers(SET_COOKIE, values));
+
+        assert_eq!(names(&[]), "-");
+        assert_eq!(
+            names(&["session=abc; Path=/; HttpOnly", "csrf=xyz; SameSite=Strict"]),
+            "csrf,session"
+        );
+        assert_eq!(names(&["session=abc", "session=; Max-Age=0"]), "session");
+        assert_eq!(names(&["HttpOnly; session=abc"]), "-");
+    }
+}
@@ -0,0 +1,185 @@
+//! Defines the `LogEntry` type, describing a single completed request.
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hyper::{HeaderMap, Method, StatusCode, Uri, Version};
+use log::Level;
+
+use super::body::CapturedBody;
+
+/// The information gathered about a single request once it has completed.
+///
+/// A `LogEntry` is built once per request by the `RequestLogger`, and then handed to each of the
+/// configured `LogFormat` and `LogSink` pairs in turn.
+#[derive(Clone)]
+pub struct LogEntry {
+    /// The level the entry is logged at.
+    pub level: Level,
+
+    /// The target the entry is logged under, when overridden for errors via
+    /// `RequestLogger::error_target`; `None` uses the default target of the sink.
+    pub target: Option<String>,
+
+    /// The unique ID of the request.
+    pub request_id: String,
+
+    /// The address of the client which made the request.
+    ///
+    /// This is `None` when the request didn't arrive from an IP peer, such as when served over
+    /// a Unix domain socket.
+    pub client_addr: Option<SocketAddr>,
+
+    /// The time at which the request started.
+    pub start_time: DateTime<Utc>,
+
+    /// The request method.
+    pub method: Method,
+
+    /// The request URI.
+    pub uri: Uri,
+
+    /// The template of the route which served the request, when provided via a `RouteTemplate`.
+    pub route_template: Option<String>,
+
+    /// The request HTTP version.
+    pub version: Version,
+
+    /// The response status.
+    pub status: StatusCode,
+
+    /// The response length, as advertised by the `Content-Length` header. For `HEAD` requests
+    /// this follows the `HeadLength` configured via `RequestLogger::head_length`.
+    pub length: Option<u64>,
+
+    /// The time taken to produce the response, measured using a monotonic clock.
+    pub duration: Duration,
+
+    /// The captured request body, when request body logging is enabled.
+    pub request_body: BodyField,
+
+    /// The captured response body, when error response body logging is enabled.
+    pub response_body: BodyField,
+
+    /// The request headers, captured only for requests which triggered verbose logging via
+    /// `RequestLogger::verbose_trigger_header`. Credentials are redacted.
+    pub request_headers: Option<HeaderMap>,
+
+    /// The hostname of the server, when enabled via `RequestLogger::include_hostname`.
+    pub hostname: Option<String>,
+
+    /// The ID of the server process, when enabled via `RequestLogger::include_pid`.
+    pub pid: Option<u32>,
+
+    /// The name or number of the thread which completed the request, when enabled via
+    /// `RequestLogger::include_thread`.
+    pub thread: Option<String>,
+
+    /// The sorted names of the cookies sent with the request, when enabled via
+    /// `RequestLogger::log_cookie_names`. Cookie values are never captured.
+    pub cookie_names: Option<String>,
+
+    /// The sorted names of the cookies set by the response, when enabled via
+    /// `RequestLogger::log_set_cookie_names`.
+    pub set_cookie_names: Option<String>,
+
+    /// The sanitized `Referer` of the request, when enabled via `RequestLogger::log_referer`.
+    pub referer: Option<String>,
+
+    /// The `User-Agent` of the request, when enabled via `RequestLogger::log_user_agent`.
+    pub user_agent: Option<String>,
+
+    /// The coarse family of the `User-Agent` of the request, when enabled via
+    /// `RequestLogger::log_user_agent_family`.
+    pub ua_family: Option<String>,
+
+    /// The normalized cache outcome of the response, when enabled via
+    /// `RequestLogger::cache_status_header`.
+    pub cache_status: Option<String>,
+
+    /// The position of the entry among those written by the logger, starting at 1, when
+    /// enabled via `RequestLogger::include_sequence`.
+    ///
+    /// Numbers are only unique within a single run of the process, restarting from 1 whenever
+    /// the process restarts.
+    pub sequence: Option<u64>,
+
+    /// Custom fields registered via `RequestLogger::add_field`, in the order they were added.
+    ///
+    /// Fields without a value for this request are kept as `None`, so that text formats can
+    /// write a placeholder in their place.
+    pub custom_fields: Vec<(String, Option<String>)>,
+}
+
+/// The length logged for responses to `HEAD` requests.
+///
+/// Responses to `HEAD` requests never carry a body, but often declare the `Content-Length` the
+/// equivalent `GET` would have, so logging the header overstates the bytes actually sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeadLength {
+    /// Logs `-` (or `null` in JSON), as no body was sent. This matches Apache's `%b`.
+    Dash,
+    /// Logs `0`, the number of body bytes actually sent.
+    Zero,
+    /// Logs the `Content-Length` declared by the response, as for any other request.
+    Declared,
+}
+
+impl Default for HeadLength {
+    fn default() -> Self {
+        HeadLength::Dash
+    }
+}
+
+/// The state of an optional body field on a `LogEntry`.
+#[derive(Clone, Debug)]
+pub enum BodyField {
+    /// Body logging is not enabled, so the field is omitted entirely.
+    Disabled,
+
+    /// Body logging is enabled, but this body was not eligible to be captured.
+    Skipped,
+
+    /// The body was captured.
+    Captured(CapturedBody),
+}
+
+impl BodyField {
+    /// Determines whether this field should be written at all.
+    pub fn is_enabled(&self) -> bool {
+        !matches!(*self, BodyField::Disabled)
+    }
+}
+
+/// Creates an entry for a plain `GET /` request, as used across the tests of the logger.
+#[cfg(test)]
+pub(super) fn test_entry() -> LogEntry {
+    LogEntry {
+        level: Level::Info,
+        target: None,
+        request_id: "abc".to_owned(),
+        client_addr: None,
+        start_time: "2024-05-01T12:34:56Z".parse().unwrap(),
+        method: Method::GET,
+        uri: Uri::from_static("/"),
+        route_template: None,
+        version: Version::HTTP_11,
+        status: StatusCode::OK,
+        length: None,
+        duration: Duration::from_micros(250),
+        request_body: BodyField::Disabled,
+        response_body: BodyField::Skipped,
+        request_headers: None,
+        hostname: None,
+        pid: None,
+        thread: None,
+        cookie_names: None,
+        set_cookie_names: None,
+        referer: None,
+        user_agent: None,
+        ua_family: None,
+        cache_status: None,
+        sequence: None,
+        custom_fields: vec![],
+    }
+}
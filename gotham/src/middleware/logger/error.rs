@@ -0,0 +1,52 @@
+//! Errors surfaced when configuring a `RequestLogger`.
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use hyper::header::HeaderName;
+
+/// An invalid option provided to one of the `try_` builders of a `RequestLogger`.
+///
+/// These allow invalid configuration to be reported at startup, rather than panicking.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoggerConfigError {
+    /// A header name, such as the one passed to `try_cache_status_header`, is not valid.
+    InvalidHeaderName {
+        /// The provided name.
+        name: String,
+    },
+    /// A content type pattern is not a valid media type.
+    InvalidContentType {
+        /// The provided pattern.
+        pattern: String,
+    },
+    /// A status pattern is neither a valid status code nor a class such as `3xx`.
+    InvalidStatusPattern {
+        /// The provided pattern.
+        pattern: String,
+    },
+}
+
+impl Display for LoggerConfigError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LoggerConfigError::InvalidHeaderName { ref name } => {
+                write!(f, "invalid header name: {:?}", name)
+            }
+            LoggerConfigError::InvalidContentType { ref pattern } => {
+                write!(f, "invalid content type pattern: {:?}", pattern)
+            }
+            LoggerConfigError::InvalidStatusPattern { ref pattern } => {
+                write!(f, "invalid status pattern: {:?}", pattern)
+            }
+        }
+    }
+}
+
+impl Error for LoggerConfigError {}
+
+/// Parses a header name provided to one of the logger options.
+pub(super) fn header_name(name: &str) -> Result<HeaderName, LoggerConfigError> {
+    HeaderName::from_bytes(name.as_bytes()).map_err(|_| LoggerConfigError::InvalidHeaderName {
+        name: name.to_owned(),
+    })
+}
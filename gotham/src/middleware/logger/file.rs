@@ -0,0 +1,530 @@
+//! Defines the `FileSink`, which appends access lines to a file from a background thread.
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+use super::entry::LogEntry;
+use super::sink::LogSink;
+
+// the default number of lines queued for the writer thread
+const DEFAULT_CAPACITY: usize = 8192;
+
+// the default interval between warnings about dropped lines, 10s
+const DEFAULT_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Determines what happens to a line written to a `FileSink` whose queue is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Waits for space in the queue, delaying the request until the line is queued. No lines
+    /// are lost, which suits audit logs. This is the default.
+    Block,
+    /// Discards the line being written, keeping the lines already queued.
+    DropNewest,
+    /// Discards the oldest line in the queue to make room for the line being written.
+    DropOldest,
+}
+
+impl Default for OverflowPolicy {
+    fn default() -> Self {
+        OverflowPolicy::Block
+    }
+}
+
+/// Configuration of the queue between a `FileSink` and its writer thread.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::logger::{OverflowPolicy, QueueConfig};
+/// let config = QueueConfig::new()
+///     .capacity(1024)
+///     .overflow_policy(OverflowPolicy::DropNewest);
+/// # let _ = config;
+/// ```
+#[derive(Clone, Debug)]
+pub struct QueueConfig {
+    capacity: usize,
+    overflow_policy: OverflowPolicy,
+    report_interval: Duration,
+}
+
+impl QueueConfig {
+    /// Creates a new `QueueConfig`, queueing up to 8192 lines and blocking once full.
+    pub fn new() -> Self {
+        QueueConfig {
+            capacity: DEFAULT_CAPACITY,
+            overflow_policy: OverflowPolicy::default(),
+            report_interval: DEFAULT_REPORT_INTERVAL,
+        }
+    }
+
+    /// Sets the number of lines which may be queued before the overflow policy applies.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        assert!(capacity > 0, "queue capacity must be positive");
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets what happens to lines written while the queue is full.
+    pub fn overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.overflow_policy = overflow_policy;
+        self
+    }
+
+    /// Sets the minimum interval between warnings about dropped lines, which is 10 seconds by
+    /// default.
+    ///
+    /// Warnings are logged through the `log` crate, rather than to the file itself.
+    pub fn report_interval(mut self, report_interval: Duration) -> Self {
+        self.report_interval = report_interval;
+        self
+    }
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A message sent to the writer thread of a `FileSink`.
+enum Message {
+    Line(String),
+    Flush(Sender<io::Result<()>>),
+    Shutdown,
+}
+
+/// A `LogSink` which appends each line to a file.
+///
+/// Lines are queued and written by a background thread, so that requests never wait on the
+/// disk. Queued lines are lost if the process exits before they are written, so the sink
+/// should be flushed before exiting; see `RequestLogger::handle`.
+///
+/// When lines are written faster than the disk accepts them the queue fills up, at which point
+/// the `OverflowPolicy` of the sink applies. The number of lines dropped as a result can be
+/// retrieved via `LoggerHandle::dropped_lines`.
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate log;
+/// # use gotham::middleware::logger::{
+/// #     CommonLogFormat, FileSink, OverflowPolicy, QueueConfig, RequestLogger,
+/// # };
+/// # use log::Level;
+/// # fn main() -> std::io::Result<()> {
+/// let debug = QueueConfig::new().overflow_policy(OverflowPolicy::DropOldest);
+///
+/// let logger = RequestLogger::new(Level::Info)
+///     .output(CommonLogFormat::new(), FileSink::open("audit.log")?)
+///     .output(
+///         CommonLogFormat::new(),
+///         FileSink::open_with_config("debug.log", debug)?,
+///     );
+/// # let _ = logger;
+/// # Ok(())
+/// # }
+/// ```
+pub struct FileSink {
+    queue: Arc<Queue>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl FileSink {
+    /// Opens the file at `path` for appending, creating it if necessary, and starts the writer
+    /// thread using the default `QueueConfig`.
+    pub fn open<P>(path: P) -> io::Result<FileSink>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open_with_config(path, QueueConfig::new())
+    }
+
+    /// Opens the file at `path` for appending, creating it if necessary, and starts the writer
+    /// thread using the provided `QueueConfig`.
+    pub fn open_with_config<P>(path: P, config: QueueConfig) -> io::Result<FileSink>
+    where
+        P: AsRef<Path>,
+    {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Self::spawn(file, config)
+    }
+
+    /// Starts a writer thread writing to the provided destination.
+    fn spawn<W>(destination: W, config: QueueConfig) -> io::Result<FileSink>
+    where
+        W: Write + Send + 'static,
+    {
+        let queue = Arc::new(Queue {
+            state: Mutex::new(QueueState {
+                messages: VecDeque::new(),
+                lines: 0,
+                closed: false,
+            }),
+            readable: Condvar::new(),
+            writable: Condvar::new(),
+            dropped: AtomicU64::new(0),
+            config,
+        });
+
+        let shared = queue.clone();
+        let writer = thread::Builder::new()
+            .name("gotham-access-log".to_owned())
+            .spawn(move || write_loop(BufWriter::new(destination), &shared))?;
+
+        Ok(FileSink {
+            queue,
+            writer: Mutex::new(Some(writer)),
+        })
+    }
+}
+
+impl LogSink for FileSink {
+    fn write(&self, _entry: &LogEntry, line: &str) -> io::Result<()> {
+        self.queue.push(Message::Line(line.to_owned()))
+    }
+
+    fn flush(&self, timeout: Duration) -> io::Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        self.queue.push(Message::Flush(sender))?;
+
+        match receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::from(io::ErrorKind::TimedOut)),
+        }
+    }
+
+    fn shutdown(&self, timeout: Duration) -> io::Result<()> {
+        let result = self.flush(timeout);
+
+        // the writer exits once every queued line has been written
+        let writer = match self.writer.lock() {
+            Ok(mut writer) => writer.take(),
+            Err(_) => None,
+        };
+
+        if let Some(writer) = writer {
+            if self.queue.push(Message::Shutdown).is_ok() && result.is_ok() {
+                let _ = writer.join();
+            }
+        }
+
+        result
+    }
+
+    fn dropped_lines(&self) -> u64 {
+        self.queue.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for FileSink {
+    fn drop(&mut self) {
+        // stops the writer once the remaining lines are written
+        let _ = self.queue.push(Message::Shutdown);
+    }
+}
+
+/// The messages waiting for the writer thread of a `FileSink`.
+struct Queue {
+    state: Mutex<QueueState>,
+    readable: Condvar,
+    writable: Condvar,
+    dropped: AtomicU64,
+    config: QueueConfig,
+}
+
+struct QueueState {
+    messages: VecDeque<Message>,
+    lines: usize,
+    closed: bool,
+}
+
+impl Queue {
+    /// Locks the queue, ignoring poisoning as the state is always consistent.
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Queues a message for the writer thread, applying the overflow policy to lines.
+    ///
+    /// Only lines count towards the capacity of the queue, so a flush is never dropped.
+    fn push(&self, message: Message) -> io::Result<()> {
+        let mut state = self.lock();
+
+        if let Message::Line(_) = message {
+            while !state.closed && state.lines >= self.config.capacity {
+                match self.config.overflow_policy {
+                    OverflowPolicy::Block => {
+                        state = match self.writable.wait(state) {
+                            Ok(state) => state,
+                            Err(poisoned) => poisoned.into_inner(),
+                        };
+                    }
+                    OverflowPolicy::DropNewest => {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        return Ok(());
+                    }
+                    OverflowPolicy::DropOldest => {
+                        let oldest = state
+                            .messages
+                            .iter()
+                            .position(|message| matches!(message, Message::Line(_)));
+
+                        if let Some(index) = oldest {
+                            state.messages.remove(index);
+                            state.lines -= 1;
+                            self.dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+            }
+
+            if !state.closed {
+                state.lines += 1;
+            }
+        }
+
+        if state.closed {
+            return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+        }
+
+        if let Message::Shutdown = message {
+            state.closed = true;
+        }
+
+        state.messages.push_back(message);
+        self.readable.notify_one();
+        Ok(())
+    }
+
+    /// Takes the next message, waiting up to `timeout` for one to arrive.
+    fn pop(&self, timeout: Option<Duration>) -> Option<Message> {
+        let mut state = self.lock();
+
+        if state.messages.is_empty() {
+            if let Some(timeout) = timeout {
+                state = match self.readable.wait_timeout(state, timeout) {
+                    Ok((state, _)) => state,
+                    Err(poisoned) => poisoned.into_inner().0,
+                };
+            }
+        }
+
+        let message = state.messages.pop_front()?;
+
+        if let Message::Line(_) = message {
+            state.lines -= 1;
+            self.writable.notify_one();
+        }
+
+        Some(message)
+    }
+
+    /// Fails any writers left blocked once the writer thread has exited.
+    fn close(&self) {
+        self.lock().closed = true;
+        self.writable.notify_all();
+    }
+}
+
+/// Writes each queued line, flushing to the destination whenever the queue is empty.
+///
+/// Failed writes are reported by the next flush, and dropped lines are reported as a warning
+/// at most once per report interval.
+fn write_loop<W: Write>(mut destination: BufWriter<W>, queue: &Queue) {
+    let interval = queue.config.report_interval;
+    let mut reporter = DropReporter {
+        reported: 0,
+        last: Instant::now(),
+    };
+    let mut failure = None;
+
+    loop {
+        let message = match queue.pop(None) {
+            Some(message) => message,
+            None => {
+                let _ = destination.flush();
+                reporter.report(queue, interval);
+
+                let remaining = interval
+                    .checked_sub(reporter.last.elapsed())
+                    .unwrap_or_default();
+                match queue.pop(Some(remaining.max(Duration::from_millis(1)))) {
+                    Some(message) => message,
+                    None => continue,
+                }
+            }
+        };
+
+        match message {
+            Message::Line(line) => {
+                let result = destination
+                    .write_all(line.as_bytes())
+                    .and_then(|_| destination.write_all(b"\n"));
+
+                if let Err(e) = result {
+                    failure = Some(e);
+                }
+            }
+            Message::Flush(done) => {
+                let result = match failure.take() {
+                    Some(e) => Err(e),
+                    None => destination.flush(),
+                };
+                let _ = done.send(result);
+            }
+            Message::Shutdown => break,
+        }
+    }
+
+    let _ = destination.flush();
+    reporter.report(queue, Duration::from_secs(0));
+    queue.close();
+}
+
+/// Tracks the dropped lines which have already been reported.
+struct DropReporter {
+    reported: u64,
+    last: Instant,
+}
+
+impl DropReporter {
+    /// Logs a warning for the lines dropped since the last report, if `interval` has elapsed.
+    fn report(&mut self, queue: &Queue, interval: Duration) {
+        if self.last.elapsed() < interval {
+            return;
+        }
+
+        let dropped = queue.dropped.load(Ordering::Relaxed);
+        if dropped > self.reported {
+            warn!(
+                "dropped {} access log lines due to backpressure",
+                dropped - self.reported
+            );
+            self.reported = dropped;
+        }
+
+        self.last = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::mpsc::Receiver;
+
+    use crate::middleware::logger::entry::test_entry;
+
+    /// A destination which stalls on its first flush of a line until released.
+    struct SlowWriter {
+        contents: Arc<Mutex<Vec<u8>>>,
+        stall: Option<(Sender<()>, Receiver<()>)>,
+    }
+
+    impl Write for SlowWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.contents.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if self.contents.lock().unwrap().is_empty() {
+                return Ok(());
+            }
+
+            if let Some((stalled, release)) = self.stall.take() {
+                stalled.send(()).unwrap();
+                release.recv().unwrap();
+            }
+            Ok(())
+        }
+    }
+
+    /// Writes 11 lines to a sink with a capacity of 4, the first of which stalls the writer
+    /// until the rest have been written (or, when blocking, until the queue is full).
+    fn stress(policy: OverflowPolicy) -> (Vec<String>, u64) {
+        let contents = Arc::new(Mutex::new(Vec::new()));
+        let (stalled, on_stall) = mpsc::channel();
+        let (release, on_release) = mpsc::channel();
+
+        let writer = SlowWriter {
+            contents: contents.clone(),
+            stall: Some((stalled, on_release)),
+        };
+
+        let config = QueueConfig::new().capacity(4).overflow_policy(policy);
+        let sink = Arc::new(FileSink::spawn(writer, config).unwrap());
+        let written = Arc::new(AtomicUsize::new(0));
+
+        let producer = {
+            let (sink, written) = (sink.clone(), written.clone());
+            thread::spawn(move || {
+                let entry = test_entry();
+                for i in 0..11 {
+                    sink.write(&entry, &format!("line {}", i)).unwrap();
+                    written.fetch_add(1, Ordering::SeqCst);
+
+                    if i == 0 {
+                        on_stall.recv().unwrap();
+                    }
+                }
+            })
+        };
+
+        if policy == OverflowPolicy::Block {
+            while written.load(Ordering::SeqCst) < 5 {
+                thread::yield_now();
+            }
+
+            // the sixth line waits for the stalled writer
+            thread::sleep(Duration::from_millis(50));
+            assert_eq!(written.load(Ordering::SeqCst), 5);
+        } else {
+            while written.load(Ordering::SeqCst) < 11 {
+                thread::yield_now();
+            }
+        }
+
+        release.send(()).unwrap();
+        producer.join().unwrap();
+        sink.shutdown(Duration::from_secs(5)).unwrap();
+
+        let contents = String::from_utf8(contents.lock().unwrap().clone()).unwrap();
+        let lines = contents.lines().map(ToOwned::to_owned).collect();
+        (lines, sink.dropped_lines())
+    }
+
+    #[test]
+    fn applies_overflow_policies() {
+        let expected = |lines: &[usize]| -> Vec<String> {
+            lines.iter().map(|i| format!("line {}", i)).collect()
+        };
+
+        let (lines, dropped) = stress(OverflowPolicy::Block);
+        assert_eq!(lines, expected(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10]));
+        assert_eq!(dropped, 0);
+
+        let (lines, dropped) = stress(OverflowPolicy::DropNewest);
+        assert_eq!(lines, expected(&[0, 1, 2, 3, 4]));
+        assert_eq!(dropped, 6);
+
+        let (lines, dropped) = stress(OverflowPolicy::DropOldest);
+        assert_eq!(lines, expected(&[0, 7, 8, 9, 10]));
+        assert_eq!(dropped, 6);
+    }
+}
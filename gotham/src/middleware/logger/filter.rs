@@ -0,0 +1,190 @@
+//! Filtering used by the `include_content_types`, `exclude_content_types` and
+//! `exclude_statuses` options.
+use hyper::header::{HeaderMap, CONTENT_TYPE};
+use hyper::StatusCode;
+use mime::Mime;
+
+use super::error::LoggerConfigError;
+
+/// Options controlling which responses are logged, based on their `Content-Type`.
+#[derive(Clone, Debug)]
+pub(super) struct ContentTypeFilter {
+    pub(super) include: Vec<Mime>,
+    pub(super) exclude: Vec<Mime>,
+    pub(super) missing: bool,
+}
+
+impl Default for ContentTypeFilter {
+    fn default() -> Self {
+        ContentTypeFilter {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            missing: true,
+        }
+    }
+}
+
+impl ContentTypeFilter {
+    /// Parses the provided patterns, such as `application/json` or `image/*`.
+    pub(super) fn patterns(content_types: &[&str]) -> Result<Vec<Mime>, LoggerConfigError> {
+        content_types
+            .iter()
+            .map(|ct| {
+                ct.parse()
+                    .map_err(|_| LoggerConfigError::InvalidContentType {
+                        pattern: (*ct).to_owned(),
+                    })
+            })
+            .collect()
+    }
+
+    /// Determines whether the response described by `headers` should be logged.
+    ///
+    /// Only the type and subtype are compared, so parameters such as `charset` are ignored.
+    /// A matching exclusion always wins over a matching inclusion.
+    pub(super) fn accepts(&self, headers: Option<&HeaderMap>) -> bool {
+        let content_type = headers
+            .and_then(|headers| headers.get(CONTENT_TYPE))
+            .and_then(|ct| ct.to_str().ok())
+            .and_then(|ct| ct.parse::<Mime>().ok());
+
+        let ct = match content_type {
+            Some(ct) => ct,
+            None => return self.missing,
+        };
+
+        if self.exclude.iter().any(|pattern| matches(pattern, &ct)) {
+            return false;
+        }
+
+        self.include.is_empty() || self.include.iter().any(|pattern| matches(pattern, &ct))
+    }
+}
+
+/// Options controlling which responses are logged, based on their status.
+#[derive(Clone, Debug, Default)]
+pub(super) struct StatusFilter {
+    exclude: Vec<StatusPattern>,
+}
+
+/// A status code to exclude, either exactly or by its class.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum StatusPattern {
+    Exact(u16),
+    Class(u16),
+}
+
+impl StatusFilter {
+    /// Parses the provided patterns, such as `304` or `3xx`.
+    pub(super) fn new(patterns: &[&str]) -> Result<Self, LoggerConfigError> {
+        let exclude = patterns
+            .iter()
+            .map(|pattern| {
+                parse(pattern).ok_or_else(|| LoggerConfigError::InvalidStatusPattern {
+                    pattern: (*pattern).to_owned(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(StatusFilter { exclude })
+    }
+
+    /// Determines whether a response with the provided status should be logged.
+    pub(super) fn accepts(&self, status: StatusCode) -> bool {
+        let code = status.as_u16();
+
+        !self.exclude.iter().any(|pattern| match *pattern {
+            StatusPattern::Exact(exact) => code == exact,
+            StatusPattern::Class(class) => code / 100 == class,
+        })
+    }
+}
+
+/// Parses a single status pattern, where a class is written as a digit followed by `xx`.
+fn parse(pattern: &str) -> Option<StatusPattern> {
+    let pattern = pattern.trim();
+
+    if pattern.len() == 3 && pattern[1..].eq_ignore_ascii_case("xx") {
+        return match pattern[..1].parse() {
+            Ok(class @ 1..=5) => Some(StatusPattern::Class(class)),
+            _ => None,
+        };
+    }
+
+    let status = StatusCode::from_bytes(pattern.as_bytes()).ok()?;
+    Some(StatusPattern::Exact(status.as_u16()))
+}
+
+/// Determines whether a content type matches a pattern, where either part may be `*`.
+fn matches(pattern: &Mime, ct: &Mime) -> bool {
+    (pattern.type_() == mime::STAR || pattern.type_() == ct.type_())
+        && (pattern.subtype() == mime::STAR || pattern.subtype() == ct.subtype())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(ct: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, ct.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn filters_by_content_type() {
+        let filter = ContentTypeFilter {
+            include: ContentTypeFilter::patterns(&["application/*", "image/*"]).unwrap(),
+            exclude: ContentTypeFilter::patterns(&["image/svg+xml", "application/pdf"]).unwrap(),
+            missing: false,
+        };
+
+        assert!(filter.accepts(Some(&headers("application/json; charset=utf-8"))));
+        assert!(filter.accepts(Some(&headers("IMAGE/PNG"))));
+        assert!(!filter.accepts(Some(&headers("image/svg+xml"))));
+        assert!(!filter.accepts(Some(&headers("application/pdf"))));
+        assert!(!filter.accepts(Some(&headers("text/html"))));
+        assert!(!filter.accepts(Some(&HeaderMap::new())));
+        assert!(!filter.accepts(None));
+
+        let filter = ContentTypeFilter {
+            exclude: ContentTypeFilter::patterns(&["text/html"]).unwrap(),
+            ..ContentTypeFilter::default()
+        };
+
+        assert!(filter.accepts(Some(&headers("text/plain"))));
+        assert!(!filter.accepts(Some(&headers("text/html"))));
+        assert!(filter.accepts(None));
+
+        assert_eq!(
+            ContentTypeFilter::patterns(&["text/html", "html"]).unwrap_err(),
+            LoggerConfigError::InvalidContentType {
+                pattern: "html".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn filters_by_status() {
+        let filter = StatusFilter::new(&["204", "3XX"]).unwrap();
+
+        assert!(!filter.accepts(StatusCode::NO_CONTENT));
+        assert!(!filter.accepts(StatusCode::NOT_MODIFIED));
+        assert!(!filter.accepts(StatusCode::MOVED_PERMANENTLY));
+        assert!(filter.accepts(StatusCode::OK));
+        assert!(filter.accepts(StatusCode::NOT_FOUND));
+
+        assert_eq!(parse("5xx"), Some(StatusPattern::Class(5)));
+        assert_eq!(parse("404"), Some(StatusPattern::Exact(404)));
+        assert_eq!(parse("6xx"), None);
+        assert_eq!(parse("abc"), None);
+        assert_eq!(parse("20"), None);
+
+        assert_eq!(
+            StatusFilter::new(&["6xx"]).unwrap_err(),
+            LoggerConfigError::InvalidStatusPattern {
+                pattern: "6xx".to_owned()
+            }
+        );
+    }
+}
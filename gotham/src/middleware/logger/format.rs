@@ -0,0 +1,1067 @@
+//! Defines the `LogFormat` trait, along with the formats provided by Gotham.
+use std::fmt::Write;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::panic::RefUnwindSafe;
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use hyper::Method;
+
+use super::body::sanitize;
+use super::entry::{BodyField, LogEntry};
+use crate::helpers::timing::Timing;
+
+/// A format used to turn a `LogEntry` into a single access log line.
+///
+/// Any carriage returns or line feeds left in a formatted line are escaped as `\r` and `\n` by
+/// the `RequestLogger` before the line reaches a sink, so a request always produces exactly one
+/// line, even when a format writes a field without escaping it.
+pub trait LogFormat: Send + Sync + RefUnwindSafe {
+    /// Formats the entry into a line, without a trailing newline.
+    fn format(&self, entry: &LogEntry) -> String;
+
+    /// Formats the entry into a line of at most `max` bytes, as configured via
+    /// `RequestLogger::max_line_length`.
+    ///
+    /// By default any line breaks are escaped first, then the line is cut at a character
+    /// boundary and suffixed with a `…[+N bytes]` marker. Structured formats should override this
+    /// to truncate individual fields instead, so that their output remains valid. Overrides must
+    /// not leave line breaks in the line, as escaping them afterwards would exceed `max`.
+    fn format_truncated(&self, entry: &LogEntry, max: usize) -> String {
+        let mut line = fold_newlines(self.format(entry));
+        truncate(&mut line, max);
+        line
+    }
+}
+
+/// Truncates a value to at most `max` bytes, including a `…[+N bytes]` marker.
+///
+/// The value is always cut at a character boundary, so that no partial UTF-8 sequences are
+/// written. When `max` is too small to hold the marker, the value is cut without one.
+pub(super) fn truncate(value: &mut String, max: usize) {
+    if value.len() <= max {
+        return;
+    }
+
+    // size the marker for the most bytes which could be removed
+    let marker = format!("…[+{} bytes]", value.len());
+    let fits = max >= marker.len();
+    let mut keep = if fits { max - marker.len() } else { max };
+
+    while !value.is_char_boundary(keep) {
+        keep -= 1;
+    }
+
+    let removed = value.len() - keep;
+    value.truncate(keep);
+
+    if fits {
+        let _ = write!(value, "…[+{} bytes]", removed);
+    }
+}
+
+/// Escapes any carriage returns and line feeds in a line as `\r` and `\n`.
+///
+/// Lines without either are returned without being copied.
+pub(super) fn fold_newlines(line: String) -> String {
+    if !line.contains(&['\r', '\n'][..]) {
+        return line;
+    }
+
+    line.replace('\r', "\\r").replace('\n', "\\n")
+}
+
+/// The [Common Log Format](https://en.wikipedia.org/wiki/Common_Log_Format) (CLF).
+///
+/// The request duration is appended after the standard fields (unless below a configured
+/// `duration_threshold`), followed by any enabled body fields and then any custom fields. This
+/// is the format used by a `RequestLogger` which has no outputs configured.
+#[derive(Clone, Debug, Default)]
+pub struct CommonLogFormat {
+    client_port: bool,
+    ipv6_format: Ipv6Format,
+    unmap_ipv4: bool,
+    missing_peer: MissingPeer,
+    path_mode: PathMode,
+    duration_format: DurationFormat,
+    duration_threshold: Option<Duration>,
+    timestamp_format: TimestampFormat,
+    combined: bool,
+}
+
+/// Controls whether the request path or the matched route template is logged.
+///
+/// Templates are only known when provided via a `RouteTemplate`; requests without one always
+/// log the request path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathMode {
+    /// Logs the request path and query string, such as `/users/48291?full=true`.
+    Path,
+    /// Logs the route template in place of the request path, such as `/users/:id`.
+    Template,
+    /// Logs the request path, followed by the template as an additional quoted field after the
+    /// duration (or `-` when unknown).
+    Both,
+}
+
+impl Default for PathMode {
+    fn default() -> Self {
+        PathMode::Path
+    }
+}
+
+/// The representation used when writing the request duration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DurationFormat {
+    /// The most readable unit, such as `850ns`, `250µs`, `1.52ms` or `2.10s`.
+    Human,
+    /// Milliseconds without a unit, always with three decimal places such as `1.520`.
+    Millis,
+    /// Whole microseconds without a unit, such as `250`, as written by Apache's `%D`.
+    Micros,
+    /// Whole nanoseconds without a unit, such as `250417`, for timing very fast handlers.
+    Nanos,
+}
+
+impl Default for DurationFormat {
+    fn default() -> Self {
+        DurationFormat::Human
+    }
+}
+
+impl DurationFormat {
+    /// Formats a duration using this representation.
+    pub fn format(self, duration: Duration) -> String {
+        match self {
+            DurationFormat::Human => Timing(duration).to_string(),
+            DurationFormat::Millis => {
+                let micros = duration.as_micros();
+                format!("{}.{:03}", micros / 1000, micros % 1000)
+            }
+            DurationFormat::Micros => duration.as_micros().to_string(),
+            DurationFormat::Nanos => duration.as_nanos().to_string(),
+        }
+    }
+}
+
+/// The representation used when writing the time at which a request started.
+///
+/// The time is captured alongside the monotonic clock used to measure the request duration, so
+/// the two always describe the same moment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// The layout of the Common Log Format, with second precision, such as
+    /// `01/Apr/2019:12:30:00 +0000`.
+    Common,
+    /// [RFC 3339](https://tools.ietf.org/html/rfc3339) in UTC, with a fixed number of subsecond
+    /// digits, such as `2019-04-01T12:30:00.123Z`.
+    Rfc3339 {
+        /// The number of subsecond digits written.
+        precision: SubsecondPrecision,
+    },
+}
+
+impl Default for TimestampFormat {
+    fn default() -> Self {
+        TimestampFormat::Common
+    }
+}
+
+/// The number of subsecond digits written by `TimestampFormat::Rfc3339`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubsecondPrecision {
+    /// Three digits, such as `12:30:00.123Z`.
+    Millis,
+    /// Six digits, such as `12:30:00.123456Z`.
+    Micros,
+    /// Nine digits, such as `12:30:00.123456789Z`.
+    Nanos,
+}
+
+impl TimestampFormat {
+    /// Formats a time using this representation.
+    pub fn format(self, time: &DateTime<Utc>) -> String {
+        let precision = match self {
+            TimestampFormat::Common => return time.format("%d/%b/%Y:%H:%M:%S %z").to_string(),
+            TimestampFormat::Rfc3339 { precision } => precision,
+        };
+
+        let digits = match precision {
+            SubsecondPrecision::Millis => SecondsFormat::Millis,
+            SubsecondPrecision::Micros => SecondsFormat::Micros,
+            SubsecondPrecision::Nanos => SecondsFormat::Nanos,
+        };
+
+        time.to_rfc3339_opts(digits, true)
+    }
+}
+
+/// The placeholder written in place of the client address when a request has no IP peer.
+///
+/// This happens when serving over a Unix domain socket, or within some test harnesses. Using a
+/// distinct placeholder keeps such lines parseable, while telling them apart from requests made
+/// by an unknown TCP peer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MissingPeer {
+    /// Writes a single `-`, as used for other unknown fields of the Common Log Format.
+    Dash,
+    /// Writes the provided value, such as `unix:` or the path of the socket.
+    ///
+    /// The value is written as-is, so it should not contain any whitespace.
+    Literal(String),
+}
+
+impl Default for MissingPeer {
+    fn default() -> Self {
+        MissingPeer::Dash
+    }
+}
+
+impl MissingPeer {
+    /// Returns the placeholder as written into the log line.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            MissingPeer::Dash => "-",
+            MissingPeer::Literal(ref value) => value,
+        }
+    }
+}
+
+/// The representation used when writing an IPv6 client address.
+///
+/// Any zone (scope ID) of a link-local address is kept, as in `fe80::1%2`. Addresses combined
+/// with a client port are always bracketed, regardless of the format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ipv6Format {
+    /// The shortest form, such as `2001:db8::1`.
+    Compressed,
+    /// The shortest form wrapped in brackets, such as `[2001:db8::1]`.
+    Bracketed,
+    /// All eight groups written in full, such as `2001:0db8:0000:0000:0000:0000:0000:0001`.
+    Expanded,
+}
+
+impl Default for Ipv6Format {
+    fn default() -> Self {
+        Ipv6Format::Compressed
+    }
+}
+
+impl CommonLogFormat {
+    /// Creates a new `CommonLogFormat`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configures whether the client's source port is included in the host position.
+    ///
+    /// The host is then written as `ip:port`, with IPv6 addresses bracketed (`[::1]:54321`)
+    /// so that the line remains parseable.
+    pub fn include_client_port(mut self, include: bool) -> Self {
+        self.client_port = include;
+        self
+    }
+
+    /// Sets the representation used for IPv6 client addresses.
+    pub fn ipv6_format(mut self, format: Ipv6Format) -> Self {
+        self.ipv6_format = format;
+        self
+    }
+
+    /// Configures whether IPv4-mapped IPv6 addresses (`::ffff:1.2.3.4`) are written as plain
+    /// IPv4 addresses (`1.2.3.4`).
+    pub fn unmap_ipv4(mut self, unmap: bool) -> Self {
+        self.unmap_ipv4 = unmap;
+        self
+    }
+
+    /// Sets whether the request path or the matched route template is logged.
+    pub fn path_mode(mut self, mode: PathMode) -> Self {
+        self.path_mode = mode;
+        self
+    }
+
+    /// Sets the placeholder written in the host position for requests without an IP peer.
+    pub fn missing_peer(mut self, placeholder: MissingPeer) -> Self {
+        self.missing_peer = placeholder;
+        self
+    }
+
+    /// Sets the representation used for the request duration.
+    pub fn duration_format(mut self, format: DurationFormat) -> Self {
+        self.duration_format = format;
+        self
+    }
+
+    /// Only appends the request duration when it meets the provided threshold, so that lines for
+    /// fast requests are plain CLF without the ` - <duration>` suffix.
+    pub fn duration_threshold(mut self, threshold: Duration) -> Self {
+        self.duration_threshold = Some(threshold);
+        self
+    }
+
+    /// Sets the representation used for the request start time, which is still written within
+    /// brackets.
+    pub fn timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.timestamp_format = format;
+        self
+    }
+
+    /// Writes the referer and user agent as quoted fields after the response length, as in the
+    /// Apache Combined Log Format, rather than as trailing `referer=` and `user_agent=` fields.
+    ///
+    /// Either is written as `"-"` when it wasn't captured, so the `RequestLogger` should also
+    /// enable `log_referer` and `log_user_agent`.
+    pub fn combined(mut self, combined: bool) -> Self {
+        self.combined = combined;
+        self
+    }
+
+    /// Formats the client address for the host position of the log line.
+    fn host(&self, addr: SocketAddr) -> String {
+        let (ip, scope_id) = match addr {
+            SocketAddr::V4(addr) => (IpAddr::V4(*addr.ip()), 0),
+            SocketAddr::V6(addr) => match mapped_ipv4(addr.ip()) {
+                Some(v4) if self.unmap_ipv4 => (IpAddr::V4(v4), 0),
+                _ => (IpAddr::V6(*addr.ip()), addr.scope_id()),
+            },
+        };
+
+        let ip = match ip {
+            IpAddr::V4(ip) if self.client_port => return format!("{}:{}", ip, addr.port()),
+            IpAddr::V4(ip) => return ip.to_string(),
+            IpAddr::V6(ip) => ip,
+        };
+
+        let mut host = match self.ipv6_format {
+            Ipv6Format::Expanded => expand(&ip),
+            Ipv6Format::Compressed | Ipv6Format::Bracketed => ip.to_string(),
+        };
+
+        if scope_id != 0 {
+            let _ = write!(host, "%{}", scope_id);
+        }
+
+        if self.client_port {
+            format!("[{}]:{}", host, addr.port())
+        } else if self.ipv6_format == Ipv6Format::Bracketed {
+            format!("[{}]", host)
+        } else {
+            host
+        }
+    }
+}
+
+/// Returns the IPv4 address embedded in an IPv4-mapped IPv6 address.
+fn mapped_ipv4(ip: &Ipv6Addr) -> Option<std::net::Ipv4Addr> {
+    let segments = ip.segments();
+    if segments[..5].iter().all(|s| *s == 0) && segments[5] == 0xffff {
+        ip.to_ipv4()
+    } else {
+        None
+    }
+}
+
+/// Writes all eight groups of an IPv6 address, without any compression.
+fn expand(ip: &Ipv6Addr) -> String {
+    let groups: Vec<String> = ip.segments().iter().map(|s| format!("{:04x}", s)).collect();
+    groups.join(":")
+}
+
+impl LogFormat for CommonLogFormat {
+    fn format(&self, entry: &LogEntry) -> String {
+        let host = match entry.client_addr {
+            Some(addr) => self.host(addr),
+            None => self.missing_peer.as_str().to_owned(),
+        };
+
+        let target = match (self.path_mode, &entry.route_template) {
+            (PathMode::Template, Some(template)) => sanitize(template.as_bytes()),
+            // the request line is quoted, so quotes and backslashes are escaped as by Apache
+            _ => sanitize(entry.uri.to_string().as_bytes()),
+        };
+
+        let mut line = format!(
+            "{} - - [{}] \"{} {} {:?}\" {} {}",
+            host,
+            self.timestamp_format.format(&entry.start_time),
+            entry.method,
+            target,
+            entry.version,
+            entry.status.as_u16(),
+            match entry.length {
+                Some(length) => length.to_string(),
+                // no body is sent in response to HEAD, as with Apache's `%b`
+                None if entry.method == Method::HEAD => "-".to_owned(),
+                None => "0".to_owned(),
+            },
+        );
+
+        if self.combined {
+            let _ = write!(
+                line,
+                " \"{}\" \"{}\"",
+                entry
+                    .referer
+                    .as_ref()
+                    .map_or_else(|| "-".to_owned(), |referer| sanitize(referer.as_bytes())),
+                entry
+                    .user_agent
+                    .as_ref()
+                    .map_or_else(|| "-".to_owned(), |agent| sanitize(agent.as_bytes())),
+            );
+        }
+
+        if self
+            .duration_threshold
+            .map_or(true, |threshold| entry.duration >= threshold)
+        {
+            let _ = write!(line, " - {}", self.duration_format.format(entry.duration));
+        }
+
+        if self.path_mode == PathMode::Both {
+            match entry.route_template {
+                Some(ref template) => {
+                    let _ = write!(line, " \"{}\"", sanitize(template.as_bytes()));
+                }
+                None => line.push_str(" -"),
+            }
+        }
+
+        push_body(&mut line, &entry.request_body);
+        push_body(&mut line, &entry.response_body);
+
+        for (_, value) in &entry.custom_fields {
+            match *value {
+                Some(ref value) => {
+                    let _ = write!(line, " \"{}\"", sanitize(value.as_bytes()));
+                }
+                None => line.push_str(" -"),
+            }
+        }
+
+        if let Some(ref hostname) = entry.hostname {
+            let _ = write!(line, " hostname={}", sanitize(hostname.as_bytes()));
+        }
+
+        if let Some(pid) = entry.pid {
+            let _ = write!(line, " pid={}", pid);
+        }
+
+        if let Some(ref thread) = entry.thread {
+            let _ = write!(line, " thread={}", sanitize(thread.as_bytes()));
+        }
+
+        if let Some(ref names) = entry.cookie_names {
+            let _ = write!(line, " cookies={}", sanitize(names.as_bytes()));
+        }
+
+        if let Some(ref names) = entry.set_cookie_names {
+            let _ = write!(line, " set_cookies={}", sanitize(names.as_bytes()));
+        }
+
+        if let (false, Some(referer)) = (self.combined, &entry.referer) {
+            let _ = write!(line, " referer={}", sanitize(referer.as_bytes()));
+        }
+
+        if let (false, Some(user_agent)) = (self.combined, &entry.user_agent) {
+            let _ = write!(line, " user_agent=\"{}\"", sanitize(user_agent.as_bytes()));
+        }
+
+        if let Some(ref family) = entry.ua_family {
+            let _ = write!(line, " ua_family={}", sanitize(family.as_bytes()));
+        }
+
+        if let Some(ref cache_status) = entry.cache_status {
+            let _ = write!(line, " cache={}", sanitize(cache_status.as_bytes()));
+        }
+
+        if let Some(sequence) = entry.sequence {
+            let _ = write!(line, " seq={}", sequence);
+        }
+
+        line
+    }
+}
+
+/// Appends an optional body field to the log line, using `-` when not captured.
+fn push_body(line: &mut String, body: &BodyField) {
+    match *body {
+        BodyField::Disabled => (),
+        BodyField::Skipped => line.push_str(" -"),
+        BodyField::Captured(ref captured) => {
+            line.push(' ');
+            line.push_str(&captured.to_field());
+        }
+    }
+}
+
+/// A compact format for reading requests as they arrive during development.
+///
+/// Each line holds the method, URI, status, duration and response length, such as
+/// `GET /users?page=2 200 1.52ms - 512`, with the length written as `-` when unknown. Nothing
+/// else is included, so this is best suited to a terminal rather than to log files.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DevFormat;
+
+impl DevFormat {
+    /// Creates a new `DevFormat`.
+    pub fn new() -> Self {
+        DevFormat
+    }
+}
+
+impl LogFormat for DevFormat {
+    fn format(&self, entry: &LogEntry) -> String {
+        format!(
+            "{} {} {} {} - {}",
+            entry.method,
+            sanitize(entry.uri.to_string().as_bytes()),
+            entry.status.as_u16(),
+            Timing(entry.duration),
+            entry
+                .length
+                .map_or_else(|| "-".to_owned(), |length| length.to_string()),
+        )
+    }
+}
+
+/// A format writing each entry as a single line JSON object.
+///
+/// Fields are written using the names `request_id`, `ip`, `client_port`, `time`, `method`, `uri`,
+/// `route`, `version`, `status`, `bytes`, `duration_us` and `duration_ns`, with `request_body`
+/// and `response_body` objects included when enabled, and a `request_headers` object included
+/// when captured. Repeated headers are joined with `, `. The `ip` and `client_port` are `null` for
+/// requests without an IP peer, and the `route` is `null` unless a `RouteTemplate` was provided.
+/// Custom fields follow under their own names, and are omitted when they have no value.
+///
+/// The `time` is written in RFC 3339 with a `+00:00` offset and only as many subsecond digits as
+/// needed, unless a `TimestampFormat` is configured.
+///
+/// When a maximum line length is configured, the `uri`, `route`, `referer`, `user_agent`, body
+/// `content` and custom field values are each truncated to that length rather than the line as a
+/// whole, so that every line remains valid JSON.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonFormat {
+    timestamp_format: Option<TimestampFormat>,
+}
+
+impl JsonFormat {
+    /// Creates a new `JsonFormat`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the representation used for the `time` field.
+    pub fn timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.timestamp_format = Some(format);
+        self
+    }
+
+    /// Writes the entry as JSON, truncating any variable length fields to `limit` bytes.
+    fn write(self, entry: &LogEntry, limit: Option<usize>) -> String {
+        let field = |value: &str| -> String {
+            let mut value = value.to_owned();
+            if let Some(max) = limit {
+                truncate(&mut value, max);
+            }
+            value
+        };
+
+        let mut line = String::with_capacity(256);
+
+        line.push('{');
+        push_json_str(&mut line, "request_id", &entry.request_id);
+        line.push(',');
+        match entry.client_addr {
+            Some(addr) => {
+                push_json_str(&mut line, "ip", &addr.ip().to_string());
+                let _ = write!(line, ",\"client_port\":{},", addr.port());
+            }
+            None => line.push_str("\"ip\":null,\"client_port\":null,"),
+        }
+
+        let time = match self.timestamp_format {
+            Some(format) => format.format(&entry.start_time),
+            None => entry.start_time.to_rfc3339(),
+        };
+
+        push_json_str(&mut line, "time", &time);
+        line.push(',');
+        push_json_str(&mut line, "method", entry.method.as_str());
+        line.push(',');
+        push_json_str(&mut line, "uri", &field(&entry.uri.to_string()));
+        line.push(',');
+        match entry.route_template {
+            Some(ref template) => push_json_str(&mut line, "route", &field(template)),
+            None => line.push_str("\"route\":null"),
+        }
+        line.push(',');
+        push_json_str(&mut line, "version", &format!("{:?}", entry.version));
+        let _ = write!(line, ",\"status\":{}", entry.status.as_u16());
+
+        match entry.length {
+            Some(length) => write!(line, ",\"bytes\":{}", length),
+            None => write!(line, ",\"bytes\":null"),
+        }
+        .unwrap();
+
+        let _ = write!(
+            line,
+            ",\"duration_us\":{},\"duration_ns\":{}",
+            entry.duration.as_micros(),
+            entry.duration.as_nanos()
+        );
+
+        if let Some(ref hostname) = entry.hostname {
+            line.push(',');
+            push_json_str(&mut line, "hostname", &field(hostname));
+        }
+
+        if let Some(pid) = entry.pid {
+            let _ = write!(line, ",\"pid\":{}", pid);
+        }
+
+        if let Some(ref thread) = entry.thread {
+            line.push(',');
+            push_json_str(&mut line, "thread", &field(thread));
+        }
+
+        if let Some(ref names) = entry.cookie_names {
+            line.push(',');
+            push_json_str(&mut line, "cookies", &field(names));
+        }
+
+        if let Some(ref names) = entry.set_cookie_names {
+            line.push(',');
+            push_json_str(&mut line, "set_cookies", &field(names));
+        }
+
+        if let Some(ref referer) = entry.referer {
+            line.push(',');
+            push_json_str(&mut line, "referer", &field(referer));
+        }
+
+        if let Some(ref user_agent) = entry.user_agent {
+            line.push(',');
+            push_json_str(&mut line, "user_agent", &field(user_agent));
+        }
+
+        if let Some(ref family) = entry.ua_family {
+            line.push(',');
+            push_json_str(&mut line, "ua_family", family);
+        }
+
+        if let Some(ref cache_status) = entry.cache_status {
+            line.push(',');
+            push_json_str(&mut line, "cache", cache_status);
+        }
+
+        if let Some(sequence) = entry.sequence {
+            let _ = write!(line, ",\"seq\":{}", sequence);
+        }
+
+        push_json_body(&mut line, "request_body", &entry.request_body, &field);
+        push_json_body(&mut line, "response_body", &entry.response_body, &field);
+
+        if let Some(ref headers) = entry.request_headers {
+            line.push_str(",\"request_headers\":{");
+
+            for (index, name) in headers.keys().enumerate() {
+                let values: Vec<_> = headers
+                    .get_all(name)
+                    .iter()
+                    .map(|value| String::from_utf8_lossy(value.as_bytes()))
+                    .collect();
+
+                if index > 0 {
+                    line.push(',');
+                }
+                push_json_str(&mut line, name.as_str(), &field(&values.join(", ")));
+            }
+
+            line.push('}');
+        }
+
+        for (name, value) in &entry.custom_fields {
+            if let Some(ref value) = *value {
+                line.push(',');
+                push_json_str(&mut line, name, &field(value));
+            }
+        }
+
+        line.push('}');
+
+        line
+    }
+}
+
+impl LogFormat for JsonFormat {
+    fn format(&self, entry: &LogEntry) -> String {
+        self.write(entry, None)
+    }
+
+    fn format_truncated(&self, entry: &LogEntry, max: usize) -> String {
+        self.write(entry, Some(max))
+    }
+}
+
+/// Appends an optional body field to a JSON object, using `null` when not captured.
+fn push_json_body(line: &mut String, name: &str, body: &BodyField, field: &dyn Fn(&str) -> String) {
+    match *body {
+        BodyField::Disabled => (),
+        BodyField::Skipped => {
+            let _ = write!(line, ",\"{}\":null", name);
+        }
+        BodyField::Captured(ref captured) => {
+            let _ = write!(line, ",\"{}\":{{", name);
+            push_json_str(line, "content", &field(&captured.content()));
+            let _ = write!(line, ",\"truncated\":{}", captured.truncated());
+            match captured.total() {
+                Some(total) => write!(line, ",\"total\":{}}}", total),
+                None => write!(line, ",\"total\":null}}"),
+            }
+            .unwrap();
+        }
+    }
+}
+
+/// Appends a `"name":"value"` pair, escaping the value as a JSON string.
+fn push_json_str(line: &mut String, name: &str, value: &str) {
+    line.push('"');
+    line.push_str(name);
+    line.push_str("\":\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(line, "\\u{:04x}", c as u32);
+            }
+            c => line.push(c),
+        }
+    }
+
+    line.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use regex::Regex;
+
+    use crate::middleware::logger::entry::test_entry;
+
+    fn entry() -> LogEntry {
+        LogEntry {
+            request_id: "a\"b".to_owned(),
+            client_addr: Some("127.0.0.1:10000".parse().unwrap()),
+            start_time: "2019-04-01T12:30:00Z".parse().unwrap(),
+            uri: "/path?q=1".parse().unwrap(),
+            length: Some(12),
+            ..test_entry()
+        }
+    }
+
+    #[test]
+    fn formats_common_log_format() {
+        assert_eq!(
+            CommonLogFormat::new().format(&entry()),
+            "127.0.0.1 - - [01/Apr/2019:12:30:00 +0000] \"GET /path?q=1 HTTP/1.1\" 200 12 - 250µs -"
+        );
+    }
+
+    #[test]
+    fn formats_client_ports() {
+        let format = CommonLogFormat::new().include_client_port(true);
+
+        let line = format.format(&entry());
+        assert!(line.starts_with("127.0.0.1:10000 - - ["));
+
+        let mut entry = entry();
+        entry.client_addr = Some("[::1]:54321".parse().unwrap());
+
+        let line = format.format(&entry);
+        assert!(line.starts_with("[::1]:54321 - - ["));
+    }
+
+    #[test]
+    fn formats_ipv6_addresses() {
+        use std::net::SocketAddrV6;
+
+        let compressed = CommonLogFormat::new();
+        let bracketed = compressed.clone().ipv6_format(Ipv6Format::Bracketed);
+        let expanded = compressed.clone().ipv6_format(Ipv6Format::Expanded);
+
+        let mapped = "[::ffff:1.2.3.4]:80".parse().unwrap();
+        assert_eq!(compressed.host(mapped), "::ffff:1.2.3.4");
+        assert_eq!(compressed.clone().unmap_ipv4(true).host(mapped), "1.2.3.4");
+        assert_eq!(
+            bracketed
+                .clone()
+                .unmap_ipv4(true)
+                .include_client_port(true)
+                .host(mapped),
+            "1.2.3.4:80"
+        );
+
+        let zoned = SocketAddr::V6(SocketAddrV6::new("fe80::1".parse().unwrap(), 80, 0, 2));
+        assert_eq!(compressed.host(zoned), "fe80::1%2");
+        assert_eq!(bracketed.host(zoned), "[fe80::1%2]");
+        assert_eq!(
+            expanded.host(zoned),
+            "fe80:0000:0000:0000:0000:0000:0000:0001%2"
+        );
+        assert_eq!(compressed.clone().unmap_ipv4(true).host(zoned), "fe80::1%2");
+
+        let unspecified = "[::]:80".parse().unwrap();
+        assert_eq!(compressed.host(unspecified), "::");
+        assert_eq!(bracketed.host(unspecified), "[::]");
+        assert_eq!(
+            expanded.host(unspecified),
+            "0000:0000:0000:0000:0000:0000:0000:0000"
+        );
+        assert_eq!(
+            expanded.clone().include_client_port(true).host(unspecified),
+            "[0000:0000:0000:0000:0000:0000:0000:0000]:80"
+        );
+    }
+
+    #[test]
+    fn formats_missing_peers() {
+        let mut entry = entry();
+        entry.client_addr = None;
+
+        let line = CommonLogFormat::new().format(&entry);
+        assert!(line.starts_with("- - - ["));
+
+        let line = CommonLogFormat::new()
+            .include_client_port(true)
+            .missing_peer(MissingPeer::Literal("unix:".to_owned()))
+            .format(&entry);
+        assert!(line.starts_with("unix: - - ["));
+
+        let line = JsonFormat::new().format(&entry);
+        assert!(line.contains(r#""ip":null,"client_port":null,"#));
+    }
+
+    #[test]
+    fn formats_route_templates() {
+        let mut entry = entry();
+
+        let template = CommonLogFormat::new().path_mode(PathMode::Template);
+        let both = CommonLogFormat::new().path_mode(PathMode::Both);
+
+        assert!(template
+            .format(&entry)
+            .contains("\"GET /path?q=1 HTTP/1.1\""));
+        assert!(both.format(&entry).ends_with(" 250µs - -"));
+
+        entry.route_template = Some("/path/:id".to_owned());
+
+        assert!(template
+            .format(&entry)
+            .contains("\"GET /path/:id HTTP/1.1\""));
+        assert!(CommonLogFormat::new()
+            .format(&entry)
+            .contains("\"GET /path?q=1 HTTP/1.1\""));
+
+        let line = both.format(&entry);
+        assert!(line.contains("\"GET /path?q=1 HTTP/1.1\""));
+        assert!(line.ends_with(" 250µs \"/path/:id\" -"));
+
+        assert!(JsonFormat::new()
+            .format(&entry)
+            .contains(r#""route":"/path/:id","#));
+    }
+
+    /// Reverses the escaping applied to quoted fields.
+    fn unescape(field: &str) -> String {
+        let mut bytes = Vec::new();
+        let mut rest = field.as_bytes();
+
+        while let Some((&b, tail)) = rest.split_first() {
+            rest = tail;
+            if b != b'\\' {
+                bytes.push(b);
+                continue;
+            }
+            match rest.split_first() {
+                Some((&b'x', tail)) => {
+                    let hex = std::str::from_utf8(&tail[..2]).unwrap();
+                    bytes.push(u8::from_str_radix(hex, 16).unwrap());
+                    rest = &tail[2..];
+                }
+                Some((&escaped, tail)) => {
+                    bytes.push(escaped);
+                    rest = tail;
+                }
+                None => panic!("dangling escape in {:?}", field),
+            }
+        }
+
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn escapes_quoted_fields() {
+        let clf = Regex::new(
+            r#"^(\S+) \S+ \S+ \[([^\]]+)\] "(\S+) ((?:[^"\\ ]|\\.)+) (\S+)" (\d{3}) (\S+) .* user_agent="((?:[^"\\]|\\.)*)"$"#,
+        )
+        .unwrap();
+
+        let mut entry = entry();
+        entry.uri = r#"/a\b\\c?q=\x22%22"#.parse().unwrap();
+        entry.user_agent = Some("curl/7.64 \"quoted\" \\ \n".to_owned());
+
+        let line = CommonLogFormat::new().format(&entry);
+        let fields = clf.captures(&line).expect("line should parse as CLF");
+
+        assert_eq!(&fields[1], "127.0.0.1");
+        assert_eq!(&fields[3], "GET");
+        assert_eq!(unescape(&fields[4]), entry.uri.to_string());
+        assert_eq!(&fields[5], "HTTP/1.1");
+        assert_eq!(&fields[6], "200");
+        assert_eq!(&fields[7], "12");
+        assert_eq!(Some(unescape(&fields[8])), entry.user_agent);
+    }
+
+    #[test]
+    fn formats_custom_fields() {
+        let mut entry = entry();
+        entry.custom_fields = vec![
+            ("tenant".to_owned(), Some("acme\ncorp".to_owned())),
+            ("shard".to_owned(), None),
+        ];
+
+        let line = CommonLogFormat::new().format(&entry);
+        assert!(line.ends_with(" 250µs - \"acme\\x0acorp\" -"));
+
+        let line = JsonFormat::new().format(&entry);
+        assert!(line.ends_with(r#""response_body":null,"tenant":"acme\ncorp"}"#));
+        assert!(!line.contains("shard"));
+    }
+
+    #[test]
+    fn omits_durations_below_threshold() {
+        let mut entry = entry();
+        let format = CommonLogFormat::new().duration_threshold(Duration::from_millis(100));
+
+        let line = format.format(&entry);
+        assert!(line.contains("\" 200 12 -"));
+        assert!(!line.contains("250µs"));
+
+        entry.duration = Duration::from_millis(100);
+        assert!(format.format(&entry).contains("\" 200 12 - 100.00ms"));
+
+        // structured formats always include the duration
+        let json = JsonFormat::new().format(&entry);
+        assert!(json.contains("\"duration_us\":100000,"));
+    }
+
+    #[test]
+    fn formats_durations() {
+        let mut entry = entry();
+        entry.duration = Duration::from_nanos(850);
+
+        let line =
+            |entry: &LogEntry, format| CommonLogFormat::new().duration_format(format).format(entry);
+
+        assert!(line(&entry, DurationFormat::Human).ends_with(" 850ns -"));
+        assert!(line(&entry, DurationFormat::Millis).ends_with(" 0.000 -"));
+        assert!(line(&entry, DurationFormat::Micros).ends_with(" 0 -"));
+        assert!(line(&entry, DurationFormat::Nanos).ends_with(" 850 -"));
+
+        let json = JsonFormat::new().format(&entry);
+        assert!(json.contains("\"duration_us\":0,\"duration_ns\":850,"));
+
+        entry.duration = Duration::from_nanos(2_001_520_400);
+        assert!(line(&entry, DurationFormat::Human).ends_with(" 2.00s -"));
+        assert!(line(&entry, DurationFormat::Millis).ends_with(" 2001.520 -"));
+        assert!(line(&entry, DurationFormat::Micros).ends_with(" 2001520 -"));
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        let mut entry = entry();
+        entry.start_time = "2024-05-01T12:34:56.789012345Z".parse().unwrap();
+
+        let rfc3339 = |precision| TimestampFormat::Rfc3339 { precision };
+
+        let line = CommonLogFormat::new()
+            .timestamp_format(rfc3339(SubsecondPrecision::Millis))
+            .format(&entry);
+        assert!(line.starts_with("127.0.0.1 - - [2024-05-01T12:34:56.789Z] \"GET"));
+
+        let line = JsonFormat::new()
+            .timestamp_format(rfc3339(SubsecondPrecision::Micros))
+            .format(&entry);
+        assert!(line.contains(r#""time":"2024-05-01T12:34:56.789012Z","#));
+
+        assert_eq!(
+            rfc3339(SubsecondPrecision::Nanos).format(&entry.start_time),
+            "2024-05-01T12:34:56.789012345Z"
+        );
+        assert_eq!(
+            TimestampFormat::Common.format(&entry.start_time),
+            "01/May/2024:12:34:56 +0000"
+        );
+    }
+
+    #[test]
+    fn truncates_long_lines() {
+        let mut entry = entry();
+        entry.uri = format!("/search?q={}", "%C3%A9".repeat(4000))
+            .parse()
+            .unwrap();
+
+        let full = CommonLogFormat::new().format(&entry);
+        let line = CommonLogFormat::new().format_truncated(&entry, 120);
+        let kept = line.find('…').unwrap();
+
+        assert!(line.len() <= 120);
+        assert!(line.starts_with(&full[..kept]));
+        assert!(line.ends_with(&format!("…[+{} bytes]", full.len() - kept)));
+
+        let line = JsonFormat::new().format_truncated(&entry, 40);
+        assert!(line.contains(r#""uri":"/search?q=%C3%A9"#));
+        assert!(line.contains(" bytes]\",\"route\":null,"));
+        assert!(line.ends_with(r#""duration_ns":250000,"response_body":null}"#));
+
+        let mut value = "αβγ".to_owned();
+        truncate(&mut value, 6);
+        assert_eq!(value, "αβγ");
+        truncate(&mut value, 5);
+        assert_eq!(value, "αβ");
+    }
+
+    #[test]
+    fn truncates_once_after_folding() {
+        struct Lines;
+
+        impl LogFormat for Lines {
+            fn format(&self, _: &LogEntry) -> String {
+                "x\n".repeat(10)
+            }
+        }
+
+        let line = Lines.format_truncated(&entry(), 20);
+        assert_eq!(line, "x\\nx\\n…[+24 bytes]");
+        assert_eq!(line.len(), 20);
+    }
+
+    #[test]
+    fn formats_json() {
+        assert_eq!(
+            JsonFormat::new().format(&entry()),
+            "{\"request_id\":\"a\\\"b\",\"ip\":\"127.0.0.1\",\"client_port\":10000,\"time\":\"2019-04-01T12:30:00+00:00\",\
+             \"method\":\"GET\",\"uri\":\"/path?q=1\",\"route\":null,\"version\":\"HTTP/1.1\",\
+             \"status\":200,\"bytes\":12,\"duration_us\":250,\"duration_ns\":250000,\"response_body\":null}"
+        );
+    }
+}
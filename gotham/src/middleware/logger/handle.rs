@@ -0,0 +1,161 @@
+//! Defines the `LoggerHandle`, used to flush the sinks of a `RequestLogger` before exiting.
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::sink::LogSink;
+
+// the default time allowed for flushing all sinks, 5s
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A handle to the sinks of a `RequestLogger`, as returned by `RequestLogger::handle`.
+///
+/// Sinks which buffer lines or write them from a background thread, such as the `FileSink`,
+/// can lose the lines of the last requests served when the process exits. The handle allows
+/// these to be flushed before exiting, either once `gotham::start` returns or from a signal
+/// handler. Dropping the handle makes a best-effort attempt to flush every sink.
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate log;
+/// # use gotham::middleware::logger::{CommonLogFormat, FileSink, RequestLogger};
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use log::Level;
+/// # fn main() -> std::io::Result<()> {
+/// let logger = RequestLogger::new(Level::Info)
+///     .output(CommonLogFormat::new(), FileSink::open("access.log")?);
+///
+/// // taken once the logger is configured
+/// let handle = logger.handle();
+///
+/// let (chain, pipelines) = single_pipeline(new_pipeline().add(logger).build());
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/").to(|state| (state, "Hello, world!"));
+/// });
+///
+/// gotham::start("127.0.0.1:7878", router);
+///
+/// // write out any lines still queued before exiting
+/// handle.shutdown()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// From a signal handler, call `flush` on a clone of the handle (or `shutdown`, if the
+/// process exits immediately after) before exiting the process.
+#[derive(Clone)]
+pub struct LoggerHandle {
+    sinks: Vec<Arc<dyn LogSink>>,
+    timeout: Duration,
+}
+
+impl LoggerHandle {
+    /// Creates a handle to the provided sinks.
+    pub(super) fn new(sinks: Vec<Arc<dyn LogSink>>) -> Self {
+        LoggerHandle {
+            sinks,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets the time allowed for flushing every sink, which is 5 seconds by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Blocks until every line written so far has reached its destination, or until the
+    /// timeout elapses.
+    ///
+    /// Every sink is flushed, even when an earlier sink fails; the first error is returned.
+    pub fn flush(&self) -> io::Result<()> {
+        self.each(|sink, timeout| sink.flush(timeout))
+    }
+
+    /// Flushes every sink as per `flush`, then stops any background threads they use, waiting
+    /// for them to exit.
+    ///
+    /// Lines written afterwards (such as by requests which are still in flight) may be lost.
+    pub fn shutdown(mut self) -> io::Result<()> {
+        let result = self.each(|sink, timeout| sink.shutdown(timeout));
+
+        // nothing left to flush on drop
+        self.sinks.clear();
+        result
+    }
+
+    /// Returns the total number of lines discarded by the sinks due to backpressure, as
+    /// configured via the `OverflowPolicy` of each `FileSink`.
+    pub fn dropped_lines(&self) -> u64 {
+        self.sinks.iter().map(|sink| sink.dropped_lines()).sum()
+    }
+
+    /// Applies an operation to each sink, sharing the timeout between them.
+    fn each<F>(&self, f: F) -> io::Result<()>
+    where
+        F: Fn(&dyn LogSink, Duration) -> io::Result<()>,
+    {
+        let deadline = Instant::now() + self.timeout;
+        let mut result = Ok(());
+
+        for sink in &self.sinks {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if let Err(e) = f(&**sink, remaining) {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl Drop for LoggerHandle {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use crate::middleware::logger::entry::test_entry;
+    use crate::middleware::logger::file::FileSink;
+
+    #[test]
+    fn writes_queued_lines_on_shutdown() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("access.log");
+
+        let sink = Arc::new(FileSink::open(&path).unwrap());
+        let handle = LoggerHandle::new(vec![sink.clone()]);
+
+        let entry = test_entry();
+        for i in 0..1000 {
+            sink.write(&entry, &format!("line {}", i)).unwrap();
+        }
+
+        handle.flush().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1000);
+
+        sink.write(&entry, "last").unwrap();
+        assert_eq!(handle.dropped_lines(), 0);
+        handle.shutdown().unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 1001);
+        assert!(contents.ends_with("line 999\nlast\n"));
+
+        // the writer has stopped
+        assert!(sink.write(&entry, "discarded").is_err());
+    }
+}
@@ -0,0 +1,22 @@
+//! Resolution of the server hostname, used by the `include_hostname` option.
+use std::env;
+use std::fs;
+
+// the placeholder used when the hostname can't be determined
+const UNKNOWN: &str = "-";
+
+/// Returns the hostname of the server, or `-` when it can't be determined.
+///
+/// This reads the hostname from the kernel or `/etc/hostname` on Unix systems, and from the
+/// environment elsewhere, so that no lookup needs to block on the network.
+pub(super) fn resolve() -> String {
+    let hostname = ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .chain(env::var("HOSTNAME").ok())
+        .chain(env::var("COMPUTERNAME").ok())
+        .map(|hostname| hostname.trim().to_owned())
+        .find(|hostname| !hostname.is_empty());
+
+    hostname.unwrap_or_else(|| UNKNOWN.to_owned())
+}
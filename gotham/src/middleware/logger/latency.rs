@@ -0,0 +1,166 @@
+//! Defines the `RouteLatencies` type, aggregating latencies per route into periodic lines.
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::summary::{bucket, percentile, Window, BUCKETS};
+
+// the next shard handed out to a thread recording latencies
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // the shard of the current thread, assigned on first use
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The latencies of a single route within a window.
+struct Accumulator {
+    requests: u64,
+    latencies: Vec<u64>,
+}
+
+impl Accumulator {
+    fn new() -> Self {
+        Accumulator {
+            requests: 0,
+            latencies: vec![0; BUCKETS],
+        }
+    }
+
+    fn merge(&mut self, other: &Accumulator) {
+        self.requests += other.requests;
+        for (bucket, count) in self.latencies.iter_mut().zip(&other.latencies) {
+            *bucket += count;
+        }
+    }
+}
+
+/// Latencies accumulated per route by a `RequestLogger` between lines.
+///
+/// Each worker thread records into a shard of its own, so the lock guarding a shard is only
+/// contended while a window is being flushed, or when there are more threads than shards. Like
+/// the `Summary`, the request which completes after the interval has elapsed merges the shards
+/// and writes a line per route, so no background thread is required.
+pub(super) struct RouteLatencies {
+    window: Window,
+    shards: Vec<Mutex<HashMap<String, Accumulator>>>,
+}
+
+impl RouteLatencies {
+    /// Creates a new `RouteLatencies`, writing lines every `interval`.
+    pub(super) fn new(interval: Duration) -> Self {
+        RouteLatencies {
+            window: Window::new(interval),
+            shards: (0..num_cpus::get().max(1))
+                .map(|_| Mutex::new(HashMap::new()))
+                .collect(),
+        }
+    }
+
+    /// Records a completed request against a route, returning a line per route if the window
+    /// has elapsed.
+    pub(super) fn record(&self, route: String, duration_us: u64) -> Option<Vec<String>> {
+        let shard = SHARD.with(|shard| *shard) % self.shards.len();
+
+        {
+            // a poisoned shard only means another request panicked mid-record
+            let mut routes = self.shards[shard].lock().unwrap_or_else(|e| e.into_inner());
+
+            let accumulator = routes.entry(route).or_insert_with(Accumulator::new);
+            accumulator.requests += 1;
+            accumulator.latencies[bucket(duration_us)] += 1;
+        }
+
+        if self.window.advance() {
+            Some(self.flush())
+        } else {
+            None
+        }
+    }
+
+    /// Resets the window, returning the lines describing it, ordered by route.
+    ///
+    /// Routes without any requests during the window are omitted.
+    fn flush(&self) -> Vec<String> {
+        let mut merged: BTreeMap<String, Accumulator> = BTreeMap::new();
+
+        for shard in &self.shards {
+            let routes = mem::take(&mut *shard.lock().unwrap_or_else(|e| e.into_inner()));
+
+            for (route, accumulator) in routes {
+                match merged.get_mut(&route) {
+                    Some(existing) => existing.merge(&accumulator),
+                    None => {
+                        merged.insert(route, accumulator);
+                    }
+                }
+            }
+        }
+
+        merged
+            .into_iter()
+            .map(|(route, accumulator)| {
+                format!(
+                    "{}: {} requests, p50 {}, p90 {}, p99 {}",
+                    route,
+                    accumulator.requests,
+                    percentile(&accumulator.latencies, 50),
+                    percentile(&accumulator.latencies, 90),
+                    percentile(&accumulator.latencies, 99),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn aggregates_routes_across_threads() {
+        let latencies = Arc::new(RouteLatencies::new(Duration::from_secs(60)));
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let latencies = latencies.clone();
+                thread::spawn(move || {
+                    for i in 0..25 {
+                        let us = if i == 0 { 84_000 } else { 2_000 };
+                        assert!(latencies.record("GET /users/:id".to_owned(), us).is_none());
+                    }
+                    latencies.record("POST /users".to_owned(), 3_000);
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(
+            latencies.flush(),
+            vec![
+                "GET /users/:id: 100 requests, p50 2ms, p90 2ms, p99 90ms",
+                "POST /users: 4 requests, p50 3ms, p90 3ms, p99 3ms",
+            ]
+        );
+        assert!(latencies.flush().is_empty());
+    }
+
+    #[test]
+    fn writes_once_the_interval_elapses() {
+        let latencies = RouteLatencies::new(Duration::from_millis(0));
+        assert_eq!(
+            latencies.record("GET -".to_owned(), 500),
+            Some(vec![
+                "GET -: 1 requests, p50 511µs, p90 511µs, p99 511µs".to_owned()
+            ])
+        );
+    }
+}
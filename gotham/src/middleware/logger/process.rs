@@ -0,0 +1,54 @@
+//! Identification of the process and thread writing an access line, used by the `include_pid`
+//! and `include_thread` options.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+// the next number handed out to an unnamed thread
+static NEXT_THREAD: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+    // the identifier of the current thread, resolved on first use
+    static THREAD: String = {
+        match thread::current().name() {
+            Some(name) => name.to_owned(),
+            None => NEXT_THREAD.fetch_add(1, Ordering::Relaxed).to_string(),
+        }
+    };
+}
+
+/// Returns the ID of the current process.
+pub(super) fn pid() -> u32 {
+    std::process::id()
+}
+
+/// Returns a stable identifier for the current thread.
+///
+/// This is the name of the thread when it has one, or otherwise a small number assigned the
+/// first time the thread asks, as the numeric form of a `ThreadId` isn't available on stable.
+pub(super) fn thread() -> String {
+    THREAD.with(Clone::clone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifies_threads() {
+        let named = thread::Builder::new()
+            .name("worker-1".to_owned())
+            .spawn(thread)
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(named, "worker-1");
+
+        let first = thread::spawn(|| (thread(), thread())).join().unwrap();
+        let second = thread::spawn(thread).join().unwrap();
+
+        assert_eq!(first.0, first.1);
+        assert_ne!(first.0, second);
+        assert!(second.parse::<usize>().is_ok());
+    }
+}
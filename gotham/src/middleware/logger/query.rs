@@ -0,0 +1,100 @@
+//! Filtering of the query string written to access lines, used by the `query_param_whitelist`
+//! option.
+use hyper::http::uri::{Parts, PathAndQuery};
+use hyper::Uri;
+
+use crate::helpers::http::FormUrlDecoded;
+
+/// The query parameters which may be written to access lines, by their decoded names.
+#[derive(Clone, Debug)]
+pub(super) struct QueryWhitelist {
+    keys: Vec<String>,
+}
+
+impl QueryWhitelist {
+    /// Creates a new `QueryWhitelist` allowing the provided parameter names.
+    pub(super) fn new(keys: &[&str]) -> Self {
+        QueryWhitelist {
+            keys: keys.iter().map(|key| (*key).to_owned()).collect(),
+        }
+    }
+
+    /// Returns a copy of `uri` where the query only contains allowed parameters.
+    ///
+    /// Allowed parameters are kept exactly as sent, in their original order and including
+    /// repeats, and are joined using `&`. The query is removed entirely when none are allowed.
+    pub(super) fn filter(&self, uri: &Uri) -> Uri {
+        let query = match uri.query() {
+            Some(query) => query,
+            None => return uri.clone(),
+        };
+
+        let kept: Vec<&str> = query
+            .split(&['&', ';'][..])
+            .filter(|pair| !pair.is_empty() && self.allows(pair))
+            .collect();
+
+        let path_and_query = if kept.is_empty() {
+            uri.path().to_owned()
+        } else {
+            format!("{}?{}", uri.path(), kept.join("&"))
+        };
+
+        let mut parts = Parts::from(uri.clone());
+        parts.path_and_query = path_and_query.parse::<PathAndQuery>().ok();
+
+        Uri::from_parts(parts).unwrap_or_else(|_| uri.clone())
+    }
+
+    /// Determines whether a raw `key=value` pair (or bare `key`) is allowed.
+    fn allows(&self, pair: &str) -> bool {
+        let key = pair.split('=').next().unwrap_or("");
+
+        match FormUrlDecoded::new(key) {
+            Some(key) => self.keys.iter().any(|allowed| allowed == key.as_ref()),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(uri: &str) -> String {
+        QueryWhitelist::new(&["page", "sort", "sort order"])
+            .filter(&uri.parse().unwrap())
+            .to_string()
+    }
+
+    #[test]
+    fn keeps_whitelisted_parameters() {
+        assert_eq!(filter("/items"), "/items");
+        assert_eq!(filter("/items?token=abc"), "/items");
+        assert_eq!(
+            filter("/items?page=2&token=abc&sort=name"),
+            "/items?page=2&sort=name"
+        );
+
+        // order and repeated keys are preserved
+        assert_eq!(
+            filter("/items?sort=a;page=1&sort=b"),
+            "/items?sort=a&page=1&sort=b"
+        );
+
+        // keys without values, and empty pairs
+        assert_eq!(filter("/items?page&&debug&sort="), "/items?page&sort=");
+
+        // keys are compared once decoded, but written as sent
+        assert_eq!(
+            filter("/items?%70age=3&sort+order=asc"),
+            "/items?%70age=3&sort+order=asc"
+        );
+        assert_eq!(filter("/items?pag%65%ZZ=1"), "/items");
+
+        assert_eq!(
+            filter("http://example.com/items?page=2&secret=x"),
+            "http://example.com/items?page=2"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Defines the `RouteTemplate` type, used to log the matched route rather than the raw path.
+use std::io;
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{State, StateData};
+
+/// The template of the route which served a request, such as `/users/:id`.
+///
+/// The `RequestLogger` can't determine which route matched a request, so this value is placed
+/// into `State` by the application; either by a `RouteTemplateMiddleware` in a pipeline for the
+/// route, or directly by a handler. When present, it's logged according to the configured
+/// `PathMode`.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use gotham::middleware::logger::RouteTemplate;
+/// # use gotham::state::State;
+/// # use hyper::{Body, Response};
+/// #
+/// fn show_user(mut state: State) -> (State, Response<Body>) {
+///     state.put(RouteTemplate::new("/users/:id"));
+///     // ...
+/// #   (state, Response::new(Body::empty()))
+/// }
+/// # fn main() { let _ = show_user; }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RouteTemplate(pub String);
+
+impl RouteTemplate {
+    /// Creates a new `RouteTemplate` from the provided template.
+    pub fn new<S: Into<String>>(template: S) -> Self {
+        RouteTemplate(template.into())
+    }
+
+    /// Returns the template as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl StateData for RouteTemplate {}
+
+/// Middleware binding to attach a fixed `RouteTemplate` to each request.
+///
+/// This is intended to be added to a pipeline used by a single route (or scope), so that the
+/// template is known to the `RequestLogger` without changing the handler.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::logger::RouteTemplateMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// let pipeline = new_pipeline()
+///     .add(RouteTemplateMiddleware::new("/users/:id"))
+///     .build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone, Debug)]
+pub struct RouteTemplateMiddleware {
+    template: RouteTemplate,
+}
+
+impl RouteTemplateMiddleware {
+    /// Creates a new `RouteTemplateMiddleware` attaching the provided template.
+    pub fn new<S: Into<String>>(template: S) -> Self {
+        RouteTemplateMiddleware {
+            template: RouteTemplate::new(template),
+        }
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for RouteTemplateMiddleware {
+    /// Attaches the template to the request `State`.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        state.put(self.template);
+        chain(state)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for RouteTemplateMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{future, Future};
+    use hyper::{Body, Response};
+
+    #[test]
+    fn attaches_route_templates() {
+        let middleware = RouteTemplateMiddleware::new("/users/:id");
+
+        let (state, _) = middleware
+            .call(State::new(), |state| {
+                Box::new(future::ok((state, Response::new(Body::empty()))))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap();
+
+        assert_eq!(state.borrow::<RouteTemplate>().as_str(), "/users/:id");
+    }
+}
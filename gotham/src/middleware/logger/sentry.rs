@@ -0,0 +1,126 @@
+//! Reporting of failed requests to Sentry, used by the `report_errors_to_sentry` option.
+//!
+//! Each report is captured on a new `Hub` derived from the current one, so the breadcrumb and
+//! context describing a request are never seen by the events of concurrent requests.
+use std::time::Duration;
+
+use hyper::{Method, StatusCode, Uri};
+use sentry_core::protocol::{Breadcrumb, Context, Level, Map, Value};
+use sentry_core::Hub;
+
+use crate::handler::HandlerError;
+
+/// Captures an event for a failed request, carrying a summary of the request as both a
+/// breadcrumb and a `request` context.
+pub(super) fn report(
+    request_id: &str,
+    method: &Method,
+    uri: &Uri,
+    status: StatusCode,
+    duration: Duration,
+    error: Option<&HandlerError>,
+) {
+    let mut data = Map::new();
+    data.insert("method".to_owned(), Value::from(method.as_str()));
+    data.insert("path".to_owned(), Value::from(uri.path()));
+    data.insert("status".to_owned(), Value::from(status.as_u16()));
+    data.insert(
+        "duration_us".to_owned(),
+        Value::from(duration.as_micros() as u64),
+    );
+    data.insert("request_id".to_owned(), Value::from(request_id));
+
+    let summary = format!("{} {} {}", method, uri.path(), status.as_u16());
+    let hub = Hub::new_from_top(Hub::current());
+
+    hub.configure_scope(|scope| {
+        scope.set_tag("request_id", request_id);
+        scope.set_context("request", Context::Other(data.clone()));
+    });
+
+    hub.add_breadcrumb(Breadcrumb {
+        ty: "http".to_owned(),
+        category: Some("request".to_owned()),
+        level: Level::Error,
+        message: Some(summary.clone()),
+        data,
+        ..Default::default()
+    });
+
+    match error {
+        Some(error) => hub.capture_error(error),
+        None => hub.capture_message(&summary, Level::Error),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use sentry_core::protocol::Event;
+    use sentry_core::{ClientOptions, Envelope, Transport};
+
+    #[derive(Default)]
+    struct Recording(Mutex<Vec<Envelope>>);
+
+    impl Transport for Recording {
+        fn send_envelope(&self, envelope: Envelope) {
+            self.0.lock().unwrap().push(envelope);
+        }
+    }
+
+    fn captured<F: FnOnce()>(f: F) -> Vec<Event<'static>> {
+        let transport = Arc::new(Recording::default());
+        let options = ClientOptions {
+            dsn: Some("https://public@sentry.invalid/1".parse().unwrap()),
+            transport: Some(Arc::new(transport.clone())),
+            ..Default::default()
+        };
+
+        let hub = Hub::new(Some(Arc::new(options.into())), Default::default());
+        Hub::run(Arc::new(hub), f);
+
+        let envelopes = transport.0.lock().unwrap();
+        envelopes
+            .iter()
+            .filter_map(|envelope| envelope.event().cloned())
+            .collect()
+    }
+
+    #[test]
+    fn reports_request_summary() {
+        let events = captured(|| {
+            let uri = "/users/1?expand=true".parse().unwrap();
+            let duration = Duration::from_micros(1500);
+
+            report(
+                "abc",
+                &Method::GET,
+                &uri,
+                StatusCode::BAD_GATEWAY,
+                duration,
+                None,
+            );
+            report("def", &Method::POST, &uri, StatusCode::OK, duration, None);
+        });
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].message.as_deref(), Some("GET /users/1 502"));
+        assert_eq!(events[0].tags["request_id"], "abc");
+        assert_eq!(events[0].breadcrumbs.len(), 1);
+
+        match events[0].contexts["request"] {
+            Context::Other(ref data) => {
+                assert_eq!(data["status"], 502);
+                assert_eq!(data["duration_us"], 1500);
+            }
+            ref context => panic!("unexpected context: {:?}", context),
+        }
+
+        // each request is reported on its own hub
+        assert_eq!(events[1].tags["request_id"], "def");
+        assert_eq!(events[1].breadcrumbs.len(), 1);
+    }
+}
@@ -0,0 +1,256 @@
+//! Defines the `LogSink` trait, and the default sink writing to the `log` crate.
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::time::Duration;
+
+#[cfg(not(feature = "tracing"))]
+use log::log;
+
+#[cfg(feature = "kv")]
+use log::kv::{self, Key, Source, Value, VisitSource};
+
+use super::entry::LogEntry;
+
+/// A destination for formatted access log lines.
+///
+/// Sinks receive both the formatted line and the `LogEntry` it was produced from, so that
+/// structured destinations can make use of the raw values. An error returned from one sink is
+/// reported, but doesn't prevent the remaining sinks from receiving the entry.
+pub trait LogSink: Send + Sync + RefUnwindSafe {
+    /// Writes a formatted line to the sink.
+    fn write(&self, entry: &LogEntry, line: &str) -> io::Result<()>;
+
+    /// Blocks until every line previously written to the sink has reached its destination, or
+    /// until `timeout` elapses, in which case an error of kind `TimedOut` is returned.
+    ///
+    /// Sinks which buffer lines, or write them from a background thread, should implement this
+    /// so that lines aren't lost when the process exits. The default does nothing.
+    fn flush(&self, timeout: Duration) -> io::Result<()> {
+        let _ = timeout;
+        Ok(())
+    }
+
+    /// Flushes the sink as per `flush`, then stops any background work. Lines written to the
+    /// sink afterwards may be discarded.
+    ///
+    /// The default only flushes the sink.
+    fn shutdown(&self, timeout: Duration) -> io::Result<()> {
+        self.flush(timeout)
+    }
+
+    /// Returns the number of lines discarded by the sink due to backpressure.
+    ///
+    /// The default returns zero, for sinks which never discard lines.
+    fn dropped_lines(&self) -> u64 {
+        0
+    }
+}
+
+/// A `LogSink` which writes through the `log` crate, at the level and target of the entry.
+///
+/// This is the sink used by a `RequestLogger` which has no outputs configured.
+///
+/// With the `tracing` feature enabled, lines are instead emitted as `tracing` events at the level
+/// of the entry, carrying the `request_id`, `method`, `path`, `status` and `duration_us` fields.
+/// Lines written with `key_values` still go through the `log` crate.
+#[derive(Clone, Debug, Default)]
+pub struct LogFacade {
+    #[cfg(feature = "kv")]
+    key_values: Option<KeyValueMode>,
+}
+
+impl LogFacade {
+    /// Creates a new `LogFacade` sink.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attaches request fields to each log record as typed key-value pairs.
+    ///
+    /// Backends which understand the `log` crate's key-value API receive real fields
+    /// without having to parse the access line; other backends will simply ignore them.
+    ///
+    /// This requires the `kv` feature.
+    #[cfg(feature = "kv")]
+    pub fn key_values(mut self, mode: KeyValueMode) -> Self {
+        self.key_values = Some(mode);
+        self
+    }
+}
+
+impl LogSink for LogFacade {
+    fn write(&self, entry: &LogEntry, line: &str) -> io::Result<()> {
+        // attach structured fields when enabled
+        #[cfg(feature = "kv")]
+        {
+            if let Some(mode) = self.key_values {
+                let message = match mode {
+                    KeyValueMode::WithMessage => line,
+                    KeyValueMode::Only => "request completed",
+                };
+
+                // the macros only accept a fixed set of keys, so build the record directly
+                if entry.level <= log::STATIC_MAX_LEVEL && entry.level <= log::max_level() {
+                    log::logger().log(
+                        &log::Record::builder()
+                            .args(format_args!("{}", message))
+                            .level(entry.level)
+                            .target(entry.target.as_deref().unwrap_or(module_path!()))
+                            .module_path_static(Some(module_path!()))
+                            .file_static(Some(file!()))
+                            .line(Some(line!()))
+                            .key_values(&EntrySource::new(entry))
+                            .build(),
+                    );
+                }
+                return Ok(());
+            }
+        }
+
+        // write structured events through `tracing` instead, when enabled
+        #[cfg(feature = "tracing")]
+        super::trace::entry(entry, line);
+
+        #[cfg(not(feature = "tracing"))]
+        match entry.target {
+            Some(ref target) => log!(target: target, entry.level, "{}", line),
+            None => log!(entry.level, "{}", line),
+        }
+
+        Ok(())
+    }
+
+    fn flush(&self, _timeout: Duration) -> io::Result<()> {
+        log::logger().flush();
+        Ok(())
+    }
+}
+
+/// Exposes the fields of a `LogEntry` as key-value pairs.
+#[cfg(feature = "kv")]
+struct EntrySource<'a> {
+    entry: &'a LogEntry,
+    ip: Option<String>,
+}
+
+#[cfg(feature = "kv")]
+impl<'a> EntrySource<'a> {
+    fn new(entry: &'a LogEntry) -> Self {
+        EntrySource {
+            entry,
+            ip: entry.client_addr.map(|addr| addr.ip().to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "kv")]
+impl Source for EntrySource<'_> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+        let entry = self.entry;
+
+        let pairs = vec![
+            ("ip", optional(self.ip.as_deref())),
+            (
+                "client_port",
+                optional(entry.client_addr.map(|addr| addr.port())),
+            ),
+            ("method", Value::from(entry.method.as_str())),
+            ("path", Value::from(entry.uri.path())),
+            ("route", optional(entry.route_template.as_deref())),
+            ("status", Value::from(entry.status.as_u16())),
+            ("bytes", optional(entry.length)),
+            (
+                "duration_us",
+                Value::from(entry.duration.as_micros() as u64),
+            ),
+            ("duration_ns", Value::from(entry.duration.as_nanos() as u64)),
+        ];
+
+        for (key, value) in pairs {
+            visitor.visit_pair(Key::from(key), value)?;
+        }
+
+        if let Some(ref hostname) = entry.hostname {
+            visitor.visit_pair(Key::from("hostname"), Value::from(hostname.as_str()))?;
+        }
+
+        if let Some(pid) = entry.pid {
+            visitor.visit_pair(Key::from("pid"), Value::from(pid))?;
+        }
+
+        if let Some(ref thread) = entry.thread {
+            visitor.visit_pair(Key::from("thread"), Value::from(thread.as_str()))?;
+        }
+
+        if let Some(ref names) = entry.cookie_names {
+            visitor.visit_pair(Key::from("cookies"), Value::from(names.as_str()))?;
+        }
+
+        if let Some(ref names) = entry.set_cookie_names {
+            visitor.visit_pair(Key::from("set_cookies"), Value::from(names.as_str()))?;
+        }
+
+        if let Some(ref referer) = entry.referer {
+            visitor.visit_pair(Key::from("referer"), Value::from(referer.as_str()))?;
+        }
+
+        if let Some(ref user_agent) = entry.user_agent {
+            visitor.visit_pair(Key::from("user_agent"), Value::from(user_agent.as_str()))?;
+        }
+
+        if let Some(ref family) = entry.ua_family {
+            visitor.visit_pair(Key::from("ua_family"), Value::from(family.as_str()))?;
+        }
+
+        if let Some(ref cache_status) = entry.cache_status {
+            visitor.visit_pair(Key::from("cache"), Value::from(cache_status.as_str()))?;
+        }
+
+        if let Some(sequence) = entry.sequence {
+            visitor.visit_pair(Key::from("seq"), Value::from(sequence))?;
+        }
+
+        for (name, value) in &entry.custom_fields {
+            if let Some(ref value) = *value {
+                visitor.visit_pair(Key::from(name.as_str()), Value::from(value.as_str()))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Converts an optional value, using a null value when missing.
+#[cfg(feature = "kv")]
+fn optional<'v, T: Into<Value<'v>>>(value: Option<T>) -> Value<'v> {
+    value.map_or_else(Value::null, Into::into)
+}
+
+/// Controls how request fields are attached as key-value pairs on log records.
+///
+/// The attached keys are `ip`, `client_port`, `method`, `path`, `route`, `status`, `bytes`,
+/// `duration_us` and `duration_ns`, where `route` is only known when a `RouteTemplate` was
+/// provided. These are followed by any keys enabled on the `RequestLogger`:
+///
+/// * `hostname`, via `RequestLogger::include_hostname`;
+/// * `pid`, via `RequestLogger::include_pid`;
+/// * `thread`, via `RequestLogger::include_thread`;
+/// * `cookies`, via `RequestLogger::log_cookie_names`;
+/// * `set_cookies`, via `RequestLogger::log_set_cookie_names`;
+/// * `referer`, via `RequestLogger::log_referer`;
+/// * `user_agent`, via `RequestLogger::log_user_agent`;
+/// * `ua_family`, via `RequestLogger::log_user_agent_family`;
+/// * `cache`, via `RequestLogger::cache_status_header`;
+/// * `seq`, via `RequestLogger::include_sequence`.
+///
+/// Custom fields added via `RequestLogger::add_field` follow under their own names when they have
+/// a value.
+#[cfg(feature = "kv")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyValueMode {
+    /// Attaches the key-value pairs alongside the usual formatted access line.
+    WithMessage,
+
+    /// Attaches only the key-value pairs, using a short fixed message.
+    Only,
+}
@@ -0,0 +1,267 @@
+//! Defines the `Summary` type, aggregating traffic into periodic summary lines, along with the
+//! windows and latency histograms it shares with `RouteLatencies` and the
+//! `DurationHistogramMiddleware`.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use hyper::StatusCode;
+
+// latencies are bucketed by power of two, each split into 8 linear sub-buckets
+const SUB_BUCKET_BITS: u32 = 3;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+pub(crate) const BUCKETS: usize = 512;
+
+/// Traffic accumulated by a `RequestLogger` between summary lines.
+///
+/// Every counter is atomic, so recording a request is lock-free and takes constant time. The
+/// request which completes after the interval has elapsed flushes the window, so no background
+/// thread is required; a window without any requests is simply never written.
+pub(super) struct Summary {
+    window: Window,
+    requests: AtomicU64,
+    client_errors: AtomicU64,
+    server_errors: AtomicU64,
+    bytes: AtomicU64,
+    latencies: Vec<AtomicU64>,
+}
+
+impl Summary {
+    /// Creates a new `Summary`, writing a line every `interval`.
+    pub(super) fn new(interval: Duration) -> Self {
+        Summary {
+            window: Window::new(interval),
+            requests: AtomicU64::new(0),
+            client_errors: AtomicU64::new(0),
+            server_errors: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            latencies: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Records a completed request, returning a summary line if the window has elapsed.
+    pub(super) fn record(
+        &self,
+        status: StatusCode,
+        bytes: Option<u64>,
+        duration_us: Option<u64>,
+    ) -> Option<String> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+
+        if status.is_client_error() {
+            self.client_errors.fetch_add(1, Ordering::Relaxed);
+        } else if status.is_server_error() {
+            self.server_errors.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if let Some(bytes) = bytes {
+            self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+
+        if let Some(us) = duration_us {
+            self.latencies[bucket(us)].fetch_add(1, Ordering::Relaxed);
+        }
+
+        if self.window.advance() {
+            Some(self.flush())
+        } else {
+            None
+        }
+    }
+
+    /// Resets the window, returning the summary line describing it.
+    ///
+    /// Requests recorded concurrently with a flush may be counted towards either window.
+    fn flush(&self) -> String {
+        let requests = self.requests.swap(0, Ordering::Relaxed);
+        let client_errors = self.client_errors.swap(0, Ordering::Relaxed);
+        let server_errors = self.server_errors.swap(0, Ordering::Relaxed);
+        let bytes = self.bytes.swap(0, Ordering::Relaxed);
+
+        let latencies: Vec<u64> = self
+            .latencies
+            .iter()
+            .map(|bucket| bucket.swap(0, Ordering::Relaxed))
+            .collect();
+
+        format!(
+            "{} requests, {} 4xx, {} 5xx, p50 {}, p99 {}, {} sent",
+            requests,
+            client_errors,
+            server_errors,
+            percentile(&latencies, 50),
+            percentile(&latencies, 99),
+            format_bytes(bytes),
+        )
+    }
+}
+
+/// A window of time which is closed by the first request completing after it has elapsed.
+pub(super) struct Window {
+    interval: Duration,
+    created: Instant,
+    // the start of the current window, in milliseconds since `created`
+    start: AtomicU64,
+}
+
+impl Window {
+    /// Creates a new `Window` lasting `interval`, starting now.
+    pub(super) fn new(interval: Duration) -> Self {
+        Window {
+            interval,
+            created: Instant::now(),
+            start: AtomicU64::new(0),
+        }
+    }
+
+    /// Starts a new window if the current one has elapsed, returning whether it did.
+    ///
+    /// Only one of the requests racing to close a window sees `true`, so only that request
+    /// writes the lines describing it.
+    pub(super) fn advance(&self) -> bool {
+        let now = self.created.elapsed().as_millis() as u64;
+        let start = self.start.load(Ordering::Relaxed);
+
+        if now.saturating_sub(start) < self.interval.as_millis() as u64 {
+            return false;
+        }
+
+        self.start
+            .compare_exchange(start, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+}
+
+/// Returns the histogram bucket for a latency, in microseconds.
+pub(crate) fn bucket(us: u64) -> usize {
+    if us < SUB_BUCKETS {
+        return us as usize;
+    }
+
+    let octave = u64::from(63 - us.leading_zeros());
+    let sub = (us >> (octave - u64::from(SUB_BUCKET_BITS))) & (SUB_BUCKETS - 1);
+    let index = (octave - u64::from(SUB_BUCKET_BITS) + 1) * SUB_BUCKETS + sub;
+
+    (index as usize).min(BUCKETS - 1)
+}
+
+/// Returns the largest latency, in microseconds, which falls within a histogram bucket.
+fn upper_bound(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+
+    let shift = index / SUB_BUCKETS - 1;
+    let sub = index % SUB_BUCKETS;
+
+    // the final bucket ends at `u64::MAX`, where the shift overflows to zero
+    ((SUB_BUCKETS + sub + 1) << shift).wrapping_sub(1)
+}
+
+/// Formats the latency at the provided percentile, or `-` when nothing was recorded.
+pub(super) fn percentile(latencies: &[u64], percentile: u64) -> String {
+    match quantile(latencies, percentile as f64) {
+        Some(us) => format_latency(us),
+        None => "-".to_owned(),
+    }
+}
+
+/// Returns the latency at the provided percentile, such as `99.9`, as the upper bound of the
+/// bucket it falls into, or `None` when nothing was recorded.
+pub(crate) fn quantile(latencies: &[u64], percentile: f64) -> Option<u64> {
+    let total: u64 = latencies.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    // the rank of the request at the percentile, rounding up
+    let rank = ((total as f64) * percentile / 100.0).ceil().max(1.0) as u64;
+    let mut seen = 0;
+
+    for (index, count) in latencies.iter().enumerate() {
+        seen += count;
+        if seen >= rank.min(total) {
+            return Some(upper_bound(index));
+        }
+    }
+
+    unreachable!("rank is never larger than the total")
+}
+
+/// Formats a latency in microseconds using the most readable unit.
+fn format_latency(us: u64) -> String {
+    match us {
+        0..=999 => format!("{}µs", us),
+        1_000..=999_999 => format!("{}ms", us / 1_000),
+        _ => format!("{:.1}s", us as f64 / 1_000_000.0),
+    }
+}
+
+/// Formats a byte count using binary units.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_latencies() {
+        for us in (0..10_000).chain(vec![1 << 40, u64::MAX]) {
+            let index = bucket(us);
+            assert!(us <= upper_bound(index) || index == BUCKETS - 1);
+            assert!(index == 0 || us > upper_bound(index - 1));
+        }
+
+        assert_eq!(upper_bound(bucket(84_000)), 90_111);
+    }
+
+    #[test]
+    fn summarizes_windows() {
+        let summary = Summary::new(Duration::from_secs(60));
+
+        for _ in 0..97 {
+            assert!(summary
+                .record(StatusCode::OK, Some(1024), Some(2_000))
+                .is_none());
+        }
+
+        summary.record(StatusCode::NOT_FOUND, Some(0), Some(3_000));
+        summary.record(StatusCode::BAD_GATEWAY, None, Some(84_000));
+        summary.record(StatusCode::INTERNAL_SERVER_ERROR, Some(1_258_291), None);
+
+        assert_eq!(
+            summary.flush(),
+            "100 requests, 1 4xx, 2 5xx, p50 2ms, p99 90ms, 1.3 MiB sent"
+        );
+        assert_eq!(
+            summary.flush(),
+            "0 requests, 0 4xx, 0 5xx, p50 -, p99 -, 0 B sent"
+        );
+    }
+
+    #[test]
+    fn writes_once_the_interval_elapses() {
+        let summary = Summary::new(Duration::from_millis(0));
+        let line = summary.record(StatusCode::OK, Some(10), Some(500));
+        assert_eq!(
+            line.as_deref(),
+            Some("1 requests, 0 4xx, 0 5xx, p50 511µs, p99 511µs, 10 B sent")
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Writes through the `tracing` crate in place of the `log` crate, when the `tracing` feature is
+//! enabled.
+use ::tracing::{info_span, Level, Span};
+use hyper::{Method, Uri};
+
+use super::entry::LogEntry;
+use crate::state::{request_id, FromState, State};
+
+/// Expands a `tracing` macro at the equivalent of a `log::Level`, as `tracing` only accepts
+/// levels known at compile time.
+macro_rules! at_level {
+    ($level:expr, $mac:ident!($($args:tt)*)) => {
+        match $level {
+            log::Level::Error => ::tracing::$mac!(Level::ERROR, $($args)*),
+            log::Level::Warn => ::tracing::$mac!(Level::WARN, $($args)*),
+            log::Level::Info => ::tracing::$mac!(Level::INFO, $($args)*),
+            log::Level::Debug => ::tracing::$mac!(Level::DEBUG, $($args)*),
+            log::Level::Trace => ::tracing::$mac!(Level::TRACE, $($args)*),
+        }
+    };
+}
+
+/// Creates the span wrapping a request, which any spans and events of the handler nest under.
+pub(super) fn request_span(state: &State) -> Span {
+    info_span!(
+        "request",
+        method = %Method::borrow_from(state),
+        path = %Uri::borrow_from(state).path(),
+        request_id = %request_id(state),
+    )
+}
+
+/// Determines whether a `tracing` subscriber is interested in events at the provided level.
+pub(super) fn enabled(level: log::Level) -> bool {
+    at_level!(level, enabled!())
+}
+
+/// Emits an access line as an event, carrying the request fields as structured values.
+///
+/// The target of the entry isn't applied, as `tracing` targets must be known at compile time.
+pub(super) fn entry(entry: &LogEntry, line: &str) {
+    at_level!(
+        entry.level,
+        event!(
+            request_id = %entry.request_id,
+            method = %entry.method,
+            path = %entry.uri.path(),
+            status = entry.status.as_u16(),
+            duration_us = entry.duration.as_micros() as u64,
+            "{}",
+            line
+        )
+    )
+}
+
+/// Emits a line which isn't tied to a single request, such as a summary.
+pub(super) fn line(level: log::Level, line: &str) {
+    at_level!(level, event!("{}", line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    use ::tracing::field::{Field, Visit};
+    use ::tracing::span::{Attributes, Id, Record};
+    use ::tracing::{Event, Metadata, Subscriber};
+    use futures::{future, Future};
+    use hyper::{Body, HeaderMap, Response, StatusCode};
+
+    use crate::middleware::logger::RequestLogger;
+    use crate::middleware::Middleware;
+
+    // collects spans and events as `name: field=value ...` strings, noting whether each event
+    // was emitted within a span
+    #[derive(Clone, Default)]
+    struct Collector {
+        lines: Arc<Mutex<Vec<String>>>,
+        depth: Arc<Mutex<usize>>,
+    }
+
+    struct Fields(String);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+
+    impl Subscriber for Collector {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            *metadata.level() <= Level::INFO
+        }
+
+        fn new_span(&self, span: &Attributes) -> Id {
+            let mut fields = Fields(format!("span {}:", span.metadata().name()));
+            span.record(&mut fields);
+            self.lines.lock().unwrap().push(fields.0);
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _: &Id, _: &Record) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event) {
+            let depth = *self.depth.lock().unwrap();
+            let mut fields = Fields(format!("event at depth {}:", depth));
+            event.record(&mut fields);
+            self.lines.lock().unwrap().push(fields.0);
+        }
+
+        fn enter(&self, _: &Id) {
+            *self.depth.lock().unwrap() += 1;
+        }
+
+        fn exit(&self, _: &Id) {
+            *self.depth.lock().unwrap() -= 1;
+        }
+    }
+
+    #[test]
+    fn writes_entries_within_request_spans() {
+        let mut state = State::new();
+        state.put(Method::POST);
+        state.put("/users?page=2".parse::<Uri>().unwrap());
+        state.put(hyper::Version::HTTP_11);
+        state.put(HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+
+        let collector = Collector::default();
+        let lines = collector.lines.clone();
+
+        ::tracing::subscriber::with_default(collector, || {
+            let _ = RequestLogger::new(log::Level::Info)
+                .call(state, |state| {
+                    ::tracing::info!("handling");
+                    let mut response = Response::new(Body::empty());
+                    *response.status_mut() = StatusCode::CREATED;
+                    Box::new(future::ok((state, response)))
+                })
+                .wait();
+        });
+
+        let lines = lines.lock().unwrap();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("span request: method=POST path=/users request_id="));
+        assert_eq!(lines[1], "event at depth 1: message=handling");
+        assert!(lines[2].starts_with("event at depth 1: message="));
+        assert!(lines[2].contains("\"POST /users?page=2 HTTP/1.1\" 201"));
+        assert!(lines[2].contains(" method=POST path=/users status=201 duration_us="));
+    }
+}
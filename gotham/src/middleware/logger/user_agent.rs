@@ -0,0 +1,112 @@
+//! Extraction of the `User-Agent` written to access lines, used by the `log_user_agent` and
+//! `log_user_agent_family` options.
+use hyper::header::{HeaderMap, USER_AGENT};
+
+use super::format::truncate;
+
+/// The coarse families a `User-Agent` is classified into, checked in order.
+///
+/// A family matches when the lowercased `User-Agent` contains any of its markers. Order matters,
+/// as most browsers claim compatibility with others: Chrome includes `Safari/`, so it has to be
+/// checked before Safari, and bots often mimic browsers, so they're checked first of all.
+/// Families are added by inserting a row at the appropriate position.
+const FAMILIES: &[(&str, &[&str])] = &[
+    ("bot", &["bot", "spider", "crawl", "slurp"]),
+    ("curl", &["curl/"]),
+    ("firefox", &["firefox/", "fxios/"]),
+    ("chrome", &["chrome/", "chromium/", "crios/"]),
+    ("safari", &["safari/"]),
+];
+
+/// The family of any `User-Agent` which doesn't match a row of `FAMILIES`.
+const OTHER: &str = "other";
+
+/// Returns the `User-Agent` of a request, truncated to at most `max` bytes when set, or `-`
+/// when there is none.
+pub(super) fn raw(headers: &HeaderMap, max: Option<usize>) -> String {
+    let mut value = match headers.get(USER_AGENT) {
+        Some(value) => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+        None => return "-".to_owned(),
+    };
+
+    if let Some(max) = max {
+        truncate(&mut value, max);
+    }
+
+    value
+}
+
+/// Returns the coarse family of the `User-Agent` of a request, or `-` when there is none.
+pub(super) fn family(headers: &HeaderMap) -> &'static str {
+    let value = match headers.get(USER_AGENT) {
+        Some(value) => String::from_utf8_lossy(value.as_bytes()).to_lowercase(),
+        None => return "-",
+    };
+
+    FAMILIES
+        .iter()
+        .find(|(_, markers)| markers.iter().any(|marker| value.contains(marker)))
+        .map_or(OTHER, |(family, _)| family)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(user_agent: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, user_agent.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn classifies_families() {
+        let cases = [
+            (
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) \
+                 Chrome/124.0.0.0 Safari/537.36",
+                "chrome",
+            ),
+            (
+                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 \
+                 (KHTML, like Gecko) Version/17.4 Safari/605.1.15",
+                "safari",
+            ),
+            (
+                "Mozilla/5.0 (X11; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0",
+                "firefox",
+            ),
+            (
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 \
+                 (KHTML, like Gecko) CriOS/124.0.6367.88 Mobile/15E148 Safari/604.1",
+                "chrome",
+            ),
+            ("curl/8.5.0", "curl"),
+            (
+                "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+                "bot",
+            ),
+            (
+                "Mozilla/5.0 AppleWebKit/537.36 (KHTML, like Gecko; compatible; bingbot/2.0) \
+                 Chrome/116.0.1938.76 Safari/537.36",
+                "bot",
+            ),
+            ("python-requests/2.31.0", "other"),
+        ];
+
+        for &(user_agent, expected) in &cases {
+            assert_eq!(family(&headers(user_agent)), expected, "{}", user_agent);
+        }
+
+        assert_eq!(family(&HeaderMap::new()), "-");
+    }
+
+    #[test]
+    fn truncates_raw_values() {
+        let headers = headers("Mozilla/5.0 (X11; Linux x86_64; rv:125.0) Firefox/125.0");
+
+        assert_eq!(raw(&headers, None).len(), 55);
+        assert_eq!(raw(&headers, Some(32)), "Mozilla/5.0 (X11; …[+37 bytes]");
+        assert_eq!(raw(&HeaderMap::new(), Some(32)), "-");
+    }
+}
@@ -0,0 +1,107 @@
+//! Per-request verbose logging used by the `verbose_trigger_header` option.
+//!
+//! A client opts into a detailed access line by sending the trigger header, optionally carrying
+//! a shared secret. Secrets are compared in constant time, so that they can't be discovered by
+//! timing repeated requests.
+use std::sync::Arc;
+
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION,
+};
+use log::Level;
+
+use super::format::{JsonFormat, LogFormat};
+
+/// Options controlling which requests are logged verbosely, and how.
+#[derive(Clone)]
+pub(super) struct VerboseTrigger {
+    pub(super) header: HeaderName,
+    pub(super) secret: Option<Vec<u8>>,
+    pub(super) level: Option<Level>,
+    pub(super) format: Arc<dyn LogFormat>,
+}
+
+impl VerboseTrigger {
+    /// Creates a trigger for the provided header, accepting any value.
+    pub(super) fn new(header: HeaderName) -> Self {
+        VerboseTrigger {
+            header,
+            secret: None,
+            level: None,
+            format: Arc::new(JsonFormat::new()),
+        }
+    }
+
+    /// Determines whether the request described by `headers` should be logged verbosely.
+    pub(super) fn matches(&self, headers: &HeaderMap) -> bool {
+        let value = match headers.get(&self.header) {
+            Some(value) => value,
+            None => return false,
+        };
+
+        match self.secret {
+            Some(ref secret) => constant_time_eq(value.as_bytes(), secret),
+            None => true,
+        }
+    }
+
+    /// Copies the request headers for logging, redacting credentials and the trigger itself.
+    pub(super) fn capture(&self, headers: &HeaderMap) -> HeaderMap {
+        let mut captured = headers.clone();
+
+        for name in &[
+            AUTHORIZATION,
+            PROXY_AUTHORIZATION,
+            COOKIE,
+            self.header.clone(),
+        ] {
+            if captured.contains_key(name) {
+                captured.insert(name, HeaderValue::from_static("[redacted]"));
+            }
+        }
+
+        captured
+    }
+}
+
+/// Compares two values in time dependent only on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_trigger_header() {
+        let mut trigger = VerboseTrigger::new(HeaderName::from_static("x-debug-log"));
+
+        let mut headers = HeaderMap::new();
+        assert!(!trigger.matches(&headers));
+
+        headers.insert("x-debug-log", HeaderValue::from_static("s3cret"));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer token"));
+        headers.insert("x-trace-id", HeaderValue::from_static("abc"));
+        assert!(trigger.matches(&headers));
+
+        trigger.secret = Some(b"s3cret".to_vec());
+        assert!(trigger.matches(&headers));
+
+        trigger.secret = Some(b"s3crex".to_vec());
+        assert!(!trigger.matches(&headers));
+
+        trigger.secret = Some(b"s3cret!".to_vec());
+        assert!(!trigger.matches(&headers));
+
+        let captured = trigger.capture(&headers);
+        assert_eq!(captured["x-debug-log"], "[redacted]");
+        assert_eq!(captured[AUTHORIZATION], "[redacted]");
+        assert_eq!(captured["x-trace-id"], "abc");
+        assert!(!captured.contains_key(COOKIE));
+    }
+}
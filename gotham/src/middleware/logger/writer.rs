@@ -0,0 +1,174 @@
+//! Defines sinks which bypass the `log` crate, writing to a dedicated writer or channel.
+use std::io::{self, Write};
+use std::sync::mpsc::Sender;
+use std::sync::{Mutex, MutexGuard};
+
+use super::entry::LogEntry;
+use super::sink::LogSink;
+
+/// A `LogSink` which writes each line to a dedicated writer, such as `io::stdout()` or a
+/// socket, keeping access lines separate from application logs.
+///
+/// Each line is written and flushed while holding a lock, so lines from concurrent requests are
+/// never interleaved. As this happens on the thread serving the request, writers which may
+/// block for long should be wrapped in a `FileSink` or `ChannelSink` instead.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate log;
+/// # use gotham::middleware::logger::{CommonLogFormat, RequestLogger, WriterSink};
+/// # use log::Level;
+/// # use std::io;
+/// let logger = RequestLogger::new(Level::Info)
+///     .output(CommonLogFormat::new(), WriterSink::new(io::stdout()));
+/// # let _ = logger;
+/// ```
+pub struct WriterSink {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl WriterSink {
+    /// Creates a new `WriterSink` writing to the provided writer.
+    pub fn new<W>(writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        WriterSink {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Locks the writer, ignoring poisoning as a partial line is no worse than a failed write.
+    fn lock(&self) -> MutexGuard<'_, Box<dyn Write + Send>> {
+        match self.writer.lock() {
+            Ok(writer) => writer,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl From<Box<dyn Write + Send>> for WriterSink {
+    fn from(writer: Box<dyn Write + Send>) -> Self {
+        WriterSink {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl LogSink for WriterSink {
+    fn write(&self, _entry: &LogEntry, line: &str) -> io::Result<()> {
+        let mut writer = self.lock();
+        writer.write_all(line.as_bytes())?;
+        writer.write_all(b"\n")?;
+        writer.flush()
+    }
+}
+
+/// A `LogSink` which sends each line to a channel, leaving the application to receive and
+/// write them wherever it likes.
+///
+/// Writing fails with an error of kind `BrokenPipe` once the receiver has been dropped.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate log;
+/// # use gotham::middleware::logger::{ChannelSink, JsonFormat, RequestLogger};
+/// # use log::Level;
+/// # use std::sync::mpsc;
+/// let (sender, receiver) = mpsc::channel();
+///
+/// let logger = RequestLogger::new(Level::Info).output(JsonFormat::new(), ChannelSink::new(sender));
+/// # let _ = (logger, receiver);
+/// ```
+pub struct ChannelSink {
+    sender: Mutex<Sender<String>>,
+}
+
+impl ChannelSink {
+    /// Creates a new `ChannelSink` sending lines via the provided sender.
+    pub fn new(sender: Sender<String>) -> Self {
+        ChannelSink {
+            sender: Mutex::new(sender),
+        }
+    }
+}
+
+impl LogSink for ChannelSink {
+    fn write(&self, _entry: &LogEntry, line: &str) -> io::Result<()> {
+        let sender = match self.sender.lock() {
+            Ok(sender) => sender,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        sender
+            .send(line.to_owned())
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::middleware::logger::entry::test_entry;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_whole_lines_from_concurrent_requests() {
+        let output = Shared::default();
+        let sink = Arc::new(WriterSink::new(output.clone()));
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let sink = sink.clone();
+                thread::spawn(move || {
+                    let entry = test_entry();
+                    for j in 0..100 {
+                        sink.write(&entry, &format!("thread {} line {}", i, j))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+
+        assert_eq!(lines.len(), 800);
+        assert!(lines.iter().all(|line| line.starts_with("thread ")));
+    }
+
+    #[test]
+    fn sends_lines_to_channels() {
+        let (sender, receiver) = mpsc::channel();
+        let sink = ChannelSink::new(sender);
+
+        sink.write(&test_entry(), "first").unwrap();
+        assert_eq!(receiver.recv().unwrap(), "first");
+
+        drop(receiver);
+        let err = sink.write(&test_entry(), "second").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,293 @@
+//! Middleware to take an application offline for maintenance, toggled at runtime.
+//!
+//! While maintenance mode is enabled, requests are answered with a `503 Service Unavailable`
+//! without reaching their handlers, except for a list of allowed paths such as health checks.
+//! The mode is toggled through a `MaintenanceSwitch`, which can be held by any thread or served
+//! as an admin endpoint, so no restart is needed.
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::header::{HeaderValue, RETRY_AFTER};
+use hyper::{Method, StatusCode, Uri};
+use log::{info, trace};
+use mime::Mime;
+
+use crate::error::Result;
+use crate::handler::{Handler, HandlerFuture, NewHandler};
+use crate::helpers::http::response::{create_empty_response, create_response};
+use crate::middleware::{short_circuit, Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State};
+
+/// A shared flag controlling whether maintenance mode is enabled.
+///
+/// Clones of a switch share the same flag, so a switch can be kept elsewhere in the application
+/// to toggle every `MaintenanceMiddleware` created from it.
+///
+/// The switch can also be routed as an admin endpoint, via `to_new_handler`, where a `GET`
+/// reports the current mode as JSON, a `POST` enables maintenance mode and a `DELETE` disables
+/// it. Such an endpoint should be protected by authentication, and its path listed via
+/// `MaintenanceMiddleware::allow_path` so that maintenance mode can be disabled again.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use gotham::middleware::maintenance::{MaintenanceMiddleware, MaintenanceSwitch};
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::test::TestServer;
+/// # use hyper::StatusCode;
+/// #
+/// # fn main() {
+/// let switch = MaintenanceSwitch::new();
+///
+/// let maintenance = MaintenanceMiddleware::new(switch.clone())
+///     .allow_path("/health")
+///     .allow_path("/admin/maintenance");
+///
+/// let (chain, pipelines) = single_pipeline(new_pipeline().add(maintenance).build());
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/").to(|state| (state, "Hello, world!"));
+///     route.get("/health").to(|state| (state, "ok"));
+///     route
+///         .request(vec![hyper::Method::GET, hyper::Method::POST, hyper::Method::DELETE], "/admin/maintenance")
+///         .to_new_handler(switch.clone());
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let client = test_server.client();
+/// # let status = |path: &str| client.get(path).perform().unwrap().status();
+/// #
+/// # client.post("http://localhost/admin/maintenance", "", mime::TEXT_PLAIN).perform().unwrap();
+/// # assert!(switch.is_enabled());
+/// # assert_eq!(status("http://localhost/"), StatusCode::SERVICE_UNAVAILABLE);
+/// # assert_eq!(status("http://localhost/health"), StatusCode::OK);
+/// #
+/// # let response = client.get("http://localhost/admin/maintenance").perform().unwrap();
+/// # assert_eq!(response.read_utf8_body().unwrap(), "{\"maintenance\":true}");
+/// #
+/// # client.delete("http://localhost/admin/maintenance").perform().unwrap();
+/// # assert_eq!(status("http://localhost/"), StatusCode::OK);
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MaintenanceSwitch {
+    enabled: Arc<AtomicBool>,
+}
+
+impl MaintenanceSwitch {
+    /// Creates a new `MaintenanceSwitch`, with maintenance mode disabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a `MaintenanceSwitch` backed by an existing flag.
+    pub fn from_flag(enabled: Arc<AtomicBool>) -> Self {
+        MaintenanceSwitch { enabled }
+    }
+
+    /// Enables maintenance mode.
+    pub fn enable(&self) {
+        self.set(true);
+    }
+
+    /// Disables maintenance mode.
+    pub fn disable(&self) {
+        self.set(false);
+    }
+
+    /// Sets whether maintenance mode is enabled.
+    pub fn set(&self, enabled: bool) {
+        if self.enabled.swap(enabled, Ordering::SeqCst) != enabled {
+            info!(
+                "maintenance mode {}",
+                if enabled { "enabled" } else { "disabled" }
+            );
+        }
+    }
+
+    /// Returns whether maintenance mode is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+}
+
+impl NewHandler for MaintenanceSwitch {
+    type Instance = Self;
+
+    fn new_handler(&self) -> Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl Handler for MaintenanceSwitch {
+    fn handle(self, state: State) -> Box<HandlerFuture> {
+        match *Method::borrow_from(&state) {
+            Method::GET => (),
+            Method::POST => self.enable(),
+            Method::DELETE => self.disable(),
+            _ => return short_circuit(state, StatusCode::METHOD_NOT_ALLOWED),
+        }
+
+        let body = format!("{{\"maintenance\":{}}}", self.is_enabled());
+        short_circuit(state, (StatusCode::OK, mime::APPLICATION_JSON, body))
+    }
+}
+
+/// Middleware binding to answer requests with a `503 Service Unavailable` while maintenance mode
+/// is enabled, and to pass them through untouched otherwise.
+///
+/// Responses include a `Retry-After` header, which is 300 seconds by default, and are empty
+/// unless a body is configured via `body`.
+#[derive(Clone)]
+pub struct MaintenanceMiddleware {
+    switch: MaintenanceSwitch,
+    allowed: Arc<Vec<String>>,
+    retry_after: Duration,
+    body: Option<Arc<(Mime, Vec<u8>)>>,
+}
+
+impl MaintenanceMiddleware {
+    /// Creates a new `MaintenanceMiddleware` controlled by the provided switch.
+    pub fn new(switch: MaintenanceSwitch) -> Self {
+        MaintenanceMiddleware {
+            switch,
+            allowed: Arc::new(Vec::new()),
+            retry_after: Duration::from_secs(300),
+            body: None,
+        }
+    }
+
+    /// Allows requests to the provided path, and any path beneath it, while maintenance mode is
+    /// enabled. This is typically used for health checks and the admin endpoint toggling the
+    /// mode.
+    pub fn allow_path(mut self, path: &str) -> Self {
+        let path = path.trim_end_matches('/').to_owned();
+        Arc::make_mut(&mut self.allowed).push(path);
+        self
+    }
+
+    /// Sets the delay advertised via `Retry-After`, which is rounded down to whole seconds.
+    pub fn retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Sets the body of the responses sent while maintenance mode is enabled, such as a
+    /// maintenance page.
+    pub fn body<B>(mut self, mime: Mime, body: B) -> Self
+    where
+        B: Into<Vec<u8>>,
+    {
+        self.body = Some(Arc::new((mime, body.into())));
+        self
+    }
+
+    /// Determines whether a path is allowed while maintenance mode is enabled.
+    fn allows(&self, path: &str) -> bool {
+        self.allowed.iter().any(|allowed| {
+            path.starts_with(allowed.as_str()) && {
+                let rest = &path[allowed.len()..];
+                rest.is_empty() || rest.starts_with('/')
+            }
+        })
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for MaintenanceMiddleware {
+    /// Answers requests with a `503 Service Unavailable` while maintenance mode is enabled.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        if !self.switch.is_enabled() || self.allows(Uri::borrow_from(&state).path()) {
+            return chain(state);
+        }
+
+        trace!("[{}] rejecting request for maintenance", request_id(&state));
+
+        let status = StatusCode::SERVICE_UNAVAILABLE;
+        let mut response = match self.body {
+            Some(ref body) => create_response(&state, status, body.0.clone(), body.1.clone()),
+            None => create_empty_response(&state, status),
+        };
+
+        let retry_after = self.retry_after.as_secs().to_string();
+        response.headers_mut().insert(
+            RETRY_AFTER,
+            HeaderValue::from_str(&retry_after).expect("digits are a valid header value"),
+        );
+
+        short_circuit(state, response)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for MaintenanceMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{future, Future, Stream};
+    use hyper::{Body, HeaderMap, Response};
+
+    fn call(middleware: &MaintenanceMiddleware, path: &str) -> Response<Body> {
+        let mut state = State::new();
+        state.put(Method::GET);
+        state.put(path.parse::<Uri>().unwrap());
+        state.put(HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+
+        middleware
+            .clone()
+            .call(state, |state| {
+                Box::new(future::ok((state, Response::new(Body::empty()))))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn rejects_requests_during_maintenance() {
+        let switch = MaintenanceSwitch::new();
+        let middleware = MaintenanceMiddleware::new(switch.clone())
+            .allow_path("/health/")
+            .retry_after(Duration::from_secs(120))
+            .body(mime::TEXT_HTML, "<h1>Back soon</h1>");
+
+        assert_eq!(call(&middleware, "/orders").status(), StatusCode::OK);
+
+        switch.enable();
+
+        let response = call(&middleware, "/orders");
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[RETRY_AFTER], "120");
+        let body = response.into_body().concat2().wait().unwrap();
+        assert_eq!(&body[..], b"<h1>Back soon</h1>");
+
+        assert_eq!(call(&middleware, "/health").status(), StatusCode::OK);
+        assert_eq!(call(&middleware, "/health/db").status(), StatusCode::OK);
+        assert_eq!(
+            call(&middleware, "/healthz").status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        switch.disable();
+        assert_eq!(call(&middleware, "/orders").status(), StatusCode::OK);
+    }
+}
@@ -4,16 +4,43 @@
 use std::io;
 use std::panic::RefUnwindSafe;
 
-use crate::handler::HandlerFuture;
+use futures::future;
+
+use crate::handler::{HandlerFuture, IntoResponse};
 use crate::state::State;
 
+pub mod audit;
+pub mod body_replay;
+pub mod cache_busting;
 pub mod chain;
+pub mod concurrency;
+pub mod connection_limit;
 pub mod cookie;
+pub mod cookie_consent;
+pub mod correlation;
+pub mod cors;
+pub mod decompression;
+pub mod domain_redirect;
+pub mod header_forwarding;
+pub mod headers;
+pub mod histogram;
+pub mod https_redirect;
+pub mod idempotency;
 pub mod logger;
+pub mod maintenance;
+pub mod proxy;
+pub mod rate_limit;
+pub mod response_extensions;
 pub mod security;
+pub mod server_timing;
 pub mod session;
+pub mod slow_read;
 pub mod state;
 pub mod timer;
+#[cfg(feature = "tracing")]
+pub mod tracing;
+pub mod upload;
+pub mod version;
 
 /// `Middleware` has the opportunity to provide additional behaviour to the `Request` / `Response`
 /// interaction. For example:
@@ -199,20 +226,18 @@ pub mod timer;
 /// # }
 /// ```
 ///
-/// Terminating the request early based on some arbitrary condition:
+/// Terminating the request early based on some arbitrary condition, by returning a response via
+/// `short_circuit` rather than invoking the chain:
 ///
 /// ```rust
 /// # extern crate gotham;
 /// # #[macro_use]
 /// # extern crate gotham_derive;
 /// # extern crate hyper;
-/// # extern crate futures;
 /// #
 /// # use hyper::{Body, Response, Method, StatusCode};
-/// # use futures::future;
-/// # use gotham::helpers::http::response::create_empty_response;
 /// # use gotham::handler::HandlerFuture;
-/// # use gotham::middleware::Middleware;
+/// # use gotham::middleware::{short_circuit, Middleware};
 /// # use gotham::pipeline::*;
 /// # use gotham::pipeline::single::*;
 /// # use gotham::router::builder::*;
@@ -229,8 +254,7 @@ pub mod timer;
 ///         if *Method::borrow_from(&state) == Method::GET {
 ///             chain(state)
 ///         } else {
-///             let response = create_empty_response(&state, StatusCode::METHOD_NOT_ALLOWED);
-///             Box::new(future::ok((state, response)))
+///             short_circuit(state, StatusCode::METHOD_NOT_ALLOWED)
 ///         }
 ///     }
 /// }
@@ -328,6 +352,53 @@ pub trait Middleware {
         Self: Sized;
 }
 
+/// Completes a request from within a `Middleware` with the provided response, without invoking
+/// the rest of the chain.
+///
+/// This is the way for guarding middleware (such as authentication, rate limiting or maintenance
+/// modes) to stop processing a request. The response may be any value implementing
+/// `IntoResponse`, such as a `StatusCode` or a `Response` built via the
+/// `gotham::helpers::http::response` functions, and it's converted using the provided `State`.
+///
+/// The response still passes back through every middleware before this one in the pipeline, and
+/// through the `ResponseExtender` and `BeforeSendHook` values of the `Router`, in the same way
+/// as a response from a handler.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::header::AUTHORIZATION;
+/// # use hyper::{HeaderMap, StatusCode};
+/// # use gotham::handler::HandlerFuture;
+/// # use gotham::middleware::{short_circuit, Middleware};
+/// # use gotham::state::{FromState, State};
+/// #
+/// struct RequireAuthorization;
+///
+/// impl Middleware for RequireAuthorization {
+///     fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+///         where Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static
+///     {
+///         if !HeaderMap::borrow_from(&state).contains_key(AUTHORIZATION) {
+///             let body = "missing credentials";
+///             return short_circuit(state, (StatusCode::UNAUTHORIZED, mime::TEXT_PLAIN, body));
+///         }
+///
+///         chain(state)
+///     }
+/// }
+/// # fn main() { let _ = RequireAuthorization; }
+/// ```
+pub fn short_circuit<R>(state: State, response: R) -> Box<HandlerFuture>
+where
+    R: IntoResponse,
+{
+    let response = response.into_response(&state);
+    Box::new(future::ok((state, response)))
+}
+
 /// A type which is used to spawn new `Middleware` values. When implementing a `Middleware`, this
 /// defines how instances of the `Middleware` are created.
 ///
@@ -373,4 +444,38 @@ pub trait NewMiddleware: Sync + RefUnwindSafe {
 
     /// Create and return a new `Middleware` value.
     fn new_middleware(&self) -> io::Result<Self::Instance>;
+
+    /// Returns the name used to identify this middleware when inspecting a pipeline.
+    ///
+    /// Defaults to the fully qualified type name. When deriving `NewMiddleware`, a shorter name
+    /// can be provided via the `#[middleware_name = "..."]` attribute.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// #
+    /// # use gotham::handler::HandlerFuture;
+    /// # use gotham::middleware::{Middleware, NewMiddleware};
+    /// # use gotham::state::State;
+    /// #
+    /// #[derive(Clone, NewMiddleware)]
+    /// #[middleware_name = "Auth"]
+    /// struct AuthMiddleware;
+    /// #
+    /// # impl Middleware for AuthMiddleware {
+    /// #     fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    /// #         where Chain: FnOnce(State) -> Box<HandlerFuture>
+    /// #     {
+    /// #         chain(state)
+    /// #     }
+    /// # }
+    ///
+    /// # fn main() {
+    /// assert_eq!(AuthMiddleware.middleware_name(), "Auth");
+    /// # }
+    /// ```
+    fn middleware_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
 }
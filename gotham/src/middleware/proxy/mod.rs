@@ -0,0 +1,267 @@
+//! Middleware to resolve the real client address of requests arriving through trusted proxies.
+//!
+//! When an application sits behind a load balancer or reverse proxy, the TCP peer of every
+//! connection is the proxy itself. Proxies typically report the original client address via
+//! the `X-Forwarded-For` or `X-Real-IP` headers, but these headers are trivially forged by
+//! clients connecting directly. This middleware only honours them when the direct peer is
+//! within one of the configured trusted networks.
+//!
+//! Once resolved, the client address is replaced in `State`, so `client_addr` (and therefore
+//! the `RequestLogger`) will report the forwarded address for the rest of the request.
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+use hyper::header::HeaderMap;
+use ipnet::IpNet;
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::client_addr::put_client_addr;
+use crate::state::{client_addr, request_id, FromState, State};
+
+// header names used by common proxies
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_REAL_IP: &str = "x-real-ip";
+
+/// Middleware binding to resolve client addresses from trusted proxy headers.
+///
+/// The leftmost public address found in `X-Forwarded-For` is preferred, falling back to
+/// a public `X-Real-IP`. Requests from peers outside of the trusted networks are left untouched,
+/// and their headers are ignored entirely to prevent address spoofing.
+///
+/// Forwarded addresses rarely carry a port, in which case the resolved address will have
+/// a port of `0`.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::proxy::TrustedProxyMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// let proxies = TrustedProxyMiddleware::new(vec![
+///     "10.0.0.0/8".parse().unwrap(),
+///     "127.0.0.1/32".parse().unwrap(),
+/// ]);
+///
+/// let pipeline = new_pipeline().add(proxies).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone)]
+pub struct TrustedProxyMiddleware {
+    trusted: Arc<Vec<IpNet>>,
+}
+
+impl TrustedProxyMiddleware {
+    /// Creates a new `TrustedProxyMiddleware` trusting the provided networks.
+    pub fn new(trusted_cidrs: Vec<IpNet>) -> Self {
+        TrustedProxyMiddleware {
+            trusted: Arc::new(trusted_cidrs),
+        }
+    }
+
+    /// Determines whether the direct peer is one of the trusted proxies.
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|net| net.contains(&ip))
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for TrustedProxyMiddleware {
+    /// Replaces the client address with the forwarded address, when trusted.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        let peer = client_addr(&state);
+
+        if let Some(peer) = peer.filter(|peer| self.is_trusted(peer.ip())) {
+            let forwarded = forwarded_addr(HeaderMap::borrow_from(&state));
+
+            if let Some(addr) = forwarded {
+                trace!(
+                    "[{}] resolved client address {} via trusted proxy {}",
+                    request_id(&state),
+                    addr,
+                    peer
+                );
+                put_client_addr(&mut state, addr);
+            }
+        }
+
+        chain(state)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for TrustedProxyMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// Locates the forwarded client address in the provided headers.
+fn forwarded_addr(headers: &HeaderMap) -> Option<SocketAddr> {
+    let forwarded_for = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(parse_addr)
+        .find(|addr| is_public(addr.ip()));
+
+    forwarded_for.or_else(|| {
+        headers
+            .get(X_REAL_IP)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_addr)
+            .filter(|addr| is_public(addr.ip()))
+    })
+}
+
+/// Parses a single forwarded address, which may or may not include a port.
+fn parse_addr(value: &str) -> Option<SocketAddr> {
+    let value = value.trim();
+
+    value.parse::<SocketAddr>().ok().or_else(|| {
+        value
+            .parse::<IpAddr>()
+            .ok()
+            .map(|ip| SocketAddr::new(ip, 0))
+    })
+}
+
+/// Determines whether an address is publicly routable.
+///
+/// This mirrors the unstable `IpAddr::is_global`, which isn't yet available.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4() {
+            Some(v4) if is_mapped(ip) => is_public_v4(v4),
+            _ => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let octets = ip.octets();
+
+    // 100.64.0.0/10 is reserved for carrier-grade NAT
+    let shared = octets[0] == 100 && (octets[1] & 0b1100_0000) == 64;
+
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || shared)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+
+    // fc00::/7 is unique local, fe80::/10 is link local
+    let unique_local = (first & 0xfe00) == 0xfc00;
+    let link_local = (first & 0xffc0) == 0xfe80;
+
+    !(ip.is_loopback() || ip.is_unspecified() || unique_local || link_local)
+}
+
+fn is_mapped(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    segments[..5].iter().all(|s| *s == 0) && segments[5] == 0xffff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{future, Future};
+    use hyper::{Body, Response};
+
+    fn resolve(peer: &str, headers: Vec<(&'static str, &'static str)>) -> SocketAddr {
+        let middleware = TrustedProxyMiddleware::new(vec!["10.0.0.0/8".parse().unwrap()]);
+
+        let mut state = State::new();
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            header_map.append(name, value.parse().unwrap());
+        }
+        state.put(header_map);
+        put_client_addr(&mut state, peer.parse().unwrap());
+        crate::state::set_request_id(&mut state);
+
+        let (state, _) = middleware
+            .call(state, |state| {
+                Box::new(future::ok((state, Response::new(Body::empty()))))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap();
+
+        client_addr(&state).unwrap()
+    }
+
+    #[test]
+    fn uses_leftmost_public_forwarded_address() {
+        let addr = resolve(
+            "10.0.0.1:5000",
+            vec![(X_FORWARDED_FOR, "192.168.1.4, 203.0.113.9, 8.8.8.8")],
+        );
+        assert_eq!(addr, "8.8.8.8:0".parse().unwrap());
+
+        let addr = resolve(
+            "10.0.0.1:5000",
+            vec![
+                (X_FORWARDED_FOR, "1.1.1.1, 8.8.8.8"),
+                (X_FORWARDED_FOR, "9.9.9.9"),
+            ],
+        );
+        assert_eq!(addr, "1.1.1.1:0".parse().unwrap());
+    }
+
+    #[test]
+    fn falls_back_to_real_ip_header() {
+        let addr = resolve(
+            "10.0.0.1:5000",
+            vec![(X_FORWARDED_FOR, "10.1.1.1"), (X_REAL_IP, "1.2.3.4")],
+        );
+        assert_eq!(addr, "1.2.3.4:0".parse().unwrap());
+
+        let addr = resolve("10.0.0.1:5000", vec![(X_REAL_IP, "127.0.0.1")]);
+        assert_eq!(addr, "10.0.0.1:5000".parse().unwrap());
+
+        let addr = resolve("10.0.0.1:5000", vec![(X_REAL_IP, "192.168.0.7")]);
+        assert_eq!(addr, "10.0.0.1:5000".parse().unwrap());
+    }
+
+    #[test]
+    fn ignores_headers_from_untrusted_peers() {
+        let addr = resolve("1.1.1.1:5000", vec![(X_FORWARDED_FOR, "8.8.8.8")]);
+        assert_eq!(addr, "1.1.1.1:5000".parse().unwrap());
+    }
+
+    #[test]
+    fn keeps_peer_without_usable_headers() {
+        let addr = resolve(
+            "10.0.0.1:5000",
+            vec![(X_FORWARDED_FOR, "garbage, 127.0.0.1")],
+        );
+        assert_eq!(addr, "10.0.0.1:5000".parse().unwrap());
+    }
+
+    #[test]
+    fn classifies_public_addresses() {
+        assert!(is_public("8.8.8.8".parse().unwrap()));
+        assert!(is_public("2001:4860:4860::8888".parse().unwrap()));
+        assert!(!is_public("100.64.0.1".parse().unwrap()));
+        assert!(!is_public("fd00::1".parse().unwrap()));
+        assert!(!is_public("fe80::1".parse().unwrap()));
+        assert!(!is_public("::ffff:192.168.0.1".parse().unwrap()));
+        assert!(is_public("::ffff:8.8.4.4".parse().unwrap()));
+    }
+}
@@ -0,0 +1,391 @@
+//! Middleware to limit the rate of requests made by each client, using a token bucket per key.
+//!
+//! Requests are grouped by a key extracted from the request, such as the client address, an
+//! API key or the ID of an authenticated user. Each key is given a bucket of tokens which is
+//! refilled at a constant rate; every request takes a token, and requests arriving at an empty
+//! bucket are rejected with a `429 Too Many Requests` response.
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, RETRY_AFTER};
+use hyper::StatusCode;
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{client_addr, request_id, FromState, State};
+
+// the number of buckets held before full buckets are pruned
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// A function extracting the rate limit key from a request, returning `None` when the request
+/// carries no key (such as an unauthenticated request).
+pub type KeyExtractor = Arc<dyn Fn(&State) -> Option<String> + Send + Sync + RefUnwindSafe>;
+
+/// Determines how a `RateLimitMiddleware` handles requests for which no key was extracted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FallbackPolicy {
+    /// Limits the request by the client address instead, which is the default.
+    ///
+    /// Requests without a client address are not limited.
+    ClientAddr,
+    /// Rejects the request with a `401 Unauthorized` response.
+    Reject,
+}
+
+impl Default for FallbackPolicy {
+    fn default() -> Self {
+        FallbackPolicy::ClientAddr
+    }
+}
+
+/// Middleware binding to limit the rate of requests made for each key.
+///
+/// Each key may make `capacity` requests in a burst, with the bucket refilled at a rate of
+/// `capacity` requests per `period`. Rejected requests receive a `429 Too Many Requests`
+/// response, with a `Retry-After` header giving the number of seconds until a token is
+/// available.
+///
+/// Buckets are shared between every instance created from the middleware, and so apply across
+/// all requests served by the application.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use std::time::Duration;
+/// # use gotham::middleware::rate_limit::{FallbackPolicy, RateLimitMiddleware};
+/// # use gotham::pipeline::new_pipeline;
+/// // 100 requests per minute for each API key, and none without one
+/// let limiter = RateLimitMiddleware::by_api_key("X-API-Key", 100, Duration::from_secs(60))
+///     .fallback_policy(FallbackPolicy::Reject);
+///
+/// let pipeline = new_pipeline().add(limiter).build();
+/// # let _ = pipeline;
+/// ```
+///
+/// Keys may be taken from any part of the request via `new`, such as the ID of a user stored
+/// in `State` by earlier authentication middleware. The fallback policy keys requests by their
+/// address prefixed by `ip:`, so custom keys shouldn't use the same prefix.
+#[derive(Clone)]
+pub struct RateLimitMiddleware {
+    buckets: Arc<Buckets>,
+    key_extractor: KeyExtractor,
+    fallback_policy: FallbackPolicy,
+}
+
+impl RateLimitMiddleware {
+    /// Creates a new `RateLimitMiddleware`, keying requests using the provided function.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` or `period` is zero.
+    pub fn new(capacity: u32, period: Duration, key_extractor: KeyExtractor) -> Self {
+        assert!(capacity > 0, "rate limit capacity must be positive");
+        assert!(
+            period > Duration::from_secs(0),
+            "rate limit period must be positive"
+        );
+
+        RateLimitMiddleware {
+            buckets: Arc::new(Buckets {
+                capacity: f64::from(capacity),
+                rate: f64::from(capacity) / period.as_secs_f64(),
+                entries: Mutex::new(HashMap::new()),
+            }),
+            key_extractor,
+            fallback_policy: FallbackPolicy::default(),
+        }
+    }
+
+    /// Creates a new `RateLimitMiddleware`, keying requests by the client address.
+    ///
+    /// When running behind a proxy, the `TrustedProxyMiddleware` should run first so that the
+    /// address of the original client is used.
+    pub fn by_client_addr(capacity: u32, period: Duration) -> Self {
+        Self::new(capacity, period, Arc::new(client_addr_key))
+    }
+
+    /// Creates a new `RateLimitMiddleware`, keying requests by the token of their
+    /// `Authorization: Bearer` header.
+    ///
+    /// Tokens are hashed rather than held by the middleware.
+    pub fn by_bearer_token(capacity: u32, period: Duration) -> Self {
+        let hasher = RandomState::new();
+
+        let extractor = move |state: &State| {
+            let token = HeaderMap::borrow_from(state)
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(bearer_token)?;
+
+            Some(format!("bearer:{:016x}", hash(&hasher, token)))
+        };
+
+        Self::new(capacity, period, Arc::new(extractor))
+    }
+
+    /// Creates a new `RateLimitMiddleware`, keying requests by the value of the named header,
+    /// such as `X-API-Key`.
+    ///
+    /// Keys are hashed rather than held by the middleware.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `header` is not a valid header name, or if `capacity` or `period` is zero.
+    pub fn by_api_key(header: &str, capacity: u32, period: Duration) -> Self {
+        let header = HeaderName::from_bytes(header.as_bytes()).expect("invalid header name");
+        let hasher = RandomState::new();
+
+        let extractor = move |state: &State| {
+            let key = HeaderMap::borrow_from(state)
+                .get(&header)
+                .map(HeaderValue::as_bytes)
+                .filter(|key| !key.is_empty())?;
+
+            Some(format!("key:{:016x}", hash(&hasher, key)))
+        };
+
+        Self::new(capacity, period, Arc::new(extractor))
+    }
+
+    /// Sets how requests for which no key was extracted are handled, which is to limit them by
+    /// the client address by default.
+    pub fn fallback_policy(mut self, policy: FallbackPolicy) -> Self {
+        self.fallback_policy = policy;
+        self
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for RateLimitMiddleware {
+    /// Rejects the request when the bucket for its key is empty.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        let key = match (self.key_extractor)(&state) {
+            Some(key) => Some(key),
+            None => match self.fallback_policy {
+                FallbackPolicy::ClientAddr => client_addr_key(&state),
+                FallbackPolicy::Reject => {
+                    trace!("[{}] no rate limit key, rejecting", request_id(&state));
+                    let response = create_empty_response(&state, StatusCode::UNAUTHORIZED);
+                    return Box::new(future::ok((state, response)));
+                }
+            },
+        };
+
+        let key = match key {
+            Some(key) => key,
+            None => return chain(state),
+        };
+
+        match self.buckets.take(key, Instant::now()) {
+            Ok(()) => chain(state),
+            Err(wait) => {
+                trace!(
+                    "[{}] rate limit exceeded, retry in {:?}",
+                    request_id(&state),
+                    wait
+                );
+
+                // round up, so that a token is always available when retrying
+                let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+
+                let mut response = create_empty_response(&state, StatusCode::TOO_MANY_REQUESTS);
+                response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, HeaderValue::from(seconds));
+
+                Box::new(future::ok((state, response)))
+            }
+        }
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for RateLimitMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance, sharing the buckets.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// The token buckets of every key seen by a `RateLimitMiddleware`.
+struct Buckets {
+    capacity: f64,
+    rate: f64,
+    entries: Mutex<HashMap<String, Bucket>>,
+}
+
+/// The tokens available to a single key, as of the last time it was used.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Buckets {
+    /// Takes a token from the bucket of `key`, or returns the time until one is available.
+    fn take(&self, key: String, now: Instant) -> Result<(), Duration> {
+        let mut entries = match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if entries.len() >= PRUNE_THRESHOLD && !entries.contains_key(&key) {
+            // a full bucket behaves just like a missing one
+            entries.retain(|_, bucket| self.refilled(bucket, now) < self.capacity);
+        }
+
+        let capacity = self.capacity;
+        let bucket = entries.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+
+    /// Returns the tokens in a bucket once refilled up to `now`.
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate).min(self.capacity)
+    }
+}
+
+/// Extracts the client address of a request as a key.
+fn client_addr_key(state: &State) -> Option<String> {
+    client_addr(state).map(|addr| format!("ip:{}", addr.ip()))
+}
+
+/// Extracts the token from an `Authorization` header using the `Bearer` scheme.
+fn bearer_token(value: &str) -> Option<&str> {
+    let mut parts = value.splitn(2, ' ');
+    let scheme = parts.next()?;
+    let token = parts.next()?.trim();
+
+    if scheme.eq_ignore_ascii_case("bearer") && !token.is_empty() {
+        Some(token)
+    } else {
+        None
+    }
+}
+
+/// Hashes a credential, so that it can be used as a key without being held in memory.
+fn hash<T: Hash + ?Sized>(hasher: &RandomState, value: &T) -> u64 {
+    let mut hasher = hasher.build_hasher();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::Future;
+    use hyper::{Body, Response};
+
+    use crate::state::client_addr::put_client_addr;
+
+    fn run(middleware: &RateLimitMiddleware, auth: Option<&'static str>) -> Response<Body> {
+        let mut headers = HeaderMap::new();
+        if let Some(auth) = auth {
+            headers.insert(AUTHORIZATION, HeaderValue::from_static(auth));
+        }
+
+        let mut state = State::new();
+        state.put(headers);
+        put_client_addr(&mut state, "127.0.0.1:10000".parse().unwrap());
+        crate::state::set_request_id(&mut state);
+
+        let (_, response) = middleware
+            .new_middleware()
+            .unwrap()
+            .call(state, |state| {
+                Box::new(future::ok((state, Response::new(Body::empty()))))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap();
+
+        response
+    }
+
+    #[test]
+    fn limits_requests_by_key() {
+        let middleware = RateLimitMiddleware::by_bearer_token(2, Duration::from_secs(4));
+
+        assert_eq!(
+            run(&middleware, Some("Bearer abc")).status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            run(&middleware, Some("bearer abc")).status(),
+            StatusCode::OK
+        );
+
+        let response = run(&middleware, Some("Bearer abc"));
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "2");
+
+        // other keys have their own bucket, as do requests falling back to the address
+        assert_eq!(
+            run(&middleware, Some("Bearer def")).status(),
+            StatusCode::OK
+        );
+        assert_eq!(run(&middleware, None).status(), StatusCode::OK);
+        assert_eq!(run(&middleware, Some("Basic abc")).status(), StatusCode::OK);
+
+        let response = run(&middleware, None);
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn rejects_requests_without_keys() {
+        let middleware = RateLimitMiddleware::by_bearer_token(1, Duration::from_secs(1))
+            .fallback_policy(FallbackPolicy::Reject);
+
+        assert_eq!(run(&middleware, None).status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            run(&middleware, Some("Bearer abc")).status(),
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn refills_buckets_over_time() {
+        let middleware = RateLimitMiddleware::by_client_addr(2, Duration::from_secs(8));
+        let buckets = &middleware.buckets;
+        let start = Instant::now();
+
+        assert!(buckets.take("a".to_owned(), start).is_ok());
+        assert!(buckets.take("a".to_owned(), start).is_ok());
+        assert_eq!(
+            buckets.take("a".to_owned(), start),
+            Err(Duration::from_secs(4))
+        );
+
+        assert!(buckets
+            .take("a".to_owned(), start + Duration::from_secs(4))
+            .is_ok());
+        assert!(buckets
+            .take("a".to_owned(), start + Duration::from_secs(5))
+            .is_err());
+    }
+}
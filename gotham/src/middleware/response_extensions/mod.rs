@@ -0,0 +1,196 @@
+//! Response extension middleware, used to add headers to the response from anywhere in the chain.
+//!
+//! Middleware which runs before the handler often has information to add to the response,
+//! such as whether a cache was hit. Rather than wrapping the rest of the chain to modify the
+//! response once it resolves, the header can be queued via `ResponseExtensions` and is added to
+//! the response by the `ResponseExtensionApplierMiddleware`.
+use std::io;
+
+use futures::{future, Future};
+use http::HttpTryFrom;
+use hyper::header::{HeaderName, HeaderValue};
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{State, StateData};
+
+/// Middleware binding to add the headers queued via `ResponseExtensions` to the response.
+///
+/// This should be added before any middleware which queues headers, so that it's outermost in
+/// the pipeline; headers queued by middleware running outside of it won't be applied. Headers
+/// aren't applied when the chain resolves to an error rather than a response.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::HeaderName;
+/// # use gotham::middleware::response_extensions::{
+/// #     ResponseExtensionApplierMiddleware, ResponseExtensions,
+/// # };
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn my_handler(mut state: State) -> (State, Response<Body>) {
+///     // typically done by earlier middleware, such as a cache
+///     ResponseExtensions::borrow_mut_from(&mut state)
+///         .push(HeaderName::from_static("x-cache-status"), "HIT");
+///
+///     (state, Response::new(Body::empty()))
+/// }
+///
+/// # fn main() {
+/// let (chain, pipelines) = single_pipeline(
+///     new_pipeline()
+///         .add(ResponseExtensionApplierMiddleware::new())
+///         .build(),
+/// );
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/").to(my_handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/")
+/// #     .perform()
+/// #     .unwrap();
+/// #
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert_eq!(response.headers()["x-cache-status"], "HIT");
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ResponseExtensionApplierMiddleware;
+
+impl ResponseExtensionApplierMiddleware {
+    /// Creates a new `ResponseExtensionApplierMiddleware`.
+    pub fn new() -> Self {
+        ResponseExtensionApplierMiddleware
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for ResponseExtensionApplierMiddleware {
+    /// Adds the queued headers to the response.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        state.put(ResponseExtensions::default());
+
+        // execute the chain and apply the headers on complete
+        let f = chain(state).and_then(|(mut state, mut response)| {
+            if let Some(extensions) = state.try_take::<ResponseExtensions>() {
+                let headers = response.headers_mut();
+
+                for (name, value) in extensions.headers {
+                    headers.append(name, value);
+                }
+            }
+
+            future::ok((state, response))
+        });
+
+        Box::new(f)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for ResponseExtensionApplierMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+/// Headers queued for the response, added by the `ResponseExtensionApplierMiddleware`.
+///
+/// An empty queue is placed into `State` by the middleware, so middleware and handlers running
+/// within it can queue headers via `ResponseExtensions::borrow_mut_from`.
+#[derive(Clone, Debug, Default)]
+pub struct ResponseExtensions {
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl ResponseExtensions {
+    /// Queues a header to be added to the response.
+    ///
+    /// Headers are appended to the response, so a name queued more than once is sent once for
+    /// each value, alongside any value set by the handler itself.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or value is not valid within a header. A `HeaderValue` is always
+    /// accepted, so values derived from the request should be converted to one beforehand.
+    pub fn push<N, V>(&mut self, name: N, value: V)
+    where
+        HeaderName: HttpTryFrom<N>,
+        HeaderValue: HttpTryFrom<V>,
+    {
+        let name = HeaderName::try_from(name)
+            .map_err(Into::<http::Error>::into)
+            .expect("invalid header name");
+
+        let value = HeaderValue::try_from(value)
+            .map_err(Into::<http::Error>::into)
+            .expect("invalid header value");
+
+        self.headers.push((name, value));
+    }
+
+    /// Returns the queued headers, in the order they were queued.
+    pub fn headers(&self) -> &[(HeaderName, HeaderValue)] {
+        &self.headers
+    }
+}
+
+impl StateData for ResponseExtensions {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::CACHE_CONTROL;
+    use hyper::{Body, Response};
+
+    use crate::state::FromState;
+
+    #[test]
+    fn applies_queued_headers() {
+        let state = State::new();
+
+        let (_, response) = ResponseExtensionApplierMiddleware::new()
+            .call(state, |mut state| {
+                {
+                    let extensions = ResponseExtensions::borrow_mut_from(&mut state);
+                    extensions.push("x-cache-status", "HIT");
+                    extensions.push(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+                    assert_eq!(extensions.headers().len(), 2);
+                }
+
+                let response = Response::builder()
+                    .header(CACHE_CONTROL, "private")
+                    .body(Body::empty())
+                    .unwrap();
+
+                Box::new(future::ok((state, response)))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap();
+
+        let headers = response.headers();
+        assert_eq!(headers["x-cache-status"], "HIT");
+
+        let cache_control: Vec<_> = headers.get_all(CACHE_CONTROL).iter().collect();
+        assert_eq!(cache_control, vec!["private", "no-store"]);
+    }
+}
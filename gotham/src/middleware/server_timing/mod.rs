@@ -0,0 +1,229 @@
+//! Server timing middleware, used to report request timings to the client.
+//!
+//! Timings are sent via the `Server-Timing` header, as defined by the
+//! [Server Timing](https://www.w3.org/TR/server-timing/) specification, where they're displayed
+//! by the developer tools of most browsers.
+use std::fmt::Write;
+use std::io;
+use std::time::{Duration, Instant};
+
+use futures::{future, Future};
+use hyper::header::HeaderValue;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::header::SERVER_TIMING;
+use crate::helpers::timing::Timer;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{FromState, State, StateData};
+
+/// Middleware binding to attach request timings to the `Server-Timing` header.
+///
+/// The total time taken to produce the response is always reported as `total`, measured from
+/// the time the request was received. Any timings recorded by handlers via `ServerTimings` are
+/// written after it, in the order they were recorded:
+///
+/// ```plain
+/// Server-Timing: total;dur=12.3, db;dur=8.04, render;dur=1.2
+/// ```
+///
+/// Durations are written in milliseconds, with up to three decimal places. Any `Server-Timing`
+/// header already set on the response is kept, with these timings appended to it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ServerTimingMiddleware;
+
+impl ServerTimingMiddleware {
+    /// Creates a new `ServerTimingMiddleware`.
+    pub fn new() -> Self {
+        ServerTimingMiddleware
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for ServerTimingMiddleware {
+    /// Attaches the request timings to the response headers.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        // measure from the time the request was received
+        let timer = Timer::from_state(&state);
+
+        state.put(ServerTimings::default());
+
+        // execute the chain and attach the timings on complete
+        let f = chain(state).and_then(move |(mut state, mut response)| {
+            let mut value = String::new();
+
+            write_metric(&mut value, "total", timer.elapsed().as_duration());
+
+            if let Some(timings) = state.try_take::<ServerTimings>() {
+                for (name, duration) in timings.metrics {
+                    value.push_str(", ");
+                    write_metric(&mut value, &name, duration);
+                }
+            }
+
+            // keep any timings already written by the handler
+            let headers = response.headers_mut();
+            if let Some(existing) = headers.get(SERVER_TIMING).and_then(|v| v.to_str().ok()) {
+                value = format!("{}, {}", existing, value);
+            }
+
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(SERVER_TIMING, value);
+            }
+
+            future::ok((state, response))
+        });
+
+        Box::new(f)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for ServerTimingMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+/// Named timings recorded while handling a request, written by the `ServerTimingMiddleware`.
+///
+/// An empty set of timings is placed into `State` by the middleware, so handlers can record
+/// timings for the parts of the request they're interested in:
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response};
+/// # use gotham::middleware::server_timing::ServerTimings;
+/// # use gotham::state::State;
+/// #
+/// fn my_handler(mut state: State) -> (State, Response<Body>) {
+///     let rows = ServerTimings::time(&mut state, "db", || vec!["row"; 3]);
+///     # let _ = rows;
+///     # (state, Response::new(Body::empty()))
+/// }
+/// # fn main() { let _ = my_handler; }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ServerTimings {
+    metrics: Vec<(String, Duration)>,
+}
+
+impl ServerTimings {
+    /// Records a timing with the provided name.
+    ///
+    /// Names are HTTP tokens; any other characters are replaced with `_`. Recording the same
+    /// name more than once writes each timing separately.
+    pub fn record<N>(&mut self, name: N, duration: Duration)
+    where
+        N: Into<String>,
+    {
+        let name = name
+            .into()
+            .chars()
+            .map(|c| if is_tchar(c) { c } else { '_' })
+            .collect();
+
+        self.metrics.push((name, duration));
+    }
+
+    /// Runs the provided function, recording the time it takes into the timings in `State`.
+    ///
+    /// This does nothing beyond running the function when the `ServerTimingMiddleware` isn't
+    /// in use.
+    pub fn time<N, F, T>(state: &mut State, name: N, f: F) -> T
+    where
+        N: Into<String>,
+        F: FnOnce() -> T,
+    {
+        let start = Instant::now();
+        let value = f();
+
+        if let Some(timings) = ServerTimings::try_borrow_mut_from(state) {
+            timings.record(name, start.elapsed());
+        }
+
+        value
+    }
+
+    /// Returns the recorded timings, in the order they were recorded.
+    pub fn metrics(&self) -> &[(String, Duration)] {
+        &self.metrics
+    }
+}
+
+impl StateData for ServerTimings {}
+
+/// Writes a single `name;dur=ms` metric.
+fn write_metric(out: &mut String, name: &str, duration: Duration) {
+    let millis = format!("{:.3}", duration.as_nanos() as f64 / 1_000_000.0);
+    let millis = millis.trim_end_matches('0').trim_end_matches('.');
+
+    let _ = write!(out, "{};dur={}", name, millis);
+}
+
+/// Determines whether a character is valid within an HTTP token.
+fn is_tchar(c: char) -> bool {
+    match c {
+        'a'..='z' | 'A'..='Z' | '0'..='9' => true,
+        _ => "!#$%&'*+-.^_`|~".contains(c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, Response};
+
+    #[test]
+    fn writes_server_timing_header() {
+        let state = State::new();
+
+        let (_, response) = ServerTimingMiddleware::new()
+            .call(state, |mut state| {
+                ServerTimings::borrow_mut_from(&mut state)
+                    .record("db query", Duration::from_micros(8_040));
+                ServerTimings::time(&mut state, "render", || ());
+
+                let response = Response::builder()
+                    .header(SERVER_TIMING, "cache;desc=\"hit\"")
+                    .body(Body::empty())
+                    .unwrap();
+
+                Box::new(future::ok((state, response)))
+            })
+            .wait()
+            .map_err(|_| ())
+            .unwrap();
+
+        let value = response.headers()[SERVER_TIMING].to_str().unwrap();
+        let metrics: Vec<&str> = value.split(", ").collect();
+
+        assert_eq!(metrics.len(), 4);
+        assert_eq!(metrics[0], "cache;desc=\"hit\"");
+        assert!(metrics[1].starts_with("total;dur="));
+        assert_eq!(metrics[2], "db_query;dur=8.04");
+        assert!(metrics[3].starts_with("render;dur="));
+    }
+
+    #[test]
+    fn formats_milliseconds() {
+        let format = |duration| {
+            let mut out = String::new();
+            write_metric(&mut out, "m", duration);
+            out
+        };
+
+        assert_eq!(format(Duration::from_micros(12_300)), "m;dur=12.3");
+        assert_eq!(format(Duration::from_millis(5)), "m;dur=5");
+        assert_eq!(format(Duration::from_nanos(42_100)), "m;dur=0.042");
+        assert_eq!(format(Duration::from_nanos(100)), "m;dur=0");
+    }
+}
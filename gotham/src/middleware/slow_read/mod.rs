@@ -0,0 +1,266 @@
+//! Middleware to protect against clients which send requests too slowly.
+//!
+//! "Slowloris" style attacks hold many connections open by trickling bytes to the server, tying
+//! up the resources dedicated to each connection. This middleware bounds the time a client has to
+//! send the headers and body of a request. As headers are read before any middleware runs, a
+//! client which never finishes sending them is only cut off by the server, via
+//! `ServerOptions::header_read_timeout`.
+use std::error::Error;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::{future, Async, Future, Poll, Stream};
+use hyper::header::{HeaderValue, CONNECTION};
+use hyper::{Body, Chunk, Response, StatusCode};
+use log::{debug, trace};
+use tokio::timer::Delay;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::server::head::HeadReadTime;
+use crate::state::{request_id, FromState, State};
+
+/// Middleware binding to bound the time taken by a client to send a request.
+///
+/// Requests whose headers took longer than the header timeout to arrive, measured from when the
+/// connection opened or the first byte following the previous request, are rejected without
+/// running the rest of the chain. The request body in `State` is replaced by one which fails with
+/// an error of kind `TimedOut` once the body timeout has elapsed, measured from when the
+/// middleware runs, after which the response from the rest of the chain (or its error) is
+/// replaced. Either way the client receives a `408 Request Timeout` with a `Connection: close`
+/// header, so that the connection isn't reused.
+///
+/// Only handlers which read the body are affected by the body timeout; a body which is never read
+/// never times out.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use std::time::Duration;
+/// # use gotham::middleware::slow_read::SlowReadProtectionMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::server::ServerOptions;
+/// let header_timeout = Duration::from_secs(10);
+/// let protection = SlowReadProtectionMiddleware::new(header_timeout, Duration::from_secs(30));
+///
+/// let pipeline = new_pipeline().add(protection).build();
+///
+/// // closes connections which never finish sending their headers
+/// let options = ServerOptions::new().header_read_timeout(Some(header_timeout));
+/// # let _ = (pipeline, options);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct SlowReadProtectionMiddleware {
+    header_timeout: Duration,
+    body_timeout: Duration,
+}
+
+impl SlowReadProtectionMiddleware {
+    /// Creates a new `SlowReadProtectionMiddleware`, allowing clients up to `header_timeout` to
+    /// send the headers of a request and up to `body_timeout` to send its body.
+    pub fn new(header_timeout: Duration, body_timeout: Duration) -> Self {
+        SlowReadProtectionMiddleware {
+            header_timeout,
+            body_timeout,
+        }
+    }
+}
+
+/// Creates the `408 Request Timeout` sent to clients which are too slow.
+fn timeout_response(state: &State) -> Response<Body> {
+    let mut response = create_empty_response(state, StatusCode::REQUEST_TIMEOUT);
+    response
+        .headers_mut()
+        .insert(CONNECTION, HeaderValue::from_static("close"));
+    response
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for SlowReadProtectionMiddleware {
+    /// Applies the deadlines to the request, and responds with a `408` when either passes.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        let head_timed_out = HeadReadTime::try_borrow_from(&state)
+            .map_or(false, |&HeadReadTime(elapsed)| {
+                elapsed > self.header_timeout
+            });
+
+        if head_timed_out {
+            debug!("[{}] request headers timed out", request_id(&state));
+
+            let response = timeout_response(&state);
+            return Box::new(future::ok((state, response)));
+        }
+
+        let timed_out = Arc::new(AtomicBool::new(false));
+
+        let body = Deadline {
+            body: state.take::<Body>(),
+            delay: Some(Delay::new(Instant::now() + self.body_timeout)),
+            timed_out: timed_out.clone(),
+        };
+        state.put(Body::wrap_stream(body));
+
+        let f = chain(state).then(move |result| {
+            if !timed_out.load(Ordering::SeqCst) {
+                return result;
+            }
+
+            let state = match result {
+                Ok((state, _)) | Err((state, _)) => state,
+            };
+
+            debug!("[{}] request body timed out", request_id(&state));
+
+            let response = timeout_response(&state);
+            Ok((state, response))
+        });
+
+        Box::new(f)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for SlowReadProtectionMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+/// A request body which fails once its deadline has passed.
+struct Deadline {
+    body: Body,
+    delay: Option<Delay>,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl Stream for Deadline {
+    type Item = Chunk;
+    type Error = Box<dyn Error + Send + Sync>;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, Self::Error> {
+        let expired = match self.delay.as_mut().map(Future::poll) {
+            Some(Ok(Async::Ready(()))) => true,
+            Some(Ok(Async::NotReady)) | None => false,
+            Some(Err(e)) => {
+                // without a timer the body can't be bounded, so it's read as usual
+                trace!("unable to apply request body timeout: {}", e);
+                self.delay = None;
+                false
+            }
+        };
+
+        if expired {
+            self.delay = None;
+            self.timed_out.store(true, Ordering::SeqCst);
+        }
+
+        if self.timed_out.load(Ordering::SeqCst) {
+            let err = io::Error::new(io::ErrorKind::TimedOut, "request body timed out");
+            return Err(Box::new(err));
+        }
+
+        self.body.poll().map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::HeaderMap;
+    use tokio::runtime::Runtime;
+
+    use crate::handler::IntoHandlerError;
+
+    fn read_body(mut state: State) -> Box<HandlerFuture> {
+        let f = state.take::<Body>().concat2().then(|result| match result {
+            Ok(body) => Ok((state, Response::new(Body::from(body)))),
+            Err(e) => Err((state, e.into_handler_error())),
+        });
+
+        Box::new(f)
+    }
+
+    fn state(body: Body) -> State {
+        let mut state = State::new();
+        state.put(body);
+        state.put(HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+        state
+    }
+
+    fn run(body: Body, timeout: Duration) -> Response<Body> {
+        let state = state(body);
+
+        let f = SlowReadProtectionMiddleware::new(Duration::from_secs(5), timeout)
+            .call(state, read_body);
+
+        let mut runtime = Runtime::new().unwrap();
+        runtime.block_on(f).map_err(|_| ()).unwrap().1
+    }
+
+    #[test]
+    fn times_out_slow_bodies() {
+        let (mut sender, body) = Body::channel();
+        sender.send_data(Chunk::from("partial")).unwrap();
+
+        // the sender is kept alive, so the body never completes
+        let response = run(body, Duration::from_millis(50));
+        drop(sender);
+
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        assert_eq!(response.headers()[CONNECTION], "close");
+    }
+
+    #[test]
+    fn passes_complete_bodies() {
+        let response = run(Body::from("complete"), Duration::from_secs(5));
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().concat2().wait().unwrap();
+        assert_eq!(&body[..], b"complete");
+
+        // bodies which are never read are unaffected
+        let state = state(Body::empty());
+        let protection =
+            SlowReadProtectionMiddleware::new(Duration::from_secs(5), Duration::from_millis(0));
+        let f = protection.call(state, |state| {
+            Box::new(future::ok((state, Response::new(Body::empty()))))
+        });
+
+        let response = Runtime::new()
+            .unwrap()
+            .block_on(f)
+            .map_err(|_| ())
+            .unwrap()
+            .1;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn rejects_slow_headers() {
+        let mut state = state(Body::empty());
+        state.put(HeadReadTime(Duration::from_secs(2)));
+
+        let protection =
+            SlowReadProtectionMiddleware::new(Duration::from_secs(1), Duration::from_secs(5));
+        let f = protection.call(state, |_| unreachable!("the chain shouldn't run"));
+
+        let response = Runtime::new()
+            .unwrap()
+            .block_on(f)
+            .map_err(|_| ())
+            .unwrap()
+            .1;
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        assert_eq!(response.headers()[CONNECTION], "close");
+    }
+}
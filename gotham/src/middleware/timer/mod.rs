@@ -21,8 +21,8 @@ impl Middleware for RequestTimer {
     where
         Chain: FnOnce(State) -> Box<HandlerFuture>,
     {
-        // start the timer
-        let timer = Timer::new();
+        // measure from the time the request was received
+        let timer = Timer::from_state(&state);
 
         // execute the chain and attach the time on complete
         let f = chain(state).and_then(move |(state, mut response)| {
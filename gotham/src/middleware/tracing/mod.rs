@@ -0,0 +1,203 @@
+//! Tracing middleware, used to wrap each request in a span from the `tracing` crate.
+//!
+//! This middleware is only available with the `tracing` feature enabled, and can be used
+//! alongside the `log` based middleware found in `gotham::middleware::logger`.
+use std::io;
+use std::time::Instant;
+
+use ::tracing::field::Empty;
+use ::tracing::{info_span, Span};
+use futures::{future, Future};
+use hyper::{Method, Uri};
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, RequestStart, State};
+
+/// Middleware binding to wrap each request in a `tracing` span.
+///
+/// A span named `request` is entered for the lifetime of the request, carrying the `method`,
+/// `path` and `request_id` fields. Any events emitted by later middleware and handlers are
+/// recorded within this span. Once the response is ready, the `status` and `latency_us`
+/// (in microseconds) fields are recorded before the span is closed.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::tracing::TracingMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// let pipeline = new_pipeline().add(TracingMiddleware::new()).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingMiddleware;
+
+impl TracingMiddleware {
+    /// Constructs a new `TracingMiddleware` instance.
+    pub fn new() -> Self {
+        TracingMiddleware
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for TracingMiddleware {
+    /// Wraps the rest of the chain in a span, recording the outcome on completion.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        // measure from the time the request was received
+        let started = RequestStart::try_borrow_from(&state)
+            .map(RequestStart::instant)
+            .unwrap_or_else(Instant::now);
+
+        let span = info_span!(
+            "request",
+            method = %Method::borrow_from(&state),
+            path = %Uri::borrow_from(&state).path(),
+            request_id = %request_id(&state),
+            status = Empty,
+            latency_us = Empty,
+        );
+
+        // the chain may do work before returning a future, so enter the span for that too
+        let f = span.in_scope(|| chain(state));
+        let f = instrument(f, span.clone());
+
+        let f = f.then(move |result| {
+            let status = match result {
+                Ok((_, ref response)) => response.status(),
+                Err((_, ref err)) => err.status(),
+            };
+
+            let elapsed = started.elapsed();
+            let latency = elapsed.as_secs() * 1_000_000 + u64::from(elapsed.subsec_micros());
+
+            span.record("status", status.as_u16());
+            span.record("latency_us", latency);
+
+            result
+        });
+
+        Box::new(f)
+    }
+}
+
+/// Enters the span each time the future is polled, so its events are recorded within the span.
+pub(crate) fn instrument(mut f: Box<HandlerFuture>, span: Span) -> Box<HandlerFuture> {
+    Box::new(future::poll_fn(move || {
+        let _entered = span.enter();
+        f.poll()
+    }))
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for TracingMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    use ::tracing::field::{Field, Visit};
+    use ::tracing::span::{Attributes, Id, Record};
+    use ::tracing::{Event, Metadata, Subscriber};
+    use futures::future;
+    use hyper::{Body, HeaderMap, Response, StatusCode};
+
+    use crate::handler::IntoHandlerError;
+
+    // collects all span fields as `name=value` strings, in the order recorded
+    #[derive(Clone, Default)]
+    struct Collector(Arc<Mutex<Vec<String>>>);
+
+    impl Visit for Collector {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            let entry = format!("{}={:?}", field.name(), value);
+            self.0.lock().unwrap().push(entry);
+        }
+    }
+
+    impl Subscriber for Collector {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes) -> Id {
+            span.record(&mut self.clone());
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _: &Id, values: &Record) {
+            values.record(&mut self.clone());
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    fn trace(result: Result<StatusCode, StatusCode>) -> Vec<String> {
+        let mut state = State::new();
+        state.put(Method::GET);
+        state.put("/users/1?page=2".parse::<Uri>().unwrap());
+        state.put(HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+
+        let collector = Collector::default();
+        let fields = collector.0.clone();
+
+        ::tracing::subscriber::with_default(collector, || {
+            let _ = TracingMiddleware::new()
+                .call(state, move |state| match result {
+                    Ok(status) => {
+                        let mut response = Response::new(Body::empty());
+                        *response.status_mut() = status;
+                        Box::new(future::ok((state, response)))
+                    }
+                    Err(status) => {
+                        let err = io::Error::last_os_error()
+                            .into_handler_error()
+                            .with_status(status);
+                        Box::new(future::err((state, err)))
+                    }
+                })
+                .wait();
+        });
+
+        let fields = fields.lock().unwrap();
+        fields.clone()
+    }
+
+    #[test]
+    fn records_request_spans() {
+        let fields = trace(Ok(StatusCode::ACCEPTED));
+
+        assert_eq!(fields[0], "method=GET");
+        assert_eq!(fields[1], "path=/users/1");
+        assert!(fields[2].starts_with("request_id="));
+        assert_eq!(fields[3], "status=202");
+        assert!(fields[4].starts_with("latency_us="));
+        assert_eq!(fields.len(), 5);
+    }
+
+    #[test]
+    fn records_error_statuses() {
+        let fields = trace(Err(StatusCode::BAD_GATEWAY));
+
+        assert_eq!(fields[3], "status=502");
+        assert!(fields[4].starts_with("latency_us="));
+    }
+}
@@ -0,0 +1,130 @@
+//! Middleware to receive file uploads sent as `multipart/form-data` request bodies.
+use std::io;
+
+use futures::Future;
+use hyper::HeaderMap;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::request::multipart::{self, parse_multipart, UploadConfig};
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{FromState, State};
+
+/// Middleware binding to parse `multipart/form-data` request bodies ahead of the handler.
+///
+/// Multipart requests are parsed via `parse_multipart`, using the provided `UploadConfig`, and
+/// the handler can then borrow the resulting `Multipart` from `State`. Any uploaded files are
+/// stored in temporary files, which are removed once the request has completed. Requests which
+/// aren't `multipart/form-data` are passed through untouched.
+///
+/// Requests are rejected before reaching the handler when a file is too large (`413`), has a
+/// type which isn't allowed (`415`), or the body is malformed (`400`).
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate mime;
+/// # use gotham::helpers::http::request::multipart::UploadConfig;
+/// # use gotham::middleware::upload::UploadMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// let config = UploadConfig::new()
+///     .max_file_size(10 * 1024 * 1024)
+///     .allowed_types(vec![mime::IMAGE_JPEG, mime::IMAGE_PNG]);
+///
+/// let pipeline = new_pipeline().add(UploadMiddleware::new(config)).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct UploadMiddleware {
+    config: UploadConfig,
+}
+
+impl UploadMiddleware {
+    /// Creates a new `UploadMiddleware`, validating uploads against the provided configuration.
+    pub fn new(config: UploadConfig) -> Self {
+        UploadMiddleware { config }
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for UploadMiddleware {
+    /// Parses multipart request bodies ahead of the rest of the chain.
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        if multipart::boundary(HeaderMap::borrow_from(&state)).is_none() {
+            return chain(state);
+        }
+
+        Box::new(parse_multipart(state, &self.config).and_then(chain))
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for UploadMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::future;
+    use hyper::header::{HeaderValue, CONTENT_TYPE};
+    use hyper::{Body, Response, StatusCode};
+
+    use crate::handler::IntoResponse;
+    use crate::helpers::http::request::multipart::Multipart;
+
+    fn call(content_type: &'static str, body: &'static str) -> (State, Response<Body>) {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+
+        let mut state = State::new();
+        state.put(headers);
+        state.put(Body::from(body));
+        crate::state::set_request_id(&mut state);
+
+        UploadMiddleware::new(UploadConfig::new().allowed_types(vec![mime::TEXT_PLAIN]))
+            .call(state, |state| {
+                let status = match Multipart::try_borrow_from(&state) {
+                    Some(multipart) => match multipart.file("notes") {
+                        Some(file) if file.size() == 5 => StatusCode::OK,
+                        _ => StatusCode::BAD_REQUEST,
+                    },
+                    None => StatusCode::NO_CONTENT,
+                };
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = status;
+                Box::new(future::ok((state, response)))
+            })
+            .wait()
+            .unwrap_or_else(|(state, err)| {
+                let response = err.into_response(&state);
+                (state, response)
+            })
+    }
+
+    #[test]
+    fn parses_uploads_before_the_handler() {
+        let body = "--b\r\n\
+                    Content-Disposition: form-data; name=\"notes\"; filename=\"a.txt\"\r\n\
+                    Content-Type: text/plain\r\n\
+                    \r\n\
+                    hello\r\n\
+                    --b--";
+
+        let (_, response) = call("multipart/form-data; boundary=b", body);
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let (_, response) = call("multipart/form-data; boundary=b", &body[..20]);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let (_, response) = call("text/plain", "hello");
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+}
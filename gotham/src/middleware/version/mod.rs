@@ -0,0 +1,74 @@
+//! Middleware to determine the API version requested by a client.
+use std::io;
+
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::request::version::{api_version, RequestVersion, VersionStrategy};
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, State};
+
+/// Middleware binding to store the requested `RequestVersion` in `State`.
+///
+/// The version is determined using the configured `VersionStrategy`. When a request doesn't
+/// specify a version, the default version (if any) is stored instead.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::helpers::http::request::version::VersionStrategy;
+/// # use gotham::middleware::version::ApiVersionMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// let versions = ApiVersionMiddleware::new(VersionStrategy::AcceptHeader).default_version(1);
+///
+/// let pipeline = new_pipeline().add(versions).build();
+/// # let _ = pipeline;
+/// ```
+#[derive(Clone, Copy)]
+pub struct ApiVersionMiddleware {
+    strategy: VersionStrategy,
+    default_version: Option<u32>,
+}
+
+impl ApiVersionMiddleware {
+    /// Creates a new `ApiVersionMiddleware` using the provided strategy.
+    pub fn new(strategy: VersionStrategy) -> Self {
+        ApiVersionMiddleware {
+            strategy,
+            default_version: None,
+        }
+    }
+
+    /// Sets the version assumed for requests which don't specify one.
+    pub fn default_version(mut self, version: u32) -> Self {
+        self.default_version = Some(version);
+        self
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for ApiVersionMiddleware {
+    /// Stores the requested version in `State`, if one can be determined.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        let version = api_version(&state, self.strategy).or(self.default_version);
+
+        if let Some(version) = version {
+            trace!("[{}] requested api version {}", request_id(&state), version);
+            state.put(RequestVersion(version));
+        }
+
+        chain(state)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for ApiVersionMiddleware {
+    type Instance = Self;
+
+    /// Copies the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
@@ -31,6 +31,10 @@ pub trait PipelineHandleChain<P>: RefUnwindSafe {
     fn call<F>(&self, pipelines: &PipelineSet<P>, state: State, f: F) -> Box<HandlerFuture>
     where
         F: FnOnce(State) -> Box<HandlerFuture> + Send + 'static;
+
+    /// Appends the names of all `Middleware` in this `PipelineHandleChain` to `names`, in the
+    /// order they're invoked when a request is dispatched.
+    fn middleware_names(&self, pipelines: &PipelineSet<P>, names: &mut Vec<&'static str>);
 }
 
 /// Part of a `PipelineHandleChain` which references a `Pipeline` and continues with a tail element.
@@ -55,6 +59,63 @@ where
             }
         }
     }
+
+    fn middleware_names(&self, pipelines: &PipelineSet<P>, names: &mut Vec<&'static str>) {
+        let (handle, ref chain) = *self;
+        chain.middleware_names(pipelines, names);
+        names.extend(pipelines.borrow(handle).middleware_names());
+    }
+}
+
+/// A `PipelineHandleChain` which continues another chain with a `Pipeline` belonging to a single
+/// route, rather than one borrowed from the `PipelineSet`.
+///
+/// This is created by `DefineSingleRoute::with_middleware`, and is invoked after every `Pipeline`
+/// of the chain it wraps.
+pub struct RoutePipelineChain<T, C>
+where
+    T: NewMiddlewareChain,
+{
+    pipeline: Pipeline<T>,
+    chain: C,
+}
+
+impl<T, C> RoutePipelineChain<T, C>
+where
+    T: NewMiddlewareChain,
+{
+    /// Creates a `RoutePipelineChain` invoking `pipeline` once `chain` has passed the request
+    /// through.
+    pub fn new(pipeline: Pipeline<T>, chain: C) -> Self {
+        RoutePipelineChain { pipeline, chain }
+    }
+}
+
+impl<P, T, C> PipelineHandleChain<P> for RoutePipelineChain<T, C>
+where
+    T: NewMiddlewareChain,
+    T::Instance: Send + 'static,
+    C: PipelineHandleChain<P>,
+{
+    fn call<F>(&self, pipelines: &PipelineSet<P>, state: State, f: F) -> Box<HandlerFuture>
+    where
+        F: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        match self.pipeline.construct() {
+            Ok(p) => self
+                .chain
+                .call(pipelines, state, move |state| p.call(state, f)),
+            Err(e) => {
+                trace!("[{}] error constructing route pipeline", request_id(&state));
+                Box::new(future::err((state, e.into_handler_error())))
+            }
+        }
+    }
+
+    fn middleware_names(&self, pipelines: &PipelineSet<P>, names: &mut Vec<&'static str>) {
+        self.chain.middleware_names(pipelines, names);
+        names.extend(self.pipeline.middleware_names());
+    }
 }
 
 /// The marker for the end of a `PipelineHandleChain`.
@@ -66,4 +127,6 @@ impl<P> PipelineHandleChain<P> for () {
         trace!("[{}] start pipeline", request_id(&state));
         f(state)
     }
+
+    fn middleware_names(&self, _: &PipelineSet<P>, _: &mut Vec<&'static str>) {}
 }
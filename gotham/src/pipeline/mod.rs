@@ -144,6 +144,13 @@ where
             chain: self.chain.construct()?,
         })
     }
+
+    /// Returns the names of the `Middleware` in this `Pipeline`, in the order they're invoked.
+    pub fn middleware_names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        self.chain.middleware_names(&mut names);
+        names
+    }
 }
 
 impl<T> PipelineInstance<T>
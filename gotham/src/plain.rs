@@ -1,13 +1,11 @@
-use futures::{Future, Stream};
-use hyper::server::conn::Http;
-use log::info;
+use futures::Future;
+use log::{debug, info};
 use std::net::ToSocketAddrs;
-use std::sync::Arc;
-use tokio::executor;
 use tokio::net::TcpListener;
 use tokio::runtime::TaskExecutor;
 
-use super::{handler::NewHandler, service::GothamService};
+use super::handler::NewHandler;
+use super::server::{serve, ServerOptions};
 use super::{new_runtime, tcp_listener};
 
 pub mod test;
@@ -32,6 +30,19 @@ where
     runtime.shutdown_on_idle().wait().unwrap();
 }
 
+/// Starts a Gotham application using the provided `ServerOptions`.
+pub fn start_with_options<NH, A>(addr: A, new_handler: NH, options: ServerOptions)
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static,
+{
+    let runtime = new_runtime(num_cpus::get());
+    runtime
+        .executor()
+        .spawn(init_server_with_options(addr, new_handler, options));
+    runtime.shutdown_on_idle().wait().unwrap();
+}
+
 /// Starts a Gotham application with a designated backing `TaskExecutor`.
 ///
 /// This function can be used to spawn the server on an existing `Runtime`.
@@ -49,6 +60,22 @@ where
 /// manual wiring that isn't supported by the Gotham API. It's unlikely that this will
 /// be required in most use cases; it's mainly exposed for shutdown handling.
 pub fn init_server<NH, A>(addr: A, new_handler: NH) -> impl Future<Item = (), Error = ()>
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static,
+{
+    init_server_with_options(addr, new_handler, ServerOptions::default())
+}
+
+/// Returns a `Future` used to spawn a Gotham application using the provided `ServerOptions`.
+///
+/// This is the same as `init_server`, but allows tuning of the connection handling, such as
+/// HTTP keep-alive behaviour.
+pub fn init_server_with_options<NH, A>(
+    addr: A,
+    new_handler: NH,
+    options: ServerOptions,
+) -> impl Future<Item = (), Error = ()>
 where
     NH: NewHandler + 'static,
     A: ToSocketAddrs + 'static,
@@ -62,26 +89,30 @@ where
     addr
     );
 
-    bind_server(listener, new_handler)
+    bind_server(listener, new_handler, options)
 }
 
-fn bind_server<NH>(listener: TcpListener, new_handler: NH) -> impl Future<Item = (), Error = ()>
+fn bind_server<NH>(
+    listener: TcpListener,
+    new_handler: NH,
+    options: ServerOptions,
+) -> impl Future<Item = (), Error = ()>
 where
     NH: NewHandler + 'static,
 {
-    let protocol = Arc::new(Http::new());
-    let gotham_service = GothamService::new(new_handler);
-
-    listener
-        .incoming()
-        .map_err(|e| panic!("socket error = {:?}", e))
-        .for_each(move |socket| {
-            let addr = socket.peer_addr().unwrap();
-            let service = gotham_service.connect(addr);
-            let handler = protocol.serve_connection(socket, service).then(|_| Ok(()));
-
-            executor::spawn(handler);
-
-            Ok(())
-        })
+    serve(
+        listener.incoming(),
+        new_handler,
+        options,
+        |socket, service| {
+            // the client may have reset the connection before it was accepted
+            match socket.peer_addr() {
+                Ok(addr) => Ok((socket, service.connect(addr))),
+                Err(e) => {
+                    debug!("closing connection without a peer address: {}", e);
+                    Err(())
+                }
+            }
+        },
+    )
 }
@@ -21,6 +21,7 @@ use tokio::timer::Delay;
 use tokio::net::TcpStream;
 
 use crate::handler::NewHandler;
+use crate::server::ServerOptions;
 
 use crate::error::*;
 
@@ -101,12 +102,30 @@ impl TestServer {
     pub fn with_timeout<NH: NewHandler + 'static>(
         new_handler: NH,
         timeout: u64,
+    ) -> Result<TestServer> {
+        TestServer::with_timeout_and_options(new_handler, timeout, ServerOptions::default())
+    }
+
+    /// Creates a `TestServer` which applies the provided `ServerOptions` to its connections.
+    ///
+    /// Timeout will be set to 10 seconds.
+    pub fn with_options<NH: NewHandler + 'static>(
+        new_handler: NH,
+        options: ServerOptions,
+    ) -> Result<TestServer> {
+        TestServer::with_timeout_and_options(new_handler, 10, options)
+    }
+
+    fn with_timeout_and_options<NH: NewHandler + 'static>(
+        new_handler: NH,
+        timeout: u64,
+        options: ServerOptions,
     ) -> Result<TestServer> {
         let mut runtime = Runtime::new()?;
         let listener = TcpListener::bind(&"127.0.0.1:0".parse()?)?;
         let addr = listener.local_addr()?;
 
-        let service_stream = super::bind_server(listener, new_handler);
+        let service_stream = super::bind_server(listener, new_handler, options);
         runtime.spawn(service_stream);
 
         let data = TestServerData {
@@ -198,7 +217,7 @@ mod tests {
 
     use std::time::{SystemTime, UNIX_EPOCH};
 
-    use hyper::header::CONTENT_LENGTH;
+    use hyper::header::{CONNECTION, CONTENT_LENGTH};
     use hyper::{Body, Response, StatusCode, Uri};
     use mime;
 
@@ -277,6 +296,178 @@ mod tests {
         assert_eq!(buf, format!("time: {}", ticks));
     }
 
+    #[test]
+    fn closes_connections_without_keep_alive() {
+        let new_service = || {
+            Ok(TestHandler {
+                response: "".to_owned(),
+            })
+        };
+
+        let test_server = TestServer::new(new_service).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.headers().get(CONNECTION), None);
+
+        let options = ServerOptions::new().keep_alive(false);
+        let test_server = TestServer::with_options(new_service, options).unwrap();
+
+        for _ in 0..2 {
+            let response = test_server
+                .client()
+                .get("http://localhost/")
+                .perform()
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers().get(CONNECTION).unwrap(), "close");
+        }
+    }
+
+    #[test]
+    fn closes_connections_after_max_requests() {
+        let new_service = || {
+            Ok(TestHandler {
+                response: "".to_owned(),
+            })
+        };
+
+        let options = ServerOptions::new().max_requests_per_connection(Some(2));
+        let test_server = TestServer::with_options(new_service, options).unwrap();
+        let client = test_server.client();
+
+        let response = client.get("http://localhost/").perform().unwrap();
+        assert_eq!(response.headers().get(CONNECTION), None);
+
+        let response = client.get("http://localhost/").perform().unwrap();
+        assert_eq!(response.headers().get(CONNECTION).unwrap(), "close");
+    }
+
+    #[test]
+    fn queues_connections_beyond_max_connections() {
+        use std::io::{Read, Write};
+
+        let new_service = || {
+            Ok(TestHandler {
+                response: "".to_owned(),
+            })
+        };
+
+        let options = ServerOptions::new().max_connections(Some(1));
+        let test_server = TestServer::with_options(new_service, options).unwrap();
+
+        let request = |stream: &mut net::TcpStream| {
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_millis(250)))
+                .unwrap();
+
+            let mut buf = [0; 64];
+            stream
+                .read(&mut buf)
+                .map(|n| String::from_utf8_lossy(&buf[..n]).into_owned())
+        };
+
+        // the first connection is kept alive, holding the only slot
+        let mut first = net::TcpStream::connect(test_server.data.addr).unwrap();
+        assert!(request(&mut first).unwrap().starts_with("HTTP/1.1 200"));
+
+        // the second connection is not accepted while the first is open
+        let mut second = net::TcpStream::connect(test_server.data.addr).unwrap();
+        assert!(request(&mut second).is_err());
+
+        drop(first);
+
+        second
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let mut buf = [0; 64];
+        let n = second.read(&mut buf).unwrap();
+        assert!(buf[..n].starts_with(b"HTTP/1.1 200"));
+    }
+
+    #[test]
+    fn times_out_slow_request_heads() {
+        use std::io::{Read, Write};
+        use std::thread;
+
+        let new_service = || {
+            Ok(TestHandler {
+                response: "".to_owned(),
+            })
+        };
+
+        let timeout = Duration::from_millis(200);
+        let options = ServerOptions::new().header_read_timeout(Some(timeout));
+        let test_server = TestServer::with_options(new_service, options).unwrap();
+
+        let started = Instant::now();
+        let mut stream = net::TcpStream::connect(test_server.data.addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // the head of the first request is dripped a byte at a time, and never completed
+        for byte in b"GET" {
+            stream.write_all(&[*byte]).unwrap();
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+        assert!(started.elapsed() >= timeout);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn continues_expected_uploads() {
+        use std::io::{Read, Write};
+
+        fn handler(mut state: State) -> Box<HandlerFuture> {
+            let f = Body::take_from(&mut state).concat2().then(move |body| {
+                let res = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, body.unwrap());
+                future::ok((state, res))
+            });
+
+            Box::new(f)
+        }
+
+        let test_server = TestServer::new(|| Ok(handler)).unwrap();
+        let mut stream = net::TcpStream::connect(test_server.data.addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let read = |stream: &mut net::TcpStream| {
+            let mut buf = [0; 256];
+            let n = stream.read(&mut buf).unwrap();
+            String::from_utf8_lossy(&buf[..n]).into_owned()
+        };
+
+        // the body is only sent once the server has asked for it
+        stream
+            .write_all(
+                b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 6\r\n\
+                  Expect: 100-continue\r\n\r\n",
+            )
+            .unwrap();
+        assert_eq!(read(&mut stream), "HTTP/1.1 100 Continue\r\n\r\n");
+
+        stream.write_all(b"upload").unwrap();
+        let response = read(&mut stream);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\nupload"));
+    }
+
     #[test]
     #[ignore] // XXX I don't understand why this doesn't work.
               // It seems like Hyper is treating the future::empty() as an empty body...
@@ -7,6 +7,7 @@ use crate::extractor::{PathExtractor, QueryStringExtractor};
 use crate::pipeline::chain::PipelineHandleChain;
 use crate::pipeline::set::PipelineSet;
 use crate::router::builder::SingleRouteBuilder;
+use crate::router::route::dispatch::RouteData;
 use crate::router::route::matcher::{
     AndRouteMatcher, AnyRouteMatcher, MethodOnlyRouteMatcher, RouteMatcher,
 };
@@ -330,6 +331,7 @@ where
             matcher: AndRouteMatcher::new(MethodOnlyRouteMatcher::new(methods), matcher.clone()),
             pipeline_chain: *pipeline_chain,
             pipelines: pipelines.clone(),
+            data: RouteData::default(),
             phantom,
         }
     }
@@ -10,6 +10,7 @@ use crate::pipeline::set::PipelineSet;
 use crate::router::builder::{
     AssociatedRouteBuilder, DelegateRouteBuilder, RouterBuilder, ScopeBuilder, SingleRouteBuilder,
 };
+use crate::router::route::dispatch::RouteData;
 use crate::router::route::matcher::{
     AnyRouteMatcher, IntoRouteMatcher, MethodOnlyRouteMatcher, RouteMatcher,
 };
@@ -361,6 +362,10 @@ where
     /// * `"/hello/world"` - a static path, matching only a request for exactly `"/hello/world"`
     /// * `"/hello/:name"` - a dynamic path, matching requests for `"/hello/any_value_here"`
     ///
+    /// The `methods` can be provided as a `Vec` or a slice of `Method` values. A single route is
+    /// created regardless of how many methods are given, so any extractors and pipelines are
+    /// attached once and shared by all of them.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -401,6 +406,51 @@ where
     /// # }
     /// ```
     ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use hyper::Method;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn my_handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// # }
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route.request(&[Method::POST, Method::PUT], "/request/path").to(my_handler);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .post("https://example.com/request/path", "", mime::TEXT_PLAIN)
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .put("https://example.com/request/path", "", mime::TEXT_PLAIN)
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/request/path")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    /// # }
+    /// ```
+    ///
     /// ```
     /// # extern crate gotham;
     /// # extern crate hyper;
@@ -470,6 +520,7 @@ where
             node_builder,
             pipeline_chain: *pipeline_chain,
             pipelines: pipelines.clone(),
+            data: RouteData::default(),
             phantom: PhantomData,
         }
     }
@@ -863,14 +914,10 @@ where
     fn component_refs(&mut self) -> (&mut Node, &mut C, &PipelineSet<P>);
 }
 
-fn descend<'n>(node_builder: &'n mut Node, path: &str) -> &'n mut Node {
+pub(crate) fn descend<'n>(node_builder: &'n mut Node, path: &str) -> &'n mut Node {
     trace!("[walking to: {}]", path);
 
-    let path = if path.starts_with('/') {
-        &path[1..]
-    } else {
-        path
-    };
+    let path = path.strip_prefix('/').unwrap_or(path);
 
     if path.is_empty() {
         node_builder
@@ -4,30 +4,40 @@ mod associated;
 mod draw;
 mod modify;
 mod single;
+mod versioned;
 
 use std::marker::PhantomData;
 use std::panic::RefUnwindSafe;
+use std::sync::Arc;
 
 use hyper::{Body, StatusCode};
+use mime::Mime;
 
 use crate::extractor::{
     NoopPathExtractor, NoopQueryStringExtractor, PathExtractor, QueryStringExtractor,
 };
+use crate::handler::Handler;
 use crate::pipeline::chain::PipelineHandleChain;
 use crate::pipeline::set::{finalize_pipeline_set, new_pipeline_set, PipelineSet};
 use crate::router::response::extender::ResponseExtender;
 use crate::router::response::finalizer::ResponseFinalizerBuilder;
-use crate::router::route::dispatch::DispatcherImpl;
+use crate::router::response::hook::BeforeSendHook;
+use crate::router::route::dispatch::{Dispatcher, DispatcherImpl, RouteData};
 use crate::router::route::matcher::{AnyRouteMatcher, RouteMatcher};
 use crate::router::route::{Delegation, Extractors, RouteImpl};
 use crate::router::tree::node::Node;
 use crate::router::tree::Tree;
-use crate::router::Router;
+use crate::router::{Fallbacks, Router};
 
 pub use self::associated::{AssociatedRouteBuilder, AssociatedSingleRouteBuilder};
 pub use self::draw::DrawRoutes;
-pub use self::modify::{ExtendRouteMatcher, ReplacePathExtractor, ReplaceQueryStringExtractor};
+pub use self::modify::{
+    ExtendRouteMatcher, ExtendRoutePipeline, ReplacePathExtractor, ReplaceQueryStringExtractor,
+};
 pub use self::single::DefineSingleRoute;
+pub use self::versioned::VersionedRouter;
+
+pub(crate) use self::draw::descend;
 
 /// Builds a `Router` using the provided closure. Routes are defined using the `RouterBuilder`
 /// value passed to the closure, and the `Router` is constructed before returning.
@@ -84,20 +94,27 @@ where
 {
     let mut tree = Tree::new();
 
-    let response_finalizer = {
+    let mut middleware = Vec::new();
+    pipeline_chain.middleware_names(&pipelines, &mut middleware);
+
+    let (response_finalizer, fallbacks) = {
         let mut builder = RouterBuilder {
             node_builder: tree.borrow_root_mut(),
             pipeline_chain,
             pipelines,
             response_finalizer_builder: ResponseFinalizerBuilder::internal_new(),
+            fallbacks: Fallbacks::default(),
         };
 
         f(&mut builder);
 
-        builder.response_finalizer_builder.finalize()
+        (
+            builder.response_finalizer_builder.finalize(),
+            builder.fallbacks,
+        )
     };
 
-    Router::internal_new(tree, response_finalizer)
+    Router::internal_new(tree, response_finalizer, middleware, fallbacks)
 }
 
 /// Builds a `Router` with **no** middleware using the provided closure. Routes are defined using
@@ -152,6 +169,7 @@ where
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
     response_finalizer_builder: ResponseFinalizerBuilder,
+    fallbacks: Fallbacks,
 }
 
 impl<'a, C, P> RouterBuilder<'a, C, P>
@@ -216,6 +234,252 @@ where
         self.response_finalizer_builder
             .add(status_code, Box::new(extender))
     }
+
+    /// Adds a `BeforeSendHook`, which mutates every response of the `Router` just before it is
+    /// sent, after any `ResponseExtender` for the status of the response has run.
+    ///
+    /// Hooks are applied in the order they are added, so each hook sees the changes made by the
+    /// hooks before it. They run after every middleware, can't short-circuit the request, and
+    /// also apply to the `404 Not Found` and `405 Method Not Allowed` responses generated by the
+    /// router; this makes them suitable for adding a correlation header, or stripping internal
+    /// debugging headers from every response.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use hyper::header::{HeaderValue, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::response::hook::BeforeSendHook;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn my_handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::new(Body::empty()))
+    /// # }
+    /// #
+    /// struct NoSniff;
+    ///
+    /// impl BeforeSendHook for NoSniff {
+    ///     fn mutate(&self, _state: &State, response: &mut Response<Body>) {
+    ///         response
+    ///             .headers_mut()
+    ///             .insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    ///     }
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.before_send(NoSniff);
+    ///         route.before_send(|_state: &State, response: &mut Response<Body>| {
+    ///             response
+    ///                 .headers_mut()
+    ///                 .insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    ///         });
+    /// #
+    /// #       route.get("/").to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/missing")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    /// #   assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+    /// #   assert_eq!(response.headers()[X_FRAME_OPTIONS], "DENY");
+    /// # }
+    /// ```
+    pub fn before_send<H>(&mut self, hook: H)
+    where
+        H: BeforeSendHook + Send + Sync + 'static,
+    {
+        self.response_finalizer_builder.add_hook(Box::new(hook))
+    }
+
+    /// Sets a function which fills in the body of error responses which would otherwise be
+    /// empty, such as the `404 Not Found` and `405 Method Not Allowed` generated by the router,
+    /// the `400 Bad Request` of a failed extraction, or a `HandlerError`.
+    ///
+    /// The function receives the status of the response, and returns the content type and body
+    /// to send. It's only applied to `4xx` and `5xx` responses without a body or a
+    /// `Content-Type`, after any `ResponseExtender` for the status and before any
+    /// `BeforeSendHook`; responses to `HEAD` requests are left empty.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::header::CONTENT_TYPE;
+    /// # use hyper::StatusCode;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.error_body(|status: StatusCode| {
+    ///             let body = format!("{{\"status\":{}}}", status.as_u16());
+    ///             (mime::APPLICATION_JSON, body.into_bytes())
+    ///         });
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/missing")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    /// #   assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "{\"status\":404}");
+    /// # }
+    /// ```
+    pub fn error_body<F>(&mut self, template: F)
+    where
+        F: Fn(StatusCode) -> (Mime, Vec<u8>) + Send + Sync + RefUnwindSafe + 'static,
+    {
+        self.response_finalizer_builder.set_error_body(template)
+    }
+
+    /// Directs requests which don't match any route to the provided `Handler`, instead of
+    /// responding with an empty `404 Not Found`.
+    ///
+    /// The handler is invoked via the default pipeline chain of the `Router`, and is responsible
+    /// for setting the status of its response. Requests delegated to another `Router` are handled
+    /// by that router, which needs its own handler.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::response::create_response;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn not_found(state: State) -> (State, Response<Body>) {
+    ///     let res = create_response(
+    ///         &state,
+    ///         StatusCode::NOT_FOUND,
+    ///         mime::TEXT_HTML,
+    ///         "<h1>Nothing to see here</h1>",
+    ///     );
+    ///     (state, res)
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.not_found(not_found);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/missing")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "<h1>Nothing to see here</h1>");
+    /// # }
+    /// ```
+    pub fn not_found<H>(&mut self, handler: H)
+    where
+        H: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+        P: RefUnwindSafe,
+    {
+        self.fallbacks.not_found = Some(self.fallback(handler));
+    }
+
+    /// Directs requests which match the path of a route, but not its method, to the provided
+    /// `Handler`, instead of responding with an empty `405 Method Not Allowed`.
+    ///
+    /// The methods which are allowed for the path are available to the handler as
+    /// `AllowedMethods` in `State`. If the response doesn't include an `Allow` header, one is
+    /// added from these values.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::response::create_response;
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::router::non_match::AllowedMethods;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn method_not_allowed(state: State) -> (State, Response<Body>) {
+    ///     let body = {
+    ///         let allowed = AllowedMethods::borrow_from(&state).methods();
+    ///         let names: Vec<&str> = allowed.iter().map(|method| method.as_str()).collect();
+    ///         format!("{{\"allowed\":{:?}}}", names)
+    ///     };
+    ///
+    ///     let res = create_response(
+    ///         &state,
+    ///         StatusCode::METHOD_NOT_ALLOWED,
+    ///         mime::APPLICATION_JSON,
+    ///         body,
+    ///     );
+    ///     (state, res)
+    /// }
+    ///
+    /// # fn handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::new(Body::empty()))
+    /// # }
+    /// #
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.get("/").to(handler);
+    ///         route.method_not_allowed(method_not_allowed);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .delete("https://example.com/")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    /// #   assert_eq!(response.headers()["allow"], "GET");
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "{\"allowed\":[\"GET\"]}");
+    /// # }
+    /// ```
+    pub fn method_not_allowed<H>(&mut self, handler: H)
+    where
+        H: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+        P: RefUnwindSafe,
+    {
+        self.fallbacks.method_not_allowed = Some(self.fallback(handler));
+    }
+
+    /// Creates a `Dispatcher` for a fallback handler, using the default pipeline chain.
+    fn fallback<H>(&self, handler: H) -> Arc<dyn Dispatcher + Send + Sync>
+    where
+        H: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+        P: RefUnwindSafe,
+    {
+        let new_handler = move || Ok(handler);
+        Arc::new(DispatcherImpl::new(
+            new_handler,
+            self.pipeline_chain,
+            self.pipelines.clone(),
+        ))
+    }
 }
 
 /// A scoped builder, which is created by `DrawRoutes::scope` and passed to the provided closure.
@@ -277,6 +541,7 @@ where
     matcher: M,
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
+    data: RouteData,
     phantom: PhantomData<(PE, QSE)>,
 }
 
@@ -301,6 +566,7 @@ where
             matcher: self.matcher,
             pipeline_chain: self.pipeline_chain,
             pipelines: self.pipelines,
+            data: self.data,
             phantom: PhantomData,
         }
     }
@@ -590,4 +856,278 @@ mod tests {
         let response_bytes = response.into_body().concat2().wait().unwrap().to_vec();
         assert_eq!(&response_bytes[..], b"It's a resource.");
     }
+
+    #[test]
+    fn reports_middleware_in_invocation_order() {
+        use crate::middleware::timer::RequestTimer;
+
+        let pipelines = new_pipeline_set();
+        let (pipelines, outer) = pipelines.add(new_pipeline().add(CookieParser).build());
+        let (pipelines, inner) = pipelines.add(new_pipeline().add(RequestTimer).build());
+
+        let pipelines = finalize_pipeline_set(pipelines);
+
+        let router = build_router((inner, (outer, ())), pipelines, |route| {
+            route.get("/").to(welcome::index);
+        });
+
+        assert_eq!(
+            router.middleware_chain_debug(),
+            vec![
+                std::any::type_name::<CookieParser>(),
+                std::any::type_name::<RequestTimer>(),
+            ]
+        );
+
+        let router = build_simple_router(|route| {
+            route.get("/").to(welcome::index);
+        });
+
+        assert!(router.middleware_chain_debug().is_empty());
+    }
+
+    #[test]
+    fn applies_route_middleware() {
+        use crate::handler::HandlerFuture;
+        use crate::middleware::{Middleware, NewMiddleware};
+        use std::io;
+
+        #[derive(Default)]
+        struct Trail(Vec<&'static str>);
+
+        impl StateData for Trail {}
+
+        #[derive(Clone, Copy)]
+        struct Stamp(&'static str);
+
+        impl NewMiddleware for Stamp {
+            type Instance = Self;
+
+            fn new_middleware(&self) -> io::Result<Self> {
+                Ok(*self)
+            }
+        }
+
+        impl Middleware for Stamp {
+            fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+            where
+                Chain: FnOnce(State) -> Box<HandlerFuture>,
+            {
+                if !state.has::<Trail>() {
+                    state.put(Trail::default());
+                }
+                state.borrow_mut::<Trail>().0.push(self.0);
+                chain(state)
+            }
+        }
+
+        fn trail(mut state: State) -> (State, Response<Body>) {
+            let trail = state.try_take::<Trail>().unwrap_or_default();
+            (state, Response::new(trail.0.join(",").into()))
+        }
+
+        let pipelines = new_pipeline_set();
+        let (pipelines, global) = pipelines.add(new_pipeline().add(Stamp("global")).build());
+        let pipelines = finalize_pipeline_set(pipelines);
+
+        let router = build_router((global, ()), pipelines, |route| {
+            route
+                .get("/admin")
+                .with_middleware(Stamp("first"))
+                .with_middleware(Stamp("second"))
+                .to(trail);
+            route.get("/").to(trail);
+        });
+
+        let new_service = GothamService::new(router);
+        let call = |req| {
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            service.call(req).wait().unwrap()
+        };
+
+        let body = |response: Response<Body>| {
+            let bytes = response.into_body().concat2().wait().unwrap().to_vec();
+            String::from_utf8(bytes).unwrap()
+        };
+
+        let response = call(Request::get("/admin").body(Body::empty()).unwrap());
+        assert_eq!(body(response), "global,first,second");
+
+        let response = call(Request::get("/").body(Body::empty()).unwrap());
+        assert_eq!(body(response), "global");
+    }
+
+    #[test]
+    fn runs_before_send_hooks_last() {
+        use crate::handler::HandlerFuture;
+        use crate::middleware::{Middleware, NewMiddleware};
+        use hyper::header::HeaderValue;
+        use std::io;
+
+        #[derive(Clone, Copy)]
+        struct Debugging;
+
+        impl NewMiddleware for Debugging {
+            type Instance = Self;
+
+            fn new_middleware(&self) -> io::Result<Self> {
+                Ok(*self)
+            }
+        }
+
+        impl Middleware for Debugging {
+            fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+            where
+                Chain: FnOnce(State) -> Box<HandlerFuture>,
+            {
+                let f = chain(state).map(|(state, mut res)| {
+                    let value = HeaderValue::from_static("internal");
+                    res.headers_mut().insert("x-debug", value);
+                    (state, res)
+                });
+
+                Box::new(f)
+            }
+        }
+
+        let pipelines = new_pipeline_set();
+        let (pipelines, global) = pipelines.add(new_pipeline().add(Debugging).build());
+        let pipelines = finalize_pipeline_set(pipelines);
+
+        let router = build_router((global, ()), pipelines, |route| {
+            route.before_send(|_state: &State, res: &mut Response<Body>| {
+                let debugged = res.headers_mut().remove("x-debug").is_some();
+                let value = HeaderValue::from_static(if debugged { "stripped" } else { "none" });
+                res.headers_mut().insert("x-finalized", value);
+            });
+
+            route.get("/").to(welcome::index);
+        });
+
+        let new_service = GothamService::new(router);
+        let call = move |req| {
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            service.call(req).wait().unwrap()
+        };
+
+        let response = call(Request::get("/").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-finalized"], "stripped");
+        assert!(!response.headers().contains_key("x-debug"));
+
+        // router generated responses bypass the pipelines, but not the hooks
+        let response = call(Request::post("/").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()["x-finalized"], "none");
+
+        let response = call(Request::get("/missing").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()["x-finalized"], "none");
+    }
+
+    #[test]
+    fn uses_fallback_handlers() {
+        use crate::router::non_match::AllowedMethods;
+        use crate::state::FromState;
+        use hyper::header::ALLOW;
+
+        fn not_found(state: State) -> (State, Response<Body>) {
+            let res = Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body("missing".into())
+                .unwrap();
+            (state, res)
+        }
+
+        fn method_not_allowed(state: State) -> (State, Response<Body>) {
+            let allowed = AllowedMethods::borrow_from(&state).methods().len();
+            let res = Response::builder()
+                .status(StatusCode::METHOD_NOT_ALLOWED)
+                .body(format!("{} allowed", allowed).into())
+                .unwrap();
+            (state, res)
+        }
+
+        let router = build_simple_router(|route| {
+            route.get_or_head("/").to(welcome::index);
+            route.not_found(not_found);
+            route.method_not_allowed(method_not_allowed);
+        });
+
+        let new_service = GothamService::new(router);
+        let call = move |req| {
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            service.call(req).wait().unwrap()
+        };
+
+        let response = call(Request::get("/missing").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response_bytes = response.into_body().concat2().wait().unwrap().to_vec();
+        assert_eq!(&response_bytes[..], b"missing");
+
+        let response = call(Request::post("/").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        let allow: Vec<_> = response.headers().get_all(ALLOW).iter().collect();
+        assert_eq!(allow, vec!["GET", "HEAD"]);
+        let response_bytes = response.into_body().concat2().wait().unwrap().to_vec();
+        assert_eq!(&response_bytes[..], b"2 allowed");
+
+        let response = call(Request::get("/").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn lists_registered_routes() {
+        let delegated_router = build_simple_router(|route| {
+            route.get("/b").to(welcome::delegated);
+        });
+
+        let router = build_simple_router(|route| {
+            route.get_or_head("/").to(welcome::index);
+            route
+                .get("/hello/:name/*")
+                .with_path_extractor::<SalutationParams>()
+                .to(welcome::globbed);
+            route
+                .get("/goodbye/:name:[a-zA-Z]+")
+                .with_path_extractor::<SalutationParams>()
+                .to(welcome::goodbye);
+            route.get(r"/literal/\:param").to(welcome::literal);
+            route.scope("/api", |route| {
+                route.post("/submit").to(api::submit);
+            });
+            route.associate("/resource", |route| {
+                route.post().to(resource::create);
+                route.delete().to(resource::destroy);
+            });
+            route.delegate("/delegated").to_router(delegated_router);
+        });
+
+        let routes: Vec<String> = router
+            .routes()
+            .into_iter()
+            .map(|(method, template)| format!("{} {}", method, template))
+            .collect();
+
+        assert_eq!(
+            routes,
+            vec![
+                "GET /",
+                "HEAD /",
+                "POST /api/submit",
+                "GET /delegated",
+                "HEAD /delegated",
+                "POST /delegated",
+                "PUT /delegated",
+                "PATCH /delegated",
+                "DELETE /delegated",
+                "OPTIONS /delegated",
+                "GET /goodbye/:name:[a-zA-Z]+",
+                "GET /hello/:name/*",
+                r"GET /literal/\:param",
+                "POST /resource",
+                "DELETE /resource",
+            ]
+        );
+    }
 }
@@ -3,7 +3,9 @@ use hyper::Body;
 use std::panic::RefUnwindSafe;
 
 use crate::extractor::{PathExtractor, QueryStringExtractor};
-use crate::pipeline::chain::PipelineHandleChain;
+use crate::middleware::NewMiddleware;
+use crate::pipeline::chain::{PipelineHandleChain, RoutePipelineChain};
+use crate::pipeline::single_middleware;
 use crate::router::builder::single::DefineSingleRoute;
 use crate::router::builder::SingleRouteBuilder;
 use crate::router::route::matcher::{AndRouteMatcher, RouteMatcher};
@@ -106,6 +108,50 @@ where
             node_builder: self.node_builder,
             pipeline_chain: self.pipeline_chain,
             pipelines: self.pipelines,
+            data: self.data,
+        }
+    }
+}
+
+/// Describes the operation of adding a `Middleware` to a single route. This trait exists to remove
+/// type clutter from the documentation of `SingleRouteBuilder::with_middleware`.
+pub trait ExtendRoutePipeline<NM>
+where
+    NM: NewMiddleware,
+{
+    /// The type returned when adding the `Middleware` to the route.
+    type Output: DefineSingleRoute;
+
+    #[doc(hidden)]
+    /// Wraps the existing `PipelineHandleChain` with a `Pipeline` containing only the
+    /// `Middleware` defined as NM
+    fn extend_route_pipeline(self, middleware: NM) -> Self::Output;
+}
+
+impl<'a, M, NM, C, P, PE, QSE> ExtendRoutePipeline<NM> for SingleRouteBuilder<'a, M, C, P, PE, QSE>
+where
+    M: RouteMatcher + Send + Sync + 'static,
+    NM: NewMiddleware + Send + 'static,
+    NM::Instance: Send + 'static,
+    C: PipelineHandleChain<P> + Send + Sync + 'static,
+    P: RefUnwindSafe + Send + Sync + 'static,
+    PE: PathExtractor<Body> + Send + Sync + 'static,
+    QSE: QueryStringExtractor<Body> + Send + Sync + 'static,
+{
+    /// The type returned when adding the `Middleware` to the route.
+    type Output = SingleRouteBuilder<'a, M, RoutePipelineChain<(NM, ()), C>, P, PE, QSE>;
+
+    fn extend_route_pipeline(self, middleware: NM) -> Self::Output {
+        SingleRouteBuilder {
+            pipeline_chain: RoutePipelineChain::new(
+                single_middleware(middleware),
+                self.pipeline_chain,
+            ),
+            matcher: self.matcher,
+            phantom: self.phantom,
+            node_builder: self.node_builder,
+            pipelines: self.pipelines,
+            data: self.data,
         }
     }
 }
@@ -1,17 +1,24 @@
 use hyper::Body;
+use serde::Deserialize;
 
 use std::panic::RefUnwindSafe;
 
-use crate::extractor::{PathExtractor, QueryStringExtractor};
+use crate::extractor::{
+    HeaderExtractor, HeaderExtractorMiddleware, PathExtractor, QueryStringExtractor,
+};
 use crate::handler::assets::{DirHandler, FileHandler, FileOptions, FilePathExtractor};
 use crate::handler::{Handler, NewHandler};
+use crate::helpers::http::request::form::FormExtractorMiddleware;
+use crate::middleware::NewMiddleware;
 use crate::pipeline::chain::PipelineHandleChain;
 use crate::router::builder::{
-    ExtendRouteMatcher, ReplacePathExtractor, ReplaceQueryStringExtractor, SingleRouteBuilder,
+    ExtendRouteMatcher, ExtendRoutePipeline, ReplacePathExtractor, ReplaceQueryStringExtractor,
+    SingleRouteBuilder,
 };
 use crate::router::route::dispatch::DispatcherImpl;
-use crate::router::route::matcher::RouteMatcher;
+use crate::router::route::matcher::{QueryRouteMatcher, RouteMatcher};
 use crate::router::route::{Delegation, Extractors, RouteImpl};
+use crate::state::StateData;
 
 /// Describes the API for defining a single route, after determining which request paths will be
 /// dispatched here. The API here uses chained function calls to build and add the route into the
@@ -453,6 +460,416 @@ pub trait DefineSingleRoute {
         NRM: RouteMatcher + Send + Sync + 'static,
         Self: ExtendRouteMatcher<NRM>,
         Self::Output: DefineSingleRoute;
+
+    /// Requires the query string to contain `key` with the value `value` for the current route to
+    /// match, comparing both exactly once decoded.
+    ///
+    /// When the constraint fails, routing continues with the next route defined for the same
+    /// path, so constrained routes should be defined before any route they fall back to.
+    ///
+    /// ```
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn articles_csv(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::new(Body::from("csv")))
+    /// }
+    ///
+    /// fn articles_json(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::new(Body::from("json")))
+    /// }
+    ///
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route
+    ///         .get("/articles")
+    ///         .with_query_constraint("format", "csv")
+    ///         .to(articles_csv);
+    ///
+    ///     route.get("/articles").to(articles_json);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let client = test_server.client();
+    /// #
+    /// #   let response = client.get("https://example.com/articles?format=csv").perform().unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "csv");
+    /// #
+    /// #   let response = client.get("https://example.com/articles").perform().unwrap();
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "json");
+    /// #
+    /// #   let response = client.get("https://example.com/articles?format=CSV").perform().unwrap();
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "json");
+    /// # }
+    /// ```
+    fn with_query_constraint(
+        self,
+        key: &str,
+        value: &str,
+    ) -> <Self as ExtendRouteMatcher<QueryRouteMatcher>>::Output
+    where
+        Self: ExtendRouteMatcher<QueryRouteMatcher>,
+        Self::Output: DefineSingleRoute;
+
+    /// Requires the query string to contain `key`, with any value or none at all, for the current
+    /// route to match. Routing continues as described by `with_query_constraint` when it doesn't.
+    ///
+    /// ```
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn preview(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// # }
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route
+    ///         .get("/articles")
+    ///         .with_required_query_key("preview")
+    ///         .to(preview);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let client = test_server.client();
+    /// #
+    /// #   let response = client.get("https://example.com/articles?preview").perform().unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// #
+    /// #   let response = client.get("https://example.com/articles").perform().unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    /// # }
+    /// ```
+    fn with_required_query_key(
+        self,
+        key: &str,
+    ) -> <Self as ExtendRouteMatcher<QueryRouteMatcher>>::Output
+    where
+        Self: ExtendRouteMatcher<QueryRouteMatcher>,
+        Self::Output: DefineSingleRoute;
+
+    /// Adds a `Middleware` which applies to the current route only, without declaring a pipeline
+    /// for it up front.
+    ///
+    /// The middleware runs after the pipelines of the route, just before the handler. When
+    /// called more than once, the middleware run in the order they were added.
+    ///
+    /// ```rust
+    /// # extern crate futures;
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// # extern crate hyper;
+    /// #
+    /// # use futures::future;
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::handler::HandlerFuture;
+    /// # use gotham::helpers::http::response::create_empty_response;
+    /// # use gotham::middleware::Middleware;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// #[derive(Clone, NewMiddleware)]
+    /// struct AdminAuthMiddleware;
+    ///
+    /// impl Middleware for AdminAuthMiddleware {
+    ///     fn call<Chain>(self, state: State, _chain: Chain) -> Box<HandlerFuture>
+    ///     where
+    ///         Chain: FnOnce(State) -> Box<HandlerFuture>,
+    ///     {
+    ///         // Authentication omitted; every request is rejected.
+    ///         let res = create_empty_response(&state, StatusCode::FORBIDDEN);
+    ///         Box::new(future::ok((state, res)))
+    ///     }
+    /// }
+    ///
+    /// fn admin_handler(state: State) -> (State, Response<Body>) {
+    ///     // Never reached in this example.
+    /// #   (state, Response::new(Body::empty()))
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route
+    ///             .get("/admin")
+    ///             .with_middleware(AdminAuthMiddleware)
+    ///             .to(admin_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/admin")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    /// # }
+    /// ```
+    fn with_middleware<NM>(self, middleware: NM) -> <Self as ExtendRoutePipeline<NM>>::Output
+    where
+        NM: NewMiddleware + Send + 'static,
+        NM::Instance: Send + 'static,
+        Self: ExtendRoutePipeline<NM>,
+        Self::Output: DefineSingleRoute;
+
+    /// Extracts the request headers into the provided `HeaderExtractor` for the current route,
+    /// storing it in `State` for the handler.
+    ///
+    /// Requests missing a required header, or with a header which can't be parsed, are answered
+    /// with a `400 Bad Request` naming the header. The extraction runs as `Middleware` added via
+    /// `with_middleware`, so it runs after the pipelines of the route.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// #[derive(HeaderExtractor)]
+    /// struct TenantHeaders {
+    ///     x_tenant_id: u64,
+    /// }
+    ///
+    /// fn handler(state: State) -> (State, Response<Body>) {
+    ///     assert_eq!(TenantHeaders::borrow_from(&state).x_tenant_id, 42);
+    /// #   (state, Response::new(Body::empty()))
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route
+    ///             .get("/")
+    ///             .with_header_extractor::<TenantHeaders>()
+    ///             .to(handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let client = test_server.client();
+    /// #
+    /// #   let response = client
+    /// #       .get("https://example.com/")
+    /// #       .with_header("x-tenant-id", "42".parse().unwrap())
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #
+    /// #   let response = client
+    /// #       .get("https://example.com/")
+    /// #       .with_header("x-tenant-id", "acme".parse().unwrap())
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "invalid header: x-tenant-id");
+    /// # }
+    /// ```
+    fn with_header_extractor<T>(
+        self,
+    ) -> <Self as ExtendRoutePipeline<HeaderExtractorMiddleware<T>>>::Output
+    where
+        T: HeaderExtractor,
+        Self: ExtendRoutePipeline<HeaderExtractorMiddleware<T>>,
+        Self::Output: DefineSingleRoute;
+
+    /// Parses the `application/x-www-form-urlencoded` request body into the provided type for
+    /// the current route, storing it in `State` for the handler.
+    ///
+    /// The type is typically a struct deriving `Deserialize` and `StateData`, and supports the
+    /// same field types as a `QueryStringExtractor`. Requests which aren't form encoded are
+    /// answered with a `415 Unsupported Media Type`, and bodies which can't be parsed with a
+    /// `400 Bad Request` naming the field at fault. The extraction runs as `Middleware` added via
+    /// `with_middleware`, so it runs after the pipelines of the route.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// # extern crate serde;
+    /// # #[macro_use]
+    /// # extern crate serde_derive;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// #[derive(Deserialize, StateData)]
+    /// struct Signup {
+    ///     name: String,
+    /// #   #[allow(dead_code)]
+    ///     age: u8,
+    /// }
+    ///
+    /// fn handler(state: State) -> (State, Response<Body>) {
+    ///     assert_eq!(Signup::borrow_from(&state).name, "Jane Doe");
+    /// #   (state, Response::new(Body::empty()))
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route
+    ///             .post("/signup")
+    ///             .with_form_extractor::<Signup>()
+    ///             .to(handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let client = test_server.client();
+    /// #   let form = mime::APPLICATION_WWW_FORM_URLENCODED;
+    /// #
+    /// #   let response = client
+    /// #       .post("https://example.com/signup", "name=Jane+Doe&age=30", form.clone())
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #
+    /// #   let response = client
+    /// #       .post("https://example.com/signup", "name=Jane+Doe", form)
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    /// #   assert_eq!(
+    /// #       response.read_utf8_body().unwrap(),
+    /// #       "form body is invalid: missing field `age`"
+    /// #   );
+    /// # }
+    /// ```
+    fn with_form_extractor<T>(
+        self,
+    ) -> <Self as ExtendRoutePipeline<FormExtractorMiddleware<T>>>::Output
+    where
+        T: for<'de> Deserialize<'de> + StateData,
+        Self: ExtendRoutePipeline<FormExtractorMiddleware<T>>,
+        Self::Output: DefineSingleRoute;
+
+    /// Attaches a value to the route, which is put into `State` whenever a request is dispatched
+    /// to the route, allowing per-route policy (such as a required role or a cache policy) to be
+    /// declared alongside the route and read by `Middleware` serving many routes.
+    ///
+    /// The value is cloned into `State` before the pipelines of the route run. When called more
+    /// than once with values of the same type, the last value wins.
+    ///
+    /// ```rust
+    /// # extern crate futures;
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// # extern crate hyper;
+    /// #
+    /// # use futures::future;
+    /// # use hyper::{Body, HeaderMap, Response, StatusCode};
+    /// # use gotham::handler::HandlerFuture;
+    /// # use gotham::helpers::http::response::create_empty_response;
+    /// # use gotham::middleware::Middleware;
+    /// # use gotham::pipeline::new_pipeline;
+    /// # use gotham::pipeline::single::single_pipeline;
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// #[derive(Clone, StateData)]
+    /// struct RequiredRole(&'static str);
+    ///
+    /// #[derive(Clone, NewMiddleware)]
+    /// struct AuthorizationMiddleware;
+    ///
+    /// impl Middleware for AuthorizationMiddleware {
+    ///     fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    ///     where
+    ///         Chain: FnOnce(State) -> Box<HandlerFuture>,
+    ///     {
+    ///         let allowed = match RequiredRole::try_borrow_from(&state) {
+    ///             // Authentication omitted; the role is read from a header.
+    ///             Some(role) => HeaderMap::borrow_from(&state)
+    ///                 .get("x-role")
+    ///                 .map_or(false, |value| value == role.0),
+    ///             None => true,
+    ///         };
+    ///
+    ///         if allowed {
+    ///             chain(state)
+    ///         } else {
+    ///             let res = create_empty_response(&state, StatusCode::FORBIDDEN);
+    ///             Box::new(future::ok((state, res)))
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// fn handler(state: State) -> (State, Response<Body>) {
+    ///     (state, Response::new(Body::empty()))
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     let (chain, pipelines) = single_pipeline(
+    ///         new_pipeline().add(AuthorizationMiddleware).build()
+    ///     );
+    ///
+    ///     build_router(chain, pipelines, |route| {
+    ///         route.get("/").to(handler);
+    ///         route
+    ///             .get("/admin")
+    ///             .with_data(RequiredRole("admin"))
+    ///             .to(handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let client = test_server.client();
+    /// #
+    /// #   let response = client.get("https://example.com/").perform().unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #
+    /// #   let response = client.get("https://example.com/admin").perform().unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    /// #
+    /// #   let response = client
+    /// #       .get("https://example.com/admin")
+    /// #       .with_header("x-role", "admin".parse().unwrap())
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// # }
+    /// ```
+    fn with_data<T>(self, data: T) -> Self
+    where
+        T: StateData + Clone + Sync + RefUnwindSafe;
 }
 
 impl<'a, M, C, P, PE, QSE> DefineSingleRoute for SingleRouteBuilder<'a, M, C, P, PE, QSE>
@@ -474,7 +891,8 @@ where
     where
         NH: NewHandler + 'static,
     {
-        let dispatcher = DispatcherImpl::new(new_handler, self.pipeline_chain, self.pipelines);
+        let dispatcher = DispatcherImpl::new(new_handler, self.pipeline_chain, self.pipelines)
+            .with_data(self.data);
         let route: RouteImpl<M, PE, QSE> = RouteImpl::new(
             self.matcher,
             Box::new(dispatcher),
@@ -506,4 +924,53 @@ where
     {
         self.extend_route_matcher(matcher)
     }
+
+    fn with_query_constraint(
+        self,
+        key: &str,
+        value: &str,
+    ) -> <Self as ExtendRouteMatcher<QueryRouteMatcher>>::Output {
+        self.extend_route_matcher(QueryRouteMatcher::new(key, value))
+    }
+
+    fn with_required_query_key(
+        self,
+        key: &str,
+    ) -> <Self as ExtendRouteMatcher<QueryRouteMatcher>>::Output {
+        self.extend_route_matcher(QueryRouteMatcher::required_key(key))
+    }
+
+    fn with_middleware<NM>(self, middleware: NM) -> <Self as ExtendRoutePipeline<NM>>::Output
+    where
+        NM: NewMiddleware + Send + 'static,
+        NM::Instance: Send + 'static,
+    {
+        self.extend_route_pipeline(middleware)
+    }
+
+    fn with_header_extractor<T>(
+        self,
+    ) -> <Self as ExtendRoutePipeline<HeaderExtractorMiddleware<T>>>::Output
+    where
+        T: HeaderExtractor,
+    {
+        self.extend_route_pipeline(HeaderExtractorMiddleware::new())
+    }
+
+    fn with_form_extractor<T>(
+        self,
+    ) -> <Self as ExtendRoutePipeline<FormExtractorMiddleware<T>>>::Output
+    where
+        T: for<'de> Deserialize<'de> + StateData,
+    {
+        self.extend_route_pipeline(FormExtractorMiddleware::new())
+    }
+
+    fn with_data<T>(mut self, data: T) -> Self
+    where
+        T: StateData + Clone + Sync + RefUnwindSafe,
+    {
+        self.data.push(data);
+        self
+    }
 }
@@ -0,0 +1,82 @@
+use std::marker::PhantomData;
+use std::panic::RefUnwindSafe;
+
+use crate::pipeline::chain::PipelineHandleChain;
+use crate::router::builder::{DrawRoutes, ScopeBuilder};
+
+/// Registers versions of an API, each under its own `/v{n}` path prefix.
+///
+/// This is a thin wrapper over `DrawRoutes::scope`, allowing the same logical routes to be
+/// defined once per version. The `ApiVersionMiddleware` can be used alongside it to store the
+/// requested `RequestVersion` in `State`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::response::create_empty_response;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn v1_handler(state: State) -> (State, Response<Body>) {
+///     let response = create_empty_response(&state, StatusCode::OK);
+///     (state, response)
+/// }
+///
+/// fn v2_handler(state: State) -> (State, Response<Body>) {
+///     let response = create_empty_response(&state, StatusCode::ACCEPTED);
+///     (state, response)
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     VersionedRouter::new(route)
+///         .version(1, |route| route.get("/users").to(v1_handler))
+///         .version(2, |route| route.get("/users").to(v2_handler));
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("https://example.com/v2/users")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::ACCEPTED);
+/// # }
+/// ```
+pub struct VersionedRouter<'a, D, C, P>
+where
+    D: DrawRoutes<C, P> + 'a,
+    C: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
+    P: RefUnwindSafe + Send + Sync + 'static,
+{
+    route: &'a mut D,
+    phantom: PhantomData<(C, P)>,
+}
+
+impl<'a, D, C, P> VersionedRouter<'a, D, C, P>
+where
+    D: DrawRoutes<C, P> + 'a,
+    C: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
+    P: RefUnwindSafe + Send + Sync + 'static,
+{
+    /// Creates a `VersionedRouter` which adds routes via the provided builder.
+    pub fn new(route: &'a mut D) -> Self {
+        VersionedRouter {
+            route,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Defines the routes for a single version, under the `/v{version}` path prefix.
+    pub fn version<F>(self, version: u32, f: F) -> Self
+    where
+        F: FnOnce(&mut ScopeBuilder<C, P>),
+    {
+        self.route.scope(&format!("/v{}", version), f);
+        self
+    }
+}
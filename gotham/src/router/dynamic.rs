@@ -0,0 +1,371 @@
+//! Defines the `DynamicRouter`, which accepts new routes after the server has started.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::panic::RefUnwindSafe;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use hyper::Method;
+use log::trace;
+
+use crate::error::Result;
+use crate::extractor::{NoopPathExtractor, NoopQueryStringExtractor};
+use crate::handler::{Handler, HandlerFuture, NewHandler};
+use crate::pipeline::chain::PipelineHandleChain;
+use crate::pipeline::set::{finalize_pipeline_set, new_pipeline_set, PipelineSet};
+use crate::router::builder::descend;
+use crate::router::route::dispatch::{Dispatcher, DispatcherImpl};
+use crate::router::route::matcher::MethodOnlyRouteMatcher;
+use crate::router::route::{Delegation, Extractors, RouteImpl};
+use crate::router::{Router, RouterData};
+use crate::state::State;
+
+/// A `Handler` registered at runtime, with its concrete type erased.
+type SharedHandler = Arc<dyn Fn(State) -> Box<HandlerFuture> + Send + Sync + RefUnwindSafe>;
+
+/// Creates the `Dispatcher` for a route registered at runtime, using the pipelines provided to
+/// the `DynamicRouter`.
+type DispatcherFactory =
+    dyn Fn(SharedHandler) -> Box<dyn Dispatcher + Send + Sync> + Send + Sync + RefUnwindSafe;
+
+/// The routes of a `DynamicRouter`, along with the means to create new ones.
+struct RouterState {
+    // replaced on write when requests still hold the previous routes
+    data: Arc<RouterData>,
+    factory: Arc<DispatcherFactory>,
+}
+
+/// A `Router` which can have routes added after the server has started, such as by plugins which
+/// discover their handlers at load time.
+///
+/// Each request takes a read lock only long enough to grab the current routes, and is dispatched
+/// after releasing it, so handlers may add routes themselves. Adding a route copies the routes
+/// when requests are still using them (the routes themselves are shared, so this is cheap), and
+/// takes effect for the next request. Once no more routes are expected, `freeze` returns a plain
+/// `Router` without any locking.
+///
+/// Routes added at runtime match on the method and path alone, using the same path syntax as the
+/// `gotham::router::builder` API, and are dispatched through the pipeline chain provided via
+/// `with_pipeline_chain` (or none at all, when created via `new`).
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Method, Response, StatusCode};
+/// # use gotham::router::builder::*;
+/// # use gotham::router::dynamic::DynamicRouter;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn plugin_handler(state: State) -> (State, Response<Body>) {
+///     (state, Response::new(Body::from("plugin")))
+/// }
+///
+/// # fn main() {
+/// let router = DynamicRouter::new(build_simple_router(|_| {}));
+///
+/// // the handle can be kept by the application, and used after the server has started
+/// let server = TestServer::new(router.clone()).unwrap();
+///
+/// router
+///     .add_route(Method::GET, "/plugins/hello", plugin_handler)
+///     .unwrap();
+///
+/// let response = server
+///     .client()
+///     .get("http://localhost/plugins/hello")
+///     .perform()
+///     .unwrap();
+///
+/// assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct DynamicRouter {
+    state: Arc<RwLock<RouterState>>,
+}
+
+impl DynamicRouter {
+    /// Wraps a `Router`, allowing routes to be added to it at runtime.
+    ///
+    /// Routes added at runtime are dispatched without any pipelines; use `with_pipeline_chain`
+    /// to run them through middleware.
+    pub fn new(router: Router) -> Self {
+        let pipelines = finalize_pipeline_set(new_pipeline_set());
+        DynamicRouter::with_pipeline_chain(router, (), pipelines)
+    }
+
+    /// Wraps a `Router`, allowing routes to be added to it at runtime.
+    ///
+    /// Routes added at runtime are dispatched through the provided pipeline chain, in the same
+    /// way as routes added via `build_router`.
+    pub fn with_pipeline_chain<C, P>(
+        router: Router,
+        pipeline_chain: C,
+        pipelines: PipelineSet<P>,
+    ) -> Self
+    where
+        C: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
+        P: RefUnwindSafe + Send + Sync + 'static,
+    {
+        let factory = move |handler: SharedHandler| -> Box<dyn Dispatcher + Send + Sync> {
+            let new_handler = move || Ok(SharedHandlerInstance(handler.clone()));
+            Box::new(DispatcherImpl::new(
+                new_handler,
+                pipeline_chain,
+                pipelines.clone(),
+            ))
+        };
+
+        DynamicRouter {
+            state: Arc::new(RwLock::new(RouterState {
+                data: router.data,
+                factory: Arc::new(factory),
+            })),
+        }
+    }
+
+    /// Adds a route for the provided method and path, which takes effect for the next request.
+    ///
+    /// Returns a `RouteConflict` if a route for the method has already been registered at the
+    /// same path, leaving the existing route in place.
+    pub fn add_route<H>(
+        &self,
+        method: Method,
+        path: &str,
+        handler: H,
+    ) -> std::result::Result<(), RouteConflict>
+    where
+        H: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+    {
+        let mut inner = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        let dispatcher = (inner.factory)(Arc::new(move |state: State| handler.handle(state)));
+
+        let data = Arc::make_mut(&mut inner.data);
+        let node = descend(data.tree.borrow_root_mut(), path);
+
+        if node.has_route_for(&method) {
+            return Err(RouteConflict {
+                method,
+                path: path.to_owned(),
+            });
+        }
+
+        trace!(" adding dynamic route: {} {}", method, path);
+
+        let route: RouteImpl<_, NoopPathExtractor, NoopQueryStringExtractor> = RouteImpl::new(
+            MethodOnlyRouteMatcher::new(vec![method]),
+            dispatcher,
+            Extractors::new(),
+            Delegation::Internal,
+        );
+
+        node.add_route(Box::new(route));
+        Ok(())
+    }
+
+    /// Returns every route registered with the router, as described by `Router::routes`.
+    pub fn routes(&self) -> Vec<(Method, String)> {
+        self.snapshot().tree.routes()
+    }
+
+    /// Creates a static `Router` from the current routes, for when no more routes are expected.
+    ///
+    /// The returned `Router` dispatches without any locking. Routes added to this
+    /// `DynamicRouter` afterwards aren't seen by the returned `Router`.
+    pub fn freeze(&self) -> Router {
+        Router {
+            data: self.snapshot(),
+        }
+    }
+
+    /// Returns the current routes, holding the read lock only while they're cloned.
+    fn snapshot(&self) -> Arc<RouterData> {
+        let inner = self.state.read().unwrap_or_else(PoisonError::into_inner);
+        inner.data.clone()
+    }
+}
+
+impl NewHandler for DynamicRouter {
+    type Instance = DynamicRouter;
+
+    fn new_handler(&self) -> Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl Handler for DynamicRouter {
+    /// Handles the `Request` in the same way as a `Router`, using the routes registered at the
+    /// time the request is received.
+    fn handle(self, state: State) -> Box<HandlerFuture> {
+        self.snapshot().handle(state)
+    }
+}
+
+/// Wraps a `SharedHandler` so that it can be used as a `Handler`.
+#[derive(Clone)]
+struct SharedHandlerInstance(SharedHandler);
+
+impl Handler for SharedHandlerInstance {
+    fn handle(self, state: State) -> Box<HandlerFuture> {
+        (self.0)(state)
+    }
+}
+
+/// The error returned when adding a route which has already been registered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RouteConflict {
+    method: Method,
+    path: String,
+}
+
+impl RouteConflict {
+    /// Returns the method of the conflicting route.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the path of the conflicting route, as provided to `add_route`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Display for RouteConflict {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a route for {} {} has already been registered",
+            self.method, self.path
+        )
+    }
+}
+
+impl Error for RouteConflict {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use futures::future;
+    use hyper::{Body, Response, StatusCode};
+
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new(Body::from("dynamic")))
+    }
+
+    fn static_handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new(Body::from("static")))
+    }
+
+    fn get(server: &TestServer, uri: &str) -> (StatusCode, String) {
+        let response = server.client().get(uri).perform().unwrap();
+        (response.status(), response.read_utf8_body().unwrap())
+    }
+
+    #[test]
+    fn adds_routes_at_runtime() {
+        let router = DynamicRouter::new(build_simple_router(|route| {
+            route.get("/static").to(static_handler);
+        }));
+
+        let server = TestServer::new(router.clone()).unwrap();
+
+        assert_eq!(
+            get(&server, "http://localhost/dynamic/1").0,
+            StatusCode::NOT_FOUND
+        );
+
+        router
+            .add_route(Method::GET, "/dynamic/:id", handler)
+            .unwrap();
+
+        assert_eq!(
+            get(&server, "http://localhost/dynamic/1"),
+            (StatusCode::OK, "dynamic".to_owned())
+        );
+        assert_eq!(
+            get(&server, "http://localhost/static"),
+            (StatusCode::OK, "static".to_owned())
+        );
+
+        let frozen = router.freeze();
+        router.add_route(Method::GET, "/later", handler).unwrap();
+
+        assert_eq!(frozen.routes().len(), 2);
+        assert_eq!(router.routes().len(), 3);
+    }
+
+    #[test]
+    fn rejects_conflicting_routes() {
+        let router = DynamicRouter::new(build_simple_router(|route| {
+            route.get("/static").to(static_handler);
+        }));
+
+        let err = router
+            .add_route(Method::GET, "/static", handler)
+            .unwrap_err();
+
+        assert_eq!(err.method(), &Method::GET);
+        assert_eq!(err.path(), "/static");
+        assert_eq!(
+            err.to_string(),
+            "a route for GET /static has already been registered"
+        );
+
+        router.add_route(Method::POST, "/static", handler).unwrap();
+        assert!(router.add_route(Method::POST, "static", handler).is_err());
+    }
+
+    // registers a route from within a request, using the router it's served by
+    #[derive(Clone)]
+    struct Registrar(Arc<Mutex<Option<DynamicRouter>>>);
+
+    impl NewHandler for Registrar {
+        type Instance = Self;
+
+        fn new_handler(&self) -> Result<Self::Instance> {
+            Ok(self.clone())
+        }
+    }
+
+    impl Handler for Registrar {
+        fn handle(self, state: State) -> Box<HandlerFuture> {
+            let router = self.0.lock().unwrap().clone().unwrap();
+            router
+                .add_route(Method::GET, "/registered", handler)
+                .unwrap();
+
+            Box::new(future::ok((state, Response::new(Body::empty()))))
+        }
+    }
+
+    #[test]
+    fn adds_routes_from_handlers() {
+        let registrar = Registrar(Arc::new(Mutex::new(None)));
+        let router = DynamicRouter::new(build_simple_router(|route| {
+            route.post("/register").to_new_handler(registrar.clone());
+        }));
+
+        *registrar.0.lock().unwrap() = Some(router.clone());
+        let server = TestServer::new(router).unwrap();
+
+        let response = server
+            .client()
+            .post("http://localhost/register", "", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            get(&server, "http://localhost/registered"),
+            (StatusCode::OK, "dynamic".to_owned())
+        );
+    }
+}
@@ -1,6 +1,7 @@
 //! Defines the Gotham `Router` and supporting types.
 
 pub mod builder;
+pub mod dynamic;
 pub mod non_match;
 pub mod response;
 pub mod route;
@@ -9,70 +10,54 @@ pub mod tree;
 use std::sync::Arc;
 
 use futures::{future, Future};
-use hyper::header::ALLOW;
-use hyper::{Body, Response, StatusCode};
+use hyper::header::{HeaderMap, ACCESS_CONTROL_REQUEST_METHOD, ALLOW};
+use hyper::{Body, Method, Response, StatusCode};
 use log::{error, trace};
 
 use crate::error::*;
 use crate::handler::{Handler, HandlerFuture, IntoResponse, NewHandler};
 use crate::helpers::http::request::path::RequestPathSegments;
 use crate::helpers::http::response::create_empty_response;
+use crate::router::non_match::{AllowedMethods, CorsPreflight};
 use crate::router::response::finalizer::ResponseFinalizer;
+use crate::router::route::dispatch::Dispatcher;
 use crate::router::route::{Delegation, Route};
+use crate::router::tree::node::Node;
 use crate::router::tree::segment::SegmentMapping;
 use crate::router::tree::Tree;
-use crate::state::{request_id, State};
+use crate::state::{request_id, FromState, State};
 
+#[derive(Clone)]
 struct RouterData {
     tree: Tree,
     response_finalizer: ResponseFinalizer,
+    middleware: Vec<&'static str>,
+    fallbacks: Fallbacks,
 }
 
 impl RouterData {
-    fn new(tree: Tree, response_finalizer: ResponseFinalizer) -> RouterData {
+    fn new(
+        tree: Tree,
+        response_finalizer: ResponseFinalizer,
+        middleware: Vec<&'static str>,
+        fallbacks: Fallbacks,
+    ) -> RouterData {
         RouterData {
             tree,
             response_finalizer,
+            middleware,
+            fallbacks,
         }
     }
-}
-
-/// Responsible for dispatching HTTP requests to defined routes, and responding with appropriate
-/// error codes when a valid `Route` is unable to be determined or the dispatch cannot be
-/// performed.
-///
-/// A `Router` is constructed through the [`gotham::router::builder`](builder/index.html#functions)
-/// API, and used with the `gotham::start` function when booting a Gotham web application.
-///
-/// The `Router` is capable of delegating requests to secondary `Router` instances, which allows
-/// the support of "modular applications". A modular application contains multiple applications
-/// within a single binary that have clear boundaries established via Rust module separation.
-/// Please see the documentation for `DrawRoutes::delegate` within `gotham::router::builder` in
-/// order to delegate to other `Router` instances.
-#[derive(Clone)]
-pub struct Router {
-    data: Arc<RouterData>,
-}
-
-impl NewHandler for Router {
-    type Instance = Router;
-
-    // Creates a new Router instance to route new HTTP requests
-    fn new_handler(&self) -> Result<Self::Instance> {
-        trace!(" cloning instance");
-        Ok(self.clone())
-    }
-}
 
-impl Handler for Router {
-    /// Handles the `Request` by determining the correct `Route` from the internal `Tree`, storing
-    /// any path related variables in `State` and dispatching to the associated `Handler`.
-    fn handle(self, mut state: State) -> Box<HandlerFuture> {
+    /// Handles the `Request` by determining the correct `Route` from the `Tree`, storing any path
+    /// related variables in `State` and dispatching to the associated `Handler`.
+    fn handle(&self, mut state: State) -> Box<HandlerFuture> {
         trace!("[{}] starting", request_id(&state));
 
         let future = match state.try_take::<RequestPathSegments>() {
             Some(rps) => {
-                if let Some((node, params, processed)) = self.data.tree.traverse(&rps.segments()) {
+                if let Some((node, params, processed)) = self.tree.traverse(rps.segments()) {
                     match node.select_route(&state) {
                         Ok(route) => match route.delegation() {
                             Delegation::External => {
@@ -88,24 +73,17 @@ impl Handler for Router {
                         },
                         Err(non_match) => {
                             let (status, allow) = non_match.deconstruct();
-
-                            trace!("[{}] responding with error status", request_id(&state));
-                            let mut res = create_empty_response(&state, status);
-                            if let StatusCode::METHOD_NOT_ALLOWED = status {
-                                for allowed in allow {
-                                    res.headers_mut().append(
-                                        ALLOW,
-                                        allowed.as_str().to_string().parse().unwrap(),
-                                    );
+                            match self.preflight_route(&mut state, node) {
+                                Some((route, requested)) => {
+                                    self.preflight(state, params, route, requested, allow)
                                 }
+                                None => self.non_match(state, status, allow),
                             }
-                            Box::new(future::ok((state, res)))
                         }
                     }
                 } else {
                     trace!("[{}] did not find routable node", request_id(&state));
-                    let res = create_empty_response(&state, StatusCode::NOT_FOUND);
-                    Box::new(future::ok((state, res)))
+                    self.non_match(state, StatusCode::NOT_FOUND, Vec::new())
                 }
             }
             None => {
@@ -117,24 +95,112 @@ impl Handler for Router {
 
         self.finalize_response(future)
     }
-}
 
-impl Router {
-    /// Manually assembles a `Router` instance from a provided `Tree`.
-    #[deprecated(
-        since = "0.2.0",
-        note = "use the new `gotham::router::builder` API to construct a Router"
-    )]
-    pub fn new(tree: Tree, response_finalizer: ResponseFinalizer) -> Router {
-        Router::internal_new(tree, response_finalizer)
+    /// Selects the route targeted by a CORS preflight request, using the method named in its
+    /// `Access-Control-Request-Method` header. The requested method is returned alongside.
+    #[allow(clippy::borrowed_box)]
+    fn preflight_route<'n>(
+        &self,
+        state: &mut State,
+        node: &'n Node,
+    ) -> Option<(&'n Box<dyn Route<ResBody = Body> + Send + Sync>, Method)> {
+        if *Method::borrow_from(state) != Method::OPTIONS {
+            return None;
+        }
+
+        let requested = HeaderMap::try_borrow_from(state)
+            .and_then(|headers| headers.get(ACCESS_CONTROL_REQUEST_METHOD))
+            .and_then(|method| Method::from_bytes(method.as_bytes()).ok())?;
+
+        // routes match against the method in state, so swap it for the selection
+        state.put(requested.clone());
+        let route = node.select_route(state).ok();
+        state.put(Method::OPTIONS);
+
+        match route {
+            Some(route) if route.delegation() == Delegation::Internal => Some((route, requested)),
+            _ => None,
+        }
     }
 
-    /// Same as `new`, but private and not deprecated.
-    fn internal_new(tree: Tree, response_finalizer: ResponseFinalizer) -> Router {
-        let router_data = RouterData::new(tree, response_finalizer);
-        Router {
-            data: Arc::new(router_data),
+    /// Dispatches a CORS preflight request through the pipelines of the route it targets.
+    ///
+    /// Preflights which aren't answered by a middleware are responded to in the same way as any
+    /// other unrouted `OPTIONS` request.
+    #[allow(clippy::borrowed_box)]
+    fn preflight<'a>(
+        &self,
+        mut state: State,
+        params: SegmentMapping<'a>,
+        route: &Box<dyn Route<ResBody = Body> + Send + Sync>,
+        requested: Method,
+        allow: Vec<Method>,
+    ) -> Box<HandlerFuture> {
+        trace!("[{}] dispatching cors preflight", request_id(&state));
+
+        state.put(CorsPreflight::new(requested));
+
+        let f = self
+            .dispatch(state, params, route)
+            .map(move |(state, mut res)| {
+                if res.status() == StatusCode::METHOD_NOT_ALLOWED
+                    && !res.headers().contains_key(ALLOW)
+                {
+                    append_allow(&mut res, &allow);
+                }
+                (state, res)
+            });
+
+        Box::new(f)
+    }
+
+    /// Responds to a request which no route matched, using a fallback handler when one has been
+    /// registered for the status.
+    fn non_match(
+        &self,
+        mut state: State,
+        status: StatusCode,
+        allow: Vec<Method>,
+    ) -> Box<HandlerFuture> {
+        let fallbacks = &self.fallbacks;
+        let fallback = match status {
+            StatusCode::NOT_FOUND => fallbacks.not_found.as_ref(),
+            StatusCode::METHOD_NOT_ALLOWED => fallbacks.method_not_allowed.as_ref(),
+            _ => None,
+        };
+
+        let dispatcher = match fallback {
+            Some(dispatcher) => dispatcher,
+            None => {
+                trace!("[{}] responding with error status", request_id(&state));
+                let mut res = create_empty_response(&state, status);
+                if let StatusCode::METHOD_NOT_ALLOWED = status {
+                    append_allow(&mut res, &allow);
+                }
+                return Box::new(future::ok((state, res)));
+            }
+        };
+
+        trace!(
+            "[{}] dispatching to fallback handler for {}",
+            request_id(&state),
+            status
+        );
+
+        if status != StatusCode::METHOD_NOT_ALLOWED {
+            return dispatcher.dispatch(state);
         }
+
+        state.put(AllowedMethods(allow));
+
+        let f = dispatcher.dispatch(state).map(|(state, mut res)| {
+            if !res.headers().contains_key(ALLOW) {
+                append_allow(&mut res, AllowedMethods::borrow_from(&state).methods());
+            }
+            (state, res)
+        });
+
+        Box::new(f)
     }
 
     fn dispatch<'a>(
@@ -175,7 +241,7 @@ impl Router {
     }
 
     fn finalize_response(&self, result: Box<HandlerFuture>) -> Box<HandlerFuture> {
-        let response_finalizer = self.data.response_finalizer.clone();
+        let response_finalizer = self.response_finalizer.clone();
         let f = result
             .or_else(|(state, err)| {
                 trace!(
@@ -196,10 +262,111 @@ impl Router {
     }
 }
 
+/// Handlers used in place of the empty responses sent when no route matches a request.
+#[derive(Clone, Default)]
+struct Fallbacks {
+    not_found: Option<Arc<dyn Dispatcher + Send + Sync>>,
+    method_not_allowed: Option<Arc<dyn Dispatcher + Send + Sync>>,
+}
+
+/// Responsible for dispatching HTTP requests to defined routes, and responding with appropriate
+/// error codes when a valid `Route` is unable to be determined or the dispatch cannot be
+/// performed.
+///
+/// A `Router` is constructed through the [`gotham::router::builder`](builder/index.html#functions)
+/// API, and used with the `gotham::start` function when booting a Gotham web application.
+///
+/// The `Router` is capable of delegating requests to secondary `Router` instances, which allows
+/// the support of "modular applications". A modular application contains multiple applications
+/// within a single binary that have clear boundaries established via Rust module separation.
+/// Please see the documentation for `DrawRoutes::delegate` within `gotham::router::builder` in
+/// order to delegate to other `Router` instances.
+#[derive(Clone)]
+pub struct Router {
+    data: Arc<RouterData>,
+}
+
+impl NewHandler for Router {
+    type Instance = Router;
+
+    // Creates a new Router instance to route new HTTP requests
+    fn new_handler(&self) -> Result<Self::Instance> {
+        trace!(" cloning instance");
+        Ok(self.clone())
+    }
+}
+
+impl Handler for Router {
+    /// Handles the `Request` by determining the correct `Route` from the internal `Tree`, storing
+    /// any path related variables in `State` and dispatching to the associated `Handler`.
+    fn handle(self, state: State) -> Box<HandlerFuture> {
+        self.data.handle(state)
+    }
+}
+
+impl Router {
+    /// Manually assembles a `Router` instance from a provided `Tree`.
+    #[deprecated(
+        since = "0.2.0",
+        note = "use the new `gotham::router::builder` API to construct a Router"
+    )]
+    pub fn new(tree: Tree, response_finalizer: ResponseFinalizer) -> Router {
+        Router::internal_new(tree, response_finalizer, Vec::new(), Fallbacks::default())
+    }
+
+    /// Same as `new`, but private and not deprecated.
+    fn internal_new(
+        tree: Tree,
+        response_finalizer: ResponseFinalizer,
+        middleware: Vec<&'static str>,
+        fallbacks: Fallbacks,
+    ) -> Router {
+        let router_data = RouterData::new(tree, response_finalizer, middleware, fallbacks);
+        Router {
+            data: Arc::new(router_data),
+        }
+    }
+
+    /// Returns the names of the middleware in the router's default pipeline chain, in the order
+    /// they're invoked for each request.
+    ///
+    /// Names default to the fully qualified type name of each middleware, unless overridden via
+    /// `NewMiddleware::middleware_name`. This is intended for debugging; pipeline chains which
+    /// are replaced for individual scopes via `with_pipeline_chain` aren't included.
+    pub fn middleware_chain_debug(&self) -> Vec<String> {
+        self.data
+            .middleware
+            .iter()
+            .map(|name| (*name).to_owned())
+            .collect()
+    }
+
+    /// Returns every route registered with the router as `(Method, template)` pairs, where the
+    /// template uses the same syntax given to the builder (e.g. `/users/:id:[0-9]+/*`).
+    ///
+    /// This walks the route tree on each call, and is intended for generating documentation or
+    /// debugging; it doesn't affect request matching. Routes which accept any method, such as
+    /// delegated routers, are listed for each common method, and the routes of a delegated
+    /// router can be listed by calling this method on that router directly.
+    pub fn routes(&self) -> Vec<(Method, String)> {
+        self.data.tree.routes()
+    }
+}
+
+/// Appends each of the allowed methods to the `Allow` header of a response.
+fn append_allow(res: &mut Response<Body>, allow: &[Method]) {
+    for allowed in allow {
+        res.headers_mut()
+            .append(ALLOW, allowed.as_str().to_string().parse().unwrap());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use hyper::header::{HeaderMap, CONTENT_LENGTH};
+    use futures::Stream;
+    use hyper::body::Payload;
+    use hyper::header::{HeaderMap, CONTENT_LENGTH, CONTENT_TYPE};
     use hyper::{Body, Method, Uri};
     use std::str::FromStr;
 
@@ -402,4 +569,64 @@ mod tests {
             Err(_) => unreachable!("Router should have correctly handled request"),
         };
     }
+
+    #[test]
+    #[allow(deprecated)]
+    fn applies_before_send_hooks_in_order() {
+        let tree = Tree::new();
+
+        let mut response_finalizer_builder = ResponseFinalizerBuilder::new();
+        let not_found_extender = |_s: &mut State, r: &mut Response<Body>| {
+            r.headers_mut()
+                .insert(CONTENT_LENGTH, "3".to_owned().parse().unwrap());
+        };
+        let first = |_s: &State, r: &mut Response<Body>| {
+            let length = r.headers()[CONTENT_LENGTH].to_str().unwrap().to_owned();
+            r.headers_mut()
+                .insert(CONTENT_LENGTH, format!("{}1", length).parse().unwrap());
+        };
+        let second = |_s: &State, r: &mut Response<Body>| {
+            let length = r.headers()[CONTENT_LENGTH].to_str().unwrap().to_owned();
+            r.headers_mut()
+                .insert(CONTENT_LENGTH, format!("{}2", length).parse().unwrap());
+        };
+        response_finalizer_builder.add(StatusCode::NOT_FOUND, Box::new(not_found_extender));
+        response_finalizer_builder.add_hook(Box::new(first));
+        response_finalizer_builder.add_hook(Box::new(second));
+        let response_finalizer = response_finalizer_builder.finalize();
+        let router = Router::new(tree, response_finalizer);
+
+        match send_request(router, Method::GET, "https://test.gotham.rs/api") {
+            Ok((_state, res)) => {
+                assert_eq!(res.headers().get(CONTENT_LENGTH).unwrap(), "312");
+            }
+            Err(_) => unreachable!("Router should have correctly handled request"),
+        };
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn fills_in_empty_error_bodies() {
+        let mut response_finalizer_builder = ResponseFinalizerBuilder::new();
+        response_finalizer_builder.set_error_body(|status: StatusCode| {
+            (mime::TEXT_PLAIN, status.as_str().as_bytes().to_vec())
+        });
+        let router = Router::new(Tree::new(), response_finalizer_builder.finalize());
+
+        let (_state, res) = send_request(router.clone(), Method::GET, "https://test.gotham.rs/api")
+            .map_err(|_| ())
+            .unwrap();
+
+        assert_eq!(res.headers()[CONTENT_TYPE], "text/plain");
+        let body = res.into_body().concat2().wait().unwrap();
+        assert_eq!(&body[..], b"404");
+
+        // responses to HEAD requests are left empty
+        let (_state, res) = send_request(router, Method::HEAD, "https://test.gotham.rs/api")
+            .map_err(|_| ())
+            .unwrap();
+
+        assert!(!res.headers().contains_key(CONTENT_TYPE));
+        assert!(res.body().is_end_stream());
+    }
 }
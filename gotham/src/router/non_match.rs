@@ -4,6 +4,8 @@ use std::collections::HashSet;
 
 use hyper::{Method, StatusCode};
 
+use crate::state::StateData;
+
 /// The error type used for a non-matching route, as returned by `RouteMatcher::is_match`. Multiple
 /// values of this type can be combined by matchers that are wrapping other matchers, using the
 /// `intersection` / `union` methods.  The data within is used by the `Router` to create a
@@ -96,6 +98,49 @@ impl RouteNonMatch {
     }
 }
 
+/// The methods accepted by the routes at the request path, placed into `State` before invoking
+/// a handler registered via `RouterBuilder::method_not_allowed`.
+///
+/// These are the methods the `Router` sends in the `Allow` header of a default `405` response.
+/// If the handler's response doesn't include an `Allow` header, one is added using these values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllowedMethods(pub Vec<Method>);
+
+impl AllowedMethods {
+    /// Returns the allowed methods as a slice.
+    pub fn methods(&self) -> &[Method] {
+        &self.0
+    }
+}
+
+impl StateData for AllowedMethods {}
+
+/// Marks a preflight request which the `Router` has routed using the method it requests, rather
+/// than `OPTIONS`.
+///
+/// The `Router` places this into `State` before dispatching such a request through the pipelines
+/// of the route it targets, and a middleware answering the preflight takes it back out. When the
+/// preflight reaches the end of the pipeline chain without being answered, the handler of the
+/// route is skipped and the request is answered with `405 Method Not Allowed`, as if the
+/// preflight hadn't been routed at all.
+#[derive(Clone, Debug)]
+pub struct CorsPreflight {
+    method: Method,
+}
+
+impl CorsPreflight {
+    pub(crate) fn new(method: Method) -> Self {
+        CorsPreflight { method }
+    }
+
+    /// Returns the method named in the `Access-Control-Request-Method` header.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+}
+
+impl StateData for CorsPreflight {}
+
 impl From<RouteNonMatch> for StatusCode {
     fn from(val: RouteNonMatch) -> StatusCode {
         val.status
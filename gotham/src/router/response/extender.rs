@@ -12,6 +12,15 @@ pub trait StaticResponseExtender: RefUnwindSafe {
 
     /// Extend the response.
     fn extend(state: &mut State, response: &mut Response<Self::ResBody>);
+
+    /// Validates a value after it has been extracted from the request.
+    ///
+    /// An `Err` causes extraction to fail, and the message is stored in `State` as a
+    /// `ValidationError` before `extend` is invoked. By default all values are accepted; this is
+    /// overridden by the `StaticResponseExtender` derive when the type is marked `#[validate]`.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
 }
 
 /// Allow complex types to extend the `Response` based on current `State` and `Response` data.
@@ -2,31 +2,44 @@
 //! and internal extenders have completed.
 
 use std::collections::HashMap;
+use std::panic::RefUnwindSafe;
 use std::sync::Arc;
 
 use futures::future;
-use hyper::{Body, Response, StatusCode};
+use hyper::body::Payload;
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Method, Response, StatusCode};
 use log::trace;
+use mime::Mime;
 
 use crate::handler::HandlerFuture;
-use crate::state::{request_id, State};
+use crate::state::{request_id, FromState, State};
 
 use crate::router::response::extender::ResponseExtender;
+use crate::router::response::hook::BeforeSendHook;
 
 /// Holds an immutable collection of `ResponseExtender` values, as configured using
 /// `ResponseFinalizerBuilder::add`. This type is constructed automatically when using the
 /// `gotham::router::builder` API. See `RouterBuilder::add_response_extender` for details on
-/// configuring `ResponseExtender` values for each `StatusCode`.
+/// configuring `ResponseExtender` values for each `StatusCode`, and `RouterBuilder::before_send`
+/// for configuring `BeforeSendHook` values.
 #[derive(Clone)]
 pub struct ResponseFinalizer {
     data: Arc<HashMap<StatusCode, Box<ResponseExtender<Body> + Send + Sync>>>,
+    hooks: Arc<Vec<Box<dyn BeforeSendHook + Send + Sync>>>,
+    error_body: Option<Arc<ErrorBody>>,
 }
 
 /// Builds an immutable `ResponseFinalizer`.
 pub struct ResponseFinalizerBuilder {
     data: HashMap<StatusCode, Box<ResponseExtender<Body> + Send + Sync>>,
+    hooks: Vec<Box<dyn BeforeSendHook + Send + Sync>>,
+    error_body: Option<Arc<ErrorBody>>,
 }
 
+/// A function producing the content type and body of an empty error response.
+type ErrorBody = dyn Fn(StatusCode) -> (Mime, Vec<u8>) + Send + Sync + RefUnwindSafe;
+
 impl ResponseFinalizerBuilder {
     /// Creates a new ResponseFinalizer instance.
     #[deprecated(
@@ -39,7 +52,11 @@ impl ResponseFinalizerBuilder {
 
     pub(in crate::router) fn internal_new() -> Self {
         let handlers = HashMap::new();
-        ResponseFinalizerBuilder { data: handlers }
+        ResponseFinalizerBuilder {
+            data: handlers,
+            hooks: Vec::new(),
+            error_body: None,
+        }
     }
 
     /// Add an Finalizer for responses that have been assigned this status_code.
@@ -52,17 +69,37 @@ impl ResponseFinalizerBuilder {
         self.data.insert(status_code, extender);
     }
 
+    /// Add a hook for all responses, to be applied after any previously added hooks.
+    pub fn add_hook(&mut self, hook: Box<dyn BeforeSendHook + Send + Sync>) {
+        trace!(" adding before send hook");
+        self.hooks.push(hook);
+    }
+
+    /// Set the function used to fill in the body of empty error responses, replacing any
+    /// previously set function.
+    pub fn set_error_body<F>(&mut self, template: F)
+    where
+        F: Fn(StatusCode) -> (Mime, Vec<u8>) + Send + Sync + RefUnwindSafe + 'static,
+    {
+        trace!(" setting error body template");
+        self.error_body = Some(Arc::new(template));
+    }
+
     /// Finalize population of error handlers for the application, ready for use by a `Router`
     pub fn finalize(self) -> ResponseFinalizer {
         ResponseFinalizer {
             data: Arc::new(self.data),
+            hooks: Arc::new(self.hooks),
+            error_body: self.error_body,
         }
     }
 }
 
 impl ResponseFinalizer {
     /// Finalize the `Response` if a `ResponseFinalizer` has been supplied for the
-    /// status code assigned to the `Response`.
+    /// status code assigned to the `Response`, then fill in the body of an empty error response
+    /// when a template has been set, and apply each `BeforeSendHook` in the order they were
+    /// added.
     pub fn finalize(&self, mut state: State, mut res: Response<Body>) -> Box<HandlerFuture> {
         match self.data.get(&res.status()) {
             Some(extender) => {
@@ -82,6 +119,37 @@ impl ResponseFinalizer {
             }
         }
 
+        if let Some(ref error_body) = self.error_body {
+            if is_empty_error(&res) && *Method::borrow_from(&state) != Method::HEAD {
+                trace!(
+                    "[{}] filling in {} response body",
+                    request_id(&state),
+                    res.status()
+                );
+
+                let (mime, body) = error_body(res.status());
+                let content_type = HeaderValue::from_str(mime.as_ref())
+                    .expect("mime produces valid header values");
+
+                res.headers_mut().insert(CONTENT_TYPE, content_type);
+                *res.body_mut() = Body::from(body);
+            }
+        }
+
+        for hook in self.hooks.iter() {
+            hook.mutate(&state, &mut res);
+        }
+
         Box::new(future::ok((state, res)))
     }
 }
+
+/// Determines whether a response is an error without a body, as generated by the `Router` or an
+/// extractor rather than a handler.
+fn is_empty_error(res: &Response<Body>) -> bool {
+    let status = res.status();
+
+    (status.is_client_error() || status.is_server_error())
+        && !res.headers().contains_key(CONTENT_TYPE)
+        && res.body().is_end_stream()
+}
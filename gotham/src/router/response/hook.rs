@@ -0,0 +1,34 @@
+//! Defines functionality for mutating every `Response` just before it is sent.
+
+use crate::state::{request_id, State};
+use hyper::{Body, Response};
+use log::trace;
+use std::panic::RefUnwindSafe;
+
+/// Mutates the final `Response` of a `Router`, regardless of its status.
+///
+/// Hooks run after any `ResponseExtender` registered for the status of the response, and are
+/// useful for cross-cutting concerns such as security headers which apply to every response.
+/// Hooks are registered via `RouterBuilder::before_send`, and are applied in the order they
+/// were registered.
+///
+/// Unlike `Middleware`, a hook can't short-circuit a request. Hooks always run last, after the
+/// pipelines of the matched route, and also receive the responses generated by the `Router`
+/// itself, such as a `404 Not Found` or `405 Method Not Allowed`.
+pub trait BeforeSendHook: RefUnwindSafe {
+    /// Mutate the response.
+    fn mutate(&self, state: &State, response: &mut Response<Body>);
+}
+
+impl<F> BeforeSendHook for F
+where
+    F: Fn(&State, &mut Response<Body>) + Send + Sync + RefUnwindSafe,
+{
+    fn mutate(&self, state: &State, res: &mut Response<Body>) {
+        trace!(
+            "[{}] running closure based before send hook",
+            request_id(state)
+        );
+        self(state, res);
+    }
+}
@@ -1,13 +1,16 @@
 //! Defines the route `Dispatcher` and supporting types.
 
 use futures::future;
+use hyper::StatusCode;
 use log::trace;
 use std::panic::RefUnwindSafe;
 
 use crate::handler::{Handler, HandlerFuture, IntoHandlerError, NewHandler};
+use crate::helpers::http::response::create_empty_response;
 use crate::pipeline::chain::PipelineHandleChain;
 use crate::pipeline::set::PipelineSet;
-use crate::state::{request_id, State};
+use crate::router::non_match::CorsPreflight;
+use crate::state::{request_id, State, StateData};
 
 /// Used by `Router` to dispatch requests via pipelines and finally into the configured `Handler`.
 pub trait Dispatcher: RefUnwindSafe {
@@ -25,6 +28,7 @@ where
     new_handler: H,
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
+    data: RouteData,
 }
 
 impl<H, C, P> DispatcherImpl<H, C, P>
@@ -46,6 +50,41 @@ where
             new_handler,
             pipeline_chain,
             pipelines,
+            data: RouteData::default(),
+        }
+    }
+
+    /// Sets the data to be put into `State` ahead of the pipelines, for each request.
+    pub(crate) fn with_data(mut self, data: RouteData) -> Self {
+        self.data = data;
+        self
+    }
+}
+
+/// Type alias for the functions placing a value of route data into `State`.
+type PutData = dyn Fn(&mut State) + Send + Sync + RefUnwindSafe;
+
+/// Typed values attached to a route via `DefineSingleRoute::with_data`, which are cloned into
+/// `State` for each request dispatched to the route.
+#[derive(Default)]
+pub(crate) struct RouteData {
+    values: Vec<Box<PutData>>,
+}
+
+impl RouteData {
+    /// Adds a value, replacing any value of the same type added before it.
+    pub(crate) fn push<T>(&mut self, value: T)
+    where
+        T: StateData + Clone + Sync + RefUnwindSafe,
+    {
+        self.values
+            .push(Box::new(move |state| state.put(value.clone())));
+    }
+
+    /// Puts each value into `State`.
+    fn put(&self, state: &mut State) {
+        for put in &self.values {
+            put(state);
         }
     }
 }
@@ -57,12 +96,23 @@ where
     C: PipelineHandleChain<P>,
     P: RefUnwindSafe,
 {
-    fn dispatch(&self, state: State) -> Box<HandlerFuture> {
+    fn dispatch(&self, mut state: State) -> Box<HandlerFuture> {
+        self.data.put(&mut state);
+
         match self.new_handler.new_handler() {
             Ok(h) => {
                 trace!("[{}] cloning handler", request_id(&state));
                 self.pipeline_chain
-                    .call(&self.pipelines, state, move |state| h.handle(state))
+                    .call(&self.pipelines, state, move |state| {
+                        // preflights routed via another method must be answered by a middleware
+                        if state.has::<CorsPreflight>() {
+                            trace!("[{}] unanswered cors preflight", request_id(&state));
+                            let res = create_empty_response(&state, StatusCode::METHOD_NOT_ALLOWED);
+                            return Box::new(future::ok((state, res)));
+                        }
+
+                        h.handle(state)
+                    })
             }
             Err(e) => {
                 trace!("[{}] error cloning handler", request_id(&state));
@@ -217,4 +267,35 @@ mod tests {
         let buf = response.read_body().unwrap();
         assert_eq!(buf.as_slice(), b"24");
     }
+
+    #[test]
+    fn route_data_precedes_pipelines() {
+        let test_server = TestServer::new(|| {
+            Ok(move |state| {
+                let pipelines = new_pipeline_set();
+                let (pipelines, p1) =
+                    pipelines.add(new_pipeline().add(Addition { value: 1 }).build());
+                let pipelines = Arc::new(pipelines);
+
+                let mut data = RouteData::default();
+                data.push(Number { value: 1 });
+                data.push(Number { value: 4 });
+
+                let new_handler = || Ok(handler);
+                let dispatcher =
+                    DispatcherImpl::new(new_handler, (p1, ()), pipelines).with_data(data);
+                dispatcher.dispatch(state)
+            })
+        })
+        .unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        let buf = response.read_body().unwrap();
+        assert_eq!(buf.as_slice(), b"5");
+    }
 }
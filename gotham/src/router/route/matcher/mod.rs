@@ -4,10 +4,12 @@ pub mod accept;
 pub mod and;
 pub mod any;
 pub mod content_type;
+pub mod query;
 
 pub use self::accept::AcceptHeaderRouteMatcher;
 pub use self::and::AndRouteMatcher;
 pub use self::any::AnyRouteMatcher;
+pub use self::query::QueryRouteMatcher;
 
 use std::panic::RefUnwindSafe;
 
@@ -22,6 +24,14 @@ use crate::state::{request_id, FromState, State};
 pub trait RouteMatcher: RefUnwindSafe + Clone {
     /// Determines if the `Request` meets pre-defined conditions.
     fn is_match(&self, state: &State) -> Result<(), RouteNonMatch>;
+
+    /// Returns the HTTP methods this matcher restricts requests to, if any.
+    ///
+    /// This is used for introspection only (e.g. `Router::routes`), and is never consulted when
+    /// matching requests. The default of `None` indicates that any method is accepted.
+    fn methods(&self) -> Option<Vec<Method>> {
+        None
+    }
 }
 
 /// Allow various types to represent themselves as a `RouteMatcher`
@@ -41,6 +51,31 @@ impl IntoRouteMatcher for Vec<Method> {
     }
 }
 
+impl IntoRouteMatcher for &[Method] {
+    type Output = MethodOnlyRouteMatcher;
+
+    fn into_route_matcher(self) -> Self::Output {
+        MethodOnlyRouteMatcher::new(self.to_vec())
+    }
+}
+
+// derive IntoRouteMatcher for method arrays, covering each method once
+macro_rules! derive_into_route_matcher {
+    ($($len:expr),+) => {
+        $(
+            impl IntoRouteMatcher for &[Method; $len] {
+                type Output = MethodOnlyRouteMatcher;
+
+                fn into_route_matcher(self) -> Self::Output {
+                    MethodOnlyRouteMatcher::new(self.to_vec())
+                }
+            }
+        )+
+    };
+}
+
+derive_into_route_matcher!(1, 2, 3, 4, 5, 6, 7, 8, 9);
+
 impl<M> IntoRouteMatcher for M
 where
     M: RouteMatcher + Send + Sync + 'static,
@@ -111,4 +146,8 @@ impl RouteMatcher for MethodOnlyRouteMatcher {
                 .with_allow_list(self.methods.as_slice()))
         }
     }
+
+    fn methods(&self) -> Option<Vec<Method>> {
+        Some(self.methods.clone())
+    }
 }
@@ -0,0 +1,108 @@
+//! Defines the `QueryRouteMatcher`.
+
+use hyper::{StatusCode, Uri};
+use log::trace;
+
+use crate::helpers::http::FormUrlDecoded;
+use crate::router::non_match::RouteNonMatch;
+use crate::router::route::RouteMatcher;
+use crate::state::{request_id, FromState, State};
+
+/// A `RouteMatcher` that succeeds when the query string of the `Request` contains a given key,
+/// optionally with a given value.
+///
+/// Keys and values are compared exactly, after being decoded. When a key appears more than once,
+/// any of its values may match. A failed match is reported as `404 Not Found`, so that routing
+/// continues with any other route for the same path.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # fn main() {
+/// #   use hyper::Uri;
+/// #   use gotham::state::State;
+/// #   use gotham::router::route::matcher::{QueryRouteMatcher, RouteMatcher};
+/// #
+/// #   State::with_new(|state| {
+/// #
+/// let matcher = QueryRouteMatcher::new("format", "csv");
+///
+/// state.put("/articles?format=csv".parse::<Uri>().unwrap());
+/// assert!(matcher.is_match(&state).is_ok());
+///
+/// state.put("/articles?format=json".parse::<Uri>().unwrap());
+/// assert!(matcher.is_match(&state).is_err());
+///
+/// let matcher = QueryRouteMatcher::required_key("preview");
+///
+/// state.put("/articles?preview".parse::<Uri>().unwrap());
+/// assert!(matcher.is_match(&state).is_ok());
+///
+/// state.put("/articles".parse::<Uri>().unwrap());
+/// assert!(matcher.is_match(&state).is_err());
+/// #
+/// #   });
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct QueryRouteMatcher {
+    key: String,
+    value: Option<String>,
+}
+
+impl QueryRouteMatcher {
+    /// Creates a new `QueryRouteMatcher`, requiring the query string to contain `key` with the
+    /// value `value`.
+    pub fn new(key: &str, value: &str) -> Self {
+        QueryRouteMatcher {
+            key: key.to_owned(),
+            value: Some(value.to_owned()),
+        }
+    }
+
+    /// Creates a new `QueryRouteMatcher`, requiring the query string to contain `key` with any
+    /// value, including none at all (as in `?key`).
+    pub fn required_key(key: &str) -> Self {
+        QueryRouteMatcher {
+            key: key.to_owned(),
+            value: None,
+        }
+    }
+}
+
+impl RouteMatcher for QueryRouteMatcher {
+    /// Determines if the query string of the `Request` satisfies the constraint.
+    fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+        let query = Uri::borrow_from(state).query().unwrap_or("");
+
+        let matched = query.split(&['&', ';'][..]).any(|pair| {
+            let (key, value) = match pair.find('=') {
+                Some(i) => (&pair[..i], &pair[i + 1..]),
+                None => (pair, ""),
+            };
+
+            let key_matches = FormUrlDecoded::new(key).map_or(false, |k| k.as_ref() == self.key);
+
+            key_matches
+                && match self.value {
+                    Some(ref expected) => {
+                        FormUrlDecoded::new(value).map_or(false, |v| v.as_ref() == expected)
+                    }
+                    None => true,
+                }
+        });
+
+        if matched {
+            return Ok(());
+        }
+
+        trace!(
+            "[{}] query string did not satisfy the constraint on `{}`",
+            request_id(state),
+            self.key
+        );
+        Err(RouteNonMatch::new(StatusCode::NOT_FOUND))
+    }
+}
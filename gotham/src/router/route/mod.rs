@@ -10,17 +10,18 @@ pub mod matcher;
 use std::marker::PhantomData;
 use std::panic::RefUnwindSafe;
 
-use hyper::{Body, Response, Uri};
+use hyper::{Body, Method, Response, Uri};
 use log::debug;
 
-use crate::extractor::{self, PathExtractor, QueryStringExtractor};
+use crate::extractor::{self, PathExtractor, QueryStringExtractor, ValidationError};
 use crate::handler::HandlerFuture;
 use crate::helpers::http::request::query_string;
 use crate::router::non_match::RouteNonMatch;
+use crate::router::response::extender::StaticResponseExtender;
 use crate::router::route::dispatch::Dispatcher;
 use crate::router::route::matcher::RouteMatcher;
 use crate::router::tree::segment::SegmentMapping;
-use crate::state::{request_id, State};
+use crate::state::{request_id, State, StateData};
 
 #[derive(Clone, Copy, PartialEq)]
 /// Indicates whether this `Route` will dispatch the request to an inner `Router` instance. To
@@ -56,6 +57,9 @@ pub trait Route: RefUnwindSafe {
     /// Determines if this `Route` should be invoked, based on the request data in `State.
     fn is_match(&self, state: &State) -> Result<(), RouteNonMatch>;
 
+    /// Returns the HTTP methods this `Route` is restricted to, or `None` if any method matches.
+    fn methods(&self) -> Option<Vec<Method>>;
+
     /// Determines if this `Route` intends to delegate requests to a secondary `Router` instance.
     fn delegation(&self) -> Delegation;
 
@@ -161,6 +165,10 @@ where
         self.matcher.is_match(state)
     }
 
+    fn methods(&self) -> Option<Vec<Method>> {
+        self.matcher.methods()
+    }
+
     fn delegation(&self) -> Delegation {
         self.delegation
     }
@@ -175,7 +183,7 @@ where
         params: SegmentMapping<'a>,
     ) -> Result<(), ExtractorFailed> {
         match extractor::internal::from_segment_mapping::<PE>(params) {
-            Ok(val) => Ok(state.put(val)),
+            Ok(val) => validate(state, val, "path"),
             Err(e) => {
                 debug!("[{}] path extractor failed: {}", request_id(&state), e);
                 Err(ExtractorFailed)
@@ -195,7 +203,7 @@ where
         };
 
         match result {
-            Ok(val) => Ok(state.put(val)),
+            Ok(val) => validate(state, val, "query string"),
             Err(e) => {
                 debug!(
                     "[{}] query string extractor failed: {}",
@@ -216,6 +224,30 @@ where
     }
 }
 
+/// Runs extractor validation, storing the value in `State` on success and the `ValidationError`
+/// on failure.
+fn validate<T>(state: &mut State, val: T, kind: &str) -> Result<(), ExtractorFailed>
+where
+    T: StaticResponseExtender + StateData,
+{
+    match StaticResponseExtender::validate(&val) {
+        Ok(()) => {
+            state.put(val);
+            Ok(())
+        }
+        Err(message) => {
+            debug!(
+                "[{}] {} extractor validation failed: {}",
+                request_id(state),
+                kind,
+                message
+            );
+            state.put(ValidationError::new(message));
+            Err(ExtractorFailed)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
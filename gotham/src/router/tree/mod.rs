@@ -4,7 +4,7 @@ use crate::helpers::http::PercentDecoded;
 use crate::router::route::Route;
 use crate::router::tree::node::Node;
 use crate::router::tree::segment::{SegmentMapping, SegmentType};
-use hyper::Body;
+use hyper::{Body, Method};
 use log::trace;
 
 pub mod node;
@@ -16,6 +16,7 @@ pub mod segment;
 ///
 /// The `Tree` is created by the `gotham::router::builder` API and used internally by the `Router`
 /// to determine the valid `Route` instances for a request path before dispatch.
+#[derive(Clone)]
 pub struct Tree {
     root: Node,
 }
@@ -52,6 +53,13 @@ impl Tree {
         self.root.has_child(segment, segment_type)
     }
 
+    /// Lists every `Route` within the `Tree` as `(Method, template)` pairs, in traversal order.
+    pub(crate) fn routes(&self) -> Vec<(Method, String)> {
+        let mut routes = Vec::new();
+        self.root.collect_routes("/", &mut routes);
+        routes
+    }
+
     /// Attempt to acquire a path from the `Tree` which matches the `Request` path and is routable.
     pub(crate) fn traverse<'a>(
         &'a self,
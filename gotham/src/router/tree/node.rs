@@ -1,6 +1,6 @@
 //! Defines `Node` for `Tree`.
 
-use hyper::{Body, StatusCode};
+use hyper::{Body, Method, StatusCode};
 use log::trace;
 
 use crate::helpers::http::PercentDecoded;
@@ -11,16 +11,30 @@ use crate::state::{request_id, State};
 
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::sync::Arc;
+
+// the methods listed for routes which accept any method
+const ANY_METHODS: [Method; 7] = [
+    Method::GET,
+    Method::HEAD,
+    Method::POST,
+    Method::PUT,
+    Method::PATCH,
+    Method::DELETE,
+    Method::OPTIONS,
+];
 
 /// A recursive member of `Tree`, representative of segment(s) in a request path.
 ///
 /// Each node includes `0..n` `Route` instances, which can be further evaluated by the `Router`
 /// based on a match. Every node may also have `0..n` children to provide the recursive tree
 /// representation.
+#[derive(Clone)]
 pub struct Node {
     segment: String,
     segment_type: SegmentType,
-    routes: Vec<Box<Route<ResBody = Body> + Send + Sync>>,
+    // routes are shared, so that cloning a tree (as done by a `DynamicRouter`) is cheap
+    routes: Vec<Arc<Box<dyn Route<ResBody = Body> + Send + Sync>>>,
     children: Vec<Node>,
 }
 
@@ -44,7 +58,7 @@ impl Node {
 
     /// Adds a `Route` to this `Node`, to be potentially evaluated by the `Router`.
     pub fn add_route(&mut self, route: Box<Route<ResBody = Body> + Send + Sync>) -> &mut Self {
-        self.routes.push(route);
+        self.routes.push(Arc::new(route));
         self
     }
 
@@ -76,6 +90,15 @@ impl Node {
         !self.routes.is_empty()
     }
 
+    /// Determines if any `Route` of this `Node` accepts the provided method, including routes
+    /// which accept any method.
+    pub(crate) fn has_route_for(&self, method: &Method) -> bool {
+        self.routes.iter().any(|route| match route.methods() {
+            Some(methods) => methods.contains(method),
+            None => true,
+        })
+    }
+
     /// Traverses this `Node` and its children, attempting to a locate a path of `Node` instances
     /// which match all segments of the provided `Request` path. The final `Node` must have at
     /// least a single `Route` attached in order to be returned.
@@ -113,6 +136,50 @@ impl Node {
         &self.segment
     }
 
+    /// Appends a `(Method, template)` pair for each `Route` of this `Node` and its children.
+    ///
+    /// The template of this `Node` is provided by the caller, as nodes don't know their parents.
+    /// Routes which accept any method (such as delegated routes) are listed once per method in
+    /// `ANY_METHODS`.
+    pub(crate) fn collect_routes(&self, template: &str, routes: &mut Vec<(Method, String)>) {
+        for route in self.routes.iter() {
+            let methods = route.methods().unwrap_or_else(|| ANY_METHODS.to_vec());
+
+            for method in methods {
+                if !routes.iter().any(|(m, t)| *m == method && t == template) {
+                    routes.push((method, template.to_owned()));
+                }
+            }
+        }
+
+        for child in self.children.iter() {
+            let mut child_template = template.trim_end_matches('/').to_owned();
+            child_template.push('/');
+
+            match child.segment_type {
+                SegmentType::Static if child.segment.starts_with(&[':', '*'][..]) => {
+                    child_template.push('\\');
+                    child_template.push_str(&child.segment);
+                }
+                SegmentType::Static | SegmentType::Glob => child_template.push_str(&child.segment),
+                SegmentType::Dynamic => {
+                    child_template.push(':');
+                    child_template.push_str(&child.segment);
+                }
+                SegmentType::Constrained { ref regex } => {
+                    // strip the anchors added by `ConstrainedSegmentRegex::new`
+                    let pattern = regex.as_str();
+                    child_template.push(':');
+                    child_template.push_str(&child.segment);
+                    child_template.push(':');
+                    child_template.push_str(&pattern[1..pattern.len() - 1]);
+                }
+            }
+
+            child.collect_routes(&child_template, routes);
+        }
+    }
+
     /// Determines if a `Route` instance associated with this `Node` is willing to `Handle` the
     /// request.
     ///
@@ -135,7 +202,7 @@ impl Node {
             match r.is_match(state) {
                 Ok(()) => {
                     trace!("[{}] found matching route", request_id(state));
-                    return Ok(r);
+                    return Ok(&**r);
                 }
                 Err(e) => {
                     // concat errors
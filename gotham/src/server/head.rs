@@ -0,0 +1,156 @@
+//! Defines the timeout applied to reading the head of each request.
+//!
+//! Hyper only honours a graceful shutdown between requests, and considers a new connection busy
+//! until its first request has been read, so a client sending headers slowly can't be cut off at
+//! the connection level. Instead the IO of the connection is wrapped, and reads fail once the
+//! head of a request has taken too long to arrive, after the client is sent a
+//! `408 Request Timeout`.
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll};
+use log::{debug, trace};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::timer::Delay;
+
+use crate::state::StateData;
+
+use super::idle::Activity;
+
+/// Sent to clients which take too long to send the head of a request, before the connection
+/// is closed.
+const TIMEOUT_RESPONSE: &[u8] =
+    b"HTTP/1.1 408 Request Timeout\r\nconnection: close\r\ncontent-length: 0\r\n\r\n";
+
+/// The time taken by the client to send the head of a request, measured from when the connection
+/// opened or the first byte following the previous request.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct HeadReadTime(pub(crate) Duration);
+
+impl StateData for HeadReadTime {}
+
+/// Wraps the IO of a connection, recording reads with its `Activity` and failing them once a
+/// request head has taken longer than `timeout` to arrive.
+pub(crate) struct HeaderTimeout<IO> {
+    io: IO,
+    activity: Arc<Activity>,
+    timeout: Option<Duration>,
+    delay: Option<Delay>,
+    timed_out: bool,
+}
+
+impl<IO> HeaderTimeout<IO>
+where
+    IO: AsyncRead + AsyncWrite,
+{
+    pub(crate) fn new(io: IO, activity: Arc<Activity>, timeout: Option<Duration>) -> Self {
+        HeaderTimeout {
+            io,
+            activity,
+            timeout,
+            delay: None,
+            timed_out: false,
+        }
+    }
+
+    /// Determines whether the head currently being read has passed its deadline, scheduling a
+    /// wake up for the deadline otherwise.
+    fn expired(&mut self) -> bool {
+        let deadline = match (self.timeout, self.activity.head_started()) {
+            (Some(timeout), Some(started)) => started + timeout,
+            _ => return false,
+        };
+
+        if deadline <= Instant::now() {
+            return true;
+        }
+
+        let delay = self.delay.get_or_insert_with(|| Delay::new(deadline));
+        delay.reset(deadline);
+
+        match delay.poll() {
+            Ok(Async::Ready(())) => true,
+            Ok(Async::NotReady) => false,
+            Err(e) => {
+                // without a timer the head can't be bounded, so it's read as usual
+                trace!("unable to apply header read timeout: {}", e);
+                self.timeout = None;
+                false
+            }
+        }
+    }
+
+    /// Sends the client a `408 Request Timeout`, without waiting for the response to be written
+    /// as the connection is closed regardless.
+    fn time_out(&mut self) -> io::Error {
+        debug!("closing connection after a request head timed out");
+
+        self.timed_out = true;
+        let _ = self
+            .io
+            .write(TIMEOUT_RESPONSE)
+            .and_then(|_| self.io.flush());
+        let _ = self.io.shutdown();
+
+        timed_out_error()
+    }
+}
+
+fn timed_out_error() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "request head timed out")
+}
+
+impl<IO> Read for HeaderTimeout<IO>
+where
+    IO: AsyncRead + AsyncWrite,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.timed_out {
+            return Err(timed_out_error());
+        }
+
+        if self.expired() {
+            return Err(self.time_out());
+        }
+
+        let n = self.io.read(buf)?;
+        if n > 0 {
+            self.activity.read();
+        }
+
+        Ok(n)
+    }
+}
+
+impl<IO> AsyncRead for HeaderTimeout<IO>
+where
+    IO: AsyncRead + AsyncWrite,
+{
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.io.prepare_uninitialized_buffer(buf)
+    }
+}
+
+impl<IO> Write for HeaderTimeout<IO>
+where
+    IO: AsyncRead + AsyncWrite,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.io.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl<IO> AsyncWrite for HeaderTimeout<IO>
+where
+    IO: AsyncRead + AsyncWrite,
+{
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
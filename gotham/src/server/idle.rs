@@ -0,0 +1,212 @@
+//! Defines the idle timeout applied to kept-alive connections.
+//!
+//! Hyper doesn't offer a keep-alive timeout for server connections, so the connection future is
+//! wrapped and shut down gracefully once no request has been active for the configured duration.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll};
+use tokio::timer::Delay;
+
+/// Tracks request activity on a single connection.
+#[derive(Debug)]
+pub(crate) struct Activity {
+    in_flight: AtomicUsize,
+    last_active: Mutex<Instant>,
+    head_started: Mutex<Option<Instant>>,
+}
+
+impl Activity {
+    /// Creates a new `Activity`, considering the connection active as of now. The head of the
+    /// first request is awaited from this point on.
+    pub(crate) fn new() -> Self {
+        let now = Instant::now();
+
+        Activity {
+            in_flight: AtomicUsize::new(0),
+            last_active: Mutex::new(now),
+            head_started: Mutex::new(Some(now)),
+        }
+    }
+
+    /// Marks the start of a request on the connection, once its head has been read.
+    pub(crate) fn begin(&self) {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        self.head_started.lock().unwrap().take();
+        self.touch();
+    }
+
+    /// Marks the completion of a request on the connection.
+    pub(crate) fn end(&self) {
+        self.touch();
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Marks data being read from the connection. Between requests this starts the head of the
+    /// next request.
+    pub(crate) fn read(&self) {
+        if self.in_flight.load(Ordering::SeqCst) == 0 {
+            let mut head_started = self.head_started.lock().unwrap();
+            if head_started.is_none() {
+                *head_started = Some(Instant::now());
+            }
+        }
+    }
+
+    /// Returns the instant at which reading the current request head started, if one is being
+    /// read.
+    pub(crate) fn head_started(&self) -> Option<Instant> {
+        if self.in_flight.load(Ordering::SeqCst) > 0 {
+            return None;
+        }
+        *self.head_started.lock().unwrap()
+    }
+
+    fn touch(&self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+    }
+
+    /// Returns the instant at which the connection becomes idle, or `None` if a request
+    /// is currently being processed.
+    fn idle_at(&self, timeout: Duration) -> Option<Instant> {
+        if self.in_flight.load(Ordering::SeqCst) > 0 {
+            return None;
+        }
+        Some(*self.last_active.lock().unwrap() + timeout)
+    }
+}
+
+/// Wraps a connection future, triggering `shutdown` once the connection has been idle for longer
+/// than `timeout`. The connection continues to be polled afterwards so it can close cleanly.
+pub(crate) struct IdleTimeout<C, S> {
+    conn: C,
+    shutdown: Option<S>,
+    activity: Arc<Activity>,
+    timeout: Duration,
+    delay: Delay,
+}
+
+impl<C, S> IdleTimeout<C, S>
+where
+    C: Future,
+    S: FnOnce(&mut C),
+{
+    pub(crate) fn new(conn: C, shutdown: S, activity: Arc<Activity>, timeout: Duration) -> Self {
+        IdleTimeout {
+            conn,
+            shutdown: Some(shutdown),
+            activity,
+            timeout,
+            delay: Delay::new(Instant::now() + timeout),
+        }
+    }
+}
+
+impl<C, S> Future for IdleTimeout<C, S>
+where
+    C: Future,
+    S: FnOnce(&mut C),
+{
+    type Item = C::Item;
+    type Error = C::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Async::Ready(item) = self.conn.poll()? {
+            return Ok(Async::Ready(item));
+        }
+
+        while self.shutdown.is_some() {
+            match self.delay.poll() {
+                Ok(Async::Ready(())) => (),
+                Ok(Async::NotReady) => break,
+                // a timer error means the runtime is going away, so just stop checking
+                Err(_) => {
+                    self.shutdown = None;
+                    break;
+                }
+            }
+
+            match self.activity.idle_at(self.timeout) {
+                Some(deadline) if deadline <= Instant::now() => {
+                    let shutdown = self.shutdown.take().unwrap();
+                    shutdown(&mut self.conn);
+                    return self.conn.poll();
+                }
+                Some(deadline) => self.delay.reset(deadline),
+                None => self.delay.reset(Instant::now() + self.timeout),
+            }
+        }
+
+        Ok(Async::NotReady)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::AtomicBool;
+
+    use futures::future;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn shuts_down_idle_connections() {
+        let closed = Arc::new(AtomicBool::new(false));
+        let activity = Arc::new(Activity::new());
+
+        let flag = closed.clone();
+        let conn = future::poll_fn(move || -> Poll<(), ()> {
+            if flag.load(Ordering::SeqCst) {
+                Ok(Async::Ready(()))
+            } else {
+                Ok(Async::NotReady)
+            }
+        });
+
+        let flag = closed.clone();
+        let shutdown = move |_: &mut _| flag.store(true, Ordering::SeqCst);
+
+        activity.begin();
+        activity.end();
+
+        let timeout = Duration::from_millis(20);
+        let started = Instant::now();
+        let idle = IdleTimeout::new(conn, shutdown, activity, timeout);
+
+        Runtime::new().unwrap().block_on(idle).unwrap();
+
+        assert!(closed.load(Ordering::SeqCst));
+        assert!(started.elapsed() >= timeout);
+    }
+
+    #[test]
+    fn tracks_request_heads() {
+        let activity = Activity::new();
+        assert!(activity.head_started().is_some());
+
+        activity.begin();
+        activity.read();
+        assert!(activity.head_started().is_none());
+
+        activity.end();
+        assert!(activity.head_started().is_none());
+
+        activity.read();
+        assert!(activity.head_started().is_some());
+    }
+
+    #[test]
+    fn waits_for_in_flight_requests() {
+        let activity = Activity::new();
+        let timeout = Duration::from_secs(1);
+
+        activity.begin();
+        assert!(activity.idle_at(timeout).is_none());
+
+        activity.end();
+        assert!(activity.idle_at(timeout).unwrap() > Instant::now());
+    }
+}
@@ -0,0 +1,91 @@
+//! Defines the limit applied to the number of concurrently open connections.
+//!
+//! The stream of incoming connections is wrapped so that it stops accepting once the limit has
+//! been reached. Further connections are left queued by the operating system until an accepted
+//! connection closes, rather than being accepted and held in memory by the server.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures::task::AtomicTask;
+use futures::{try_ready, Async, Poll, Stream};
+
+/// Counts the connections currently open, waking the accept loop as they close.
+struct Limit {
+    max: Option<usize>,
+    open: AtomicUsize,
+    task: AtomicTask,
+}
+
+impl Limit {
+    fn is_reached(&self) -> bool {
+        match self.max {
+            Some(max) => self.open.load(Ordering::SeqCst) >= max,
+            None => false,
+        }
+    }
+}
+
+/// Held for as long as an accepted connection is open, releasing its slot when dropped.
+pub(crate) struct ConnectionPermit {
+    limit: Arc<Limit>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.limit.open.fetch_sub(1, Ordering::SeqCst);
+        self.limit.task.notify();
+    }
+}
+
+/// Wraps a stream of incoming connections, yielding each alongside a `ConnectionPermit` and
+/// pausing while `max` connections are open.
+pub(crate) struct LimitConnections<S> {
+    incoming: S,
+    limit: Arc<Limit>,
+}
+
+impl<S> LimitConnections<S> {
+    pub(crate) fn new(incoming: S, max: Option<usize>) -> Self {
+        LimitConnections {
+            incoming,
+            limit: Arc::new(Limit {
+                max,
+                open: AtomicUsize::new(0),
+                task: AtomicTask::new(),
+            }),
+        }
+    }
+}
+
+impl<S> Stream for LimitConnections<S>
+where
+    S: Stream,
+{
+    type Item = (S::Item, ConnectionPermit);
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if self.limit.is_reached() {
+            // register before checking again, so a connection closing in between isn't missed
+            self.limit.task.register();
+
+            if self.limit.is_reached() {
+                return Ok(Async::NotReady);
+            }
+        }
+
+        match try_ready!(self.incoming.poll()) {
+            Some(connection) => {
+                self.limit.open.fetch_add(1, Ordering::SeqCst);
+
+                let permit = ConnectionPermit {
+                    limit: self.limit.clone(),
+                };
+
+                Ok(Async::Ready(Some((connection, permit))))
+            }
+            None => Ok(Async::Ready(None)),
+        }
+    }
+}
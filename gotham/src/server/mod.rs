@@ -0,0 +1,258 @@
+//! Defines the `ServerOptions` type used to tune how a Gotham server manages its connections,
+//! along with the connection handling shared by each transport.
+
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::Either;
+use futures::{Future, IntoFuture, Stream};
+use hyper::server::conn::Http;
+use tokio::executor;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::handler::NewHandler;
+use crate::service::{ConnectedGothamService, GothamService};
+
+use self::head::HeaderTimeout;
+use self::idle::IdleTimeout;
+use self::limit::LimitConnections;
+
+pub(crate) mod head;
+pub(crate) mod idle;
+pub(crate) mod limit;
+
+/// Connection level options applied by the Gotham server when serving requests.
+///
+/// The defaults are suitable for most deployments; keep-alive is enabled, idle connections are
+/// closed after 75 seconds and there is no limit on the number of connections, or the number of
+/// requests per connection. When
+/// running behind a load balancer the idle timeout should generally be longer than the idle
+/// timeout of the load balancer itself, to avoid racing it when closing connections.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use std::time::Duration;
+/// # use gotham::server::ServerOptions;
+/// let options = ServerOptions::new()
+///     .keep_alive_timeout(Some(Duration::from_secs(120)))
+///     .max_requests_per_connection(Some(1000));
+///
+/// // alternatively, close each connection after a single response
+/// let close = ServerOptions::new().keep_alive(false);
+/// # let _ = (options, close);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ServerOptions {
+    keep_alive: bool,
+    keep_alive_timeout: Option<Duration>,
+    max_requests_per_connection: Option<usize>,
+    max_connections: Option<usize>,
+    header_read_timeout: Option<Duration>,
+    max_uri_length: Option<usize>,
+}
+
+impl ServerOptions {
+    /// Creates a new `ServerOptions` using the default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enables or disables HTTP keep-alive.
+    ///
+    /// When disabled, every response is sent with a `Connection: close` header and the
+    /// connection is closed once the response has been written.
+    pub fn keep_alive(mut self, enabled: bool) -> Self {
+        self.keep_alive = enabled;
+        self
+    }
+
+    /// Sets how long a kept-alive connection may sit idle before it is closed.
+    ///
+    /// Providing `None` leaves idle connections open until the client closes them.
+    pub fn keep_alive_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.keep_alive_timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum number of requests served on a single connection.
+    ///
+    /// The response to the final request carries a `Connection: close` header, after which
+    /// the connection is closed. Providing `None` removes the limit.
+    pub fn max_requests_per_connection(mut self, max: Option<usize>) -> Self {
+        self.max_requests_per_connection = max.map(|max| max.max(1));
+        self
+    }
+
+    /// Sets the maximum number of connections open at once.
+    ///
+    /// Once the limit is reached the server stops accepting connections until an open connection
+    /// closes, leaving new connections queued by the operating system in the meantime. This
+    /// bounds the resources used under a spike in load, at the cost of latency for the queued
+    /// clients. Providing `None` removes the limit.
+    pub fn max_connections(mut self, max: Option<usize>) -> Self {
+        self.max_connections = max.map(|max| max.max(1));
+        self
+    }
+
+    /// Sets how long a client may take to send the headers of a request, measured from when the
+    /// connection opens or the previous response completes.
+    ///
+    /// Connections which exceed it are sent a `408 Request Timeout` and closed, protecting the
+    /// server against "Slowloris" style attacks which hold connections open by sending headers a
+    /// byte at a time. Between requests the timeout starts with the first byte of the next
+    /// request, leaving connections which send nothing to the `keep_alive_timeout`. For TLS
+    /// connections it starts once the handshake has completed. Providing `None`, the default,
+    /// leaves only the keep-alive timeout in place.
+    ///
+    /// Slow request bodies can be guarded against using the `SlowReadProtectionMiddleware`.
+    pub fn header_read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.header_read_timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum length of the request target (the URI in the request line), in bytes.
+    ///
+    /// Requests with a longer target are answered with a `414 URI Too Long` before any routing
+    /// takes place, so that overly long URIs never reach handlers or request logs. Providing
+    /// `None`, the default, leaves only the limits imposed by Hyper in place.
+    pub fn max_uri_length(mut self, max: Option<usize>) -> Self {
+        self.max_uri_length = max;
+        self
+    }
+
+    /// Returns the maximum length of a request target, if any.
+    pub(crate) fn uri_length_limit(&self) -> Option<usize> {
+        self.max_uri_length
+    }
+
+    /// Returns the maximum number of connections open at once, if any.
+    pub(crate) fn connection_limit(&self) -> Option<usize> {
+        self.max_connections
+    }
+
+    /// Returns the number of requests after which a connection should be closed, if any.
+    pub(crate) fn requests_per_connection(&self) -> Option<usize> {
+        if self.keep_alive {
+            self.max_requests_per_connection
+        } else {
+            Some(1)
+        }
+    }
+
+    /// Returns the idle timeout to apply to kept-alive connections, if any.
+    pub(crate) fn idle_timeout(&self) -> Option<Duration> {
+        if self.keep_alive {
+            self.keep_alive_timeout
+        } else {
+            None
+        }
+    }
+
+    /// Returns the time allowed to read the head of each request, if limited.
+    pub(crate) fn header_timeout(&self) -> Option<Duration> {
+        self.header_read_timeout
+    }
+
+    /// Creates the Hyper protocol configuration matching these options.
+    pub(crate) fn protocol(&self) -> Http {
+        let mut protocol = Http::new();
+        protocol.keep_alive(self.keep_alive);
+        protocol
+    }
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        ServerOptions {
+            keep_alive: true,
+            keep_alive_timeout: Some(Duration::from_secs(75)),
+            max_requests_per_connection: None,
+            max_connections: None,
+            header_read_timeout: None,
+            max_uri_length: None,
+        }
+    }
+}
+
+/// Serves the connections accepted from `incoming`, applying the connection limit and timeouts
+/// of the provided `ServerOptions`.
+///
+/// Each transport provides `connect`, which completes any handshake required by an accepted
+/// connection and pairs it with the service it's served by. Connections which fail are closed,
+/// without affecting the others.
+pub(crate) fn serve<S, NH, C, F, IO>(
+    incoming: S,
+    new_handler: NH,
+    options: ServerOptions,
+    connect: C,
+) -> impl Future<Item = (), Error = ()>
+where
+    S: Stream<Error = io::Error>,
+    NH: NewHandler + 'static,
+    C: Fn(S::Item, &GothamService<NH>) -> F,
+    F: IntoFuture<Item = (IO, ConnectedGothamService<NH>), Error = ()>,
+    F::Future: Send + 'static,
+    IO: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let protocol = Arc::new(options.protocol());
+    let max_connections = options.connection_limit();
+    let header_timeout = options.header_timeout();
+    let gotham_service = GothamService::with_options(new_handler, options);
+
+    let incoming = incoming.map_err(|e| panic!("socket error = {:?}", e));
+
+    LimitConnections::new(incoming, max_connections).for_each(move |(socket, permit)| {
+        let protocol = protocol.clone();
+
+        let handler = connect(socket, &gotham_service)
+            .into_future()
+            .and_then(move |(io, service)| {
+                let activity = service.activity();
+                let idle_timeout = service.idle_timeout();
+
+                let io = HeaderTimeout::new(io, activity.clone(), header_timeout);
+                let connection = protocol.serve_connection(io, service);
+
+                match idle_timeout {
+                    Some(timeout) => Either::A(IdleTimeout::new(
+                        connection,
+                        |connection| connection.graceful_shutdown(),
+                        activity,
+                        timeout,
+                    )),
+                    None => Either::B(connection),
+                }
+                .then(|_| Ok(()))
+            })
+            .then(move |result| {
+                drop(permit);
+                result
+            });
+
+        executor::spawn(handler);
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separates_idle_and_header_timeouts() {
+        let secs = |secs| Some(Duration::from_secs(secs));
+
+        assert_eq!(ServerOptions::new().idle_timeout(), secs(75));
+        assert_eq!(ServerOptions::new().header_timeout(), None);
+
+        let options = ServerOptions::new().header_read_timeout(secs(10));
+        assert_eq!(options.idle_timeout(), secs(75));
+        assert_eq!(options.header_timeout(), secs(10));
+
+        let options = options.keep_alive(false);
+        assert_eq!(options.idle_timeout(), None);
+        assert_eq!(options.header_timeout(), secs(10));
+    }
+}
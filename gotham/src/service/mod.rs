@@ -5,19 +5,27 @@ use std::net::SocketAddr;
 use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
 use failure;
 
+use futures::future::{self, Either};
 use futures::Future;
 use http::request;
+use hyper::header::{HeaderValue, CONNECTION, EXPECT};
 use hyper::service::Service;
-use hyper::{Body, Request, Response};
+use hyper::{Body, Request, Response, StatusCode, Uri};
 use log::debug;
 
 use crate::handler::NewHandler;
 use crate::helpers::http::request::path::RequestPathSegments;
+use crate::helpers::http::response::create_empty_response;
+use crate::server::head::HeadReadTime;
+use crate::server::idle::Activity;
+use crate::server::ServerOptions;
 use crate::state::client_addr::put_client_addr;
-use crate::state::{set_request_id, State};
+use crate::state::{request_id, set_request_id, set_request_start, RequestContext, State};
+use crate::tls::{AlpnProtocol, TlsConnection};
 
 mod trap;
 
@@ -28,34 +36,92 @@ where
     T: NewHandler + 'static,
 {
     handler: Arc<T>,
+    options: ServerOptions,
 }
 
 impl<T> GothamService<T>
 where
     T: NewHandler + 'static,
 {
+    #[cfg(test)]
     pub(crate) fn new(handler: T) -> GothamService<T> {
+        GothamService::with_options(handler, ServerOptions::default())
+    }
+
+    pub(crate) fn with_options(handler: T, options: ServerOptions) -> GothamService<T> {
         GothamService {
             handler: Arc::new(handler),
+            options,
         }
     }
 
     pub(crate) fn connect(&self, client_addr: SocketAddr) -> ConnectedGothamService<T> {
+        self.connect_with(Some(client_addr))
+    }
+
+    /// Connects a client which has no address, such as one accepted on a Unix domain socket.
+    #[cfg(unix)]
+    pub(crate) fn connect_without_addr(&self) -> ConnectedGothamService<T> {
+        self.connect_with(None)
+    }
+
+    fn connect_with(&self, client_addr: Option<SocketAddr>) -> ConnectedGothamService<T> {
         ConnectedGothamService {
             client_addr,
+            alpn_protocol: None,
+            secure: false,
             handler: self.handler.clone(),
+            served: 0,
+            max_requests: self.options.requests_per_connection(),
+            max_uri_length: self.options.uri_length_limit(),
+            activity: Arc::new(Activity::new()),
+            idle_timeout: self.options.idle_timeout(),
         }
     }
 }
 
 /// A `GothamService` which has been connected to a client. The major difference is that a
-/// `client_addr` has been assigned (as this isn't available from Hyper).
+/// `client_addr` has been assigned (as this isn't available from Hyper), when the client has one.
 pub(crate) struct ConnectedGothamService<T>
 where
     T: NewHandler + 'static,
 {
     handler: Arc<T>,
-    client_addr: SocketAddr,
+    client_addr: Option<SocketAddr>,
+    alpn_protocol: Option<AlpnProtocol>,
+    secure: bool,
+    served: usize,
+    max_requests: Option<usize>,
+    max_uri_length: Option<usize>,
+    activity: Arc<Activity>,
+    idle_timeout: Option<Duration>,
+}
+
+impl<T> ConnectedGothamService<T>
+where
+    T: NewHandler + 'static,
+{
+    /// Sets the protocol negotiated via ALPN, to be placed into the `State` of each request.
+    pub(crate) fn alpn_protocol(mut self, protocol: Option<AlpnProtocol>) -> Self {
+        self.alpn_protocol = protocol;
+        self
+    }
+
+    /// Sets whether the connection is secured by TLS, marking each request with `TlsConnection`.
+    pub(crate) fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Returns the tracker of request activity on the connection.
+    pub(crate) fn activity(&self) -> Arc<Activity> {
+        self.activity.clone()
+    }
+
+    /// Returns the timeout used to close idle connections, if enabled.
+    pub(crate) fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
 }
 
 impl<T> Service for ConnectedGothamService<T>
@@ -69,8 +135,19 @@ where
 
     fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
         let mut state = State::new();
+        set_request_start(&mut state);
+
+        if let Some(addr) = self.client_addr {
+            put_client_addr(&mut state, addr);
+        }
+
+        if let Some(ref protocol) = self.alpn_protocol {
+            state.put(protocol.clone());
+        }
 
-        put_client_addr(&mut state, self.client_addr);
+        if self.secure {
+            state.put(TlsConnection);
+        }
 
         let (
             request::Parts {
@@ -84,12 +161,21 @@ where
             body,
         ) = req.into_parts();
 
+        let uri_too_long = self
+            .max_uri_length
+            .map_or(false, |max| request_target_length(&uri) > max);
+
+        let unsupported_expectation = headers
+            .get(EXPECT)
+            .map_or(false, |expect| !expects_continue(expect));
+
         state.put(RequestPathSegments::new(uri.path()));
         state.put(method);
         state.put(uri);
         state.put(version);
         state.put(headers);
         state.put(body);
+        state.put(RequestContext::new());
 
         {
             let request_id = set_request_id(&mut state);
@@ -100,10 +186,73 @@ where
             );
         };
 
-        trap::call_handler(&*self.handler, AssertUnwindSafe(state))
+        self.served += 1;
+        let close = self.max_requests.map_or(false, |max| self.served >= max);
+        let activity = self.activity.clone();
+
+        if let Some(started) = activity.head_started() {
+            state.put(HeadReadTime(started.elapsed()));
+        }
+
+        activity.begin();
+
+        let f = if uri_too_long {
+            debug!(
+                "[{}] rejecting request with an overly long URI",
+                request_id(&state)
+            );
+            Either::A(future::ok(create_empty_response(
+                &state,
+                StatusCode::URI_TOO_LONG,
+            )))
+        } else if unsupported_expectation {
+            debug!(
+                "[{}] rejecting request with an unsupported expectation",
+                request_id(&state)
+            );
+            Either::A(future::ok(create_empty_response(
+                &state,
+                StatusCode::EXPECTATION_FAILED,
+            )))
+        } else {
+            Either::B(trap::call_handler(&*self.handler, AssertUnwindSafe(state)))
+        };
+
+        let f = f.then(move |result| {
+            activity.end();
+
+            result.map(|mut response| {
+                if close {
+                    response
+                        .headers_mut()
+                        .insert(CONNECTION, HeaderValue::from_static("close"));
+                }
+                response
+            })
+        });
+
+        Box::new(f)
     }
 }
 
+/// Determines whether an `Expect` header only asks for a `100 Continue`, which is the only
+/// expectation supported. Hyper sends the `100 Continue` itself, as soon as the request head has
+/// been read, so the client starts sending the body while the request is routed.
+fn expects_continue(expect: &HeaderValue) -> bool {
+    expect.as_bytes().eq_ignore_ascii_case(b"100-continue")
+}
+
+/// Returns the length of the request target a `Uri` was parsed from, without formatting it.
+fn request_target_length(uri: &Uri) -> usize {
+    let prefix = match (uri.scheme_part(), uri.authority_part()) {
+        (Some(scheme), Some(authority)) => scheme.as_str().len() + 3 + authority.as_str().len(),
+        (None, Some(authority)) => authority.as_str().len(),
+        _ => 0,
+    };
+
+    prefix + uri.path_and_query().map_or(0, |pq| pq.as_str().len())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,4 +299,62 @@ mod tests {
         let response = f.wait().unwrap();
         assert_eq!(response.status(), StatusCode::ACCEPTED);
     }
+
+    #[test]
+    fn rejects_long_uris() {
+        let router = build_simple_router(|route| {
+            route.get("/*").to(handler);
+        });
+
+        let options = ServerOptions::new().max_uri_length(Some(32));
+        let service = GothamService::with_options(router, options);
+
+        let call = |uri: &str| {
+            let req = Request::get(uri).body(Body::empty()).unwrap();
+            let f = service
+                .connect("127.0.0.1:10000".parse().unwrap())
+                .call(req);
+            f.wait().unwrap().status()
+        };
+
+        // "http://localhost" accounts for 16 bytes of each target
+        let path = format!("/{}", "a".repeat(15));
+        assert_eq!(
+            call(&format!("http://localhost{}", path)),
+            StatusCode::ACCEPTED
+        );
+        assert_eq!(
+            call(&format!("http://localhost{}x", path)),
+            StatusCode::URI_TOO_LONG
+        );
+
+        assert_eq!(
+            call("/path?query=0123456789abcdefghij"),
+            StatusCode::ACCEPTED
+        );
+        assert_eq!(
+            call("/path?query=0123456789abcdefghijk"),
+            StatusCode::URI_TOO_LONG
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_expectations() {
+        let service = GothamService::new(|| Ok(handler));
+
+        let call = |expect: &str| {
+            let req = Request::post("http://localhost/")
+                .header(EXPECT, expect)
+                .body(Body::from("upload"))
+                .unwrap();
+            let f = service
+                .connect("127.0.0.1:10000".parse().unwrap())
+                .call(req);
+            f.wait().unwrap().status()
+        };
+
+        assert_eq!(call("100-continue"), StatusCode::ACCEPTED);
+        assert_eq!(call("100-Continue"), StatusCode::ACCEPTED);
+        assert_eq!(call("202-accepted"), StatusCode::EXPECTATION_FAILED);
+    }
 }
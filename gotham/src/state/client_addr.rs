@@ -14,7 +14,8 @@ pub(crate) fn put_client_addr(state: &mut State, addr: SocketAddr) {
 }
 
 /// Returns the client `SocketAddr` as reported by hyper, if one was present. Certain connections
-/// do not report a client address, in which case this will return `None`.
+/// do not report a client address, such as those accepted on a Unix domain socket, in which case
+/// this will return `None`.
 ///
 /// # Examples
 ///
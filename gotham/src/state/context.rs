@@ -0,0 +1,154 @@
+//! Defines a map of arbitrary metadata attached to a `Request`.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+use crate::state::StateData;
+
+/// Arbitrary metadata attached to a request, without requiring a new `StateData` type for each
+/// value.
+///
+/// Values can be stored either under a string key, for small pieces of textual metadata such as
+/// a trace identifier, or by type, with one value of each type retained in the same way as
+/// `State`. The two are stored independently, so a string key never collides with a type.
+///
+/// An empty `RequestContext` is stored in `State` by `GothamService` as soon as a request is
+/// received, so it is always available to middleware and handlers.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response};
+/// # use gotham::state::{FromState, RequestContext, State};
+/// #
+/// struct Tenant(u64);
+///
+/// fn my_handler(mut state: State) -> (State, Response<Body>) {
+///     {
+///         let ctx = RequestContext::borrow_mut_from(&mut state);
+///         ctx.insert("trace_id", "abc123");
+///         ctx.insert_typed(Tenant(42));
+///     }
+///
+///     let ctx = RequestContext::borrow_from(&state);
+///     assert_eq!(ctx.get("trace_id"), Some("abc123"));
+///     assert_eq!(ctx.get_typed::<Tenant>().map(|tenant| tenant.0), Some(42));
+/// #   (state, Response::new(Body::empty()))
+/// }
+/// # fn main() { let _ = my_handler; }
+/// ```
+#[derive(Default)]
+pub struct RequestContext {
+    values: HashMap<String, String>,
+    typed: HashMap<TypeId, Box<dyn Any + Send>>,
+}
+
+impl RequestContext {
+    /// Creates a new, empty `RequestContext`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores a value under the provided key, returning any value previously stored.
+    pub fn insert<K, V>(&mut self, key: K, value: V) -> Option<String>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.values.insert(key.into(), value.into())
+    }
+
+    /// Returns the value stored under the provided key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// Removes and returns the value stored under the provided key.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.values.remove(key)
+    }
+
+    /// Returns an iterator over each key and value, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Stores a value by its type, returning any value of the same type previously stored.
+    pub fn insert_typed<T>(&mut self, value: T) -> Option<T>
+    where
+        T: Any + Send,
+    {
+        self.typed
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast::<T>().ok())
+            .map(|previous| *previous)
+    }
+
+    /// Returns the value of the provided type.
+    pub fn get_typed<T>(&self) -> Option<&T>
+    where
+        T: Any + Send,
+    {
+        self.typed
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+    }
+
+    /// Returns the value of the provided type, mutably.
+    pub fn get_typed_mut<T>(&mut self) -> Option<&mut T>
+    where
+        T: Any + Send,
+    {
+        self.typed
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut::<T>())
+    }
+
+    /// Removes and returns the value of the provided type.
+    pub fn remove_typed<T>(&mut self) -> Option<T>
+    where
+        T: Any + Send,
+    {
+        self.typed
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast::<T>().ok())
+            .map(|value| *value)
+    }
+}
+
+impl StateData for RequestContext {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_keyed_and_typed_values() {
+        struct TraceId(&'static str);
+
+        let mut ctx = RequestContext::new();
+
+        assert_eq!(ctx.insert("trace_id", "abc123"), None);
+        assert_eq!(ctx.insert("trace_id", "def456"), Some("abc123".to_owned()));
+        assert_eq!(ctx.get("trace_id"), Some("def456"));
+        assert_eq!(ctx.get("missing"), None);
+
+        assert!(ctx.insert_typed(TraceId("typed")).is_none());
+        assert_eq!(ctx.get_typed::<TraceId>().map(|id| id.0), Some("typed"));
+        assert_eq!(ctx.get_typed::<String>(), None);
+
+        ctx.get_typed_mut::<TraceId>().unwrap().0 = "changed";
+        assert_eq!(
+            ctx.insert_typed(TraceId("new")).map(|id| id.0),
+            Some("changed")
+        );
+
+        assert_eq!(ctx.iter().collect::<Vec<_>>(), vec![("trace_id", "def456")]);
+        assert_eq!(ctx.remove("trace_id"), Some("def456".to_owned()));
+        assert_eq!(ctx.remove_typed::<TraceId>().map(|id| id.0), Some("new"));
+        assert!(ctx.get_typed::<TraceId>().is_none());
+    }
+}
@@ -0,0 +1,83 @@
+//! Defines convenience functions for reading the headers of the current request
+
+use hyper::header::{AsHeaderName, HeaderMap};
+
+use crate::state::{FromState, State};
+
+/// Returns the first value of a request header as a string, or `None` when the header is
+/// missing or its value isn't visible ASCII.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::USER_AGENT;
+/// # use gotham::state::{header_str, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn my_handler(state: State) -> (State, Response<Body>) {
+///     let user_agent = header_str(&state, USER_AGENT).unwrap_or("unknown");
+///     let tenant = header_str(&state, "x-tenant-id").unwrap_or("default");
+///
+///     let body = format!("{} for {}", user_agent, tenant);
+///     (state, Response::new(Body::from(body)))
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(my_handler)).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://localhost/")
+/// #       .with_header(USER_AGENT, "curl/8.5.0".parse().unwrap())
+/// #       .perform()
+/// #       .unwrap();
+/// #
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "curl/8.5.0 for default");
+/// # }
+/// ```
+pub fn header_str<K: AsHeaderName>(state: &State, name: K) -> Option<&str> {
+    HeaderMap::borrow_from(state)
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Returns every value of a request header as strings, in the order they were received.
+///
+/// Values which aren't visible ASCII are skipped, and a missing header results in an empty
+/// `Vec`. Values are returned as sent, so a single header containing a comma separated list
+/// remains a single value.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::header::HeaderMap;
+/// # use gotham::state::{header_all, State};
+/// #
+/// # fn main() {
+/// #   State::with_new(|state| {
+/// #       let mut headers = HeaderMap::new();
+/// #       headers.append("x-feature-flag", "beta".parse().unwrap());
+/// #       headers.append("x-feature-flag", "dark-mode".parse().unwrap());
+/// #       state.put(headers);
+/// #
+/// let flags = header_all(&state, "x-feature-flag");
+/// assert_eq!(flags, vec!["beta", "dark-mode"]);
+///
+/// assert!(header_all(&state, "x-missing").is_empty());
+/// #   });
+/// # }
+/// ```
+pub fn header_all<K: AsHeaderName>(state: &State, name: K) -> Vec<&str> {
+    HeaderMap::borrow_from(state)
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect()
+}
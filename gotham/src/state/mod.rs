@@ -1,9 +1,12 @@
 //! Defines types for passing request state through `Middleware` and `Handler` implementations
 
 pub(crate) mod client_addr;
+mod context;
 mod data;
 mod from_state;
+mod headers;
 pub mod request_id;
+mod request_start;
 
 use log::trace;
 
@@ -11,11 +14,15 @@ use std::any::{Any, TypeId};
 use std::collections::HashMap;
 
 pub use crate::state::client_addr::client_addr;
+pub use crate::state::context::RequestContext;
 pub use crate::state::data::StateData;
 pub use crate::state::from_state::FromState;
+pub use crate::state::headers::{header_all, header_str};
 pub use crate::state::request_id::request_id;
+pub use crate::state::request_start::RequestStart;
 
 pub(crate) use crate::state::request_id::set_request_id;
+pub(crate) use crate::state::request_start::set_request_start;
 
 /// Provides storage for request state, and stores one item of each type. The types used for
 /// storage must implement the `gotham::state::StateData` trait to allow its storage. The
@@ -0,0 +1,74 @@
+//! Defines the time at which Gotham began processing a `Request`.
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+use crate::state::{FromState, State, StateData};
+
+/// The time at which Gotham began processing a request.
+///
+/// This is stored in `State` by `GothamService` as soon as a request is received, before any
+/// middleware runs, so that every middleware measures durations from the same origin. Both the
+/// wall clock time (for display) and a monotonic `Instant` (for measuring) are captured.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response};
+/// # use gotham::state::{FromState, RequestStart, State};
+/// #
+/// fn my_handler(state: State) -> (State, Response<Body>) {
+///     let elapsed = RequestStart::borrow_from(&state).elapsed();
+///     println!("handler reached after {:?}", elapsed);
+/// #   (state, Response::new(Body::empty()))
+/// }
+/// # fn main() { let _ = my_handler; }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct RequestStart {
+    time: DateTime<Utc>,
+    instant: Instant,
+}
+
+impl RequestStart {
+    /// Creates a `RequestStart` for the current time.
+    pub fn now() -> Self {
+        RequestStart {
+            time: Utc::now(),
+            instant: Instant::now(),
+        }
+    }
+
+    /// Returns the wall clock time at which the request started.
+    pub fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    /// Returns the monotonic instant at which the request started.
+    pub fn instant(&self) -> Instant {
+        self.instant
+    }
+
+    /// Returns the time elapsed since the request started.
+    pub fn elapsed(&self) -> Duration {
+        self.instant.elapsed()
+    }
+}
+
+impl StateData for RequestStart {}
+
+/// Stores the start time of the request, if it has not already been stored.
+///
+/// This function is invoked by `GothamService` when a request is received, ensuring that a value
+/// for `RequestStart` is available to all middleware.
+pub(crate) fn set_request_start(state: &mut State) -> RequestStart {
+    if let Some(start) = RequestStart::try_borrow_from(state) {
+        return *start;
+    }
+
+    let start = RequestStart::now();
+    state.put(start);
+    start
+}
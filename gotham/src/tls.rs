@@ -1,18 +1,67 @@
-use futures::{Future, Stream};
-use hyper::server::conn::Http;
-use log::info;
+use futures::future::{self, Either};
+use futures::Future;
+use log::{debug, info};
 use std::net::ToSocketAddrs;
 use std::sync::Arc;
-use tokio::executor;
 use tokio::net::TcpListener;
 use tokio::runtime::TaskExecutor;
-use tokio_rustls::{rustls, TlsAcceptor};
+use tokio_rustls::rustls::{self, Session};
+use tokio_rustls::TlsAcceptor;
 
-use super::{handler::NewHandler, service::GothamService};
+use super::handler::NewHandler;
+use super::server::{serve, ServerOptions};
+use super::state::StateData;
 use super::{new_runtime, tcp_listener};
 
 pub mod test;
 
+/// The application protocol negotiated via ALPN for a TLS connection, such as `h2` or `http/1.1`.
+///
+/// This is placed into `State` for every request made over a TLS connection which negotiated a
+/// protocol, allowing handlers serving multiple protocols on the same port to tell them apart.
+/// It's absent for plaintext connections, and for TLS connections where no protocol was agreed,
+/// which includes servers without any protocols set on their `rustls::ServerConfig`.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use gotham::state::{FromState, State};
+/// # use gotham::tls::AlpnProtocol;
+/// # use hyper::{Body, Response};
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let http2 = AlpnProtocol::try_borrow_from(&state)
+///         .map(|protocol| protocol.as_str() == "h2")
+///         .unwrap_or(false);
+///     // ...
+/// #   let _ = http2;
+/// #   (state, Response::new(Body::empty()))
+/// }
+/// # fn main() { let _ = handler; }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AlpnProtocol(pub String);
+
+impl AlpnProtocol {
+    /// Returns the protocol as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl StateData for AlpnProtocol {}
+
+/// Marks a request made over a TLS connection, placed into `State` by the TLS server.
+///
+/// This is absent for requests accepted by a plaintext server, including those forwarded by a
+/// proxy which terminated TLS, where headers such as `X-Forwarded-Proto` must be consulted
+/// instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TlsConnection;
+
+impl StateData for TlsConnection {}
+
 /// Starts a Gotham application with the default number of threads.
 pub fn start<NH, A>(addr: A, new_handler: NH, tls_config: rustls::ServerConfig)
 where
@@ -37,6 +86,26 @@ pub fn start_with_num_threads<NH, A>(
     runtime.shutdown_on_idle().wait().unwrap();
 }
 
+/// Starts a Gotham application using the provided `ServerOptions`.
+pub fn start_with_options<NH, A>(
+    tls_config: rustls::ServerConfig,
+    addr: A,
+    new_handler: NH,
+    options: ServerOptions,
+) where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static,
+{
+    let runtime = new_runtime(num_cpus::get());
+    runtime.executor().spawn(init_server_with_options(
+        tls_config,
+        addr,
+        new_handler,
+        options,
+    ));
+    runtime.shutdown_on_idle().wait().unwrap();
+}
+
 /// Starts a Gotham application with a designated backing `TaskExecutor`.
 ///
 /// This function can be used to spawn the server on an existing `Runtime`.
@@ -62,6 +131,23 @@ pub fn init_server<NH, A>(
     addr: A,
     new_handler: NH,
 ) -> impl Future<Item = (), Error = ()>
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static,
+{
+    init_server_with_options(tls_config, addr, new_handler, ServerOptions::default())
+}
+
+/// Returns a `Future` used to spawn a Gotham application using the provided `ServerOptions`.
+///
+/// This is the same as `init_server`, but allows tuning of the connection handling, such as
+/// HTTP keep-alive behaviour.
+pub fn init_server_with_options<NH, A>(
+    tls_config: rustls::ServerConfig,
+    addr: A,
+    new_handler: NH,
+    options: ServerOptions,
+) -> impl Future<Item = (), Error = ()>
 where
     NH: NewHandler + 'static,
     A: ToSocketAddrs + 'static,
@@ -75,39 +161,46 @@ where
     addr
     );
 
-    bind_server(tls_config, listener, new_handler)
+    bind_server(tls_config, listener, new_handler, options)
 }
 
 fn bind_server<NH>(
     tls_config: rustls::ServerConfig,
     listener: TcpListener,
     new_handler: NH,
+    options: ServerOptions,
 ) -> impl Future<Item = (), Error = ()>
 where
     NH: NewHandler + 'static,
 {
-    let protocol = Arc::new(Http::new());
-    let gotham_service = GothamService::new(new_handler);
     let tls = TlsAcceptor::from(Arc::new(tls_config));
 
-    listener
-        .incoming()
-        .map_err(|e| panic!("socket error = {:?}", e))
-        .for_each(move |socket| {
-            let addr = socket.peer_addr().unwrap();
-            let service = gotham_service.connect(addr);
-            let accepted_protocol = protocol.clone();
-            let handler = tls
+    serve(
+        listener.incoming(),
+        new_handler,
+        options,
+        move |socket, service| {
+            // the client may have reset the connection before it was accepted
+            let service = match socket.peer_addr() {
+                Ok(addr) => service.connect(addr),
+                Err(e) => {
+                    debug!("closing connection without a peer address: {}", e);
+                    return Either::A(future::err(()));
+                }
+            };
+
+            let f = tls
                 .accept(socket)
-                .map_err(|e| panic!("https error = {:?}", e))
-                .and_then(move |socket| {
-                    accepted_protocol
-                        .serve_connection(socket, service)
-                        .map_err(|e| panic!("http error = {:?}", e))
-                });
+                .map_err(|e| debug!("closing connection after a failed TLS handshake: {}", e))
+                .map(move |socket| {
+                    let alpn_protocol = socket.get_ref().1.get_alpn_protocol().map(|protocol| {
+                        AlpnProtocol(String::from_utf8_lossy(protocol).into_owned())
+                    });
 
-            executor::spawn(handler);
+                    (socket, service.alpn_protocol(alpn_protocol).secure(true))
+                });
 
-            Ok(())
-        })
+            Either::B(f)
+        },
+    )
 }
@@ -32,6 +32,7 @@ use tokio_rustls::{
 };
 
 use crate::handler::NewHandler;
+use crate::server::ServerOptions;
 
 use crate::error::*;
 
@@ -123,8 +124,10 @@ impl TestServer {
         let certs = certs(&mut cert_file).unwrap();
         let mut keys = pkcs8_private_keys(&mut key_file).unwrap();
         cfg.set_single_cert(certs, keys.remove(0))?;
+        cfg.set_protocols(&[b"http/1.1".to_vec()]);
 
-        let service_stream = super::bind_server(cfg, listener, new_handler);
+        let service_stream =
+            super::bind_server(cfg, listener, new_handler, ServerOptions::default());
         runtime.spawn(service_stream);
 
         let data = TestServerData {
@@ -179,6 +182,7 @@ impl TestServer {
         let mut config = rustls::ClientConfig::new();
         let mut cert_file = BufReader::new(&include_bytes!("ca_cert.pem")[..]);
         config.root_store.add_pem_file(&mut cert_file).unwrap();
+        config.set_protocols(&[b"http/1.1".to_vec()]);
 
         let client = Client::builder().build(TestConnect {
             addr: self.data.addr,
@@ -236,6 +240,7 @@ mod tests {
     use crate::handler::{Handler, HandlerFuture, IntoHandlerError, NewHandler};
     use crate::helpers::http::response::create_response;
     use crate::state::{client_addr, FromState, State};
+    use crate::tls::{AlpnProtocol, TlsConnection};
     use futures::{future, Stream};
     use http::header::CONTENT_TYPE;
     use log::info;
@@ -271,6 +276,24 @@ mod tests {
 
                     Box::new(future::ok((state, response)))
                 }
+                "/alpn" => {
+                    info!("TestHandler responding to /alpn");
+                    let response = Response::builder()
+                        .status(StatusCode::OK)
+                        .body(AlpnProtocol::borrow_from(&state).as_str().to_owned().into())
+                        .unwrap();
+
+                    Box::new(future::ok((state, response)))
+                }
+                "/secure" => {
+                    info!("TestHandler responding to /secure");
+                    let response = Response::builder()
+                        .status(StatusCode::OK)
+                        .body(state.has::<TlsConnection>().to_string().into())
+                        .unwrap();
+
+                    Box::new(future::ok((state, response)))
+                }
                 _ => unreachable!(),
             }
         }
@@ -309,6 +332,41 @@ mod tests {
         assert_eq!(buf, format!("time: {}", ticks));
     }
 
+    #[test]
+    fn closes_failed_handshakes() {
+        use std::io::{Read, Write};
+
+        let new_service = || {
+            Ok(TestHandler {
+                response: "secure".to_owned(),
+            })
+        };
+
+        let test_server = TestServer::new(new_service).unwrap();
+
+        // a plain HTTP request isn't a TLS handshake, so the connection is closed
+        let mut stream = net::TcpStream::connect(test_server.data.addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+
+        let mut buf = Vec::new();
+        let _ = stream.read_to_end(&mut buf);
+        assert!(!buf.starts_with(b"HTTP/1.1"));
+
+        let response = test_server
+            .client()
+            .get("https://example.com/")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "secure");
+    }
+
     #[test]
     #[ignore] // XXX I don't understand why this doesn't work.
               // It seems like Hyper is treating the future::empty() as an empty body...
@@ -364,6 +422,43 @@ mod tests {
         assert_eq!(received_addr, client_addr);
     }
 
+    #[test]
+    fn sets_alpn_protocol() {
+        let new_service = || {
+            Ok(TestHandler {
+                response: String::new(),
+            })
+        };
+
+        let test_server = TestServer::new(new_service).unwrap();
+        let response = test_server
+            .client()
+            .get("https://localhost/alpn")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "http/1.1");
+    }
+
+    #[test]
+    fn marks_tls_connections() {
+        let new_service = || {
+            Ok(TestHandler {
+                response: String::new(),
+            })
+        };
+
+        let test_server = TestServer::new(new_service).unwrap();
+        let response = test_server
+            .client()
+            .get("https://localhost/secure")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.read_utf8_body().unwrap(), "true");
+    }
+
     #[test]
     fn async_echo() {
         fn handler(mut state: State) -> Box<HandlerFuture> {
@@ -0,0 +1,160 @@
+use futures::Future;
+use log::info;
+use std::fs;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net;
+use std::path::{Path, PathBuf};
+use tokio::net::UnixListener;
+
+use super::handler::NewHandler;
+use super::new_runtime;
+use super::server::{serve, ServerOptions};
+
+/// Starts a Gotham application listening on a Unix domain socket at `path`.
+///
+/// Requests served over the socket have no client address, so `client_addr` returns `None` for
+/// them. A socket file left behind by a server which didn't shut down cleanly is replaced, and
+/// the socket file is removed once the server shuts down.
+pub fn start_unix<NH, P>(path: P, new_handler: NH)
+where
+    NH: NewHandler + 'static,
+    P: AsRef<Path>,
+{
+    start_unix_with_options(path, new_handler, ServerOptions::default())
+}
+
+/// Starts a Gotham application listening on a Unix domain socket, using the provided
+/// `ServerOptions`.
+pub fn start_unix_with_options<NH, P>(path: P, new_handler: NH, options: ServerOptions)
+where
+    NH: NewHandler + 'static,
+    P: AsRef<Path>,
+{
+    let runtime = new_runtime(num_cpus::get());
+    runtime
+        .executor()
+        .spawn(init_unix_server(path.as_ref(), new_handler, options));
+    runtime.shutdown_on_idle().wait().unwrap();
+}
+
+/// Returns a `Future` used to spawn a Gotham application listening on a Unix domain socket.
+///
+/// The socket file is removed when the `Future` completes or is dropped, such as when the
+/// `Runtime` it was spawned on is shut down.
+pub fn init_unix_server<NH>(
+    path: &Path,
+    new_handler: NH,
+    options: ServerOptions,
+) -> impl Future<Item = (), Error = ()>
+where
+    NH: NewHandler + 'static,
+{
+    let listener = unix_listener(path);
+
+    info!(
+    target: "gotham::start",
+    " Gotham listening on unix:{}",
+    path.display()
+    );
+
+    bind_server(
+        listener,
+        SocketFile(path.to_path_buf()),
+        new_handler,
+        options,
+    )
+}
+
+fn unix_listener(path: &Path) -> UnixListener {
+    // replace a stale socket from an unclean shutdown, but never one which is still in use
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() && net::UnixStream::connect(path).is_err() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    UnixListener::bind(path).expect("unable to open Unix listener")
+}
+
+/// Removes the socket file when dropped, so that the path can be bound again.
+struct SocketFile(PathBuf);
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn bind_server<NH>(
+    listener: UnixListener,
+    socket_file: SocketFile,
+    new_handler: NH,
+    options: ServerOptions,
+) -> impl Future<Item = (), Error = ()>
+where
+    NH: NewHandler + 'static,
+{
+    serve(
+        listener.incoming(),
+        new_handler,
+        options,
+        move |socket, service| {
+            // owned by the accept loop, so the socket file is removed once the loop is dropped
+            let _ = &socket_file;
+
+            Ok((socket, service.connect_without_addr()))
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+
+    use hyper::{Body, Response};
+    use tempfile::tempdir;
+    use tokio::runtime::Runtime;
+
+    use crate::state::{client_addr, State};
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let body = match client_addr(&state) {
+            Some(addr) => addr.to_string(),
+            None => "no address".to_owned(),
+        };
+
+        (state, Response::new(Body::from(body)))
+    }
+
+    #[test]
+    fn serves_requests_on_unix_sockets() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("gotham.sock");
+
+        // a stale socket, with nothing listening, is replaced
+        drop(net::UnixListener::bind(&path).unwrap());
+
+        let runtime = Runtime::new().unwrap();
+        runtime.executor().spawn(init_unix_server(
+            &path,
+            || Ok(handler),
+            ServerOptions::default(),
+        ));
+
+        let mut stream = net::UnixStream::connect(&path).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\nno address"));
+
+        runtime.shutdown_now().wait().unwrap();
+        assert!(!path.exists());
+    }
+}
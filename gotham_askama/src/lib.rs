@@ -0,0 +1,177 @@
+//! Askama template rendering for Gotham applications.
+//!
+//! Askama compiles templates into Rust code along with the application, so any error in a
+//! template is reported by the compiler rather than when the template is first rendered. This
+//! crate turns those templates into responses, using the extension of the template to determine
+//! the `Content-Type` (such as `text/html; charset=utf-8` for a `.html` template).
+//!
+//! Templates can be rendered explicitly, via `render_askama` or `render_response`, or returned
+//! directly from a handler once `IntoResponse` has been implemented with `into_response!`.
+//!
+//! ```rust
+//! # extern crate askama;
+//! # extern crate gotham;
+//! # #[macro_use]
+//! # extern crate gotham_askama;
+//! #
+//! # use askama::Template;
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! #
+//! #[derive(Template)]
+//! #[template(source = "<h1>Hello, {{ name }}!</h1>", ext = "html")]
+//! struct Hello {
+//!     name: String,
+//! }
+//!
+//! into_response!(Hello);
+//!
+//! fn index(state: State) -> (State, Hello) {
+//!     let template = Hello {
+//!         name: "Gotham".to_owned(),
+//!     };
+//!     (state, template)
+//! }
+//!
+//! # fn main() {
+//! let router = build_simple_router(|route| {
+//!     route.get("/").to(index);
+//! });
+//! # let _ = router;
+//! # }
+//! ```
+#![warn(missing_docs, deprecated)]
+
+use askama::Template;
+use futures::future;
+use gotham::handler::{HandlerFuture, IntoHandlerError};
+use gotham::helpers::http::response::{create_empty_response, create_response};
+use gotham::state::{request_id, State};
+use hyper::header::CONTENT_LENGTH;
+use hyper::{Body, Response, StatusCode};
+use log::error;
+use mime::Mime;
+
+/// Renders a template into a response, returning it as a `HandlerFuture`.
+///
+/// A failure to render the template is returned as a `HandlerError`, resulting in a
+/// `500 Internal Server Error` response.
+///
+/// ```rust
+/// # extern crate askama;
+/// # extern crate gotham;
+/// # extern crate gotham_askama;
+/// #
+/// # use askama::Template;
+/// # use gotham::handler::HandlerFuture;
+/// # use gotham::state::State;
+/// # use gotham_askama::render_askama;
+/// #
+/// #[derive(Template)]
+/// #[template(source = "Hello, {{ name }}!", ext = "txt")]
+/// struct Hello<'a> {
+///     name: &'a str,
+/// }
+///
+/// fn index(state: State) -> Box<HandlerFuture> {
+///     render_askama(state, Hello { name: "Gotham" })
+/// }
+/// # fn main() { let _ = index; }
+/// ```
+pub fn render_askama<T: Template>(state: State, template: T) -> Box<HandlerFuture> {
+    match render(&state, &template) {
+        Ok(response) => Box::new(future::ok((state, response))),
+        Err(e) => Box::new(future::err((state, e.into_handler_error()))),
+    }
+}
+
+/// Renders a template into a response.
+///
+/// A failure to render the template is logged, and results in an empty
+/// `500 Internal Server Error` response.
+pub fn render_response<T: Template>(state: &State, template: &T) -> Response<Body> {
+    render(state, template).unwrap_or_else(|e| {
+        error!("[{}] unable to render template: {}", request_id(state), e);
+        create_empty_response(state, StatusCode::INTERNAL_SERVER_ERROR)
+    })
+}
+
+/// Implements `IntoResponse` for one or more `Template` types, via `render_response`.
+///
+/// This allows handlers to return their template directly, rather than rendering it into a
+/// response themselves. A trait implementation can't be provided for all templates by this
+/// crate, so this macro generates one for each type in your own crate instead.
+///
+/// ```rust
+/// # extern crate askama;
+/// # #[macro_use]
+/// # extern crate gotham_askama;
+/// #
+/// # use askama::Template;
+/// #
+/// #[derive(Template)]
+/// #[template(source = "<p>{{ count }} items</p>", ext = "html")]
+/// struct Items {
+///     count: usize,
+/// }
+///
+/// #[derive(Template)]
+/// #[template(source = "{{ count }} items", ext = "txt")]
+/// struct PlainItems {
+///     count: usize,
+/// }
+///
+/// into_response!(Items, PlainItems);
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! into_response {
+    ($($template:ty),+ $(,)?) => {
+        $(
+            impl $crate::__private::IntoResponse for $template {
+                fn into_response(
+                    self,
+                    state: &$crate::__private::State,
+                ) -> $crate::__private::Response<$crate::__private::Body> {
+                    $crate::render_response(state, &self)
+                }
+            }
+        )+
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    //! Re-exports used by `into_response!`, so that callers need not depend on them directly.
+    pub use gotham::handler::IntoResponse;
+    pub use gotham::state::State;
+    pub use hyper::{Body, Response};
+}
+
+/// Renders a template into a `200 OK` response, with the `Content-Type` of the template.
+fn render<T: Template>(state: &State, template: &T) -> Result<Response<Body>, askama::Error> {
+    let content = template.render()?;
+    let length = content.len();
+
+    let mut response = create_response(state, StatusCode::OK, content_type::<T>(), content);
+    response.headers_mut().insert(CONTENT_LENGTH, length.into());
+
+    Ok(response)
+}
+
+/// Determines the `Content-Type` of a template from its extension, defaulting to plain text.
+///
+/// Templates always render to a `String`, so textual types are marked as UTF-8.
+fn content_type<T: Template>() -> Mime {
+    let mime = T::extension()
+        .and_then(|ext| mime_guess::from_ext(ext).first())
+        .unwrap_or(mime::TEXT_PLAIN);
+
+    if mime.type_() != mime::TEXT || mime.get_param(mime::CHARSET).is_some() {
+        return mime;
+    }
+
+    format!("{}; charset=utf-8", mime)
+        .parse()
+        .expect("a valid mime type with a charset")
+}
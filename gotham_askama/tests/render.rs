@@ -0,0 +1,103 @@
+use askama::Template;
+use gotham::router::builder::*;
+use gotham::router::Router;
+use gotham::state::State;
+use gotham::test::TestServer;
+use gotham_askama::{into_response, render_askama};
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::StatusCode;
+
+#[derive(Template)]
+#[template(source = "<h1>Hello, {{ name }}!</h1>", ext = "html")]
+struct Page {
+    name: String,
+}
+
+#[derive(Template)]
+#[template(source = "{\"name\":\"{{ name }}\"}", ext = "json", escape = "none")]
+struct Json {
+    name: String,
+}
+
+#[derive(Template)]
+#[template(source = "Hello, {{ name }}!", ext = "txt")]
+struct Plain<'a> {
+    name: &'a str,
+}
+
+into_response!(Page, Json);
+
+fn page(state: State) -> (State, Page) {
+    let template = Page {
+        name: "Gotham".to_owned(),
+    };
+    (state, template)
+}
+
+fn json(state: State) -> (State, Json) {
+    let template = Json {
+        name: "Gotham".to_owned(),
+    };
+    (state, template)
+}
+
+fn router() -> Router {
+    build_simple_router(|route| {
+        route.get("/page").to(page);
+        route.get("/json").to(json);
+        route
+            .get("/plain")
+            .to(|state| render_askama(state, Plain { name: "Gotham" }));
+    })
+}
+
+fn get(path: &str) -> (StatusCode, String, String, String) {
+    let test_server = TestServer::new(router()).unwrap();
+    let response = test_server
+        .client()
+        .get(format!("http://localhost{}", path))
+        .perform()
+        .unwrap();
+
+    let status = response.status();
+    let content_type = response.headers()[CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let length = response.headers()[CONTENT_LENGTH]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let body = response.read_utf8_body().unwrap();
+
+    (status, content_type, length, body)
+}
+
+#[test]
+fn renders_templates_returned_from_handlers() {
+    let (status, content_type, length, body) = get("/page");
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "text/html; charset=utf-8");
+    assert_eq!(body, "<h1>Hello, Gotham!</h1>");
+    assert_eq!(length, body.len().to_string());
+}
+
+#[test]
+fn renders_templates_into_handler_futures() {
+    let (status, content_type, length, body) = get("/plain");
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "text/plain; charset=utf-8");
+    assert_eq!(body, "Hello, Gotham!");
+    assert_eq!(length, "14");
+}
+
+#[test]
+fn uses_the_content_type_of_the_template() {
+    let (status, content_type, _, body) = get("/json");
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/json");
+    assert_eq!(body, "{\"name\":\"Gotham\"}");
+}
@@ -8,6 +8,17 @@ pub(crate) fn bad_request_static_response_extender(
     let name = &ast.ident;
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
 
+    // `#[validate]` delegates to the type's implementation of `Validate`
+    let validate = if ast.attrs.iter().any(|attr| attr.path.is_ident("validate")) {
+        quote! {
+            fn validate(&self) -> ::std::result::Result<(), ::std::string::String> {
+                ::gotham::extractor::Validate::validate(self)
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     let expanded = quote! {
         impl #impl_generics ::gotham::router::response::extender::StaticResponseExtender for #name
             #ty_generics #where_clause
@@ -18,7 +29,15 @@ pub(crate) fn bad_request_static_response_extender(
                 res.headers_mut().insert(::gotham::helpers::http::header::X_REQUEST_ID,
                                          ::gotham::state::request_id(state).parse().unwrap());
                 *res.status_mut() = ::hyper::StatusCode::BAD_REQUEST;
+
+                if let Some(err) = state.try_take::<::gotham::extractor::ValidationError>() {
+                    res.headers_mut().insert(::hyper::header::CONTENT_TYPE,
+                                             ::hyper::header::HeaderValue::from_static("text/plain"));
+                    *res.body_mut() = err.message().to_owned().into();
+                }
             }
+
+            #validate
         }
     };
 
@@ -19,3 +19,72 @@ pub(crate) fn base_query_string(_ast: &syn::DeriveInput) -> proc_macro::TokenStr
     };
     expanded.into()
 }
+
+pub(crate) fn header(ast: &syn::DeriveInput) -> proc_macro::TokenStream {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let fields = match ast.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(ref fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            let expanded = quote! {
+                compile_error!("#[derive(HeaderExtractor)] is only supported for structs with \
+                                named fields.");
+            };
+            return expanded.into();
+        }
+    };
+
+    let extracted = fields.iter().map(|field| {
+        let ident = field.ident.as_ref().unwrap();
+
+        // `x_tenant_id` is read from `x-tenant-id`, allowing for raw identifiers such as `r#from`
+        let header = ident
+            .to_string()
+            .trim_start_matches("r#")
+            .replace('_', "-")
+            .to_lowercase();
+
+        if is_option(&field.ty) {
+            quote! {
+                #ident: ::gotham::extractor::optional_header(headers, #header)?
+            }
+        } else {
+            quote! {
+                #ident: ::gotham::extractor::required_header(headers, #header)?
+            }
+        }
+    });
+
+    let expanded = quote! {
+        impl #impl_generics ::gotham::extractor::HeaderExtractor for #name #ty_generics
+            #where_clause
+        {
+            fn extract(
+                headers: &::hyper::HeaderMap,
+            ) -> ::std::result::Result<Self, ::gotham::extractor::HeaderError> {
+                ::std::result::Result::Ok(#name {
+                    #(#extracted,)*
+                })
+            }
+        }
+
+        impl #impl_generics ::gotham::state::StateData for #name #ty_generics #where_clause {}
+    };
+
+    expanded.into()
+}
+
+// determines whether a field is optional, by the last segment of its type path
+fn is_option(ty: &syn::Type) -> bool {
+    match *ty {
+        syn::Type::Path(ref path) => match path.path.segments.iter().last() {
+            Some(segment) => segment.ident == "Option",
+            None => false,
+        },
+        _ => false,
+    }
+}
@@ -18,7 +18,13 @@ pub fn base_query_string_extractor(input: proc_macro::TokenStream) -> proc_macro
     extractors::base_query_string(&ast)
 }
 
-#[proc_macro_derive(StaticResponseExtender)]
+#[proc_macro_derive(HeaderExtractor)]
+pub fn header_extractor(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = syn::parse(input).unwrap();
+    extractors::header(&ast)
+}
+
+#[proc_macro_derive(StaticResponseExtender, attributes(validate))]
 pub fn static_response_extender(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse(input).unwrap();
     extenders::bad_request_static_response_extender(&ast)
@@ -30,7 +36,7 @@ pub fn state_data(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     state::state_data(&ast)
 }
 
-#[proc_macro_derive(NewMiddleware)]
+#[proc_macro_derive(NewMiddleware, attributes(middleware_name))]
 pub fn new_middleware(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse(input).unwrap();
     new_middleware::new_middleware(&ast)
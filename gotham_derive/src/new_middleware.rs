@@ -6,6 +6,26 @@ pub(crate) fn new_middleware(ast: &syn::DeriveInput) -> proc_macro::TokenStream
     let name = &ast.ident;
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
 
+    // `#[middleware_name = "..."]` replaces the type name used when inspecting pipelines
+    let middleware_name = ast
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("middleware_name"))
+        .map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(name),
+                ..
+            })) => name,
+            _ => panic!("expected #[middleware_name = \"...\"]"),
+        })
+        .map(|name| {
+            quote! {
+                fn middleware_name(&self) -> &'static str {
+                    #name
+                }
+            }
+        });
+
     let expanded = quote! {
         impl #impl_generics ::gotham::middleware::NewMiddleware for #name #ty_generics
             #where_clause
@@ -22,6 +42,8 @@ pub(crate) fn new_middleware(ast: &syn::DeriveInput) -> proc_macro::TokenStream
                 let new = <Self as Clone>::clone(self);
                 Ok(new)
             }
+
+            #middleware_name
         }
     };
 
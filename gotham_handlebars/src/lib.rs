@@ -0,0 +1,199 @@
+//! Handlebars template rendering for Gotham applications.
+//!
+//! A `HandlebarsEngine` loads every template within a directory when the application starts,
+//! and is shared with handlers via the `StateMiddleware`. Templates which fail to compile cause
+//! a panic during startup, rather than an error on the first request which happens to use them.
+//!
+//! ```rust,no_run
+//! # extern crate gotham;
+//! # extern crate gotham_handlebars;
+//! # extern crate hyper;
+//! # extern crate serde_json;
+//! #
+//! # use gotham::helpers::http::response::create_empty_response;
+//! # use gotham::middleware::state::StateMiddleware;
+//! # use gotham::pipeline::single::single_pipeline;
+//! # use gotham::pipeline::single_middleware;
+//! # use gotham::router::builder::*;
+//! # use gotham::state::{FromState, State};
+//! # use gotham_handlebars::HandlebarsEngine;
+//! # use hyper::{Body, Response, StatusCode};
+//! #
+//! fn index(state: State) -> (State, Response<Body>) {
+//!     let data = serde_json::json!({ "name": "Gotham" });
+//!
+//!     let response = HandlebarsEngine::borrow_from(&state)
+//!         .render("index", &data)
+//!         .unwrap_or_else(|_| create_empty_response(&state, StatusCode::INTERNAL_SERVER_ERROR));
+//!
+//!     (state, response)
+//! }
+//!
+//! # fn main() {
+//! let engine = HandlebarsEngine::new("templates").watch();
+//! let (chain, pipelines) = single_pipeline(single_middleware(StateMiddleware::new(engine)));
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     route.get("/").to(index);
+//! });
+//! # let _ = router;
+//! # }
+//! ```
+#![warn(missing_docs, deprecated)]
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use gotham::state::StateData;
+use handlebars::Handlebars;
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{Body, Response};
+use log::{error, info};
+use notify::{watcher, RecursiveMode, Watcher};
+use serde::Serialize;
+
+pub use handlebars::RenderError;
+
+// the default extension of template files
+const DEFAULT_EXTENSION: &str = ".hbs";
+
+// the content type of rendered responses
+const TEXT_HTML_UTF_8: &str = "text/html; charset=utf-8";
+
+// how long to wait for file changes to settle before reloading
+const WATCH_DELAY: Duration = Duration::from_millis(250);
+
+/// A template engine rendering Handlebars templates loaded from a directory.
+///
+/// Templates are named by their path relative to the directory, without the extension, so
+/// `templates/users/show.hbs` is rendered as `users/show`. The same names are used to include
+/// templates as partials.
+///
+/// The engine is cheap to clone, with all clones sharing the same set of templates.
+#[derive(Clone)]
+pub struct HandlebarsEngine {
+    registry: Arc<RwLock<Handlebars>>,
+    directory: PathBuf,
+    extension: &'static str,
+}
+
+impl HandlebarsEngine {
+    /// Creates a new `HandlebarsEngine`, loading all `.hbs` templates within `directory`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a template can't be read or fails to compile.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        Self::with_extension(directory, DEFAULT_EXTENSION)
+    }
+
+    /// Creates a new `HandlebarsEngine`, loading all templates within `directory` which end
+    /// with `extension` (such as `".html"`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if a template can't be read or fails to compile.
+    pub fn with_extension<P: Into<PathBuf>>(directory: P, extension: &'static str) -> Self {
+        let directory = directory.into();
+        let registry = match load(&directory, extension) {
+            Ok(registry) => registry,
+            Err(e) => panic!(
+                "unable to load templates from {}: {}",
+                directory.display(),
+                e
+            ),
+        };
+
+        HandlebarsEngine {
+            registry: Arc::new(RwLock::new(registry)),
+            directory,
+            extension,
+        }
+    }
+
+    /// Watches the template directory, reloading all templates whenever a file changes.
+    ///
+    /// This is intended for development. Templates which fail to compile during a reload are
+    /// logged, and the previously loaded templates remain in use.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory can't be watched.
+    pub fn watch(self) -> Self {
+        let (tx, rx) = channel();
+
+        let mut watcher = watcher(tx, WATCH_DELAY).expect("unable to create template watcher");
+        watcher
+            .watch(&self.directory, RecursiveMode::Recursive)
+            .expect("unable to watch template directory");
+
+        let registry = Arc::downgrade(&self.registry);
+        let directory = self.directory.clone();
+        let extension = self.extension;
+
+        thread::spawn(move || {
+            // the watcher stops once dropped, so it lives as long as this thread
+            let _watcher = watcher;
+
+            for _ in rx {
+                // stop watching once every clone of the engine is gone
+                let registry = match registry.upgrade() {
+                    Some(registry) => registry,
+                    None => break,
+                };
+
+                match load(&directory, extension) {
+                    Ok(reloaded) => {
+                        *registry.write().unwrap() = reloaded;
+                        info!("reloaded templates from {}", directory.display());
+                    }
+                    Err(e) => error!(
+                        "unable to reload templates from {}: {}",
+                        directory.display(),
+                        e
+                    ),
+                }
+            }
+        });
+
+        self
+    }
+
+    /// Renders the named template into a `text/html` response.
+    pub fn render<T>(&self, template_name: &str, data: &T) -> Result<Response<Body>, RenderError>
+    where
+        T: Serialize,
+    {
+        let html = self.render_to_string(template_name, data)?;
+
+        let response = Response::builder()
+            .header(CONTENT_TYPE, TEXT_HTML_UTF_8)
+            .header(CONTENT_LENGTH, html.len())
+            .body(Body::from(html))
+            .expect("Response built from a compatible type");
+
+        Ok(response)
+    }
+
+    /// Renders the named template into a `String`.
+    pub fn render_to_string<T>(&self, template_name: &str, data: &T) -> Result<String, RenderError>
+    where
+        T: Serialize,
+    {
+        self.registry.read().unwrap().render(template_name, data)
+    }
+}
+
+impl StateData for HandlebarsEngine {}
+
+/// Creates a registry containing every template within the directory.
+fn load(directory: &Path, extension: &'static str) -> Result<Handlebars, String> {
+    let mut registry = Handlebars::new();
+    registry
+        .register_templates_directory(extension, directory)
+        .map_err(|e| e.to_string())?;
+    Ok(registry)
+}
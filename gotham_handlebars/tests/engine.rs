@@ -0,0 +1,62 @@
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::{Future, Stream};
+use gotham_handlebars::HandlebarsEngine;
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use serde_json::json;
+
+const VALID: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/templates/valid");
+const INVALID: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/templates/invalid");
+
+#[test]
+fn renders_templates_to_html_responses() {
+    let engine = HandlebarsEngine::new(VALID);
+    let response = engine
+        .render("index", &json!({ "name": "Gotham", "year": 2019 }))
+        .unwrap();
+
+    let expected = "<h1>Hello, Gotham!</h1>\n<footer>2019</footer>\n";
+
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+    assert_eq!(
+        response.headers()[CONTENT_LENGTH],
+        expected.len().to_string().as_str()
+    );
+
+    let body = response.into_body().concat2().wait().unwrap();
+    assert_eq!(&body[..], expected.as_bytes());
+}
+
+#[test]
+fn reports_missing_templates_when_rendering() {
+    let engine = HandlebarsEngine::new(VALID);
+    assert!(engine.render("missing", &json!({})).is_err());
+}
+
+#[test]
+#[should_panic(expected = "unable to load templates from")]
+fn panics_on_invalid_templates_at_startup() {
+    HandlebarsEngine::new(INVALID);
+}
+
+#[test]
+fn reloads_templates_when_watching() {
+    let directory = std::env::temp_dir().join(format!("gotham_handlebars_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("page.hbs"), "first").unwrap();
+
+    let engine = HandlebarsEngine::new(&directory).watch();
+    assert_eq!(engine.render_to_string("page", &()).unwrap(), "first");
+
+    fs::write(directory.join("page.hbs"), "second").unwrap();
+
+    let started = Instant::now();
+    while engine.render_to_string("page", &()).unwrap() != "second" {
+        assert!(started.elapsed() < Duration::from_secs(10));
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    fs::remove_dir_all(&directory).unwrap();
+}
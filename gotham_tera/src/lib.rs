@@ -0,0 +1,221 @@
+//! Tera template rendering for Gotham applications.
+//!
+//! A `TeraEngine` loads every template matching a glob when the application starts, and is
+//! shared with handlers via the `StateMiddleware`. Templates which fail to compile cause a panic
+//! during startup, rather than an error on the first request which happens to use them.
+//!
+//! With the `auto-reload` feature enabled, debug builds watch the template directory and reload
+//! all templates whenever a file changes. Release builds never watch for changes.
+//!
+//! ```rust,no_run
+//! # extern crate gotham;
+//! # extern crate gotham_tera;
+//! # extern crate tera;
+//! #
+//! # use gotham::middleware::state::StateMiddleware;
+//! # use gotham::pipeline::single::single_pipeline;
+//! # use gotham::pipeline::single_middleware;
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham_tera::{render_template, TeraEngine};
+//! # use tera::Context;
+//! #
+//! fn index(state: State) -> (State, gotham_tera::RenderResult) {
+//!     let mut context = Context::new();
+//!     context.insert("name", "Gotham");
+//!
+//!     let response = render_template(&state, "index.html", &context);
+//!     (state, response)
+//! }
+//!
+//! # fn main() {
+//! let engine = TeraEngine::new("templates/**/*.html");
+//! let (chain, pipelines) = single_pipeline(single_middleware(StateMiddleware::new(engine)));
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     route.get("/").to(index);
+//! });
+//! # let _ = router;
+//! # }
+//! ```
+#![warn(missing_docs, deprecated)]
+
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use gotham::handler::IntoResponse;
+use gotham::helpers::http::response::{create_empty_response, create_response};
+use gotham::state::{request_id, FromState, State, StateData};
+use hyper::{Body, Response, StatusCode};
+use log::error;
+use tera::{Context, Tera};
+
+/// The result of rendering a template, which can be returned directly from a handler.
+pub type RenderResult = std::result::Result<Response<Body>, TeraError>;
+
+/// A template engine rendering Tera templates loaded from a glob.
+///
+/// Templates are named by their path relative to the directory containing the glob, so with a
+/// glob of `templates/**/*.html` the file `templates/users/show.html` is named `users/show.html`.
+///
+/// The engine is cheap to clone, with all clones sharing the same set of templates.
+#[derive(Clone)]
+pub struct TeraEngine {
+    tera: Arc<RwLock<Tera>>,
+}
+
+impl TeraEngine {
+    /// Creates a new `TeraEngine`, loading all templates matching `glob`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a template fails to compile.
+    pub fn new(glob: &str) -> Self {
+        let tera = match Tera::new(glob) {
+            Ok(tera) => tera,
+            Err(e) => panic!("unable to load templates from {}: {}", glob, describe(&e)),
+        };
+
+        let engine = TeraEngine {
+            tera: Arc::new(RwLock::new(tera)),
+        };
+
+        #[cfg(all(feature = "auto-reload", debug_assertions))]
+        reload::watch(glob, &engine.tera);
+
+        engine
+    }
+
+    /// Renders the named template into a `String`.
+    pub fn render(&self, template_name: &str, context: &Context) -> tera::Result<String> {
+        self.tera.read().unwrap().render(template_name, context)
+    }
+}
+
+impl StateData for TeraEngine {}
+
+/// Renders the named template into a `text/html` response, using the `TeraEngine` in `State`.
+///
+/// Failures are returned as a `TeraError`, which is converted into a
+/// `500 Internal Server Error` response when returned from a handler.
+///
+/// # Panics
+///
+/// Panics if no `TeraEngine` has been attached to the `State`.
+pub fn render_template(state: &State, template_name: &str, context: &Context) -> RenderResult {
+    let html = TeraEngine::borrow_from(state)
+        .render(template_name, context)
+        .map_err(TeraError)?;
+
+    Ok(create_response(
+        state,
+        StatusCode::OK,
+        mime::TEXT_HTML_UTF_8,
+        html,
+    ))
+}
+
+/// An error raised while rendering a template.
+#[derive(Debug)]
+pub struct TeraError(tera::Error);
+
+impl TeraError {
+    /// Returns the underlying Tera error.
+    pub fn inner(&self) -> &tera::Error {
+        &self.0
+    }
+}
+
+impl fmt::Display for TeraError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&describe(&self.0))
+    }
+}
+
+impl Error for TeraError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+impl IntoResponse for TeraError {
+    fn into_response(self, state: &State) -> Response<Body> {
+        error!(
+            "[{}] unable to render template: {}",
+            request_id(state),
+            self
+        );
+        create_empty_response(state, StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+/// Describes an error along with all of its causes, as Tera nests the useful details.
+fn describe(err: &tera::Error) -> String {
+    err.iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+#[cfg(all(feature = "auto-reload", debug_assertions))]
+mod reload {
+    //! Watches the template directory, reloading templates when they change.
+    use std::path::Path;
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, RwLock};
+    use std::thread;
+    use std::time::Duration;
+
+    use log::{error, info};
+    use notify::{watcher, RecursiveMode, Watcher};
+    use tera::Tera;
+
+    // how long to wait for file changes to settle before reloading
+    const WATCH_DELAY: Duration = Duration::from_millis(250);
+
+    /// Starts watching the directory containing `glob`.
+    ///
+    /// A failed reload is logged, and templates may be unavailable until the error is fixed.
+    pub(super) fn watch(glob: &str, tera: &Arc<RwLock<Tera>>) {
+        // Tera names templates relative to everything before the first wildcard
+        let root = match glob.find('*') {
+            Some(index) => &glob[..index],
+            None => glob,
+        };
+        let root = if root.is_empty() { "." } else { root };
+
+        let (tx, rx) = channel();
+
+        let mut watcher = watcher(tx, WATCH_DELAY).expect("unable to create template watcher");
+        watcher
+            .watch(Path::new(root), RecursiveMode::Recursive)
+            .expect("unable to watch template directory");
+
+        let tera = Arc::downgrade(tera);
+        let root = root.to_owned();
+
+        thread::spawn(move || {
+            // the watcher stops once dropped, so it lives as long as this thread
+            let _watcher = watcher;
+
+            for _ in rx {
+                // stop watching once every clone of the engine is gone
+                let tera = match tera.upgrade() {
+                    Some(tera) => tera,
+                    None => break,
+                };
+
+                let reloaded = tera.write().unwrap().full_reload();
+                match reloaded {
+                    Ok(()) => info!("reloaded templates from {}", root),
+                    Err(e) => error!(
+                        "unable to reload templates from {}: {}",
+                        root,
+                        super::describe(&e)
+                    ),
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,106 @@
+use gotham::middleware::state::StateMiddleware;
+use gotham::pipeline::single::single_pipeline;
+use gotham::pipeline::single_middleware;
+use gotham::router::builder::*;
+use gotham::router::Router;
+use gotham::state::State;
+use gotham::test::TestServer;
+use gotham_tera::{render_template, TeraEngine};
+use hyper::header::CONTENT_TYPE;
+use hyper::StatusCode;
+use tera::Context;
+
+const VALID: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/templates/valid/**/*.html"
+);
+const INVALID: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/templates/invalid/**/*.html"
+);
+
+fn index(state: State) -> (State, gotham_tera::RenderResult) {
+    let mut context = Context::new();
+    context.insert("name", "Gotham");
+
+    let response = render_template(&state, "index.html", &context);
+    (state, response)
+}
+
+fn missing(state: State) -> (State, gotham_tera::RenderResult) {
+    let response = render_template(&state, "missing.html", &Context::new());
+    (state, response)
+}
+
+fn router() -> Router {
+    let engine = TeraEngine::new(VALID);
+    let (chain, pipelines) = single_pipeline(single_middleware(StateMiddleware::new(engine)));
+
+    build_router(chain, pipelines, |route| {
+        route.get("/").to(index);
+        route.get("/missing").to(missing);
+    })
+}
+
+#[test]
+fn renders_templates_to_html_responses() {
+    let test_server = TestServer::new(router()).unwrap();
+    let response = test_server
+        .client()
+        .get("http://localhost/")
+        .perform()
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+    assert_eq!(
+        response.read_utf8_body().unwrap(),
+        "<h1>Hello, Gotham!</h1>\n"
+    );
+}
+
+#[test]
+fn responds_with_server_errors_on_render_failures() {
+    let test_server = TestServer::new(router()).unwrap();
+    let response = test_server
+        .client()
+        .get("http://localhost/missing")
+        .perform()
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[test]
+#[should_panic(expected = "unable to load templates from")]
+fn panics_on_invalid_templates_at_startup() {
+    TeraEngine::new(INVALID);
+}
+
+#[test]
+#[cfg(all(feature = "auto-reload", debug_assertions))]
+fn reloads_templates_when_changed() {
+    use std::fs;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    let directory = std::env::temp_dir().join(format!("gotham_tera_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("page.html"), "first").unwrap();
+
+    let engine = TeraEngine::new(&format!("{}/*.html", directory.display()));
+    assert_eq!(
+        engine.render("page.html", &Context::new()).unwrap(),
+        "first"
+    );
+
+    fs::write(directory.join("page.html"), "second").unwrap();
+
+    let started = Instant::now();
+    while engine.render("page.html", &Context::new()).ok().as_deref() != Some("second") {
+        assert!(started.elapsed() < Duration::from_secs(10));
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -2,7 +2,7 @@ use crate::state_data::AuthorizationToken;
 use futures::{future, Future};
 use gotham::{
     handler::HandlerFuture,
-    helpers::http::response::create_empty_response,
+    helpers::http::response::{problem_response, ProblemDetails},
     middleware::{Middleware, NewMiddleware},
     state::{request_id, FromState, State},
 };
@@ -24,7 +24,9 @@ use std::{io, marker::PhantomData, panic::RefUnwindSafe};
 /// Requests that lack the `Authorization` header are
 /// returned with the Status Code `400: Bad Request`.
 /// Tokens that fail validation cause the middleware
-/// to return Status Code `401: Unauthorized`.
+/// to return Status Code `401: Unauthorized`. Both
+/// carry an `application/problem+json` body, as
+/// described by RFC 7807.
 ///
 /// Example:
 /// ```rust
@@ -132,7 +134,9 @@ where
 
         if token.is_none() {
             trace!("[{}] bad request jwt middleware", request_id(&state));
-            let res = create_empty_response(&state, StatusCode::BAD_REQUEST);
+            let problem = ProblemDetails::new(StatusCode::BAD_REQUEST)
+                .detail("The request did not include a bearer token.");
+            let res = problem_response(&state, problem);
             return Box::new(future::ok((state, res)));
         }
 
@@ -149,7 +153,9 @@ where
             }
             Err(e) => {
                 trace!("[{}] error jwt middleware", e);
-                let res = create_empty_response(&state, StatusCode::UNAUTHORIZED);
+                let problem = ProblemDetails::new(StatusCode::UNAUTHORIZED)
+                    .detail("The bearer token could not be validated.");
+                let res = problem_response(&state, problem);
                 Box::new(future::ok((state, res)))
             }
         }
@@ -177,11 +183,13 @@ mod tests {
     use futures::future;
     use gotham::{
         handler::HandlerFuture,
+        helpers::http::response::create_empty_response,
         pipeline::{new_pipeline, single::*},
         router::{builder::*, Router},
         state::State,
-        test::TestServer,
+        test::{TestResponse, TestServer},
     };
+    use hyper::header::CONTENT_TYPE;
     use jsonwebtoken::{encode, Algorithm, Header};
 
     const SECRET: &str = "some-secret";
@@ -218,6 +226,35 @@ mod tests {
         Box::new(future::ok((state, res)))
     }
 
+    fn assert_problem(res: TestResponse, status: StatusCode, detail: &str) {
+        assert_eq!(res.status(), status);
+        assert_eq!(res.headers()[CONTENT_TYPE], "application/problem+json");
+
+        let expected = format!(
+            "{{\"type\":\"about:blank\",\"title\":\"{}\",\"status\":{},\"detail\":\"{}\"}}",
+            status.canonical_reason().unwrap(),
+            status.as_u16(),
+            detail
+        );
+        assert_eq!(res.read_utf8_body().unwrap(), expected);
+    }
+
+    fn assert_bad_request(res: TestResponse) {
+        assert_problem(
+            res,
+            StatusCode::BAD_REQUEST,
+            "The request did not include a bearer token.",
+        );
+    }
+
+    fn assert_unauthorized(res: TestResponse) {
+        assert_problem(
+            res,
+            StatusCode::UNAUTHORIZED,
+            "The bearer token could not be validated.",
+        );
+    }
+
     fn router() -> Router {
         // Create JWTMiddleware with HS256 algorithm (default).
         let valid = Validation {
@@ -242,7 +279,7 @@ mod tests {
             .perform()
             .unwrap();
 
-        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_bad_request(res);
     }
 
     #[test]
@@ -255,7 +292,7 @@ mod tests {
             .perform()
             .unwrap();
 
-        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_bad_request(res);
     }
 
     #[test]
@@ -268,7 +305,7 @@ mod tests {
             .perform()
             .unwrap();
 
-        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_bad_request(res);
     }
 
     #[test]
@@ -281,7 +318,7 @@ mod tests {
             .perform()
             .unwrap();
 
-        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_unauthorized(res);
     }
 
     #[test]
@@ -294,7 +331,7 @@ mod tests {
             .perform()
             .unwrap();
 
-        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_bad_request(res);
     }
 
     #[test]
@@ -307,7 +344,7 @@ mod tests {
             .perform()
             .unwrap();
 
-        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_unauthorized(res);
     }
 
     #[test]
@@ -0,0 +1,24 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// Error returned when a `RequestValidationMiddleware` can't be built, because the
+/// provided schema is not a valid JSON Schema.
+#[derive(Debug)]
+pub struct BuildError {
+    message: String,
+}
+
+impl BuildError {
+    /// Creates a new `BuildError` from the message of the schema compiler.
+    pub(crate) fn new(message: String) -> Self {
+        BuildError { message }
+    }
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "invalid JSON schema: {}", self.message)
+    }
+}
+
+impl Error for BuildError {}
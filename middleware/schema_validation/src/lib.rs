@@ -0,0 +1,29 @@
+//! Validates the JSON bodies of incoming requests against a JSON Schema.
+//!
+//! `POST`, `PUT` and `PATCH` requests with a `Content-Type` of `application/json` are
+//! buffered and validated before being passed along the chain. Bodies which fail
+//! validation are returned with the Status Code `422: Unprocessable Entity`, and a JSON
+//! body listing the path and message of each error. Bodies which aren't valid JSON at
+//! all are returned with the Status Code `400: Bad Request`.
+//!
+//! Valid bodies are placed back into `State` for the handler to read, alongside the
+//! parsed value as a `ValidatedJson`.
+#![warn(missing_docs, deprecated)]
+extern crate futures;
+extern crate gotham;
+#[macro_use]
+extern crate gotham_derive;
+extern crate hyper;
+extern crate jsonschema;
+#[macro_use]
+extern crate log;
+extern crate mime;
+extern crate serde_json;
+
+mod error;
+mod middleware;
+mod state_data;
+
+pub use self::error::BuildError;
+pub use self::middleware::RequestValidationMiddleware;
+pub use self::state_data::ValidatedJson;
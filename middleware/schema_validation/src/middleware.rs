@@ -0,0 +1,314 @@
+use crate::error::BuildError;
+use crate::state_data::ValidatedJson;
+use futures::{future, Future, Stream};
+use gotham::{
+    handler::{HandlerFuture, IntoHandlerError},
+    helpers::http::response::create_response,
+    middleware::{Middleware, NewMiddleware},
+    state::{request_id, FromState, State},
+};
+use hyper::{
+    header::{HeaderMap, CONTENT_TYPE},
+    Body, Method, StatusCode,
+};
+use jsonschema::Validator;
+use mime::Mime;
+use serde_json::{json, Value};
+use std::{io, panic::AssertUnwindSafe, sync::Arc};
+
+/// This middleware validates the JSON bodies of requests
+/// against a JSON Schema, before passing control to
+/// middleware beneath this middleware for a given mount
+/// point.
+///
+/// Only `POST`, `PUT` and `PATCH` requests with a JSON
+/// `Content-Type` are validated; all other requests are
+/// passed through untouched.
+///
+/// Bodies which fail validation are returned with the
+/// Status Code `422: Unprocessable Entity`, and a body
+/// such as:
+///
+/// ```json
+/// {"errors":[{"path":"/age","message":"\"x\" is not of type \"integer\""}]}
+/// ```
+///
+/// Example:
+/// ```rust
+/// extern crate futures;
+/// extern crate gotham;
+/// extern crate gotham_middleware_schema_validation;
+/// extern crate hyper;
+/// #[macro_use]
+/// extern crate serde_json;
+///
+/// use futures::future;
+/// use gotham::{
+///     helpers::http::response::create_empty_response,
+///     handler::HandlerFuture,
+///     pipeline::{new_pipeline, single::single_pipeline},
+///     router::{builder::*, Router},
+///     state::{FromState, State},
+/// };
+/// use gotham_middleware_schema_validation::{RequestValidationMiddleware, ValidatedJson};
+/// use hyper::StatusCode;
+///
+/// fn handler(state: State) -> Box<HandlerFuture> {
+///     {
+///         let body = ValidatedJson::borrow_from(&state);
+///         // body.0 -> serde_json::Value
+///     }
+///     let res = create_empty_response(&state, StatusCode::CREATED);
+///     Box::new(future::ok((state, res)))
+/// }
+///
+/// fn router() -> Router {
+///     let schema = json!({
+///         "type": "object",
+///         "required": ["name"],
+///         "properties": { "name": { "type": "string" } }
+///     });
+///
+///     let middleware = RequestValidationMiddleware::new(schema).unwrap();
+///     let (chain, pipelines) = single_pipeline(new_pipeline().add(middleware).build());
+///
+///     build_router(chain, pipelines, |route| {
+///         route.post("/users").to(handler);
+///     })
+/// }
+///
+/// # fn main() {
+/// #    let _ = router();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RequestValidationMiddleware {
+    // compiled validators are never mutated once built, so are safe across a panic
+    validator: Arc<AssertUnwindSafe<Validator>>,
+}
+
+impl RequestValidationMiddleware {
+    /// Creates a RequestValidationMiddleware instance from the provided
+    /// schema, which is compiled once up front.
+    ///
+    /// Returns a `BuildError` if the schema is not a valid JSON Schema.
+    pub fn new(schema: Value) -> Result<Self, BuildError> {
+        let validator =
+            jsonschema::validator_for(&schema).map_err(|e| BuildError::new(e.to_string()))?;
+
+        Ok(RequestValidationMiddleware {
+            validator: Arc::new(AssertUnwindSafe(validator)),
+        })
+    }
+
+    /// Determines whether the body of the request should be validated.
+    fn applies(state: &State) -> bool {
+        match *Method::borrow_from(state) {
+            Method::POST | Method::PUT | Method::PATCH => (),
+            _ => return false,
+        }
+
+        HeaderMap::borrow_from(state)
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<Mime>().ok())
+            .map(|mime| {
+                mime.type_() == mime::APPLICATION
+                    && (mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON))
+            })
+            .unwrap_or(false)
+    }
+
+    /// Collects the path and message of each error found in the value.
+    fn errors(&self, value: &Value) -> Vec<Value> {
+        self.validator
+            .iter_errors(value)
+            .map(|error| {
+                json!({
+                    "path": error.instance_path().to_string(),
+                    "message": error.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Middleware for RequestValidationMiddleware {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+    {
+        if !Self::applies(&state) {
+            return chain(state);
+        }
+
+        trace!("[{}] pre-chain schema validation", request_id(&state));
+
+        let f = state
+            .take::<Body>()
+            .concat2()
+            .then(move |result| -> Box<HandlerFuture> {
+                let chunk = match result {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        let err = e.into_handler_error().with_status(StatusCode::BAD_REQUEST);
+                        return Box::new(future::err((state, err)));
+                    }
+                };
+
+                let value = match serde_json::from_slice::<Value>(&chunk) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        trace!("[{}] malformed json body: {}", request_id(&state), e);
+                        let errors = vec![json!({ "path": "", "message": e.to_string() })];
+                        return reject(state, StatusCode::BAD_REQUEST, errors);
+                    }
+                };
+
+                let errors = self.errors(&value);
+
+                if !errors.is_empty() {
+                    trace!("[{}] invalid json body", request_id(&state));
+                    return reject(state, StatusCode::UNPROCESSABLE_ENTITY, errors);
+                }
+
+                state.put(Body::from(chunk));
+                state.put(ValidatedJson(value));
+
+                chain(state)
+            });
+
+        Box::new(f)
+    }
+}
+
+/// Responds with the provided status and a JSON body listing the errors.
+fn reject(state: State, status: StatusCode, errors: Vec<Value>) -> Box<HandlerFuture> {
+    let body = json!({ "errors": errors }).to_string();
+    let res = create_response(&state, status, mime::APPLICATION_JSON, body);
+    Box::new(future::ok((state, res)))
+}
+
+impl NewMiddleware for RequestValidationMiddleware {
+    type Instance = RequestValidationMiddleware;
+
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gotham::{
+        pipeline::{new_pipeline, single::*},
+        router::{builder::*, Router},
+        test::TestServer,
+    };
+    use hyper::Response;
+
+    fn handler(mut state: State) -> (State, Response<Body>) {
+        let name = ValidatedJson::try_borrow_from(&state)
+            .map(|body| body.0["name"].to_string())
+            .unwrap_or_else(|| "-".to_owned());
+
+        let body = state.take::<Body>().concat2().wait().unwrap();
+        let res = create_response(
+            &state,
+            StatusCode::OK,
+            mime::TEXT_PLAIN,
+            format!("{} {}", name, String::from_utf8_lossy(&body)),
+        );
+
+        (state, res)
+    }
+
+    fn router() -> Router {
+        let schema = json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": { "type": "string" },
+                "age": { "type": "integer" }
+            }
+        });
+
+        let middleware = RequestValidationMiddleware::new(schema).unwrap();
+        let (chain, pipelines) = single_pipeline(new_pipeline().add(middleware).build());
+
+        build_router(chain, pipelines, |route| {
+            route.get("/").to(handler);
+            route.post("/").to(handler);
+        })
+    }
+
+    fn post(body: &str, mime: Mime) -> (StatusCode, String) {
+        let test_server = TestServer::new(router()).unwrap();
+        let res = test_server
+            .client()
+            .post("http://localhost/", body.to_owned(), mime)
+            .perform()
+            .unwrap();
+
+        (res.status(), res.read_utf8_body().unwrap())
+    }
+
+    #[test]
+    fn schema_validation_valid_body_test() {
+        let (status, body) = post(r#"{"name":"gotham"}"#, mime::APPLICATION_JSON);
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#""gotham" {"name":"gotham"}"#);
+    }
+
+    #[test]
+    fn schema_validation_invalid_body_test() {
+        let (status, body) = post(r#"{"age":"x"}"#, mime::APPLICATION_JSON);
+        let body: Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body,
+            json!({
+                "errors": [
+                    { "path": "", "message": "\"name\" is a required property" },
+                    { "path": "/age", "message": "\"x\" is not of type \"integer\"" }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn schema_validation_malformed_body_test() {
+        let (status, body) = post("{", mime::APPLICATION_JSON);
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.starts_with(r#"{"errors":[{"message":"EOF"#));
+    }
+
+    #[test]
+    fn schema_validation_skips_other_requests_test() {
+        let (status, body) = post("{", mime::TEXT_PLAIN);
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "- {");
+
+        let test_server = TestServer::new(router()).unwrap();
+        let res = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn schema_validation_invalid_schema_test() {
+        let err = RequestValidationMiddleware::new(json!({ "type": "nope" }))
+            .err()
+            .unwrap();
+
+        assert!(err.to_string().starts_with("invalid JSON schema: "));
+    }
+}
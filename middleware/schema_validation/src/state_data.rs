@@ -0,0 +1,8 @@
+use serde_json::Value;
+
+/// Struct to contain a request body which passed validation, parsed on a per-request basis.
+///
+/// The raw body is also placed back into `State`, so handlers which prefer to deserialize
+/// into their own types can still do so.
+#[derive(StateData, Debug)]
+pub struct ValidatedJson(pub Value);